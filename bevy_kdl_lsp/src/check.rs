@@ -0,0 +1,145 @@
+//! Report the errors of a document as lsp diagnostics.
+use std::{collections::HashSet, fs, path::Path};
+
+use bevy_kdl_reflect_deser::{validate_with_schema, DeserOptions, Error, RegistrySchema};
+use kdl::KdlDocument;
+use lsp_types::{Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, Url};
+use template_kdl::{
+    analysis::parse_recovering, err::Error as TemplateError, ExportedBindingsList, RequiredBindings,
+};
+
+use crate::text::LineIndex;
+
+/// Read the exports of `file`, relative to `assets`, and of the files it
+/// imports, into `loaded`.
+pub(crate) fn load_exports(
+    assets: &Path,
+    file: &str,
+    loaded: &mut ExportedBindingsList,
+) -> Result<(), String> {
+    // Files importing each other would otherwise be read forever.
+    loaded.insert(file, Default::default());
+    let text = fs::read_to_string(assets.join(file)).map_err(|e| format!("reading {file}: {e}"))?;
+    let document: KdlDocument = text.parse().map_err(|e| format!("parsing {file}: {e}"))?;
+    let show = |err: TemplateError| format!("{file}@{}: {err}", err.span.offset);
+    let imports = template_kdl::get_imports(&document).map_err(show)?;
+    for dep in imports.required_files() {
+        if loaded.get(dep).is_none() {
+            load_exports(assets, dep, loaded)?;
+        }
+    }
+    let required = imports.bindings(loaded).map_err(show)?;
+    let document = template_kdl::read_document(document, required).into_result();
+    let document = document.map_err(|errs| {
+        let errors: Vec<_> = errs.into_iter().map(show).collect();
+        errors.join("\n")
+    })?;
+    match document.exports() {
+        Some(exports) => loaded.insert(file, exports.clone()),
+        None => return Err(format!("{file} doesn't export anything")),
+    };
+    Ok(())
+}
+
+/// The templates `document` imports from the files of `assets`, none if one
+/// of the imports can't be read.
+pub(crate) fn imported(assets: &Path, document: &KdlDocument) -> RequiredBindings {
+    let Ok(imports) = template_kdl::get_imports(document) else {
+        return RequiredBindings::default();
+    };
+    let mut loaded = ExportedBindingsList::new();
+    for file in imports.required_files() {
+        if loaded.get(file).is_none() && load_exports(assets, file, &mut loaded).is_err() {
+            return RequiredBindings::default();
+        }
+    }
+    imports.bindings(&loaded).unwrap_or_default()
+}
+
+/// Check documents against a schema, loading imported files from `assets`.
+pub(crate) struct Checker<'a> {
+    pub(crate) schema: &'a RegistrySchema,
+    pub(crate) assets: &'a Path,
+    /// The type of the values the documents declare.
+    pub(crate) expected: Option<&'a str>,
+}
+impl Checker<'_> {
+    /// The errors of `text`, the content of `uri`.
+    pub(crate) fn diagnostics(&self, uri: &Url, text: &str) -> Vec<Diagnostic> {
+        let index = LineIndex::new(text);
+        let error = |offset: usize, len: usize, message: String| Diagnostic {
+            range: index.range(offset, len),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("bevy-kdl".to_owned()),
+            message,
+            ..Default::default()
+        };
+        let template_error = |err: TemplateError| {
+            let span = err.span;
+            error(span.offset as usize, span.size as usize, err.to_string())
+        };
+        let convert_error = |err: &Error| {
+            let mut message = err.to_string();
+            if let Some(help) = miette::Diagnostic::help(err) {
+                message = format!("{message}\n{help}");
+            }
+            let mut diagnostic = error(err.span.offset(), err.span.len(), message);
+            let related = err.labels.iter().map(|label| DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.clone(),
+                    range: index.range(label.span.offset(), label.span.len()),
+                },
+                message: label.text.clone(),
+            });
+            diagnostic.related_information = Some(related.collect());
+            diagnostic
+        };
+        // What comes before a syntax error in the last node is still checked.
+        let (document, mut diagnostics) = match parse_recovering(text) {
+            Ok(document) => (document, Vec::new()),
+            Err((err, document)) => {
+                let diagnostics = vec![convert_error(&err.into())];
+                match document {
+                    Some(document) => (document, diagnostics),
+                    None => return diagnostics,
+                }
+            }
+        };
+        let imports = match template_kdl::get_imports(&document) {
+            Ok(imports) => imports,
+            Err(err) => {
+                diagnostics.push(template_error(err));
+                return diagnostics;
+            }
+        };
+        let mut loaded = ExportedBindingsList::new();
+        let mut failed = HashSet::new();
+        for import in imports.entries() {
+            let Some(file) = import.file() else { continue };
+            if loaded.get(file).is_some() {
+                continue;
+            }
+            if let Err(message) = load_exports(self.assets, file, &mut loaded) {
+                let span = import.span;
+                diagnostics.push(error(span.offset as usize, span.size as usize, message));
+                failed.insert(file);
+            }
+        }
+        let required = match imports.bindings(&loaded) {
+            Ok(required) => required,
+            Err(_) => {
+                let loaded_fine = |file: Option<&str>| file.map_or(true, |f| !failed.contains(f));
+                let entries = imports.entries().iter().filter(|i| loaded_fine(i.file()));
+                let missing = entries.filter_map(|i| i.missing(&loaded));
+                diagnostics.extend(missing.map(template_error));
+                return diagnostics;
+            }
+        };
+        let options = DeserOptions::new();
+        let result = validate_with_schema(document, self.expected, self.schema, required, &options);
+        if let Err(errors) = result {
+            diagnostics.extend(errors.errors().map(convert_error));
+        }
+        diagnostics
+    }
+}
@@ -0,0 +1,146 @@
+//! Complete type, field and template names.
+use bevy_kdl_reflect_deser::{RegistrySchema, TypeKind, TypeSchema};
+use kdl::KdlDocument;
+use lsp_types::{CompletionItem, CompletionItemKind, Documentation};
+use template_kdl::{analysis, RequiredBindings};
+
+use crate::outline::{in_children, nodes_at, on_name, types};
+
+fn documentation(docs: &Option<String>) -> Option<Documentation> {
+    docs.clone().map(Documentation::String)
+}
+/// The name `ty` can be declared with, its short name if no other type has
+/// it.
+fn declared_name<'s>(schema: &RegistrySchema, ty: &'s TypeSchema) -> &'s str {
+    match schema.get_with_short_name(&ty.short_name) {
+        Some(_) => &ty.short_name,
+        None => &ty.name,
+    }
+}
+
+fn template_item(label: &str, kind: CompletionItemKind, detail: &str) -> CompletionItem {
+    CompletionItem {
+        label: label.to_owned(),
+        kind: Some(kind),
+        detail: Some(detail.to_owned()),
+        ..Default::default()
+    }
+}
+
+/// Completions at `offset` of `text`, parsed as `document` with
+/// [`analysis::parse_tolerant`]. `imports` are the templates `text` imports.
+///
+/// In the children of a node, or on the name of a child node, its fields and
+/// the registered types are proposed, as well as the templates and
+/// tparameters [`analysis::completions_at`] finds. After the name of a node,
+/// its fields and the targuments it doesn't pass yet are proposed as
+/// properties.
+pub(crate) fn completions(
+    schema: &RegistrySchema,
+    expected: Option<&str>,
+    text: &str,
+    document: &KdlDocument,
+    offset: usize,
+    imports: &RequiredBindings,
+) -> Vec<CompletionItem> {
+    let nodes = nodes_at(document, offset);
+    let types = types(schema, expected, &nodes);
+    let (container, as_property) = match nodes.last() {
+        Some(node) if in_children(node, offset) => (types.last().copied().flatten(), false),
+        Some(node) if !on_name(node, offset) => (types.last().copied().flatten(), true),
+        Some(_) => (types.iter().rev().nth(1).copied().flatten(), false),
+        None => (None, false),
+    };
+    let mut items = Vec::new();
+    let fields = container.filter(|ty| ty.kind == TypeKind::Struct);
+    for field in fields.iter().flat_map(|ty| &ty.fields) {
+        let Some(name) = &field.name else { continue };
+        items.push(CompletionItem {
+            label: name.clone(),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: Some(field.type_name.clone()),
+            documentation: documentation(&field.docs),
+            insert_text: as_property.then(|| format!("{name}=")),
+            ..Default::default()
+        });
+    }
+    let templates = analysis::completions_at(text, offset, imports);
+    for targument in &templates.fields {
+        let mut item = template_item(targument, CompletionItemKind::FIELD, "targument");
+        item.insert_text = as_property.then(|| format!("{targument}="));
+        items.push(item);
+    }
+    if as_property {
+        return items;
+    }
+    for ty in schema.types() {
+        items.push(CompletionItem {
+            label: declared_name(schema, ty).to_owned(),
+            kind: Some(CompletionItemKind::STRUCT),
+            detail: Some(ty.name.clone()),
+            documentation: documentation(&ty.docs),
+            ..Default::default()
+        });
+    }
+    for binding in &templates.bindings {
+        items.push(template_item(
+            binding,
+            CompletionItemKind::FUNCTION,
+            "template",
+        ));
+    }
+    for parameter in &templates.parameters {
+        items.push(template_item(
+            parameter,
+            CompletionItemKind::VARIABLE,
+            "tparameter",
+        ));
+    }
+    items
+}
+
+#[cfg(test)]
+mod test {
+    use bevy_kdl_reflect_deser::FieldSchema;
+
+    use super::*;
+
+    #[test]
+    fn unfinished_template_invocation() {
+        let hp = TypeSchema {
+            name: "game::Hp".to_owned(),
+            short_name: "Hp".to_owned(),
+            kind: TypeKind::Struct,
+            docs: None,
+            fields: vec![FieldSchema {
+                name: Some("value".to_owned()),
+                type_name: "f32".to_owned(),
+                docs: None,
+            }],
+            variants: Vec::new(),
+        };
+        let schema = RegistrySchema::new(vec![hp]);
+        let text = r#"label "text" { Text "text"; }
+button "label" {
+  expand "style"
+  Button
+}
+root {
+  button label="Ok" {
+    "#;
+        let document = analysis::parse_tolerant(text, text.len()).unwrap();
+        let imports = RequiredBindings::default();
+        let items = completions(&schema, None, text, &document, text.len(), &imports);
+        let items: Vec<_> = items
+            .iter()
+            .map(|item| (item.label.as_str(), item.kind.unwrap()))
+            .collect();
+        let expected = [
+            ("style", CompletionItemKind::FIELD),
+            ("Hp", CompletionItemKind::STRUCT),
+            ("button", CompletionItemKind::FUNCTION),
+            ("label", CompletionItemKind::FUNCTION),
+        ];
+        assert_eq!(items, expected);
+    }
+}
@@ -0,0 +1,138 @@
+//! Find where templates are declared, following imports across files.
+use std::{fs, path::Path};
+
+use kdl::{KdlDocument, KdlNode};
+
+use crate::outline::{children, templates};
+
+/// Imports can't be deeper than this, files importing each other would
+/// otherwise be followed forever.
+const MAX_DEPTH: usize = 32;
+
+/// Where a template is declared.
+pub(crate) struct Definition {
+    /// The file the template is declared in, relative to the assets folder,
+    /// `None` if it is the file the template is invoked from.
+    pub(crate) file: Option<String>,
+    pub(crate) text: String,
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+}
+
+/// The local name of the template exported as `exported` in `document`.
+fn exported_as<'d>(document: &'d KdlDocument, exported: &str) -> Option<&'d str> {
+    let export = document
+        .nodes()
+        .iter()
+        .find(|n| n.name().value() == "export")?;
+    let string = |entry: &'d kdl::KdlEntry| entry.value().as_string();
+    // `export { local "exported" }` and `export "local" local="exported"`
+    let by_child = children(export).iter().find_map(|child| {
+        let local = child.name().value();
+        let argument = child.entries().first().and_then(string);
+        (argument.unwrap_or(local) == exported).then_some(local)
+    });
+    let by_entry = || {
+        export
+            .entries()
+            .iter()
+            .find_map(|entry| match entry.name() {
+                Some(local) => (string(entry)? == exported).then(|| local.value()),
+                None => string(entry).filter(|local| *local == exported),
+            })
+    };
+    by_child.or_else(by_entry)
+}
+
+fn definition(file: Option<&str>, text: &str, template: &KdlNode) -> Definition {
+    let span = template.name().span();
+    Definition {
+        file: file.map(str::to_owned),
+        text: text.to_owned(),
+        offset: span.offset(),
+        len: span.len(),
+    }
+}
+
+/// Find template definitions in the files of the `assets` folder.
+pub(crate) struct Definitions<'a> {
+    pub(crate) assets: &'a Path,
+}
+impl Definitions<'_> {
+    /// The declaration of the template `name` invoked in `document`, parsed
+    /// from `text`.
+    pub(crate) fn find(
+        &self,
+        document: &KdlDocument,
+        text: &str,
+        name: &str,
+    ) -> Option<Definition> {
+        self.find_in(None, document, text, name, 0)
+    }
+    fn find_in(
+        &self,
+        file: Option<&str>,
+        document: &KdlDocument,
+        text: &str,
+        name: &str,
+        depth: usize,
+    ) -> Option<Definition> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        // Definitions in the file shadow imported templates.
+        if let Some(template) = templates(document).find(|t| t.name().value() == name) {
+            return Some(definition(file, text, template));
+        }
+        let imports = template_kdl::get_imports(document).ok()?;
+        // Later imports shadow earlier ones.
+        imports.entries().iter().rev().find_map(|import| {
+            let imported = match (&import.name, &import.alias) {
+                (Some(local), _) if **local == *name => import.binding()?,
+                (Some(_), _) => return None,
+                (None, Some(alias)) => name.strip_prefix(&**alias)?.strip_prefix(':')?,
+                (None, None) => name,
+            };
+            self.find_exported(import.file()?, imported, depth + 1)
+        })
+    }
+    /// The declaration of the template exported as `exported` by `file`.
+    fn find_exported(&self, file: &str, exported: &str, depth: usize) -> Option<Definition> {
+        let text = fs::read_to_string(self.assets.join(file)).ok()?;
+        let document: KdlDocument = text.parse().ok()?;
+        let local = exported_as(&document, exported)?;
+        self.find_in(Some(file), &document, &text, local, depth)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_local_and_imported() {
+        let assets = std::env::temp_dir().join(format!("bevy-kdl-lsp-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&assets);
+        fs::create_dir_all(&assets).unwrap();
+        let widgets = "button { Button; }\nexport { button \"fancy-button\"; }\n";
+        fs::write(assets.join("widgets.kdl"), widgets).unwrap();
+
+        let text = r#"import "widgets.kdl" as="ui" my-button="widgets.kdl/fancy-button"
+local { Node; }
+Root { local; ui:fancy-button; my-button; missing; }"#;
+        let document: KdlDocument = text.parse().unwrap();
+        let definitions = Definitions { assets: &assets };
+        let find = |name| {
+            let definition = definitions.find(&document, text, name)?;
+            let at = &definition.text[definition.offset..definition.offset + definition.len];
+            Some((definition.file, at.to_owned(), definition.offset))
+        };
+        let local = text.find("local").unwrap();
+        assert_eq!(find("local"), Some((None, "local".to_owned(), local)));
+        let imported = Some((Some("widgets.kdl".to_owned()), "button".to_owned(), 0));
+        assert_eq!(find("ui:fancy-button"), imported);
+        assert_eq!(find("my-button"), imported);
+        assert_eq!(find("missing"), None);
+        assert_eq!(find("ui:button"), None);
+    }
+}
@@ -0,0 +1,81 @@
+//! Describe the rust type of the node or field under the cursor.
+use std::fmt::Write;
+
+use bevy_kdl_reflect_deser::{FieldSchema, RegistrySchema, TypeKind, TypeSchema};
+use kdl::KdlDocument;
+
+use crate::outline::{contains, field, nodes_at, on_name, types};
+
+fn kind_name(kind: TypeKind) -> &'static str {
+    match kind {
+        TypeKind::Struct | TypeKind::TupleStruct => "struct",
+        TypeKind::Tuple => "tuple",
+        TypeKind::List => "list",
+        TypeKind::Array => "array",
+        TypeKind::Map => "map",
+        TypeKind::Enum => "enum",
+        TypeKind::Value => "value",
+    }
+}
+/// Markdown describing `ty`, its doc comment and its fields.
+fn describe_type(out: &mut String, ty: &TypeSchema) {
+    let _ = writeln!(out, "```rust\n{} {}\n```", kind_name(ty.kind), ty.name);
+    if let Some(docs) = &ty.docs {
+        let _ = writeln!(out, "{docs}\n");
+    }
+    for (i, field) in ty.fields.iter().enumerate() {
+        let type_name = &field.type_name;
+        let _ = match &field.name {
+            Some(name) => writeln!(out, "* `{name}`: `{type_name}`"),
+            None => writeln!(out, "* `{i}`: `{type_name}`"),
+        };
+    }
+    for variant in &ty.variants {
+        let _ = writeln!(out, "* `{}`", variant.name);
+    }
+}
+/// Markdown describing the field `name`, and its type if it is registered.
+fn describe_field(schema: &RegistrySchema, name: &str, field: &FieldSchema) -> String {
+    let mut out = format!("```rust\n{name}: {}\n```\n", field.type_name);
+    if let Some(docs) = &field.docs {
+        let _ = writeln!(out, "{docs}\n");
+    }
+    if let Some(ty) = schema.get_with_name(&field.type_name) {
+        out.push_str("---\n");
+        describe_type(&mut out, ty);
+    }
+    out
+}
+
+/// Markdown describing the name under `offset` of `document`, `None` if it
+/// isn't a registered type or one of its fields.
+pub(crate) fn hover(
+    schema: &RegistrySchema,
+    expected: Option<&str>,
+    document: &KdlDocument,
+    offset: usize,
+) -> Option<String> {
+    let nodes = nodes_at(document, offset);
+    let types = types(schema, expected, &nodes);
+    let node = nodes.last()?;
+    let on_property = |entry: &&kdl::KdlEntry| {
+        let name = entry.name().map(|name| name.span());
+        name.map_or(false, |span| contains(span.offset(), span.len(), offset))
+    };
+    if let Some(entry) = node.entries().iter().find(on_property) {
+        let name = entry.name()?.value();
+        let ty = (*types.last()?)?;
+        return Some(describe_field(schema, name, field(ty, name)?));
+    }
+    if !on_name(node, offset) {
+        return None;
+    }
+    let name = node.name().value();
+    let parent = types.iter().rev().nth(1).copied().flatten();
+    if let Some(field) = parent.and_then(|parent| field(parent, name)) {
+        return Some(describe_field(schema, name, field));
+    }
+    let mut out = String::new();
+    describe_type(&mut out, (*types.last()?)?);
+    Some(out)
+}
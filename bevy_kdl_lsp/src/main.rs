@@ -0,0 +1,269 @@
+//! A language server for bevy_kdl_scene files.
+//!
+//! Reads the types registered in the bevy app from a schema written with
+//! `bevy_kdl_reflect_deser::dump_registry_schema`, and provides:
+//! * Diagnostics when a file is opened or saved.
+//! * Completion of type, field and template names, even in unfinished files.
+//! * Hover showing the rust type of nodes and fields.
+//! * Go to the definition of templates, in the file or in imported files.
+//! * Semantic highlighting of templates, tparameters, types and fields.
+mod check;
+mod complete;
+mod definition;
+mod hover;
+mod outline;
+mod semantic;
+mod text;
+
+use std::{collections::HashMap, env, path::PathBuf, process::ExitCode};
+
+use anyhow::{bail, Context};
+use bevy_kdl_reflect_deser::RegistrySchema;
+use kdl::KdlDocument;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Request as _, SemanticTokensFullRequest},
+    CompletionOptions, CompletionParams, CompletionResponse, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, PublishDiagnosticsParams,
+    SemanticTokens, SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensResult, SemanticTokensServerCapabilities, ServerCapabilities,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use template_kdl::analysis::parse_tolerant;
+
+use crate::{check::Checker, definition::Definitions, outline::nodes_at, text::LineIndex};
+
+const USAGE: &str = "usage: bevy-kdl-lsp --schema <types.kdl> [--assets <dir>] [--expected <type>]";
+
+fn capabilities() -> ServerCapabilities {
+    let sync = TextDocumentSyncOptions {
+        open_close: Some(true),
+        change: Some(TextDocumentSyncKind::FULL),
+        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+        ..Default::default()
+    };
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(sync)),
+        completion_provider: Some(CompletionOptions::default()),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: semantic::legend(),
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        ..Default::default()
+    }
+}
+
+/// Run `handle` on the parameters of `request`.
+fn respond<P, R>(request: Request, handle: impl FnOnce(P) -> R) -> Response
+where
+    P: DeserializeOwned,
+    R: Serialize,
+{
+    match serde_json::from_value(request.params) {
+        Ok(params) => Response::new_ok(request.id, handle(params)),
+        Err(err) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, err.to_string()),
+    }
+}
+fn notified<P: DeserializeOwned>(notification: Notification) -> Option<P> {
+    serde_json::from_value(notification.params).ok()
+}
+
+struct Server {
+    schema: RegistrySchema,
+    /// Imports are relative to this folder, like in the bevy asset folder.
+    assets: PathBuf,
+    /// The type of the values files declare.
+    expected: Option<String>,
+    /// The text of the open files.
+    documents: HashMap<Url, String>,
+}
+impl Server {
+    /// The parsed open file at `position` and the offset of `position`.
+    fn document_at(&self, position: &TextDocumentPositionParams) -> Option<(KdlDocument, usize)> {
+        let text = self.documents.get(&position.text_document.uri)?;
+        let offset = LineIndex::new(text).offset(position.position);
+        Some((text.parse().ok()?, offset))
+    }
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = &params.text_document_position;
+        let text = self.documents.get(&position.text_document.uri)?;
+        let offset = LineIndex::new(text).offset(position.position);
+        // The file is being edited, it often doesn't parse yet.
+        let document = parse_tolerant(text, offset)?;
+        let imports = check::imported(&self.assets, &document);
+        let expected = self.expected.as_deref();
+        let items =
+            complete::completions(&self.schema, expected, text, &document, offset, &imports);
+        Some(CompletionResponse::Array(items))
+    }
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let (document, offset) = self.document_at(&params.text_document_position_params)?;
+        let expected = self.expected.as_deref();
+        let value = hover::hover(&self.schema, expected, &document, offset)?;
+        let contents = MarkupContent { kind: MarkupKind::Markdown, value };
+        Some(Hover {
+            contents: HoverContents::Markup(contents),
+            range: None,
+        })
+    }
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = &params.text_document_position_params;
+        let (document, offset) = self.document_at(position)?;
+        let node = *nodes_at(&document, offset).last()?;
+        if !outline::on_name(node, offset) {
+            return None;
+        }
+        let text = &self.documents[&position.text_document.uri];
+        let definitions = Definitions { assets: &self.assets };
+        let definition = definitions.find(&document, text, node.name().value())?;
+        let uri = match &definition.file {
+            Some(file) => Url::from_file_path(self.assets.join(file).canonicalize().ok()?).ok()?,
+            None => position.text_document.uri.clone(),
+        };
+        let range = LineIndex::new(&definition.text).range(definition.offset, definition.len);
+        Some(GotoDefinitionResponse::Scalar(Location { uri, range }))
+    }
+    fn semantic_tokens(&self, params: SemanticTokensParams) -> Option<SemanticTokensResult> {
+        let text = self.documents.get(&params.text_document.uri)?;
+        // Highlight what can be read of unfinished files.
+        let document = parse_tolerant(text, text.len())?;
+        let imports = check::imported(&self.assets, &document);
+        let tokens = template_kdl::classify_tokens(&document, &imports);
+        let data = semantic::encode(text, &tokens);
+        Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        }))
+    }
+    fn handle_request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            Completion::METHOD => respond(request, |params| self.completion(params)),
+            HoverRequest::METHOD => respond(request, |params| self.hover(params)),
+            GotoDefinition::METHOD => respond(request, |params| self.definition(params)),
+            SemanticTokensFullRequest::METHOD => {
+                respond(request, |params| self.semantic_tokens(params))
+            }
+            method => {
+                let message = format!("unsupported request: {method}");
+                Response::new_err(request.id, ErrorCode::MethodNotFound as i32, message)
+            }
+        }
+    }
+    /// The diagnostics of the open file `uri`.
+    fn diagnostics(&self, uri: Url) -> Notification {
+        let checker = Checker {
+            schema: &self.schema,
+            assets: &self.assets,
+            expected: self.expected.as_deref(),
+        };
+        let text = self.documents.get(&uri).map_or("", String::as_str);
+        let diagnostics = checker.diagnostics(&uri, text);
+        let params = PublishDiagnosticsParams { uri, diagnostics, version: None };
+        Notification::new(PublishDiagnostics::METHOD.to_owned(), params)
+    }
+    /// Update the open files, returns the diagnostics to publish if any.
+    fn handle_notification(&mut self, notification: Notification) -> Option<Notification> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = notified(notification)?;
+                let document = params.text_document;
+                self.documents.insert(document.uri.clone(), document.text);
+                Some(self.diagnostics(document.uri))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = notified(notification)?;
+                // Changes are full documents, the last one is the current text
+                let text = params.content_changes.into_iter().last()?.text;
+                self.documents.insert(params.text_document.uri, text);
+                None
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams = notified(notification)?;
+                Some(self.diagnostics(params.text_document.uri))
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = notified(notification)?;
+                self.documents.remove(&params.text_document.uri);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+fn serve(connection: Connection, mut server: Server) -> anyhow::Result<()> {
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(diagnostics) = server.handle_notification(notification) {
+                    connection.sender.send(Message::Notification(diagnostics))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn run() -> anyhow::Result<()> {
+    let mut assets = PathBuf::from(".");
+    let mut schema = None;
+    let mut expected = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => assets = args.next().context(USAGE)?.into(),
+            "--schema" => schema = Some(args.next().context(USAGE)?),
+            "--expected" => expected = Some(args.next().context(USAGE)?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => bail!(USAGE),
+        }
+    }
+    let schema = schema.context(USAGE)?;
+    let schema = RegistrySchema::load(&schema).with_context(|| format!("reading {schema}"))?;
+    let server = Server {
+        schema,
+        assets,
+        expected,
+        documents: HashMap::new(),
+    };
+
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    serve(connection, server)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
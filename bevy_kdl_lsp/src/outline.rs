@@ -0,0 +1,97 @@
+//! Find the nodes at a position of a document and the types they declare.
+use bevy_kdl_reflect_deser::{FieldSchema, RegistrySchema, TypeKind, TypeSchema};
+use kdl::{KdlDocument, KdlNode};
+
+/// Whether `offset` is in the span starting at `start` of `len` bytes,
+/// including its end, where the cursor is after typing.
+pub(crate) fn contains(start: usize, len: usize, offset: usize) -> bool {
+    (start..=start + len).contains(&offset)
+}
+pub(crate) fn children(node: &KdlNode) -> &[KdlNode] {
+    node.children().map_or(&[], KdlDocument::nodes)
+}
+
+/// The nodes containing `offset`, from the document root to the most inner
+/// one.
+pub(crate) fn nodes_at(document: &KdlDocument, offset: usize) -> Vec<&KdlNode> {
+    let mut path = Vec::new();
+    let mut nodes = document.nodes();
+    let contains_offset =
+        |node: &&KdlNode| contains(node.span().offset(), node.span().len(), offset);
+    while let Some(node) = nodes.iter().find(contains_offset) {
+        path.push(node);
+        nodes = children(node);
+    }
+    path
+}
+/// Whether `offset` is on the name of `node`.
+pub(crate) fn on_name(node: &KdlNode, offset: usize) -> bool {
+    let span = node.name().span();
+    contains(span.offset(), span.len(), offset)
+}
+/// Whether `offset` is in the children block of `node`.
+pub(crate) fn in_children(node: &KdlNode, offset: usize) -> bool {
+    let span = node.children().map(KdlDocument::span);
+    span.map_or(false, |span| contains(span.offset(), span.len(), offset))
+}
+
+/// The type named `name`, by its full or short name.
+pub(crate) fn lookup<'s>(schema: &'s RegistrySchema, name: &str) -> Option<&'s TypeSchema> {
+    schema
+        .get_with_name(name)
+        .or_else(|| schema.get_with_short_name(name))
+}
+/// The field of `parent` a child node named `name` declares.
+pub(crate) fn field<'s>(parent: &'s TypeSchema, name: &str) -> Option<&'s FieldSchema> {
+    match parent.kind {
+        TypeKind::Struct => parent.field(name),
+        TypeKind::List | TypeKind::Array => parent.fields.first(),
+        TypeKind::Map => parent.field("value"),
+        _ => None,
+    }
+}
+
+/// The types of `nodes`, a path from the document root, `None` where it is
+/// unknown.
+///
+/// Like when converting, the document root is named after its type, and a
+/// type annotation takes precedence over the type of the field. Nodes that
+/// aren't fields, such as components, are named after their type.
+pub(crate) fn types<'s>(
+    schema: &'s RegistrySchema,
+    expected: Option<&str>,
+    nodes: &[&KdlNode],
+) -> Vec<Option<&'s TypeSchema>> {
+    let mut types = Vec::with_capacity(nodes.len());
+    let mut parent: Option<&TypeSchema> = None;
+    for (i, node) in nodes.iter().enumerate() {
+        let name = node.name().value();
+        let declared = node.ty().and_then(|ty| lookup(schema, ty.value()));
+        let ty = if i == 0 {
+            let expected = expected.and_then(|e| lookup(schema, e));
+            declared.or_else(|| lookup(schema, name)).or(expected)
+        } else {
+            let field = parent.and_then(|parent| field(parent, name));
+            let field = field.and_then(|f| schema.get_with_name(&f.type_name));
+            declared.or(field).or_else(|| lookup(schema, name))
+        };
+        types.push(ty);
+        parent = ty;
+    }
+    types
+}
+
+/// The templates declared in `document`, all top level nodes but the last
+/// one, except for the `import` and `export` nodes.
+pub(crate) fn templates(document: &KdlDocument) -> impl Iterator<Item = &KdlNode> {
+    let nodes = document.nodes();
+    let is_value = |node: &KdlNode| node.name().value() != "export";
+    let declarations = match nodes.last() {
+        Some(last) if is_value(last) => &nodes[..nodes.len() - 1],
+        _ => nodes,
+    };
+    let keywords = ["import", "export"];
+    declarations
+        .iter()
+        .filter(move |node| !keywords.contains(&node.name().value()))
+}
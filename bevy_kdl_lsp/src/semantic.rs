@@ -0,0 +1,98 @@
+//! Highlight documents with the tokens of [`template_kdl::classify_tokens`].
+use lsp_types::{SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend};
+use template_kdl::{Token, TokenKind};
+
+use crate::text::LineIndex;
+
+const TOKEN_TYPES: [SemanticTokenType; 6] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::VARIABLE,
+];
+const DECLARATION: u32 = 1;
+
+pub(crate) fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: vec![SemanticTokenModifier::DECLARATION],
+    }
+}
+/// The index in [`TOKEN_TYPES`] and the modifiers of `kind`.
+///
+/// Values are left to the syntax highlighting, which tells strings from
+/// numbers.
+fn encoded(kind: TokenKind) -> Option<(u32, u32)> {
+    Some(match kind {
+        TokenKind::Keyword => (0, 0),
+        TokenKind::TemplateDefinition => (1, DECLARATION),
+        TokenKind::CallSite => (1, 0),
+        TokenKind::Parameter => (2, 0),
+        TokenKind::Component => (3, 0),
+        TokenKind::Field => (4, 0),
+        TokenKind::Node => (5, 0),
+        TokenKind::Value => return None,
+    })
+}
+
+/// `tokens` of `text` in the relative encoding of the lsp protocol.
+///
+/// Tokens spanning several lines, such as quoted names with a newline, are
+/// skipped.
+pub(crate) fn encode(text: &str, tokens: &[Token]) -> Vec<SemanticToken> {
+    let index = LineIndex::new(text);
+    let (mut line, mut start) = (0, 0);
+    let mut encoded_tokens = Vec::new();
+    for token in tokens {
+        let Some((token_type, modifiers)) = encoded(token.kind) else {
+            continue;
+        };
+        let range = index.range(token.span.offset as usize, token.span.size as usize);
+        if range.start.line != range.end.line {
+            continue;
+        }
+        let delta_line = range.start.line - line;
+        let delta_start = match delta_line {
+            0 => range.start.character - start,
+            _ => range.start.character,
+        };
+        encoded_tokens.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: range.end.character - range.start.character,
+            token_type,
+            token_modifiers_bitset: modifiers,
+        });
+        (line, start) = (range.start.line, range.start.character);
+    }
+    encoded_tokens
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy_kdl_reflect_deser::Span;
+
+    fn token(kind: TokenKind, offset: u32, size: u32) -> Token {
+        Token { kind, span: Span { offset, size } }
+    }
+
+    #[test]
+    fn relative_positions() {
+        let text = "🦀 { x 1; }\n  Name\n\"a\nb\"";
+        let tokens = [
+            token(TokenKind::CallSite, 0, 4),
+            token(TokenKind::Field, 7, 1),
+            token(TokenKind::Value, 9, 1),
+            token(TokenKind::Component, 16, 4),
+            token(TokenKind::Node, 21, 5),
+        ];
+        let encoded: Vec<_> = encode(text, &tokens)
+            .into_iter()
+            .map(|t| (t.delta_line, t.delta_start, t.length, t.token_type))
+            .collect();
+        assert_eq!(encoded, [(0, 0, 2, 1), (0, 6, 1, 4), (1, 2, 4, 3)]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Convert between byte offsets and lsp positions.
+use lsp_types::{Position, Range};
+
+/// The start of each line of a text, to convert byte offsets into the utf-16
+/// based positions of the lsp protocol.
+pub(crate) struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        let newlines = text.match_indices('\n').map(|(i, _)| i + 1);
+        let line_starts = std::iter::once(0).chain(newlines).collect();
+        Self { text, line_starts }
+    }
+    pub(crate) fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let start = self.line_starts[line];
+        let before = self.text.get(start..offset).unwrap_or_default();
+        let character = before.encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+    pub(crate) fn range(&self, offset: usize, len: usize) -> Range {
+        Range::new(self.position(offset), self.position(offset + len))
+    }
+    /// The byte offset of `position`, clamped to the end of its line.
+    pub(crate) fn offset(&self, position: Position) -> usize {
+        let Some(start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let line = self.text[*start..].split('\n').next().unwrap_or_default();
+        let mut character = 0;
+        for (i, c) in line.char_indices() {
+            if character >= position.character as usize {
+                return start + i;
+            }
+            character += c.len_utf16();
+        }
+        start + line.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utf16_positions() {
+        // `é` is 2 bytes and 1 utf-16 unit, `🦀` is 4 bytes and 2 units.
+        let text = "Name \"é🦀\" x=1\n🦀 {\n}";
+        let index = LineIndex::new(text);
+        let x = text.find('x').unwrap();
+        assert_eq!(index.position(x), Position::new(0, 11));
+        assert_eq!(index.offset(Position::new(0, 11)), x);
+        let brace = text.find('{').unwrap();
+        assert_eq!(index.position(brace), Position::new(1, 3));
+        assert_eq!(index.offset(Position::new(1, 3)), brace);
+        assert_eq!(index.position(text.len()), Position::new(2, 1));
+        for (offset, _) in text.char_indices() {
+            assert_eq!(index.offset(index.position(offset)), offset);
+        }
+        let range = index.range(text.find('é').unwrap(), "é🦀".len());
+        assert_eq!(range, Range::new(Position::new(0, 6), Position::new(0, 9)));
+    }
+    #[test]
+    fn clamped_offsets() {
+        let text = "first\n🦀\n";
+        let index = LineIndex::new(text);
+        // Past the end of a line is its end, past the last line the text end
+        assert_eq!(index.offset(Position::new(0, 40)), 5);
+        assert_eq!(index.offset(Position::new(1, 40)), text.len() - 1);
+        assert_eq!(index.offset(Position::new(7, 0)), text.len());
+        assert_eq!(index.position(text.len() + 3), Position::new(2, 0));
+    }
+}
@@ -1,5 +1,5 @@
-use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry};
 use bevy_kdl_reflect_deser::*;
+use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry};
 use bevy_utils::HashMap;
 use miette::Result;
 
@@ -102,10 +102,14 @@ fn main() -> Result<()> {
     );
     for def in KDL_DEFS {
         let doc = def.parse().unwrap();
-        let reflect = match from_doc_untyped(doc, &reg) {
+        let reflect = match from_doc_untyped(doc, Default::default(), &reg, &Default::default()) {
             ConvertResult::Errors(errs) => return Err(errs.into()),
             ConvertResult::Exports(_) => panic!("shouldn't export anything in this example"),
             ConvertResult::Deserialized(reflect) => reflect,
+            ConvertResult::Warned(reflect, warnings) => {
+                println!("{:?}", miette::Report::new(warnings));
+                reflect
+            }
         };
         if let Some(m) = Newtype::from_reflect(reflect.as_ref()) {
             println!("Netype: {m:?}");
@@ -0,0 +1,86 @@
+//! The `bevy_reflect` API the deserializer relies on.
+//!
+//! Bevy releases rename the type info accessors, the rest of the crate
+//! imports reflect items from this module and reads type names and ids
+//! through its traits, so that supporting another bevy version only changes
+//! this file.
+use std::any::TypeId;
+
+pub(crate) use bevy_reflect::{
+    std_traits::ReflectDefault, ArrayInfo, DynamicArray, DynamicInfo, DynamicList, DynamicMap,
+    DynamicStruct, DynamicTuple, DynamicTupleStruct, EnumInfo, FromReflect, FromType,
+    GetTypeRegistration, ListInfo, Map, MapInfo, NamedField, Reflect, ReflectDeserialize,
+    ReflectRef, Struct, StructInfo, Tuple, TupleInfo, TupleStruct, TupleStructInfo, TypeInfo,
+    TypeRegistration, TypeRegistry, Typed, UnnamedField, ValueInfo, VariantInfo,
+};
+
+/// The type described by a type info, a field or a registration.
+///
+/// The methods are named unlike any `bevy_reflect` method, so that they
+/// can't silently call themselves, or [`Any::type_id`](std::any::Any::type_id)
+/// of the info itself, when bevy renames its own.
+pub(crate) trait TypeIdentity {
+    /// The full name of the type, such as `alloc::string::String`.
+    fn full_name(&self) -> &'static str;
+    fn reflected_id(&self) -> TypeId;
+}
+macro_rules! impl_identity {
+    ($($info:ty),* $(,)?) => {$(
+        impl TypeIdentity for $info {
+            fn full_name(&self) -> &'static str {
+                <$info>::type_name(self)
+            }
+            fn reflected_id(&self) -> TypeId {
+                <$info>::type_id(self)
+            }
+        }
+    )*};
+}
+impl_identity! {
+    TypeInfo, ValueInfo, StructInfo, TupleStructInfo, TupleInfo, ListInfo, ArrayInfo, MapInfo,
+    EnumInfo, DynamicInfo, NamedField, UnnamedField, TypeRegistration,
+}
+
+/// The types of the items of lists, arrays and maps.
+pub(crate) trait ItemTypes {
+    /// The type of the items, the values for maps.
+    fn item_name(&self) -> &'static str;
+    fn item_id(&self) -> TypeId;
+}
+impl ItemTypes for ListInfo {
+    fn item_name(&self) -> &'static str {
+        self.item_type_name()
+    }
+    fn item_id(&self) -> TypeId {
+        self.item_type_id()
+    }
+}
+impl ItemTypes for ArrayInfo {
+    fn item_name(&self) -> &'static str {
+        self.item_type_name()
+    }
+    fn item_id(&self) -> TypeId {
+        self.item_type_id()
+    }
+}
+impl ItemTypes for MapInfo {
+    fn item_name(&self) -> &'static str {
+        self.value_type_name()
+    }
+    fn item_id(&self) -> TypeId {
+        self.value_type_id()
+    }
+}
+/// The type of the keys of maps.
+pub(crate) trait KeyType {
+    fn key_name(&self) -> &'static str;
+    fn key_id(&self) -> TypeId;
+}
+impl KeyType for MapInfo {
+    fn key_name(&self) -> &'static str {
+        self.key_type_name()
+    }
+    fn key_id(&self) -> TypeId {
+        self.key_type_id()
+    }
+}
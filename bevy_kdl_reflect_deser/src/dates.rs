@@ -0,0 +1,62 @@
+//! Dates declared as ISO-8601 strings, such as `"2023-04-01"`.
+//!
+//! `bevy_reflect` 0.9 doesn't reflect the `chrono` or `time` types, so they
+//! are wrapped in newtypes, registered as opaque values with
+//! [`ReflectFromStr`]. Register them like any other type:
+//!
+//! ```ignore
+//! registry.register::<bevy_kdl_reflect_deser::dates::Date>();
+//! ```
+use std::{fmt, str::FromStr};
+
+use bevy_reflect::{FromReflect, Reflect};
+
+use crate::ReflectFromStr;
+
+/// A calendar date, declared as `"2023-04-01"`.
+#[cfg(feature = "chrono")]
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[reflect_value(PartialEq, Hash, FromStr)]
+pub struct Date(pub chrono::NaiveDate);
+#[cfg(feature = "chrono")]
+impl FromStr for Date {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let date = s
+            .parse()
+            .map_err(|err| format!("{err}, expected YYYY-MM-DD"))?;
+        Ok(Date(date))
+    }
+}
+#[cfg(feature = "chrono")]
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A date and time with an UTC offset, declared as
+/// `"2023-04-01T12:30:00+02:00"`.
+#[cfg(feature = "time")]
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[reflect_value(PartialEq, Hash, FromStr)]
+pub struct DateTime(pub time::OffsetDateTime);
+#[cfg(feature = "time")]
+impl FromStr for DateTime {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use time::format_description::well_known::Iso8601;
+
+        let date = time::OffsetDateTime::parse(s, &Iso8601::DEFAULT);
+        Ok(DateTime(date.map_err(|err| err.to_string())?))
+    }
+}
+#[cfg(feature = "time")]
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use time::format_description::well_known::Iso8601;
+
+        let formatted = self.0.format(&Iso8601::DEFAULT).map_err(|_| fmt::Error)?;
+        f.write_str(&formatted)
+    }
+}
@@ -1,28 +1,77 @@
-use std::marker::PhantomData;
-
-use bevy_reflect::{
-    DynamicList, DynamicMap, DynamicStruct, DynamicTuple, DynamicTupleStruct, ListInfo, Map,
-    MapInfo, NamedField, Reflect, Struct, StructInfo, Tuple, TupleInfo, TupleStruct,
-    TupleStructInfo, TypeInfo, TypeRegistry,
+use std::{
+    any::{self, TypeId},
+    collections::BTreeSet,
+    iter,
+    marker::PhantomData,
+    sync::Arc,
 };
+
+use kdl::KdlIdentifier;
 use multierr_span::{Span, Spanned};
 use template_kdl::{
-    multi_err::{MultiError, MultiErrorTrait, MultiResult},
-    multi_try,
-    navigate::{Navigable, Sstring, ThunkField, Value},
+    multi_err::{MultiError, MultiErrorTrait, MultiResult, OrAccumulate},
+    navigate::{Navigable, Sstring, Value},
 };
 
 use crate::{
+    compat::{
+        ArrayInfo, DynamicArray, DynamicList, DynamicMap, DynamicStruct, DynamicTuple,
+        DynamicTupleStruct, ItemTypes, KeyType, ListInfo, Map, MapInfo, NamedField, Reflect,
+        ReflectDefault, Struct, StructInfo, Tuple, TupleInfo, TupleStruct, TupleStructInfo,
+        TypeIdentity, TypeInfo,
+    },
     err::{ConvResult, ErrorType as ErrTy, ErrorType::GenericUnsupported as TODO, MResult},
-    newtype, DynRefl,
+    newtype,
+    options::{Context, DuplicateFields},
+    shape,
+    typed::ReflectFromReflect,
+    value::{self, ReflectBitflags, ReflectSet},
+    visit::Field,
+    DynRefl,
 };
 
-type Reg = TypeRegistry;
-type FieldIter = Box<dyn Iterator<Item = Field>>;
-type Field = ThunkField;
+type Reg<'r> = Context<'r>;
+pub(crate) type FieldIter = Box<dyn Iterator<Item = Field>>;
+/// The name of the field marking a struct or map as built from its `Default`.
+pub(crate) const DEFAULT_MARKER: &str = "@default";
+/// The name of the property marking a node as kept in the file, but skipped.
+pub(crate) const DISABLED_MARKER: &str = "@disabled";
+
+/// The name of the node including its children only with a feature enabled.
+pub(crate) const ONLY_IF: &str = "only-if";
+
+/// Whether `field` is skipped: a node declared with `@disabled=true`, or the
+/// `@disabled` property itself.
+fn is_disabled(field: &Field) -> bool {
+    let is_marker = field.is_entry() && field.name().map_or(false, |n| &*n == DISABLED_MARKER);
+    let disabled = field.property(DISABLED_MARKER);
+    is_marker || disabled.map_or(false, |value| value.as_bool() == Some(true))
+}
+/// The feature of `field` if it is an `only-if feature="name"` node.
+fn only_if_feature(field: &Field) -> Option<String> {
+    let is_only_if = !field.is_entry() && field.name().map_or(false, |n| &*n == ONLY_IF);
+    let feature = is_only_if.then(|| field.property("feature"))??;
+    feature.as_string().map(str::to_owned)
+}
+/// The fields `field` declares: none if it is disabled, the children of an
+/// `only-if` node if its feature is in `features`, `field` itself otherwise.
+pub(crate) fn declared_fields(field: Field, features: Arc<BTreeSet<String>>) -> FieldIter {
+    if is_disabled(&field) {
+        return Box::new(iter::empty());
+    }
+    match only_if_feature(&field) {
+        Some(feature) if features.contains(&feature) => {
+            let children = field.value().unwrap_list().filter(|f| !f.is_entry());
+            Box::new(children.flat_map(move |f| declared_fields(f, features.clone())))
+        }
+        Some(_) => Box::new(iter::empty()),
+        None => Box::new(iter::once(field)),
+    }
+}
 trait Infos {
     type DynamicWrapper: Builder<Info = Self>;
     fn name(&self) -> &'static str;
+    fn id(&self) -> TypeId;
     fn new_dynamic(&self, node: FieldIter, span: Span, reg: &Reg) -> MResult<DynRefl> {
         Self::DynamicWrapper::new_dynamic(self, node, span, reg)
     }
@@ -35,7 +84,10 @@ macro_rules! impl_infos {
         impl Infos for $ty_name {
             type DynamicWrapper = Wrapper<$field, $ty_name, $dynamic>;
             fn name(&self) -> &'static str {
-                self.type_name()
+                self.full_name()
+            }
+            fn id(&self) -> TypeId {
+                self.reflected_id()
             }
         }
         impl<'i> FromInfo<&'i $ty_name> for $dynamic {
@@ -48,39 +100,96 @@ macro_rules! impl_infos {
 impl_infos! {MapInfo, Sstring, DynamicMap}
 impl_infos! {StructInfo, Sstring, DynamicStruct}
 impl_infos! {ListInfo, Span, DynamicList}
+impl_infos! {ArrayInfo, Span, ArrayItems}
 impl_infos! {TupleInfo, Span, DynamicTuple}
 impl_infos! {TupleStructInfo, Span, DynamicTupleStruct}
 
 pub(crate) fn from_expected(info: Option<&TypeInfo>, field: &Field, reg: &Reg) -> MResult<DynRefl> {
-    use TypeInfo::{List, Map, Struct, Tuple, TupleStruct};
+    use TypeInfo::{Array, List, Map, Struct, Tuple, TupleStruct, Value as Tvalue};
     let span = field.span();
     let is_first_named = field.is_first_named();
+    let features = reg.options.enabled_features();
     let fields = field.value().unwrap_list();
+    let fields: FieldIter =
+        Box::new(fields.flat_map(move |f| declared_fields(f, features.clone())));
     // TODO(reporting): have different return error types for the `new_dynamic`
     // stuff, and collect them so that you can report them together for errors
     // in the style "is missing fields XYZ" and avoid spamming errors
     match info {
         None => AnonTupleInfo.new_dynamic(fields, span, reg),
         Some(Map(v)) if !is_first_named => PairMapBuilder::new_dynamic(v, fields, span, reg),
-        Some(Map(v)) => v.new_dynamic(fields, span, reg),
-        Some(List(v)) => v.new_dynamic(fields, span, reg),
+        Some(Map(v)) => {
+            let fields: Vec<_> = fields.collect();
+            // Point at all the unnamed fields
+            let unnamed = Spanned::spanning(fields.iter().filter(|f| f.name().is_none()));
+            if let Some(unnamed) = unnamed {
+                let declared = declared_name(field);
+                let value = v.item_name();
+                let err = ErrTy::UnnamedMapDeclaration { declared, value }.spanned(&unnamed);
+                return MultiResult::Err(vec![err]);
+            }
+            v.new_dynamic(Box::new(fields.into_iter()), span, reg)
+        }
+        Some(List(v)) => {
+            let fields: Vec<_> = fields.collect();
+            let is_named = |f: &&Field| f.is_entry() && f.name().is_some();
+            if let Some(named) = fields.iter().find(is_named) {
+                // unwrap: `is_named` only accepts fields with a name
+                let field_name = named.name().unwrap().to_string();
+                let (declared, item) = (declared_name(field), v.item_name());
+                let err = ErrTy::NamedListDeclaration { field: field_name, declared, item };
+                // unwrap: there is at least the `named` field
+                let all_named = Spanned::spanning(fields.iter().filter(is_named)).unwrap();
+                return MultiResult::Err(vec![err.spanned(&all_named)]);
+            }
+            v.new_dynamic(Box::new(fields.into_iter()), span, reg)
+        }
+        Some(Array(v)) => v.new_dynamic(fields, span, reg),
         Some(Tuple(v)) => v.new_dynamic(fields, span, reg),
-        // Some(Tvalue(v)) => v.new_dynamic(fields, span, reg),
+        Some(Tvalue(v))
+            if reg
+                .get_type_data::<ReflectBitflags>(v.reflected_id())
+                .is_some() =>
+        {
+            // unwrap: we just checked `v` has bitflags type data
+            value::from_flags(reg, v, fields).unwrap()
+        }
+        Some(Tvalue(v)) if reg.get_type_data::<ReflectSet>(v.reflected_id()).is_some() => {
+            // unwrap: we just checked `v` has set type data
+            value::from_set(reg, v, fields).unwrap()
+        }
         Some(Struct(v)) if is_first_named => v.new_dynamic(fields, span, reg),
         Some(Struct(v)) => Wrapper::<_, _, AnonDynamicStruct>::new_dynamic(v, fields, span, reg),
         Some(TupleStruct(v)) => v.new_dynamic(fields, span, reg),
-        Some(_) => {
-            let msg = format!("cannot turn field into type: {field:?} \n {info:?}");
+        Some(info) => {
+            let (actual, expected) = (shape::of_field(field), shape::of_type(info));
+            let msg = format!("`{actual}` can't be turned into `{expected}`");
             MultiResult::Err(vec![TODO(msg).spanned(field)])
         }
     }
 }
+/// The name of `field` as written in kdl, for declaration examples.
+fn declared_name(field: &Field) -> String {
+    let name = field.name();
+    name.map_or_else(|| "-".to_owned(), |n| KdlIdentifier::from(&*n).to_string())
+}
 trait Primitive {
     type Field;
     type Info: Infos;
     fn set_name(&mut self, name: String);
-    fn add_boxed(&mut self, field: Self::Field, boxed: DynRefl) -> ConvResult<()>;
+    /// Add `boxed` as `field`, `duplicates` tells what to do if `field` is
+    /// already declared.
+    fn add_boxed(
+        &mut self,
+        field: Self::Field,
+        boxed: DynRefl,
+        duplicates: DuplicateFields,
+    ) -> ConvResult<()>;
     fn expected(&self, at_field: &Self::Field, info: &Self::Info) -> ConvResult<&'static str>;
+    /// Convert `field` into `expected`, the type of the field being added.
+    fn make_field(reg: &Reg, expected: &str, field: Field) -> MResult<DynRefl> {
+        newtype::make_dyn(reg, Some(expected), field)
+    }
     fn validate(&self, info: &Self::Info) -> Result<(), ErrTy>;
     fn reflect(self) -> Box<dyn Reflect>;
 }
@@ -88,30 +197,73 @@ trait Primitive {
 /// A Builder for maps declared as a pair of complex types rather than
 /// `name value` style.
 struct PairMapBuilder(DynamicMap, MapInfo);
+impl PairMapBuilder {
+    /// `key` as a value of the key type, so that it hashes and compares like
+    /// the keys of the map it is applied to.
+    ///
+    /// Compound keys are `Dynamic*` values without a hash, they are built with
+    /// the key type's `ReflectFromReflect`, or applied to its `Default` if it
+    /// has none.
+    fn concrete_key(&self, key: DynRefl, reg: &Reg) -> Result<DynRefl, ErrTy> {
+        let (key_name, key_id) = (self.1.key_name(), self.1.key_id());
+        if key.as_any().type_id() == key_id {
+            return Ok(key);
+        }
+        let unhashable = || ErrTy::UnhashableKey(key_name);
+        let concrete = match reg.get_type_data::<ReflectFromReflect>(key_id) {
+            Some(from_reflect) => from_reflect
+                .from_reflect(key.as_ref())
+                .ok_or(ErrTy::FromReflect { expected: key_name, actual: None })?,
+            None if key.reflect_hash().is_some() => return Ok(key),
+            None => {
+                let default = reg.get_type_data::<ReflectDefault>(key_id);
+                let mut concrete = default.ok_or_else(unhashable)?.default();
+                concrete.apply(key.as_ref());
+                concrete
+            }
+        };
+        match concrete.reflect_hash() {
+            Some(_) => Ok(concrete),
+            None => Err(unhashable()),
+        }
+    }
+}
 impl Builder for PairMapBuilder {
     type Info = MapInfo;
 
-    fn new(expected: &Self::Info) -> Self {
+    fn new(expected: &Self::Info, _: &Reg) -> Self {
         Self(DynamicMap::default(), expected.clone())
     }
     fn add_field(&mut self, field: Field, reg: &Reg) -> MResult<()> {
-        let mut err = MultiError::default();
         let field_count = match field.value_count() {
             Value::Bare(_) => 1,
             Value::List(i) => i,
         };
         if field_count != 2 {
-            let err = ErrTy::PairMapNotPair(field_count as u8).spanned(&field);
+            let err = ErrTy::PairMapNotPair(field_count).spanned(&field);
             return MultiResult::Err(vec![err]);
         }
-        let key_name = self.1.key_type_name();
-        let value_name = self.1.value_type_name();
+        let key_name = self.1.key_name();
+        let value_name = self.1.item_name();
         let mut fields = field.value().unwrap_list();
-        let key = newtype::make_dyn(reg, Some(key_name), fields.next().unwrap());
-        let value = newtype::make_dyn(reg, Some(value_name), fields.next().unwrap());
-        self.0
-            .insert_boxed(multi_try!(err, key), multi_try!(err, value));
-        err.into_result(())
+        // `value_count` doesn't account for template expansion
+        let (Some(key), Some(value)) = (fields.next(), fields.next()) else {
+            let err = ErrTy::PairMapNotPair(field_count).spanned(&field);
+            return MultiResult::Err(vec![err]);
+        };
+        let key_span = key.span();
+        let key = newtype::make_dyn(reg, Some(key_name), key);
+        let value = newtype::make_dyn(reg, Some(value_name), value);
+        MultiError::scope(|errs| {
+            let (key, value) = (key.or_accumulate(errs)?, value.or_accumulate(errs)?);
+            let key = self
+                .concrete_key(key, reg)
+                .map_err(|e| e.spanned(&key_span));
+            if let Some(key) = key.or_accumulate(errs) {
+                self.0.insert_boxed(key, value);
+            }
+            Some(())
+        })
     }
     fn complete(self) -> MultiResult<DynRefl, ErrTy> {
         MultiResult::Ok(Box::new(self.0))
@@ -121,17 +273,37 @@ impl Builder for PairMapBuilder {
 impl Primitive for DynamicMap {
     type Field = Sstring;
     type Info = MapInfo;
-    fn add_boxed(&mut self, field: Self::Field, boxed: DynRefl) -> ConvResult<()> {
+    fn add_boxed(
+        &mut self,
+        field: Sstring,
+        boxed: DynRefl,
+        dups: DuplicateFields,
+    ) -> ConvResult<()> {
         let field_name = Box::new(field.to_string());
         if self.get(&*field_name).is_some() {
-            let name = self.name().to_owned();
-            return Err(ErrTy::MultipleSameField { name, field: *field_name }.spanned(&field));
+            match dups {
+                DuplicateFields::KeepFirst => return Ok(()),
+                DuplicateFields::KeepLast => {}
+                DuplicateFields::Reject => {
+                    let (name, field_name) = (self.name().to_owned(), *field_name);
+                    let err = ErrTy::MultipleSameField { name, field: field_name };
+                    return Err(err.spanned(&field));
+                }
+            }
         }
         self.insert_boxed(field_name, boxed);
         Ok(())
     }
-    fn expected(&self, _: &Self::Field, info: &MapInfo) -> ConvResult<&'static str> {
-        Ok(info.value_type_name())
+    /// Named entries have `String` keys, other key types must be declared
+    /// as pairs, see [`PairMapBuilder`].
+    fn expected(&self, field: &Self::Field, info: &MapInfo) -> ConvResult<&'static str> {
+        if info.key_id() != TypeId::of::<String>() {
+            let expected = info.key_name();
+            let actual = any::type_name::<String>().to_owned();
+            let err = ErrTy::TypeMismatch { expected, actual, shape: None };
+            return Err(err.spanned(field));
+        }
+        Ok(info.item_name())
     }
     fn set_name(&mut self, name: String) {
         self.set_name(name);
@@ -146,12 +318,16 @@ impl Primitive for DynamicMap {
 impl Primitive for DynamicList {
     type Field = Span;
     type Info = ListInfo;
-    fn add_boxed(&mut self, _: Span, boxed: DynRefl) -> ConvResult<()> {
+    fn add_boxed(&mut self, _: Span, boxed: DynRefl, _: DuplicateFields) -> ConvResult<()> {
         self.push_box(boxed);
         Ok(())
     }
     fn expected(&self, _: &Span, info: &Self::Info) -> ConvResult<&'static str> {
-        Ok(info.item_type_name())
+        Ok(info.item_name())
+    }
+    /// Item nodes are either anonymous `-` nodes or named after the item type.
+    fn make_field(reg: &Reg, expected: &str, field: Field) -> MResult<DynRefl> {
+        newtype::make_named_dyn(reg, Some(expected), field)
     }
     fn set_name(&mut self, name: String) {
         self.set_name(name);
@@ -163,20 +339,77 @@ impl Primitive for DynamicList {
         Box::new(self)
     }
 }
+/// The items of a fixed-size array, `DynamicArray` can't grow, so it is only
+/// created once all items are declared.
+#[derive(Default)]
+struct ArrayItems {
+    name: String,
+    items: Vec<DynRefl>,
+}
+impl Primitive for ArrayItems {
+    type Field = Span;
+    type Info = ArrayInfo;
+    fn add_boxed(&mut self, _: Span, boxed: DynRefl, _: DuplicateFields) -> ConvResult<()> {
+        self.items.push(boxed);
+        Ok(())
+    }
+    fn expected(&self, span: &Span, info: &Self::Info) -> ConvResult<&'static str> {
+        let requested = self.items.len() as u32;
+        let actual = info.capacity() as u32;
+        if requested >= actual {
+            let name = info.full_name();
+            return Err(ErrTy::TooManyFields { name, actual, requested }.spanned(span));
+        }
+        Ok(info.item_name())
+    }
+    /// Array items are declared like list items.
+    fn make_field(reg: &Reg, expected: &str, field: Field) -> MResult<DynRefl> {
+        DynamicList::make_field(reg, expected, field)
+    }
+    fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+    fn validate(&self, info: &Self::Info) -> Result<(), ErrTy> {
+        let actual = self.items.len() as u32;
+        let expected = info.capacity() as u32;
+        if actual != expected {
+            Err(ErrTy::NotEnoughArrayItems { name: info.full_name(), actual, expected })
+        } else {
+            Ok(())
+        }
+    }
+    fn reflect(self) -> Box<dyn Reflect> {
+        let mut array = DynamicArray::new(self.items.into_boxed_slice());
+        array.set_name(self.name);
+        Box::new(array)
+    }
+}
 impl Primitive for DynamicStruct {
     type Field = Sstring;
     type Info = StructInfo;
-    fn add_boxed(&mut self, field: Sstring, boxed: DynRefl) -> ConvResult<()> {
+    fn add_boxed(
+        &mut self,
+        field: Sstring,
+        boxed: DynRefl,
+        dups: DuplicateFields,
+    ) -> ConvResult<()> {
         if self.field(&field).is_some() {
-            let name = self.name().to_owned();
-            let field_name = field.to_string();
-            return Err(ErrTy::MultipleSameField { name, field: field_name }.spanned(&field));
+            match dups {
+                DuplicateFields::KeepFirst => return Ok(()),
+                // `insert_boxed` replaces the existing field
+                DuplicateFields::KeepLast => {}
+                DuplicateFields::Reject => {
+                    let (name, field_name) = (self.name().to_owned(), field.to_string());
+                    let err = ErrTy::MultipleSameField { name, field: field_name };
+                    return Err(err.spanned(&field));
+                }
+            }
         }
         self.insert_boxed(&field, boxed);
         Ok(())
     }
     fn expected(&self, field: &Sstring, info: &Self::Info) -> ConvResult<&'static str> {
-        let name_type = |field: &NamedField| (field.name().to_owned(), field.type_name());
+        let name_type = |field: &NamedField| (field.name().to_owned(), field.full_name());
         let err = || {
             ErrTy::NoSuchStructField {
                 name: info.name(),
@@ -185,14 +418,14 @@ impl Primitive for DynamicStruct {
             }
             .spanned(field)
         };
-        info.field(field).ok_or_else(err).map(|f| f.type_name())
+        info.field(field).ok_or_else(err).map(|f| f.full_name())
     }
     fn set_name(&mut self, name: String) {
         self.set_name(name);
     }
     fn validate(&self, info: &Self::Info) -> Result<(), ErrTy> {
-        let actual = self.field_len() as u8;
-        let expected = info.field_len() as u8;
+        let actual = self.field_len() as u32;
+        let expected = info.field_len() as u32;
         if actual != expected {
             let name = info.name();
             // TODO(reporting): find name of missing fields and add them to error
@@ -201,7 +434,7 @@ impl Primitive for DynamicStruct {
             let missing = expected
                 .iter()
                 .enumerate()
-                .filter_map(|(i, n)| is_missing(n).then_some(i as u8))
+                .filter_map(|(i, n)| is_missing(n).then_some(i as u32))
                 .collect();
             Err(ErrTy::NotEnoughStructFields { name, missing, expected })
         } else {
@@ -213,27 +446,31 @@ impl Primitive for DynamicStruct {
     }
 }
 
+/// The type name of anonymous tuples, usable as node name or type annotation
+/// where the expected type is unknown.
+pub(crate) const ANON_TUPLE: &str = "Tuple";
+
 // TODO(??): consider explicit declaration of tuple length
 struct AnonTupleInfo;
 impl Infos for AnonTupleInfo {
     type DynamicWrapper = AnonTupleBuilder;
     fn name(&self) -> &'static str {
-        "Tuple"
+        ANON_TUPLE
+    }
+    fn id(&self) -> TypeId {
+        TypeId::of::<DynamicTuple>()
     }
 }
 struct AnonTupleBuilder(DynamicTuple);
 impl Builder for AnonTupleBuilder {
     type Info = AnonTupleInfo;
 
-    fn new(_: &Self::Info) -> Self {
+    fn new(_: &Self::Info, _: &Reg) -> Self {
         Self(DynamicTuple::default())
     }
 
     fn add_field(&mut self, field: Field, reg: &Reg) -> MResult<()> {
-        let mut errors = MultiError::default();
-        let value = multi_try!(errors, newtype::make_named_dyn(reg, None, field));
-        self.0.insert_boxed(value);
-        errors.into_result(())
+        newtype::make_named_dyn(reg, None, field).map(|value| self.0.insert_boxed(value))
     }
 
     fn complete(self) -> MultiResult<DynRefl, ErrTy> {
@@ -251,21 +488,23 @@ impl<'i> FromInfo<&'i StructInfo> for AnonDynamicStruct {
 impl Primitive for AnonDynamicStruct {
     type Field = Span;
     type Info = StructInfo;
-    fn add_boxed(&mut self, _: Span, boxed: DynRefl) -> ConvResult<()> {
+    fn add_boxed(&mut self, _: Span, boxed: DynRefl, _: DuplicateFields) -> ConvResult<()> {
         let next_index = self.0.field_len();
-        let next_field = self.1.field_at(next_index).unwrap();
-        self.0.insert_boxed(next_field.name(), boxed);
+        // Extra fields are already reported by `expected` as `TooManyFields`
+        if let Some(next_field) = self.1.field_at(next_index) {
+            self.0.insert_boxed(next_field.name(), boxed);
+        }
         Ok(())
     }
     fn expected(&self, span: &Span, info: &Self::Info) -> ConvResult<&'static str> {
-        let requested = self.0.field_len() as u8;
+        let requested = self.0.field_len() as u32;
         let err = || {
-            let actual = info.field_len() as u8;
+            let actual = info.field_len() as u32;
             ErrTy::TooManyFields { name: info.name(), actual, requested }.spanned(span)
         };
         info.field_at(requested as usize)
             .ok_or_else(err)
-            .map(|f| f.type_name())
+            .map(|f| f.full_name())
     }
     fn set_name(&mut self, name: String) {
         self.0.set_name(name);
@@ -273,8 +512,8 @@ impl Primitive for AnonDynamicStruct {
     fn validate(&self, info: &Self::Info) -> Result<(), ErrTy> {
         // The only possible error here is that there are not enough fields, since we
         // already check for too many, and we assume the correct types are provided.
-        let actual = self.0.field_len() as u8;
-        let expected = info.field_len() as u8;
+        let actual = self.0.field_len() as u32;
+        let expected = info.field_len() as u32;
         if actual != expected {
             // TODO(reporting): Have a variant where the type name is stored
             Err(ErrTy::NotEnoughTupleFields { actual, expected })
@@ -289,19 +528,19 @@ impl Primitive for AnonDynamicStruct {
 impl Primitive for DynamicTuple {
     type Field = Span;
     type Info = TupleInfo;
-    fn add_boxed(&mut self, _: Span, boxed: DynRefl) -> ConvResult<()> {
+    fn add_boxed(&mut self, _: Span, boxed: DynRefl, _: DuplicateFields) -> ConvResult<()> {
         self.insert_boxed(boxed);
         Ok(())
     }
     fn expected(&self, span: &Span, info: &Self::Info) -> ConvResult<&'static str> {
-        let requested = self.field_len() as u8;
+        let requested = self.field_len() as u32;
         let err = || {
-            let actual = info.field_len() as u8;
+            let actual = info.field_len() as u32;
             ErrTy::TooManyFields { name: "Tuple", actual, requested }.spanned(span)
         };
         info.field_at(requested as usize)
             .ok_or_else(err)
-            .map(|f| f.type_name())
+            .map(|f| f.full_name())
     }
     fn set_name(&mut self, name: String) {
         self.set_name(name);
@@ -309,8 +548,8 @@ impl Primitive for DynamicTuple {
     fn validate(&self, info: &Self::Info) -> Result<(), ErrTy> {
         // The only possible error here is that there are not enough fields, since we
         // already check for too many, and we assume the correct types are provided.
-        let actual = self.field_len() as u8;
-        let expected = info.field_len() as u8;
+        let actual = self.field_len() as u32;
+        let expected = info.field_len() as u32;
         if actual != expected {
             Err(ErrTy::NotEnoughTupleFields { actual, expected })
         } else {
@@ -324,19 +563,19 @@ impl Primitive for DynamicTuple {
 impl Primitive for DynamicTupleStruct {
     type Field = Span;
     type Info = TupleStructInfo;
-    fn add_boxed(&mut self, _: Span, boxed: DynRefl) -> ConvResult<()> {
+    fn add_boxed(&mut self, _: Span, boxed: DynRefl, _: DuplicateFields) -> ConvResult<()> {
         self.insert_boxed(boxed);
         Ok(())
     }
     fn expected(&self, span: &Span, info: &Self::Info) -> ConvResult<&'static str> {
-        let requested = self.field_len() as u8;
+        let requested = self.field_len() as u32;
         let err = || {
-            let actual = info.field_len() as u8;
+            let actual = info.field_len() as u32;
             ErrTy::TooManyFields { name: info.name(), actual, requested }.spanned(span)
         };
         info.field_at(requested as usize)
             .ok_or_else(err)
-            .map(|f| f.type_name())
+            .map(|f| f.full_name())
     }
     fn set_name(&mut self, name: String) {
         self.set_name(name);
@@ -344,8 +583,8 @@ impl Primitive for DynamicTupleStruct {
     fn validate(&self, info: &Self::Info) -> Result<(), ErrTy> {
         // The only possible error here is that there are not enough fields, since we
         // already check for too many, and we assume the correct types are provided.
-        let actual = self.field_len() as u8;
-        let expected = info.field_len() as u8;
+        let actual = self.field_len() as u32;
+        let expected = info.field_len() as u32;
         if actual != expected {
             // TODO(reporting): Have a variant where the type name is stored
             Err(ErrTy::NotEnoughTupleFields { actual, expected })
@@ -360,7 +599,7 @@ impl Primitive for DynamicTupleStruct {
 
 trait Builder: Sized {
     type Info: Infos;
-    fn new(expected: &Self::Info) -> Self;
+    fn new(expected: &Self::Info, reg: &Reg) -> Self;
     fn add_field(&mut self, field: Field, reg: &Reg) -> MResult<()>;
     fn complete(self) -> MultiResult<DynRefl, ErrTy>;
     fn new_dynamic(
@@ -370,7 +609,7 @@ trait Builder: Sized {
         reg: &Reg,
     ) -> MResult<DynRefl> {
         let mut errors = MultiError::default();
-        let mut builder = Self::new(expected);
+        let mut builder = Self::new(expected, reg);
         for field in value {
             let _ = errors.optionally(builder.add_field(field, reg));
         }
@@ -385,35 +624,97 @@ fn add_expected<P, T, I>(field: Field, acc: &mut P, name: T, reg: &Reg, info: &I
 where
     P: Primitive<Field = T, Info = I>,
 {
-    let mut errors = MultiError::default();
-    let expected = errors.optionally(acc.expected(&name, info));
-    let value = multi_try!(errors, newtype::make_dyn(reg, expected, field));
-    let _ = errors.optionally(acc.add_boxed(name, value));
-    errors.into_result(())
+    MultiError::scope(|errors| {
+        // The content of a field that doesn't exist in the type can't be checked
+        let Some(expected) = acc.expected(&name, info).or_accumulate(errors) else {
+            return Some(());
+        };
+        let value = P::make_field(reg, expected, field).or_accumulate(errors)?;
+        let duplicates = reg.options.on_duplicate();
+        let _ = acc.add_boxed(name, value, duplicates).or_accumulate(errors);
+        Some(())
+    })
 }
 struct Wrapper<F, I, T> {
     acc: T,
     info: I,
+    /// The `Default` of the type, when declared with `@default`, the declared
+    /// fields are applied to it instead of requiring all fields.
+    default: Option<DynRefl>,
+    /// The first declaration of each named field, to point at it when a
+    /// field is declared twice.
+    declared: Vec<Sstring>,
     // This exists so that it's possible to implement Builder separately for
     // wrappers wrapping Field=() and Field=String.
     _f: PhantomData<F>,
 }
+/// Whether the `@default` field `marker` builds the value from its `Default`:
+/// `@default` and `@default=true` do, `@default=false` doesn't.
+fn default_requested(marker: &Field) -> Result<bool, ErrTy> {
+    let Value::Bare(value) = marker.value() else {
+        // A `@default` node without arguments
+        return Ok(true);
+    };
+    value.as_bool().ok_or_else(|| {
+        let actual = shape::of_field(marker);
+        ErrTy::TypeMismatch { expected: "bool", actual, shape: None }
+    })
+}
+impl<F, I: Infos, T> Wrapper<F, I, T> {
+    fn new_wrapper(acc: T, info: &I, reg: &Reg) -> Self
+    where
+        I: Clone,
+    {
+        let default = reg.get_type_data::<ReflectDefault>(info.id());
+        let fill = reg.options.fills_defaults();
+        Self {
+            acc,
+            info: info.clone(),
+            default: default.filter(|_| fill).map(ReflectDefault::default),
+            declared: Vec::new(),
+            _f: PhantomData,
+        }
+    }
+    /// Read the `@default` field `marker`, only the `Default` of the type
+    /// needs all fields to be declared when it is `false`.
+    fn set_default(&mut self, marker: &Field, reg: &Reg) -> MResult<()> {
+        let span = marker.span();
+        let mut errors = MultiError::default();
+        match default_requested(marker) {
+            Ok(false) => self.default = None,
+            Ok(true) => match reg.get_type_data::<ReflectDefault>(self.info.id()) {
+                Some(default) => self.default = Some(default.default()),
+                None => errors.add_error(ErrTy::NoDefault(self.info.name()).spanned(&span)),
+            },
+            Err(err) => errors.add_error(err.spanned(&span)),
+        }
+        errors.into_result(())
+    }
+}
 impl<T> Builder for Wrapper<Span, T::Info, T>
 where
     T: Primitive<Field = Span> + for<'a> FromInfo<&'a T::Info>,
     T::Info: Clone,
 {
     type Info = T::Info;
-    fn new(expected: &Self::Info) -> Self {
+    fn new(expected: &Self::Info, reg: &Reg) -> Self {
         let mut acc = T::from_info(expected);
         acc.set_name(expected.name().to_owned());
-        Self { acc, info: expected.clone(), _f: PhantomData }
+        Self::new_wrapper(acc, expected, reg)
     }
     fn add_field(&mut self, field: Field, reg: &Reg) -> MResult<()> {
+        if field.name().map_or(false, |name| &*name == DEFAULT_MARKER) {
+            return self.set_default(&field, reg);
+        }
         let span = field.span();
         add_expected(field, &mut self.acc, span, reg, &self.info)
     }
     fn complete(self) -> MultiResult<DynRefl, ErrTy> {
+        if let Some(mut default) = self.default {
+            // The fields that are not declared are those of the default
+            default.apply(self.acc.reflect().as_ref());
+            return MultiResult::Ok(default);
+        }
         let mut errors = MultiError::default();
         let _ = errors.optionally(self.acc.validate(&self.info));
         errors.into_result(self.acc.reflect())
@@ -426,22 +727,55 @@ where
     T::Info: Clone,
 {
     type Info = T::Info;
-    fn new(expected: &Self::Info) -> Self {
+    fn new(expected: &Self::Info, reg: &Reg) -> Self {
         let mut acc = T::from_info(expected);
         acc.set_name(expected.name().to_owned());
-        Self { acc, info: expected.clone(), _f: PhantomData }
+        Self::new_wrapper(acc, expected, reg)
     }
     fn add_field(&mut self, field: Field, reg: &Reg) -> MResult<()> {
         let span = field.span();
-        if let Some(name) = field.name() {
-            add_expected(field, &mut self.acc, name, reg, &self.info)
-        } else {
-            let mut errors = MultiError::default();
-            errors.add_error(ErrTy::UnnamedMapField { name: self.info.name() }.spanned(&span));
-            errors.into_result(())
+        match field.name() {
+            Some(name) if &*name == DEFAULT_MARKER => self.set_default(&field, reg),
+            Some(mut name) => {
+                // Deprecated field names are read as their replacement, with a warning
+                let mut warnings = MultiError::default();
+                let replacement = reg.options.replacement(&name);
+                if let Some(replacement) = replacement {
+                    if self.acc.expected(&name, &self.info).is_err() {
+                        let (old, new) = (name.to_string(), replacement.to_owned());
+                        let deprecated = ErrTy::Deprecated { name: old, replacement: new };
+                        warnings.add_error(deprecated.spanned(&name));
+                        name.inner = replacement.to_owned().into();
+                    }
+                }
+                let first = self.declared.iter().find(|declared| ***declared == *name);
+                let first = first.map(Spanned::span);
+                if first.is_none() {
+                    self.declared.push(name.clone());
+                }
+                let added = add_expected(field, &mut self.acc, name, reg, &self.info);
+                added
+                    .map_err(|err| match (first, err.source.as_ref()) {
+                        (Some(first), ErrTy::MultipleSameField { .. }) => {
+                            err.with_label(&first, "first declared here")
+                        }
+                        _ => err,
+                    })
+                    .combine(warnings)
+            }
+            None => {
+                let mut errors = MultiError::default();
+                errors.add_error(ErrTy::UnnamedMapField { name: self.info.name() }.spanned(&span));
+                errors.into_result(())
+            }
         }
     }
     fn complete(self) -> MultiResult<DynRefl, ErrTy> {
+        if let Some(mut default) = self.default {
+            // The missing fields are those of the default, no need to validate
+            default.apply(self.acc.reflect().as_ref());
+            return MultiResult::Ok(default);
+        }
         let mut errors = MultiError::default();
         let _ = errors.optionally(self.acc.validate(&self.info));
         errors.into_result(self.acc.reflect())
@@ -1,9 +1,10 @@
-use std::fmt::Write;
+use std::fmt::{self, Write};
 
 #[cfg(feature = "fancy-errors")]
-use miette::Diagnostic;
+use miette::{Diagnostic, LabeledSpan};
 
-use multierr_span::Spanned;
+use kdl::KdlError;
+use multierr_span::{Span, Spanned};
 
 mod miette_compat {
     #[cfg(feature = "fancy-errors")]
@@ -11,58 +12,139 @@ mod miette_compat {
 
     #[cfg(not(feature = "fancy-errors"))]
     #[derive(Clone, Copy, Debug, PartialEq)]
-    pub(super) struct SourceSpan(template_kdle::span::Span);
+    pub struct SourceSpan(multierr_span::Span);
     #[cfg(not(feature = "fancy-errors"))]
     impl SourceSpan {
-        pub(super) fn offset(&self) -> usize {
+        pub fn offset(&self) -> usize {
             self.0.offset as usize
         }
-        pub(super) fn len(&self) -> usize {
+        pub fn len(&self) -> usize {
             self.0.size as usize
         }
     }
     #[cfg(not(feature = "fancy-errors"))]
     impl From<(usize, usize)> for SourceSpan {
         fn from((offset, size): (usize, usize)) -> Self {
-            Self(super::Span { offset: offset as u32, size: size as u32 })
+            Self(multierr_span::Span { offset: offset as u32, size: size as u32 })
         }
     }
 }
 use miette_compat::*;
-use template_kdl::{multi_err::MultiResult, ExportedBindings};
+use template_kdl::multi_err::MultiResult;
 
-use crate::DynRefl;
+use crate::{DeserOptions, DynRefl, ExportedBindings};
 
 // TODO: consider using TypeId instead of &'static str and String, and convert
 // into "proper" error message at one point with the help of the registry
 /// Ways for the conversion from KDL to Reflect to fail
-#[cfg_attr(feature = "fancy-errors", derive(Diagnostic), diagnostic())]
+///
+/// The message starts with the [`Error::path`], it is also the message of
+/// the `Diagnostic`.
 #[derive(Debug, Clone, thiserror::Error, PartialEq)]
-#[error("{source}")]
+#[error("{}{source}", in_path(.path))]
 pub struct Error {
     pub source: Box<ErrorType>,
-    #[cfg_attr(feature = "fancy-errors", label)]
     pub span: SourceSpan,
+    /// Other places in the source relevant to this error, such as the first
+    /// declaration of a duplicate field.
+    pub labels: Vec<SecondaryLabel>,
+    /// Names of the nodes leading to the error, from the document root.
+    path: Vec<String>,
 
     #[cfg(feature = "fancy-errors")]
-    #[help]
     help: Option<String>,
 }
+#[cfg(feature = "fancy-errors")]
+impl Diagnostic for Error {
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.help.as_ref().map(|help| Box::new(help) as _)
+    }
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let primary = LabeledSpan::new_with_span(None, self.span);
+        let secondary = self
+            .labels
+            .iter()
+            .map(|label| LabeledSpan::new_with_span(Some(label.text.clone()), label.span));
+        Some(Box::new(std::iter::once(primary).chain(secondary)))
+    }
+}
+/// `in Bar > regulars: `, the start of the message of an error in the nodes
+/// of `path`.
+fn in_path(path: &[String]) -> String {
+    match path {
+        [] => String::new(),
+        path => format!("in {}: ", path.join(" > ")),
+    }
+}
+/// A span of the source related to an [`Error`], with an explanation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryLabel {
+    pub span: SourceSpan,
+    pub text: String,
+}
 impl From<template_kdl::err::Error> for Error {
     fn from(terr: template_kdl::err::Error) -> Self {
         let span = terr.span();
         Self::new(&span, terr.source.into())
     }
 }
+impl From<KdlError> for Error {
+    fn from(err: KdlError) -> Self {
+        let (offset, size) = (err.span.offset() as u32, err.span.len() as u32);
+        let error = ErrorType::Syntax { message: err.to_string(), help: err.help };
+        Self::new(&Span { offset, size }, error)
+    }
+}
 impl Error {
     pub(super) fn new(span: &impl Spanned, error: ErrorType) -> Self {
         Self {
             span: span.span().pair().into(),
+            labels: Vec::new(),
+            path: Vec::new(),
             #[cfg(feature = "fancy-errors")]
             help: error.help(),
             source: Box::new(error),
         }
     }
+    /// An error of the application about the document, such as a value
+    /// outside of its valid range, pointing at `span`.
+    ///
+    /// The fields of [`template_kdl::read_plain`] have the spans of the
+    /// nodes and entries of a document.
+    pub fn custom(span: &impl Spanned, message: impl Into<String>) -> Self {
+        Self::new(span, ErrorType::Custom(message.into()))
+    }
+    /// Suggest how to fix this error, shown with the `fancy-errors` feature.
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        #[cfg(feature = "fancy-errors")]
+        {
+            self.help = Some(help.into());
+        }
+        #[cfg(not(feature = "fancy-errors"))]
+        let _ = help;
+        self
+    }
+    /// Point at `span` with `text` in addition to the error's own span.
+    pub fn with_label(mut self, span: &impl Spanned, text: impl Into<String>) -> Self {
+        let span = span.span().pair().into();
+        self.labels.push(SecondaryLabel { span, text: text.into() });
+        self
+    }
+    /// Mark this error as happening inside the node `name`.
+    pub(super) fn within(mut self, name: &str) -> Self {
+        self.path.insert(0, name.to_owned());
+        self
+    }
+    /// The names of the nodes leading to this error, such as
+    /// `Bar > regulars > pierre > weight`, to locate it without the source.
+    pub fn path(&self) -> String {
+        self.path.join(" > ")
+    }
+    /// Whether this doesn't prevent deserialization. Conversion can still turn
+    /// it into an error, see [`DeserOptions`](crate::DeserOptions).
+    pub fn is_warning(&self) -> bool {
+        self.source.is_warning()
+    }
     #[cfg(test)]
     pub(super) fn offset(&self) -> usize {
         self.span.offset()
@@ -84,14 +166,35 @@ pub enum ErrorType {
     TypeMismatch {
         expected: &'static str,
         actual: String,
+        /// The fields of `expected`, if it has any, see [`crate::shape`].
+        shape: Option<String>,
     },
-    #[error("Invalid integer, value {0} out of bound for rust type: {1}")]
-    IntDomain(i64, &'static str),
+    #[error("Invalid integer, value {repr} out of bound for rust type: {ty}")]
+    IntDomain {
+        value: i128,
+        /// `value` as declared, such as `0xFF_FF`.
+        repr: String,
+        ty: &'static str,
+        /// The newtypes wrapping `ty`, from the most outer one.
+        wrappers: Vec<&'static str>,
+    },
+    #[error("Invalid integer, value {value} out of the domain of {ty}: {domain}")]
+    IntConstraint {
+        value: i64,
+        ty: &'static str,
+        domain: &'static str,
+    },
+    #[error("{0} cannot be exactly represented as a f32")]
+    LossyFloat(f64),
+    #[error("This item is already declared in this {0}")]
+    DuplicateSetItem(&'static str),
+    #[error("NaN is not accepted as a float value")]
+    RejectedNan,
     #[error("There is no such registered type: {0}")]
     NoSuchType(String),
     #[error("Expected a value in first entry field for type: {0}, got nothing")]
     NoValuesInNode(&'static str),
-    #[error("Anon tuples with unkown type had a field with unknown type")]
+    #[error("The type of this field is unknown, it must be declared explicitly")]
     UntypedTupleField,
     #[error("The field {field} is declared multiple time for struct {name}")]
     MultipleSameField { name: String, field: String },
@@ -101,72 +204,190 @@ pub enum ErrorType {
         name: &'static str,
         available: Vec<(String, &'static str)>,
     },
+    #[error("{requested} is not a field of {name}")]
+    NoSuchSchemaField {
+        requested: String,
+        name: String,
+        available: Vec<String>,
+    },
     #[error("Maps declared with pair style should only have two fields, this one has {0} fields")]
-    PairMapNotPair(u8),
+    PairMapNotPair(u32),
+    #[error("{0} can't be used as a map key, it has no registered Hash")]
+    UnhashableKey(&'static str),
     #[error("{name} has {actual} fields, but the declaration contains at least {requested}")]
     TooManyFields {
         name: &'static str,
-        actual: u8,
-        requested: u8,
+        actual: u32,
+        requested: u32,
     },
     #[error("Not all fields in {name} are declared.")]
     NotEnoughStructFields {
-        missing: Vec<u8>,
+        missing: Vec<u32>,
         name: &'static str,
         expected: Vec<String>,
     },
     #[error("{expected} fields were expected in this tuple, but only {actual} were declared")]
-    NotEnoughTupleFields { actual: u8, expected: u8 },
-    #[error("List cannot be declared using explicit positioning. expected `-`, got `{0}`")]
-    NamedListDeclaration(String),
+    NotEnoughTupleFields { actual: u32, expected: u32 },
+    #[error("{name} has {expected} items, but only {actual} were declared")]
+    NotEnoughArrayItems {
+        name: &'static str,
+        actual: u32,
+        expected: u32,
+    },
+    #[error("List cannot be declared using explicit positioning. expected `-`, got `{field}`")]
+    NamedListDeclaration {
+        field: String,
+        /// The name of the list node, for the declaration example.
+        declared: String,
+        item: &'static str,
+    },
+    #[error("This map is declared with named values, but this one has no name.")]
+    UnnamedMapDeclaration {
+        /// The name of the map node, for the declaration example.
+        declared: String,
+        value: &'static str,
+    },
     #[error("{name} requires all its field to be named, but one of them wasn't.")]
     UnnamedMapField { name: &'static str },
     #[error("The declaration of this Map started in tuple style, but this field has a name.")]
     TupleMapDeclarationMixup,
     #[error("Field at component declaration site.")]
     BadComponentTypeName,
+    #[error("\"{value}\" is not a valid {ty}: {reason}")]
+    ValueParse {
+        value: String,
+        ty: &'static str,
+        reason: String,
+    },
+    #[error("{requested} is not a flag of {name}")]
+    NoSuchFlag {
+        requested: String,
+        name: &'static str,
+        available: Vec<&'static str>,
+    },
+    #[error("Templating is disabled, the document must contain a single node")]
+    TemplatingDisabled,
+    #[error("{0} is declared with @default, but it has no registered Default")]
+    NoDefault(&'static str),
+    #[error("This document only exports templates, it doesn't declare a value")]
+    NotValue,
+    #[error("This document declares a value, it doesn't export templates")]
+    NotExports,
+    #[error("The declared value can't be converted into {expected}")]
+    FromReflect {
+        expected: &'static str,
+        /// The type the value was converted into, `None` if it is `expected`.
+        actual: Option<String>,
+    },
+    #[error("The field {field} of {name} is not declared")]
+    UndeclaredField { name: &'static str, field: String },
+    #[error("{name} has no field {field}")]
+    UnexpectedField { name: &'static str, field: String },
+    #[error("This value is nested deeper than the limit of {0} values")]
+    MaxDepthExceeded(u32),
+    #[error("`{name}` is deprecated, it is read as `{replacement}`")]
+    Deprecated { name: String, replacement: String },
+    #[error("The environment variable {0} is not allowed in documents")]
+    EnvNotAllowed(String),
+    #[error("The environment variable {0} is not set")]
+    EnvNotSet(String),
+    #[error("(env) values must be the name of an environment variable")]
+    EnvNotString,
+    #[error("Invalid path \"{path}\": {reason}")]
+    BadPath { path: String, reason: &'static str },
+    #[error("(path) values must be strings")]
+    PathNotString,
+    #[error("Invalid kdl: {message}")]
+    Syntax {
+        message: String,
+        help: Option<&'static str>,
+    },
+    /// An error reported by the application, see [`Error::custom`].
+    #[error("{0}")]
+    Custom(String),
 }
 impl ErrorType {
     pub(crate) fn spanned(self, span: &impl Spanned) -> Error {
         Error::new(span, self)
     }
+    fn is_warning(&self) -> bool {
+        match self {
+            Self::LossyFloat(_) => true,
+            Self::DuplicateSetItem(_) => true,
+            Self::Deprecated { .. } => true,
+            _ => false,
+        }
+    }
     #[cfg(feature = "fancy-errors")]
     fn help(&self) -> Option<String> {
         use strsim::levenshtein;
         use ErrorType::*;
-        let max_of = |ty: &str| -> i64 {
-            match ty {
-                "i8" => i8::MAX as i64,
-                "i16" => i16::MAX as i64,
-                "i32" => i32::MAX as i64,
-                "u8" => u8::MAX as i64,
-                "u16" => u16::MAX as i64,
-                "u32" => u32::MAX as i64,
-                _ => i64::MAX,
-            }
-        };
-        let representable = ["i8", "i16", "i32", "u8", "u16", "u32"];
         match self {
             Template(template) => template.help(),
             GenericUnsupported(_) =>Some("This error is on the TODO list!".to_owned()),
-            TypeMismatch { expected, .. } => Some(format!("You probably meant to declare a {expected}.")),
-            IntDomain(i, ty) if representable.contains(ty) && *i > max_of(ty) =>
-                Some(format!("{i} is larger than {}, the largest possible {ty}, try using a larger integer type.", max_of(ty))),
-            IntDomain(i, u_ty) if u_ty.starts_with('u') && i.is_negative() =>
-                Some(format!("Try replacing {u_ty} by i{}, or using a positive value.", u_ty.strip_prefix('u').unwrap())),
-            IntDomain(..) =>Some("Either use a larger interger type or update the value to be representable with your type.".to_owned()),
+            TypeMismatch { expected, shape: None, .. } => Some(format!("You probably meant to declare a {expected}.")),
+            TypeMismatch { expected, shape: Some(shape), .. } => Some(format!("You probably meant to declare a {expected}, its shape is `{shape}`.")),
+            IntDomain { value, repr, ty, wrappers } => {
+                let fitting = fitting_int(*value, ty.starts_with('u'));
+                let wrapped = if wrappers.is_empty() {
+                    String::new()
+                } else {
+                    format!(" (the field of {})", wrappers.join(" in "))
+                };
+                let value = if repr == &value.to_string() {
+                    repr.clone()
+                } else {
+                    format!("{repr} ({value})")
+                };
+                Some(format!("{value} doesn't fit in {ty}{wrapped}, the smallest integer type it fits in is {fitting}."))
+            }
+            IntConstraint { ty, domain, .. } => Some(format!("{ty} only accepts {domain}.")),
+            LossyFloat(_) => Some("Use a f64 if the exact value matters.".to_owned()),
+            DuplicateSetItem(_) => Some("Remove the repeated item, it is ignored.".to_owned()),
+            RejectedNan => Some("NaN is rejected by the `reject-nan` feature or `DeserOptions::reject_nan`, use a number, \"inf\" or \"-inf\".".to_owned()),
             NoSuchType(ty) => Some(format!("Try adding it to the type registry with `reg.register::<{ty}>()`.")),
             NoValuesInNode(ty) => Some(format!("{ty} has fields, you should specify their values.")),
-            NamedListDeclaration(_) => Some("Instead of using `foo=bar` use `bar`.".to_owned()),
+            NamedListDeclaration { declared, item, .. } => {
+                let example = match example_values(item) {
+                    Some(values) => format!("{declared} {}", values.join(" ")),
+                    None => format!("{declared} {{ - ...; - ...; }}"),
+                };
+                Some(format!("Instead of using `foo=bar` use `bar`, declare the list as `{example}`."))
+            }
+            UnnamedMapDeclaration { declared, value } => {
+                let [first, second, _] = example_values(value).unwrap_or(["...", "...", "..."]);
+                let example = format!("{declared} one={first} two={second}");
+                Some(format!("Add a key to the values, declare the map as `{example}`."))
+            }
             UnnamedMapField { .. } => Some("Add a key to the values.".to_owned()),
             BadComponentTypeName => Some("You are declaring a field type, but only components are expected here.".to_owned()),
+            ValueParse { .. } => None,
+            TemplatingDisabled => Some("Enable the `templating` feature of bevy_kdl_reflect_deser or remove the extra nodes.".to_owned()),
+            NotValue => Some("Read export documents with `read_exports`, or add a node after the `export` node.".to_owned()),
+            NotExports => Some("Add an `export` node listing the templates to export, or read the value with `deserialize_value`.".to_owned()),
+            FromReflect { expected, actual: Some(actual) } => Some(format!("It was converted into {actual}, which `FromReflect` doesn't accept as a {expected}.")),
+            FromReflect { expected, actual: None } => Some(format!("{expected} rejected it in its `FromReflect` implementation.")),
+            UndeclaredField { field, .. } => Some(format!("Spawning it would keep the previous value of {field}, declare it.")),
+            UnexpectedField { field, .. } => Some(format!("Spawning it would silently ignore {field}, remove it.")),
+            MaxDepthExceeded(_) => Some("Flatten the declaration, or raise the limit with `DeserOptions::max_depth`.".to_owned()),
+            Syntax { help, .. } => help.map(str::to_owned),
+            Custom(_) => None,
+            Deprecated { name, replacement } => Some(format!("Replace `{name}` with `{replacement}`.")),
+            EnvNotAllowed(name) => Some(format!("Add {name} to `DeserOptions::env_vars` if documents may read it.")),
+            EnvNotSet(name) => Some(format!("Set {name} before converting the document, or declare the value directly.")),
+            EnvNotString => Some("Declare the name of the variable as a string, such as `(env)\"ASSET_QUALITY\"`.".to_owned()),
+            BadPath { .. } => Some("Paths are relative to the asset folder, such as `\"textures/player.png\"`.".to_owned()),
+            PathNotString => Some("Declare the path as a string, such as `(path)\"textures/player.png\"`.".to_owned()),
+            NoDefault(ty) => Some(format!("Add `#[reflect(Default)]` to {ty}, or declare all its fields and remove @default.")),
 
-            PairMapNotPair(_) => None,
-            UntypedTupleField => None,
+            PairMapNotPair(_) => Some("Declare each pair as a `-` node with the key and the value, such as `- { Key ...; Value ...; }`.".to_owned()),
+            UnhashableKey(ty) => Some(format!("Add `#[reflect(Hash)]` to {ty}, and `#[reflect(Default)]` if it isn't a `reflect_value` type.")),
+            UntypedTupleField => Some("Fields of a `Tuple`, and documents read without an expected type, must declare their type: use a type annotation such as `(u8)25` or the type as node name such as `String \"text\"`. Use `Tuple` or `(Tuple)` to declare an anonymous tuple.".to_owned()),
             TupleMapDeclarationMixup => None,
             MultipleSameField { .. } => Some("Remove one of the fields".to_owned()),
             TooManyFields { .. } => Some("Remove the extraneous one".to_owned()),
             NotEnoughTupleFields {..} =>  Some("Add the missing ones".to_owned()) ,
+            NotEnoughArrayItems {..} =>  Some("Add the missing ones".to_owned()) ,
             NotEnoughStructFields { name, expected, missing } => {
                 let mut missing_fields = String::with_capacity(missing.len() * 12);
                 let mut first = true;
@@ -193,13 +414,64 @@ impl ErrorType {
                 }
                 Some(format!("{name}'s field are {existing}. Maybe you meant {closest}?"))
             }
+            NoSuchSchemaField { requested, name, available } => {
+                let closest = available.iter().min_by_key(|s| levenshtein(requested, s));
+                let closest = closest.map_or("something else", |s| s);
+                let existing = available.join(", ");
+                Some(format!("{name}'s field are {existing}. Maybe you meant {closest}?"))
+            }
+            NoSuchFlag { requested, name, available } => {
+                let closest = available.iter().min_by_key(|s| levenshtein(requested, s));
+                let closest = closest.map_or("something else", |s| s);
+                let existing = available.join(", ");
+                Some(format!("{name}'s flags are {existing}. Maybe you meant {closest}?"))
+            }
         }
     }
 }
 
+/// Example kdl values of the primitive type `ty`, `None` if it isn't one.
+#[cfg(feature = "fancy-errors")]
+fn example_values(ty: &str) -> Option<[&'static str; 3]> {
+    match ty {
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => Some(["1", "2", "3"]),
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => Some(["1", "-2", "3"]),
+        "f32" | "f64" => Some(["1.0", "2.5", "3.0"]),
+        "bool" => Some(["true", "false", "true"]),
+        "alloc::string::String" => Some([r#""a""#, r#""b""#, r#""c""#]),
+        _ => None,
+    }
+}
+
+/// The smallest integer type `value` fits in, prefering unsigned types if
+/// `unsigned`.
+#[cfg(feature = "fancy-errors")]
+fn fitting_int(value: i128, unsigned: bool) -> &'static str {
+    const INTS: [(&str, i128, i128); 10] = [
+        ("u8", 0, u8::MAX as i128),
+        ("u16", 0, u16::MAX as i128),
+        ("u32", 0, u32::MAX as i128),
+        ("u64", 0, u64::MAX as i128),
+        ("u128", 0, i128::MAX),
+        ("i8", i8::MIN as i128, i8::MAX as i128),
+        ("i16", i16::MIN as i128, i16::MAX as i128),
+        ("i32", i32::MIN as i128, i32::MAX as i128),
+        ("i64", i64::MIN as i128, i64::MAX as i128),
+        ("i128", i128::MIN, i128::MAX),
+    ];
+    let candidates = INTS
+        .iter()
+        .filter(|(ty, ..)| unsigned || ty.starts_with('i'));
+    let mut fitting = candidates.filter(|(_, min, max)| (*min..=*max).contains(&value));
+    fitting.next().map_or("i128", |(ty, ..)| ty)
+}
+
+/// All the errors found while converting a document.
+///
+/// Its [`source`](std::error::Error::source) is the first error, iterate over
+/// it to triage the individual errors.
 #[cfg_attr(feature = "fancy-errors", derive(Diagnostic), diagnostic())]
-#[derive(Debug, PartialEq, thiserror::Error)]
-#[error("Failed to parse source kdl file into Reflect")]
+#[derive(Debug, PartialEq)]
 pub struct ConvertErrors {
     #[cfg_attr(feature = "fancy-errors", source_code)]
     pub(super) source_code: String,
@@ -208,12 +480,14 @@ pub struct ConvertErrors {
     pub(super) errors: Vec<Error>,
 }
 impl ConvertErrors {
-    pub(super) fn new(source_code: String, errors: Vec<Error>) -> Self {
+    /// The `errors` found in `source_code`, the printed document they point
+    /// into.
+    pub fn new(source_code: String, errors: Vec<Error>) -> Self {
         Self { source_code, errors }
     }
     pub fn show_for(&self) -> String {
         let mut ret = String::with_capacity(self.errors.len() * 160);
-        for Error { span, source, .. } in &self.errors {
+        for error @ Error { span, source, .. } in &self.errors {
             ret.push('\n');
             ret.push_str(&self.source_code);
             writeln!(
@@ -224,25 +498,97 @@ impl ConvertErrors {
                 x = ""
             )
             .unwrap();
-            write!(&mut ret, "\nat {}: {source}", span.offset()).unwrap();
+            write!(&mut ret, "\nat {}", span.offset()).unwrap();
+            if !error.path.is_empty() {
+                write!(&mut ret, " in {}", error.path()).unwrap();
+            }
+            write!(&mut ret, ": {source}").unwrap();
         }
         ret
     }
-    #[cfg(test)]
-    pub(super) fn errors(&self) -> impl Iterator<Item = &Error> {
+    pub fn errors(&self) -> impl Iterator<Item = &Error> {
         self.errors.iter()
     }
+    /// Add `error`, such as an [`Error::custom`] found validating the
+    /// converted value.
+    pub fn push(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+}
+impl Extend<Error> for ConvertErrors {
+    fn extend<T: IntoIterator<Item = Error>>(&mut self, errors: T) {
+        self.errors.extend(errors);
+    }
+}
+impl fmt::Display for ConvertErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Failed to parse source kdl file into Reflect")
+    }
+}
+impl std::error::Error for ConvertErrors {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.errors.first().map(|error| error as _)
+    }
+}
+impl IntoIterator for ConvertErrors {
+    type Item = Error;
+    type IntoIter = std::vec::IntoIter<Error>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+impl<'a> IntoIterator for &'a ConvertErrors {
+    type Item = &'a Error;
+    type IntoIter = std::slice::Iter<'a, Error>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
+    }
+}
+impl From<ConvertErrors> for Vec<Error> {
+    fn from(errors: ConvertErrors) -> Self {
+        errors.errors
+    }
 }
 
 pub enum ConvertResult {
     Deserialized(DynRefl),
+    /// The document was deserialized, but some of its content is suspicious,
+    /// see [`Error::is_warning`].
+    Warned(DynRefl, ConvertErrors),
     Exports(ExportedBindings),
     Errors(ConvertErrors),
 }
 impl ConvertResult {
-    pub(crate) fn errors(repr: impl Into<String>, errors: Vec<Error>) -> Self {
+    pub(crate) fn errors(
+        repr: impl Into<String>,
+        mut errors: Vec<Error>,
+        options: &DeserOptions,
+    ) -> Self {
+        options.truncate(&mut errors);
         Self::Errors(ConvertErrors::new(repr.into(), errors))
     }
+    /// `repr` is only called when there are errors to show.
+    pub(crate) fn deserialized(
+        repr: impl FnOnce() -> String,
+        result: MResult<DynRefl>,
+        options: &DeserOptions,
+    ) -> Self {
+        match result {
+            MultiResult::Ok(value) => Self::Deserialized(value),
+            MultiResult::OkErr(value, mut errs) if errs.iter().all(|e| options.is_warning(e)) => {
+                options.truncate(&mut errs);
+                Self::Warned(value, ConvertErrors::new(repr(), errs))
+            }
+            MultiResult::OkErr(_, errs) | MultiResult::Err(errs) => {
+                Self::errors(repr(), errs, options)
+            }
+        }
+    }
 }
+// Conversion happens on the async asset loader pool, results must be Send.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<ConvertResult>();
+};
 pub(super) type ConvResult<T> = Result<T, Error>;
 pub type MResult<T> = MultiResult<T, Error>;
@@ -4,13 +4,64 @@
 //! that it's possible to report more than a single error to the user.
 use bevy_reflect::Reflect;
 
+/// Enter a `tracing` span until the end of the scope, when the `tracing`
+/// feature is enabled.
+///
+/// The feature is the one of the crate using the macro, `bevy_kdl_scene`
+/// forwards its own to this crate.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! enter_span {
+    ($($span:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = $crate::__tracing::info_span!($($span)*).entered();
+    };
+}
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+mod compat;
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub mod dates;
 mod dyn_wrappers;
 mod err;
 mod newtype;
+mod options;
+mod register;
+mod schema;
+mod ser;
+mod shape;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+mod typed;
+mod used;
+mod value;
 mod visit;
 
-pub use err::{ConvertErrors, ConvertResult, Error};
-pub use visit::{from_doc, from_doc_untyped};
+pub use err::{ConvertErrors, ConvertResult, Error, SecondaryLabel};
+pub use multierr_span::{Span, Spanned};
+pub use options::{DeserOptions, DuplicateFields};
+pub use register::register_recursive;
+pub use schema::{
+    dump_registry_schema, FieldSchema, RegistrySchema, SchemaError, TypeKind, TypeSchema,
+    VariantSchema,
+};
+pub use ser::{serialize_value, FieldOrder, SerError};
+#[cfg(feature = "templating")]
+pub use template_kdl::{ExportedBindings, RequiredBindings};
+pub use typed::{check_declared, ReflectFromReflect};
+pub use used::UsedTypes;
+#[cfg(feature = "uuid")]
+pub use value::ReflectFromUuid;
+pub use value::{Bitflags, ReflectBitflags, ReflectFromInt, ReflectFromStr, ReflectSet};
+pub use visit::{
+    build_typed, convert_node_at, deserialize_value, from_doc, from_doc_typed, from_doc_untyped,
+    from_shared_doc, from_shared_doc_untyped, from_text, from_text_untyped, read_exports,
+    validate_with_schema, Converted,
+};
+#[cfg(not(feature = "templating"))]
+pub use visit::{ExportedBindings, RequiredBindings};
 
 pub type DynRefl = Box<dyn Reflect>;
 
@@ -18,10 +69,14 @@ pub type DynRefl = Box<dyn Reflect>;
 #[allow(unused)]
 mod test {
     use super::*;
-    use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry};
-    use bevy_utils::HashMap;
+    use crate::err::ErrorType;
+    use bevy_reflect::{
+        std_traits::ReflectDefault, FromReflect, Reflect, TypeRegistration, TypeRegistry,
+    };
+    use bevy_utils::{HashMap, HashSet};
     use kdl::KdlDocument;
     use miette::Result;
+    use std::num::{NonZeroI32, NonZeroU8};
 
     macro_rules! map {
         ($($key:expr => $value:expr),*$(,)?) => ({
@@ -49,23 +104,60 @@ mod test {
     #[derive(Reflect, Debug, PartialEq, Default, FromReflect)]
     struct C(f32);
 
+    #[derive(Reflect, Debug, PartialEq, Default, FromReflect)]
+    struct Tiny(u8);
+
+    #[derive(Reflect, Debug, PartialEq, Default, FromReflect)]
+    struct Range {
+        start: f64,
+        end: Option<f32>,
+    }
+
     #[derive(Clone, Reflect, Hash, PartialEq, Debug, Default, FromReflect)]
     #[reflect(Hash, PartialEq)]
     struct D {
         x: isize,
     }
 
-    #[derive(Reflect, Copy, Clone, PartialEq, Debug)]
-    #[reflect_value(PartialEq)]
+    #[derive(Reflect, FromReflect, Copy, Clone, PartialEq, Debug)]
+    #[reflect_value(PartialEq, FromStr)]
     enum E {
         X,
         Y,
     }
+    impl std::str::FromStr for E {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "X" => Ok(Self::X),
+                "Y" => Ok(Self::Y),
+                _ => Err(format!("expected X or Y, got {s}")),
+            }
+        }
+    }
     impl Default for E {
         fn default() -> Self {
             Self::X
         }
     }
+    #[derive(Reflect, FromReflect, Copy, Clone, PartialEq, Debug, Default)]
+    #[reflect_value(PartialEq, Bitflags)]
+    struct Layers(u8);
+    impl std::ops::BitOr for Layers {
+        type Output = Self;
+        fn bitor(self, rhs: Self) -> Self {
+            Self(self.0 | rhs.0)
+        }
+    }
+    impl Bitflags for Layers {
+        fn flags() -> &'static [(&'static str, Self)] {
+            &[
+                ("Player", Layers(1)),
+                ("Enemies", Layers(2)),
+                ("Walls", Layers(4)),
+            ]
+        }
+    }
     #[derive(FromReflect, PartialEq, Reflect, Default, Debug)]
     #[reflect(PartialEq)]
     struct F {
@@ -79,6 +171,12 @@ mod test {
         y: Vec<String>,
         z: HashMap<String, f32>,
     }
+    #[derive(PartialEq, Reflect, Debug, FromReflect)]
+    #[reflect(PartialEq)]
+    struct H {
+        count: NonZeroU8,
+        offset: NonZeroI32,
+    }
     #[derive(PartialEq, Clone, Reflect, Default, Debug, FromReflect)]
     #[reflect(PartialEq)]
     struct Foo {
@@ -89,7 +187,92 @@ mod test {
     #[derive(Clone, PartialEq, Reflect, Default, Debug, FromReflect)]
     #[reflect(PartialEq)]
     struct Bar(f64);
+    #[derive(PartialEq, Reflect, Debug, FromReflect)]
+    #[reflect(PartialEq, Default)]
+    struct Volume {
+        master: f64,
+        music: f64,
+        muted: bool,
+    }
+    impl Default for Volume {
+        fn default() -> Self {
+            Self { master: 1.0, music: 0.8, muted: false }
+        }
+    }
+    #[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash, Default, FromReflect)]
+    #[reflect(Hash, PartialEq, Default)]
+    struct Cell {
+        x: i32,
+        y: i32,
+    }
+    #[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash, Default, FromReflect)]
+    #[reflect(Default)]
+    struct Unhashed(i32);
+    #[derive(Reflect, Debug, PartialEq, FromReflect)]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+    #[derive(Reflect, Debug, PartialEq, FromReflect)]
+    struct Tags {
+        ids: HashSet<u32>,
+    }
+    #[derive(Reflect, Debug, PartialEq, FromReflect)]
+    struct Big {
+        unsigned: u64,
+        signed: i128,
+        huge: u128,
+    }
+    #[derive(Reflect, Debug, PartialEq, FromReflect)]
+    struct Tuples {
+        unit: (),
+        single: (u32,),
+    }
+    // Generated code sometimes has structs with more fields than fit in a u8
+    #[rustfmt::skip]
+    #[derive(Reflect, Debug, PartialEq, Default, FromReflect)]
+    struct Wide {
+        f0: u8, f1: u8, f2: u8, f3: u8, f4: u8, f5: u8, f6: u8, f7: u8,
+        f8: u8, f9: u8, f10: u8, f11: u8, f12: u8, f13: u8, f14: u8, f15: u8,
+        f16: u8, f17: u8, f18: u8, f19: u8, f20: u8, f21: u8, f22: u8, f23: u8,
+        f24: u8, f25: u8, f26: u8, f27: u8, f28: u8, f29: u8, f30: u8, f31: u8,
+        f32: u8, f33: u8, f34: u8, f35: u8, f36: u8, f37: u8, f38: u8, f39: u8,
+        f40: u8, f41: u8, f42: u8, f43: u8, f44: u8, f45: u8, f46: u8, f47: u8,
+        f48: u8, f49: u8, f50: u8, f51: u8, f52: u8, f53: u8, f54: u8, f55: u8,
+        f56: u8, f57: u8, f58: u8, f59: u8, f60: u8, f61: u8, f62: u8, f63: u8,
+        f64: u8, f65: u8, f66: u8, f67: u8, f68: u8, f69: u8, f70: u8, f71: u8,
+        f72: u8, f73: u8, f74: u8, f75: u8, f76: u8, f77: u8, f78: u8, f79: u8,
+        f80: u8, f81: u8, f82: u8, f83: u8, f84: u8, f85: u8, f86: u8, f87: u8,
+        f88: u8, f89: u8, f90: u8, f91: u8, f92: u8, f93: u8, f94: u8, f95: u8,
+        f96: u8, f97: u8, f98: u8, f99: u8, f100: u8, f101: u8, f102: u8, f103: u8,
+        f104: u8, f105: u8, f106: u8, f107: u8, f108: u8, f109: u8, f110: u8, f111: u8,
+        f112: u8, f113: u8, f114: u8, f115: u8, f116: u8, f117: u8, f118: u8, f119: u8,
+        f120: u8, f121: u8, f122: u8, f123: u8, f124: u8, f125: u8, f126: u8, f127: u8,
+        f128: u8, f129: u8, f130: u8, f131: u8, f132: u8, f133: u8, f134: u8, f135: u8,
+        f136: u8, f137: u8, f138: u8, f139: u8, f140: u8, f141: u8, f142: u8, f143: u8,
+        f144: u8, f145: u8, f146: u8, f147: u8, f148: u8, f149: u8, f150: u8, f151: u8,
+        f152: u8, f153: u8, f154: u8, f155: u8, f156: u8, f157: u8, f158: u8, f159: u8,
+        f160: u8, f161: u8, f162: u8, f163: u8, f164: u8, f165: u8, f166: u8, f167: u8,
+        f168: u8, f169: u8, f170: u8, f171: u8, f172: u8, f173: u8, f174: u8, f175: u8,
+        f176: u8, f177: u8, f178: u8, f179: u8, f180: u8, f181: u8, f182: u8, f183: u8,
+        f184: u8, f185: u8, f186: u8, f187: u8, f188: u8, f189: u8, f190: u8, f191: u8,
+        f192: u8, f193: u8, f194: u8, f195: u8, f196: u8, f197: u8, f198: u8, f199: u8,
+        f200: u8, f201: u8, f202: u8, f203: u8, f204: u8, f205: u8, f206: u8, f207: u8,
+        f208: u8, f209: u8, f210: u8, f211: u8, f212: u8, f213: u8, f214: u8, f215: u8,
+        f216: u8, f217: u8, f218: u8, f219: u8, f220: u8, f221: u8, f222: u8, f223: u8,
+        f224: u8, f225: u8, f226: u8, f227: u8, f228: u8, f229: u8, f230: u8, f231: u8,
+        f232: u8, f233: u8, f234: u8, f235: u8, f236: u8, f237: u8, f238: u8, f239: u8,
+        f240: u8, f241: u8, f242: u8, f243: u8, f244: u8, f245: u8, f246: u8, f247: u8,
+        f248: u8, f249: u8, f250: u8, f251: u8, f252: u8, f253: u8, f254: u8, f255: u8,
+        f256: u8,
+    }
     fn parse_kdl<T: FromReflect>(text: &str) -> Result<T, ConvertErrors> {
+        parse_kdl_with(text, &DeserOptions::default())
+    }
+    fn parse_kdl_with<T: FromReflect>(
+        text: &str,
+        options: &DeserOptions,
+    ) -> Result<T, ConvertErrors> {
         let mut registry = TypeRegistry::default();
         macro_rules! register_all {
             ($($ty_name:ty ),* $(,)? ) => ({$(
@@ -102,13 +285,20 @@ mod test {
             )*})
         }
         register_all!(
-            Foo, Bar, A, B, C, D, E, F, G, bool, f64, f32, i8, i16, i32, i64, i128, isize, u8, u16,
-            u32, u64, u128, usize, String,
+            Foo, Bar, A, B, C, D, E, F, G, H, Range, Tiny, Layers, Volume, Wide, Cell, Unhashed,
+            Tags, Tuples, Big, Mode, NonZeroU8, NonZeroI32, bool, f64, f32, i8, i16, i32, i64,
+            i128, isize, u8, u16, u32, u64, u128, usize, String,
         );
-        register_more!((i128, f32, String, f32, u32), Option<u8>, Vec<String>, HashMap<String, f32>);
+        register_more!(
+            (i128, f32, String, f32, u32), (u32,), Option<u8>, Option<f32>, Vec<String>,
+            HashMap<String, f32>, HashMap<Cell, u8>, HashMap<Unhashed, u8>, HashSet<u32>
+        );
+        registry.register_type_data::<HashSet<u32>, ReflectSet>();
         let mut document: KdlDocument = text.parse().unwrap();
-        match from_doc_untyped(document, &registry) {
-            ConvertResult::Deserialized(val) => Ok(T::from_reflect(val.as_ref()).unwrap()),
+        match from_doc_untyped(document, Default::default(), &registry, options) {
+            ConvertResult::Deserialized(val) | ConvertResult::Warned(val, _) => {
+                Ok(T::from_reflect(val.as_ref()).unwrap())
+            }
             ConvertResult::Errors(errs) => Err(errs),
             ConvertResult::Exports(_) => panic!("Never call parse_kdl with an export node"),
         }
@@ -125,16 +315,1070 @@ mod test {
         let expected_bar = Bar(3.0);
         assert_eq!(parse_kdl::<Bar>(kdl_bar), Ok(expected_bar));
     }
+    #[test]
+    fn lossy_float() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<C>();
+        registry.register::<f32>();
+        let convert = |text: &str| {
+            from_doc_untyped(
+                text.parse().unwrap(),
+                Default::default(),
+                &registry,
+                &Default::default(),
+            )
+        };
+        assert!(matches!(convert("C 0.1"), ConvertResult::Deserialized(_)));
+        #[cfg(not(feature = "strict-floats"))]
+        assert!(matches!(convert("C 3943.13456"), ConvertResult::Warned(..)));
+        #[cfg(feature = "strict-floats")]
+        assert!(matches!(convert("C 3943.13456"), ConvertResult::Errors(_)));
+    }
+    #[test]
+    fn shared_document() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Bar>();
+        registry.register::<f64>();
+        let document = std::sync::Arc::new("Bar 3.0".parse::<KdlDocument>().unwrap());
+        let options = DeserOptions::new();
+        for _ in 0..2 {
+            let shared = document.clone();
+            let converted = from_shared_doc::<Bar>(shared, Default::default(), &registry, &options);
+            let ConvertResult::Deserialized(value) = converted else {
+                panic!("expected a value");
+            };
+            assert_eq!(Bar::from_reflect(value.as_ref()), Some(Bar(3.0)));
+        }
+        assert_eq!(document.nodes().len(), 1);
+    }
+    #[test]
+    fn expansion_limits_count_invocations_once() {
+        let text = r#"
+x_field { x 1; }
+y_field { y 2; }
+Cell { x_field; y_field; }
+"#;
+        let limits = |max| {
+            let limits = template_kdl::Limits::new().max_nodes(Some(max));
+            DeserOptions::new().expansion_limits(limits)
+        };
+        // Navigation may read the fields of `Cell` several times, the two
+        // invocations still only produce two nodes.
+        assert_eq!(
+            parse_kdl_with::<Cell>(text, &limits(2)),
+            Ok(Cell { x: 1, y: 2 })
+        );
+        assert!(parse_kdl_with::<Cell>(text, &limits(1)).is_err());
+    }
+    #[derive(Reflect, Debug, Clone, PartialEq, Eq, Hash)]
+    #[reflect(Hash)]
+    struct Even(u8);
+    impl FromReflect for Even {
+        fn from_reflect(reflect: &dyn Reflect) -> Option<Self> {
+            let bevy_reflect::ReflectRef::TupleStruct(even) = reflect.reflect_ref() else {
+                return None;
+            };
+            let value = *even.field(0)?.downcast_ref::<u8>()?;
+            (value % 2 == 0).then_some(Even(value))
+        }
+    }
+    #[test]
+    fn rejected_from_reflect() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Even>();
+        registry.register::<u8>();
+        let options = DeserOptions::new();
+        let convert = |text: &str| {
+            let document = text.parse().unwrap();
+            from_doc_typed::<Even>(document, Default::default(), &registry, &options)
+        };
+        assert_eq!(convert("Even 4").unwrap().value, Even(4));
+        let errors = convert("Even 3").unwrap_err();
+        let error = errors.errors().next().unwrap();
+        let expected = ErrorType::FromReflect {
+            expected: "bevy_kdl_reflect_deser::test::Even",
+            actual: None,
+        };
+        assert_eq!(*error.source, expected);
+        assert_eq!(error.range(), 0..4);
+    }
+    #[test]
+    fn declared_fields() {
+        use bevy_reflect::DynamicStruct;
+        let mut registry = TypeRegistry::default();
+        registry.register::<Volume>();
+        registry.register::<f64>();
+        registry.register::<bool>();
+        let node: kdl::KdlNode = "Volume master=0.5 music=0.2 loud=true".parse().unwrap();
+        let mut volume = DynamicStruct::default();
+        volume.set_name(std::any::type_name::<Volume>().to_owned());
+        volume.insert("master", 0.5_f64);
+        volume.insert("music", 0.2_f64);
+        let check = |volume: &DynamicStruct| check_declared(&node, volume, &registry);
+        let name = "bevy_kdl_reflect_deser::test::Volume";
+        let undeclared = ErrorType::UndeclaredField { name, field: "muted".to_owned() };
+        let error = check(&volume).unwrap();
+        assert_eq!((*error.source, error.range()), (undeclared, 0..6));
+
+        volume.insert("muted", false);
+        assert_eq!(check(&volume), None);
+
+        volume.insert("loud", true);
+        let unexpected = ErrorType::UnexpectedField { name, field: "loud".to_owned() };
+        assert_eq!(*check(&volume).unwrap().source, unexpected);
+    }
+    #[test]
+    fn concrete_values() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Bar>();
+        registry.register::<f64>();
+        registry.register_type_data::<Bar, ReflectFromReflect>();
+        let document = "Bar 3.0".parse().unwrap();
+        let options = DeserOptions::new();
+        let converted = from_doc_untyped(document, Default::default(), &registry, &options);
+        let ConvertResult::Deserialized(value) = converted else {
+            panic!("expected a value");
+        };
+        let registration = registry.get_with_name(value.type_name()).unwrap();
+        let from_reflect = registration.data::<ReflectFromReflect>().unwrap();
+        let concrete = from_reflect.from_reflect(value.as_ref()).unwrap();
+        assert_eq!(concrete.downcast_ref::<Bar>(), Some(&Bar(3.0)));
+    }
+    #[test]
+    fn node_at_offset() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Tiny>();
+        registry.register::<u8>();
+        let source = "Tiny 3\nTiny 300";
+        let document: KdlDocument = source.parse().unwrap();
+        let node = document.nodes()[1].clone();
+        let (bindings, options) = (Default::default(), DeserOptions::new());
+        let converted = convert_node_at(node, 7, source, None, &registry, bindings, &options);
+        let ConvertResult::Errors(errors) = converted else {
+            panic!("300 doesn't fit in a u8, it must be an error");
+        };
+        let error = errors.errors().next().unwrap();
+        assert_eq!(&source[error.range()], "300");
+    }
+    #[test]
+    fn spans_around_comments() {
+        let error_text = |text: &str| {
+            let errors = parse_kdl::<Tiny>(text).unwrap_err();
+            let range = errors.errors().next().unwrap().range();
+            text[range].to_owned()
+        };
+        let text = "// comment\n/- Tiny 1\n/* multi\n   line */ Tiny /- 2 /* in */ 300 /- 4 ;\n";
+        assert_eq!(error_text(text), "300");
+        let text = "Tiny\t/-\"a\" \\\n  300   /-{ Child; } // trailing\n/- Tiny 5";
+        assert_eq!(error_text(text), "300");
+
+        let text = r#"G {
+    /- y "x"
+    y "a" /* between */ "b" \
+        300;
+    /-z one=1.0
+    z one=1.0 ; /* after */
+}"#;
+        let errors = parse_kdl::<G>(text).unwrap_err();
+        let error = errors.errors().next().unwrap();
+        assert_eq!(&text[error.range()], "300");
+    }
+    #[test]
+    fn custom_errors() {
+        // Validating converted values reports errors pointing at the source
+        let text = "Tiny 3\nTiny 0";
+        let document: KdlDocument = text.parse().unwrap();
+        let zero = template_kdl::read_plain(document).nth(1).unwrap();
+        let mut errors = ConvertErrors::new(text.to_owned(), Vec::new());
+        errors.push(Error::custom(&zero, "Tiny must be positive").with_help("Use 1 instead."));
+        errors.extend([Error::custom(&Span { offset: 0, size: 4 }, "unused")]);
+
+        let errors: Vec<_> = errors.errors().collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            *errors[0].source,
+            ErrorType::Custom("Tiny must be positive".to_owned())
+        );
+        assert_eq!(&text[errors[0].range()], "Tiny 0");
+        assert_eq!(errors[1].range(), 0..4);
+        #[cfg(feature = "fancy-errors")]
+        {
+            use miette::Diagnostic;
+            assert_eq!(errors[0].help().unwrap().to_string(), "Use 1 instead.");
+        }
+    }
+    #[test]
+    fn empty_document() {
+        let registry = TypeRegistry::default();
+        for text in ["", "\n  \n", "// a comment\n/* and another */"] {
+            let converted = from_doc_untyped(
+                text.parse().unwrap(),
+                Default::default(),
+                &registry,
+                &Default::default(),
+            );
+            assert!(
+                matches!(converted, ConvertResult::Exports(_)),
+                "in {text:?}"
+            );
+        }
+    }
+    #[test]
+    fn value_or_exports() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Bar>();
+        registry.register::<f32>();
+        let value = |text: &str| {
+            deserialize_value::<Bar>(
+                text.parse().unwrap(),
+                Default::default(),
+                &registry,
+                &Default::default(),
+            )
+        };
+        let converted = value("Bar 3.0").unwrap();
+        assert_eq!(Bar::from_reflect(converted.value.as_ref()), Some(Bar(3.0)));
+        assert!(converted.warnings.is_none());
+
+        let exports = |text: &str| read_exports(text.parse().unwrap(), Default::default());
+        assert!(exports("").is_ok());
+        let errors = exports("Bar 3.0").unwrap_err();
+        let error = errors.errors().next().unwrap();
+        assert_eq!(*error.source, ErrorType::NotExports);
+        assert_eq!(error.range(), 0..3);
+        #[cfg(feature = "templating")]
+        {
+            let errors = value("Foo { bar 10; }\nexport \"Foo\"").unwrap_err();
+            let error = errors.errors().next().unwrap();
+            assert_eq!(*error.source, ErrorType::NotValue);
+            assert_eq!(error.range(), 16..22);
+            assert!(exports("Foo { bar 10; }\nexport \"Foo\"").is_ok());
+            assert!(exports("Foo { bar 10; }\nexport \"Foo\"\nBar 3.0").is_ok());
+        }
+    }
+    #[test]
+    fn partial_default() {
+        let volume = |text| parse_kdl::<Volume>(text).unwrap();
+        let expected = Volume { master: 1.0, music: 0.2, muted: false };
+        assert_eq!(volume("Volume { @default; music 0.2; }"), expected);
+        assert_eq!(volume("Volume @default=true music=0.2"), expected);
+        assert_eq!(volume("Volume { @default; }"), Volume::default());
+        assert!(parse_kdl::<Volume>("Volume music=0.2").is_err());
+        assert!(parse_kdl::<Foo>("Foo { @default; bar 10; }").is_err());
+
+        // `false` requires all fields, even when filling defaults
+        assert!(parse_kdl::<Volume>("Volume @default=false music=0.2").is_err());
+        let fill = DeserOptions::new().fill_defaults(true);
+        let text = "Volume @default=false music=0.2";
+        assert!(parse_kdl_with::<Volume>(text, &fill).is_err());
+        let text = "Volume @default=false master=1.0 music=0.2 muted=false";
+        assert_eq!(parse_kdl::<Volume>(text), Ok(expected));
+        let errors = parse_kdl::<Volume>("Volume @default=1 music=0.2").unwrap_err();
+        let not_bool =
+            |e: &Error| matches!(*e.source, ErrorType::TypeMismatch { expected: "bool", .. });
+        assert!(errors.errors().any(not_bool));
+
+        // Positional fields are the first fields, the others are the default
+        let expected = Volume { master: 0.5, music: 0.8, muted: false };
+        assert_eq!(volume("Volume 0.5 @default=true"), expected);
+        assert!(parse_kdl::<Volume>("Volume 0.5").is_err());
+        assert!(parse_kdl::<Volume>("Volume 0.5 @default=false").is_err());
+    }
+    #[test]
+    fn anonymous_tuple() {
+        type Nested = (u8, (u8, String));
+        let expected = (25, (3, "x".to_owned()));
+        let text = r#"Tuple (u8)25 { (Tuple)- (u8)3 (String)"x"; }"#;
+        assert_eq!(parse_kdl::<Nested>(text).unwrap(), expected);
+        let text = r#"(Tuple)- (u8)25 { Tuple (u8)3 { String "x"; }; }"#;
+        assert_eq!(parse_kdl::<Nested>(text).unwrap(), expected);
+
+        let untyped = |text| {
+            let errors = parse_kdl::<Nested>(text).unwrap_err();
+            let sources: Vec<_> = errors.errors().map(|e| &*e.source).cloned().collect();
+            sources
+        };
+        assert_eq!(untyped("Tuple 25"), vec![ErrorType::UntypedTupleField]);
+        assert_eq!(
+            untyped("Tuple (u8)25 { - 3; }"),
+            vec![ErrorType::UntypedTupleField]
+        );
+        // The missing type is reported, not the lack of type
+        let missing = ErrorType::NoSuchType("Missing".to_owned());
+        assert_eq!(untyped("Tuple (u8)25 { Missing 3; }"), vec![missing]);
+    }
+    #[test]
+    fn used_types() {
+        use std::any::TypeId;
+        let mut registry = TypeRegistry::empty();
+        registry.register::<A>();
+        registry.register::<C>();
+        registry.register::<D>();
+        registry.register::<Foo>();
+        registry.register::<i32>();
+        registry.register::<isize>();
+        registry.register::<f32>();
+        let text = "A x=3 { d x=1; c 2.0; }";
+        let value = match from_doc_untyped(
+            text.parse().unwrap(),
+            Default::default(),
+            &registry,
+            &Default::default(),
+        ) {
+            ConvertResult::Deserialized(value) => value,
+            _ => panic!("{text} should deserialize"),
+        };
+        let used = UsedTypes::of(value.as_ref(), &registry);
+        let expected = [
+            TypeId::of::<A>(),
+            TypeId::of::<C>(),
+            TypeId::of::<D>(),
+            TypeId::of::<i32>(),
+            TypeId::of::<isize>(),
+            TypeId::of::<f32>(),
+        ];
+        let mut used_ids: Vec<_> = used.iter().collect();
+        used_ids.sort();
+        let mut expected = expected.to_vec();
+        expected.sort();
+        assert_eq!(used_ids, expected);
+        let unused: Vec<_> = used.unused(&registry).map(|r| r.type_id()).collect();
+        assert_eq!(unused, vec![TypeId::of::<Foo>()]);
+        let snapshot = used.snapshot(&registry);
+        assert!(snapshot.get(TypeId::of::<A>()).is_some());
+        assert!(snapshot.get(TypeId::of::<Foo>()).is_none());
+    }
+    #[test]
+    fn wide_struct() {
+        let errors = |text: &str| {
+            let errors = parse_kdl::<Wide>(text).unwrap_err();
+            let sources: Vec<_> = errors.errors().map(|e| *e.source.clone()).collect();
+            sources
+        };
+        let values = |count| {
+            (0..count)
+                .map(|i| format!(" {}", i % 256))
+                .collect::<String>()
+        };
+        let wide = parse_kdl::<Wide>(&format!("Wide{}", values(257))).unwrap();
+        assert_eq!((wide.f0, wide.f255, wide.f256), (0, 255, 0));
+
+        let too_many = ErrorType::TooManyFields { name: "Wide", actual: 257, requested: 257 };
+        assert_eq!(errors(&format!("Wide{}", values(258))), vec![too_many]);
+        let not_enough = errors("Wide f1=1");
+        let [ErrorType::NotEnoughStructFields { missing, .. }] = &not_enough[..] else {
+            panic!("expected missing fields, got {not_enough:?}");
+        };
+        assert_eq!(missing.len(), 256);
+        assert_eq!((missing[0], missing[255]), (0, 256));
+    }
+    #[test]
+    fn declaration_mode() {
+        // `G` misses the badly declared field, only check the declaration error
+        let declaration_error = |text| {
+            let errors = parse_kdl::<G>(text).unwrap_err();
+            let is_declaration =
+                |e: &&Error| !matches!(*e.source, ErrorType::NotEnoughStructFields { .. });
+            let error = errors.errors().find(is_declaration).unwrap().clone();
+            error
+        };
+        let list = declaration_error(r#"G { y "a" "b" c="c"; z one=1.0; }"#);
+        let is_named_c =
+            matches!(&*list.source, ErrorType::NamedListDeclaration { field, .. } if field == "c");
+        assert!(is_named_c, "{list:?}");
+        assert_eq!(list.range(), 14..19);
+        let map = declaration_error(r#"G { y "a" "b"; z one=1.0 2.0; }"#);
+        let is_unnamed = matches!(*map.source, ErrorType::UnnamedMapDeclaration { .. });
+        assert!(is_unnamed, "{map:?}");
+        assert_eq!(map.range(), 25..28);
+        // Errors point at all the badly declared fields
+        let list = declaration_error(r#"G { y "a" b="b" c="c"; z one=1.0; }"#);
+        assert_eq!(list.range(), 10..21);
+        let map = declaration_error(r#"G { y "a" "b"; z one=1.0 2.0 3.0; }"#);
+        assert_eq!(map.range(), 25..32);
+        #[cfg(feature = "fancy-errors")]
+        {
+            use miette::Diagnostic;
+            let help = |error: Error| error.help().unwrap().to_string();
+            assert!(help(list).ends_with(r#"declare the list as `y "a" "b" "c"`."#));
+            assert!(help(map).ends_with("declare the map as `z one=1.0 two=2.5`."));
+        }
+    }
+    #[test]
+    fn named_list_items() {
+        let expected = G {
+            y: vec!["a".to_owned(), "b".to_owned()],
+            z: HashMap::default(),
+        };
+        let text = r#"G { y { - "a"; String "b"; }; z; }"#;
+        assert_eq!(parse_kdl::<G>(text).unwrap(), expected);
+
+        let text = r#"G { y { - "a"; u8 3; Missing "c"; }; z; }"#;
+        let errors = parse_kdl::<G>(text).unwrap_err();
+        let errors: Vec<_> = errors
+            .errors()
+            .map(|e| (*e.source.clone(), e.range()))
+            .collect();
+        let mismatch = ErrorType::TypeMismatch {
+            expected: "alloc::string::String",
+            actual: "u8".to_owned(),
+            shape: None,
+        };
+        let missing = ErrorType::NoSuchType("Missing".to_owned());
+        assert_eq!(errors, vec![(mismatch, 15..17), (missing, 21..28)]);
+    }
+    #[test]
+    fn compound_map_keys() {
+        let text = r#""HashMap<Cell, u8>" {
+            - { Cell x=0 y=1; u8 10; }
+            - { Cell { x 2; y 3; }; - 20; }
+        }"#;
+        let expected = map! { Cell { x: 0, y: 1 } => 10, Cell { x: 2, y: 3 } => 20 };
+        assert_eq!(parse_kdl::<HashMap<Cell, u8>>(text).unwrap(), expected);
+
+        let sources = |text| {
+            let errors = parse_kdl::<HashMap<Cell, u8>>(text).unwrap_err();
+            let sources: Vec<_> = errors
+                .errors()
+                .map(|e| (*e.source.clone(), e.range()))
+                .collect();
+            sources
+        };
+        let text = r#""HashMap<Cell, u8>" { - { Cell x=0 y=1; u8 10; u8 11; }; }"#;
+        assert_eq!(sources(text), vec![(ErrorType::PairMapNotPair(3), 22..55)]);
+
+        let text = r#""HashMap<Unhashed, u8>" { - { Unhashed 3; u8 10; }; }"#;
+        let errors = parse_kdl::<HashMap<Unhashed, u8>>(text).unwrap_err();
+        let error = errors.errors().next().unwrap();
+        let ty = "bevy_kdl_reflect_deser::test::Unhashed";
+        assert_eq!(*error.source, ErrorType::UnhashableKey(ty));
+        assert_eq!(error.range(), 30..40);
+    }
+    #[test]
+    fn concrete_map_keys() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Even>();
+        registry.register::<u8>();
+        registry.register_type_data::<Even, ReflectFromReflect>();
+        registry.add_registration(TypeRegistration::of::<HashMap<Even, u8>>());
+        let options = DeserOptions::new();
+        let convert = |text: &str| {
+            let document = text.parse().unwrap();
+            from_doc_typed::<HashMap<Even, u8>>(document, Default::default(), &registry, &options)
+        };
+        let text = r#""HashMap<Even, u8>" { - { Even 2; u8 10; }; - { Even 4; u8 20; }; }"#;
+        let expected: HashMap<_, _> = [(Even(2), 10), (Even(4), 20)].into_iter().collect();
+        assert_eq!(convert(text).unwrap().value, expected);
+
+        let even = "bevy_kdl_reflect_deser::test::Even";
+        let text = r#""HashMap<Even, u8>" { - { Even 3; u8 10; }; }"#;
+        let errors = convert(text).unwrap_err();
+        let error = errors.errors().next().unwrap();
+        let rejected = ErrorType::FromReflect { expected: even, actual: None };
+        assert_eq!((*error.source.clone(), error.range()), (rejected, 26..32));
+
+        let errors = convert(r#""HashMap<Even, u8>" two=10"#).unwrap_err();
+        let error = errors.errors().next().unwrap();
+        let actual = "alloc::string::String".to_owned();
+        let mismatch = ErrorType::TypeMismatch { expected: even, actual, shape: None };
+        assert_eq!(*error.source, mismatch);
+    }
+    #[test]
+    fn sets() {
+        let tags = |ids: &[u32]| Tags { ids: ids.iter().copied().collect() };
+        assert_eq!(
+            parse_kdl::<Tags>("Tags { ids 1 2 3; }").unwrap(),
+            tags(&[1, 2, 3])
+        );
+        assert_eq!(parse_kdl::<Tags>("Tags ids=5").unwrap(), tags(&[5]));
+        assert_eq!(
+            parse_kdl::<Tags>("Tags { ids { - 1; u32 2; }; }").unwrap(),
+            tags(&[1, 2])
+        );
+        assert!(parse_kdl::<Tags>(r#"Tags { ids 1 "two"; }"#).is_err());
+
+        let mut registry = TypeRegistry::default();
+        registry.register::<Tags>();
+        registry.register::<u32>();
+        registry.register::<HashSet<u32>>();
+        registry.register_type_data::<HashSet<u32>, ReflectSet>();
+        let text = "Tags { ids 1 2 1; }";
+        let converted = from_doc_untyped(
+            text.parse().unwrap(),
+            Default::default(),
+            &registry,
+            &Default::default(),
+        );
+        let ConvertResult::Warned(value, warnings) = converted else {
+            panic!("Duplicate set items should be a warning");
+        };
+        assert_eq!(Tags::from_reflect(value.as_ref()), Some(tags(&[1, 2])));
+        let warning = warnings.errors().next().unwrap();
+        assert!(matches!(*warning.source, ErrorType::DuplicateSetItem(_)));
+        assert_eq!(warning.range(), 15..16);
+    }
+    #[test]
+    fn shapes() {
+        let sources = |text| {
+            let errors = parse_kdl::<()>(text).unwrap_err();
+            let sources: Vec<_> = errors.errors().map(|e| *e.source.clone()).collect();
+            sources
+        };
+        let unsupported = "`Mode 1 x=2 { - …; }` can't be turned into `enum Mode { Fast, Slow }`";
+        let unsupported = ErrorType::GenericUnsupported(unsupported.to_owned());
+        assert_eq!(sources("Mode 1 x=2 { - 3; }"), vec![unsupported]);
+        let mismatch = ErrorType::TypeMismatch {
+            expected: "alloc::vec::Vec<alloc::string::String>",
+            actual: "int(1)".to_owned(),
+            shape: Some("list of String".to_owned()),
+        };
+        assert!(sources("G y=1 z=1").contains(&mismatch));
+        let mismatch = ErrorType::TypeMismatch {
+            expected: "bevy_kdl_reflect_deser::test::C",
+            actual: "bevy_kdl_reflect_deser::test::D".to_owned(),
+            shape: Some("C(f32)".to_owned()),
+        };
+        assert!(sources("A x=1 d=1 c=(D)1.0").contains(&mismatch));
+    }
+    #[test]
+    fn annotation_spans() {
+        let errors = parse_kdl::<A>("A x=1 d=1 c=(D)1.0").unwrap_err();
+        let is_mismatch = |e: &&Error| matches!(*e.source, ErrorType::TypeMismatch { .. });
+        let mismatch = errors.errors().find(is_mismatch).unwrap();
+        assert_eq!(mismatch.range(), 13..14);
+
+        let text = r#"Volume master=(f64)"loud" music=0.2 muted=false"#;
+        let errors = parse_kdl::<Volume>(text).unwrap_err();
+        let error = errors.errors().next().unwrap();
+        assert!(is_mismatch(&error));
+        assert_eq!(error.range(), 19..25);
+        let label = &error.labels[0];
+        assert_eq!((label.span.offset(), label.span.len()), (15, 3));
+    }
+    #[test]
+    fn error_chain() {
+        use std::error::Error as _;
+        let errors = parse_kdl::<Foo>("Foo bar=1 baz=2 missing=3").unwrap_err();
+        let first = errors.errors().next().unwrap().clone();
+        let source = errors.source().unwrap();
+        assert_eq!(source.to_string(), first.to_string());
+        assert_eq!(
+            source.source().unwrap().to_string(),
+            first.source.to_string()
+        );
+
+        let count = errors.errors().count();
+        assert!(count > 1);
+        assert_eq!((&errors).into_iter().count(), count);
+        let errors: Vec<Error> = errors.into();
+        assert_eq!(errors.len(), count);
+        assert_eq!(errors[0], first);
+    }
+    #[test]
+    fn error_path() {
+        let errors = parse_kdl::<G>(r#"G { y "a" "b"; z pierre="heavy"; }"#).unwrap_err();
+        let error = errors.errors().next().unwrap();
+        assert_eq!(error.path(), "G > z > pierre");
+        assert!(errors.show_for().contains("in G > z > pierre: "));
+        let message = format!("in G > z > pierre: {}", error.source);
+        assert_eq!(error.to_string(), message);
+    }
+    #[test]
+    fn schema_validation() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<B>();
+        assert!(register_recursive::<G>(&mut registry).is_empty());
+        assert!(register_recursive::<Foo>(&mut registry).is_empty());
+        let schema = RegistrySchema::of(&registry);
+        let schema_text = schema.to_kdl().to_string();
+        assert_eq!(schema_text.parse::<RegistrySchema>().unwrap(), schema);
+
+        let validate = |text: &str| {
+            let options = DeserOptions::new();
+            let doc = text.parse().unwrap();
+            let result = validate_with_schema(doc, None, &schema, Default::default(), &options);
+            let Err(errors) = result else { return Ok(()) };
+            let sources = errors.errors().map(|e| e.source.clone());
+            Err(sources.collect::<Vec<_>>())
+        };
+        assert_eq!(validate(r#"G { y "a" "b"; z pierre=1.0; }"#), Ok(()));
+        assert_eq!(validate(r#"Foo bar=1 baz="a""#), Ok(()));
+        let no_field = ErrorType::NoSuchSchemaField {
+            requested: "w".to_owned(),
+            name: "bevy_kdl_reflect_deser::test::G".to_owned(),
+            available: string_vec!["y", "z"],
+        };
+        assert_eq!(validate(r#"G { w "a"; }"#), Err(vec![no_field]));
+        let no_type = ErrorType::NoSuchType("Gee".to_owned());
+        assert_eq!(validate(r#"Gee { y "a"; }"#), Err(vec![no_type]));
+    }
+    #[test]
+    fn schema_docs() {
+        let mut registry = TypeRegistry::default();
+        assert!(register_recursive::<G>(&mut registry).is_empty());
+        let mut schema = RegistrySchema::of(&registry);
+        let g = "bevy_kdl_reflect_deser::test::G";
+        assert!(schema.set_docs(g, "A type"));
+        assert!(schema.set_field_docs(g, "y", "A field"));
+        assert!(!schema.set_docs("Gee", "No type"));
+        assert!(!schema.set_field_docs(g, "w", "No field"));
+
+        let schema_text = schema.to_kdl().to_string();
+        let schema: RegistrySchema = schema_text.parse().unwrap();
+        let ty = schema.get_with_short_name("G").unwrap();
+        assert_eq!(ty.docs.as_deref(), Some("A type"));
+        assert_eq!(ty.field("y").unwrap().docs.as_deref(), Some("A field"));
+        assert_eq!(ty.field("z").unwrap().docs, None);
+    }
+    #[test]
+    fn disabled_nodes() {
+        let volume = || Volume { master: 0.5, music: 0.2, muted: false };
+        // Disabled nodes are kept in the file, but not converted even if invalid
+        let text = r#"Volume {
+            master 0.5; music 0.2; muted false
+            music "loud" @disabled=true
+        }"#;
+        assert_eq!(parse_kdl::<Volume>(text), Ok(volume()));
+        let text = "Volume master=0.5 music=0.2 muted=false @disabled=false";
+        assert_eq!(parse_kdl::<Volume>(text), Ok(volume()));
+        let text = "Volume master=0.5 /-music=0.3 music=0.2 muted=false";
+        assert_eq!(parse_kdl::<Volume>(text), Ok(volume()));
+        let text = r#"Tags { ids { - 1; - 2 @disabled=true; - "two" @disabled=true; }; }"#;
+        let tags = Tags { ids: [1].into_iter().collect() };
+        assert_eq!(parse_kdl::<Tags>(text), Ok(tags));
+    }
+    #[test]
+    fn big_integers() {
+        let text = r#"Big unsigned=5 signed=-5 huge=5"#;
+        let small = Big { unsigned: 5, signed: -5, huge: 5 };
+        assert_eq!(parse_kdl::<Big>(text), Ok(small));
+        // kdl can't parse integers out of the i64 range, they are strings
+        let text = r#"Big {
+            unsigned "18_446_744_073_709_551_615"
+            signed "-170141183460469231731687303715884105728"
+            huge "340282366920938463463374607431768211455"
+        }"#;
+        let big = Big {
+            unsigned: u64::MAX,
+            signed: i128::MIN,
+            huge: u128::MAX,
+        };
+        assert_eq!(parse_kdl::<Big>(text), Ok(big));
+
+        let errors = |text| {
+            let errors = parse_kdl::<Big>(text).unwrap_err();
+            errors
+                .errors()
+                .map(|e| (*e.source).clone())
+                .collect::<Vec<_>>()
+        };
+        let text = r#"Big {
+            unsigned "18446744073709551616"
+            signed 0
+            huge "-1_000_000_000_000_000_000"
+        }"#;
+        let [ErrorType::IntDomain { ty: "u64", .. }, ErrorType::IntDomain { ty: "u128", .. }] =
+            &errors(text)[..]
+        else {
+            panic!("out of range big integers must be IntDomain errors");
+        };
+        // Integers in the i64 range must be numbers
+        let text = r#"Big unsigned="5" signed=0 huge=0"#;
+        let [ErrorType::TypeMismatch { .. }] = &errors(text)[..] else {
+            panic!("small integers declared as strings must be TypeMismatch errors");
+        };
+    }
+    #[test]
+    fn unit_and_single_tuples() {
+        let tuples = || Tuples { unit: (), single: (5,) };
+        // `()` isn't registered, but can still be declared
+        assert_eq!(
+            parse_kdl::<Tuples>("Tuples unit=null single=5"),
+            Ok(tuples())
+        );
+        assert_eq!(
+            parse_kdl::<Tuples>("Tuples { unit; single 5; }"),
+            Ok(tuples())
+        );
+        let text = "Tuples { unit; single { - 5; }; }";
+        assert_eq!(parse_kdl::<Tuples>(text), Ok(tuples()));
+        assert_eq!(parse_kdl::<Tuples>("Tuples null 5"), Ok(tuples()));
+        assert!(parse_kdl::<Tuples>("Tuples unit=1 single=5").is_err());
+        assert!(parse_kdl::<Tuples>("Tuples { unit 1; single 5; }").is_err());
+    }
+    #[test]
+    fn only_if_features() {
+        let text = r#"Tags {
+            ids {
+                - 1
+                only-if feature="debug" { - 2; only-if feature="cheats" { - 3; }; }
+                only-if feature="release" { - "not a number"; }
+            }
+        }"#;
+        let with = |features: &[&str]| {
+            let options = DeserOptions::new().features(features.iter().copied());
+            parse_kdl_with::<Tags>(text, &options).map(|tags| tags.ids)
+        };
+        assert_eq!(with(&["debug"]), Ok([1, 2].into_iter().collect()));
+        assert_eq!(
+            with(&["debug", "cheats"]),
+            Ok([1, 2, 3].into_iter().collect())
+        );
+        assert_eq!(with(&["cheats"]), Ok([1].into_iter().collect()));
+        assert!(with(&["release"]).is_err());
+    }
+    #[test]
+    fn deprecated_names() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<D>();
+        registry.register::<isize>();
+        let options = DeserOptions::new().deprecated_names([("Dee", "D"), ("ex", "x")]);
+        let text = "Dee ex=3";
+        let converted = from_doc_untyped(
+            text.parse().unwrap(),
+            Default::default(),
+            &registry,
+            &options,
+        );
+        let ConvertResult::Warned(value, warnings) = converted else {
+            panic!("Deprecated names should be a warning");
+        };
+        assert_eq!(D::from_reflect(value.as_ref()), Some(D { x: 3 }));
+        let warnings: Vec<_> = warnings.errors().collect();
+        let deprecated = |name: &str, replacement: &str| ErrorType::Deprecated {
+            name: name.to_owned(),
+            replacement: replacement.to_owned(),
+        };
+        assert_eq!(*warnings[0].source, deprecated("Dee", "D"));
+        assert_eq!(warnings[0].range(), 0..3);
+        assert_eq!(*warnings[1].source, deprecated("ex", "x"));
+        assert_eq!(warnings[1].range(), 4..6);
+        // Existing names are not replaced
+        let options = DeserOptions::new().deprecated_names([("x", "y")]);
+        assert_eq!(parse_kdl_with::<D>("D x=3", &options), Ok(D { x: 3 }));
+    }
+    #[test]
+    fn env_values() {
+        std::env::set_var("KDL_DESER_TEST_X", "42");
+        let with = |text: &str, allowed: &[&str]| {
+            let options = DeserOptions::new().env_vars(allowed.iter().copied());
+            parse_kdl_with::<D>(text, &options)
+        };
+        let error = |text, allowed| {
+            let errors = with(text, allowed).unwrap_err();
+            let error = errors.errors().next().unwrap();
+            *error.source.clone()
+        };
+        let text = r#"D x=(env)"KDL_DESER_TEST_X""#;
+        assert_eq!(with(text, &["KDL_DESER_TEST_X"]), Ok(D { x: 42 }));
+        assert_eq!(
+            error(text, &[]),
+            ErrorType::EnvNotAllowed("KDL_DESER_TEST_X".to_owned())
+        );
+        let unset = r#"D x=(env)"KDL_DESER_TEST_UNSET""#;
+        assert_eq!(
+            error(unset, &["KDL_DESER_TEST_UNSET"]),
+            ErrorType::EnvNotSet("KDL_DESER_TEST_UNSET".to_owned())
+        );
+        assert_eq!(error("D x=(env)42", &[]), ErrorType::EnvNotString);
+    }
+    #[cfg(all(feature = "chrono", feature = "time"))]
+    #[test]
+    fn dates() {
+        use dates::{Date, DateTime};
+
+        #[derive(Reflect, FromReflect, Debug, PartialEq)]
+        struct Event {
+            day: Date,
+            start: DateTime,
+        }
+        let mut registry = TypeRegistry::default();
+        registry.register::<Event>();
+        registry.register::<Date>();
+        registry.register::<DateTime>();
+        let convert = |text: &str| {
+            from_text::<Event>(text, Default::default(), &registry, &Default::default())
+        };
+        let text = r#"Event day="2023-04-01" start="2023-04-01T12:30:00+02:00""#;
+        let ConvertResult::Deserialized(event) = convert(text) else {
+            panic!("valid dates must be read");
+        };
+        let event = Event::from_reflect(event.as_ref()).unwrap();
+        assert_eq!(event.day.to_string(), "2023-04-01");
+        assert_eq!(event.start.0.offset().whole_hours(), 2);
+
+        let text = r#"Event day="2023-13-01" start="noon""#;
+        let ConvertResult::Errors(errors) = convert(text) else {
+            panic!("invalid dates must not be read");
+        };
+        let errors: Vec<_> = errors.errors().collect();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(*errors[0].source, ErrorType::ValueParse { .. }));
+        // The errors point at the strings
+        assert_eq!(errors[0].range(), 10..22);
+        assert_eq!(errors[1].range(), 29..35);
+    }
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuids() {
+        use uuid::Uuid;
+
+        #[derive(Reflect, FromReflect, Clone, Copy, Debug, PartialEq)]
+        #[reflect_value(PartialEq, FromUuid)]
+        struct AssetGuid(Uuid);
+        impl From<Uuid> for AssetGuid {
+            fn from(uuid: Uuid) -> Self {
+                AssetGuid(uuid)
+            }
+        }
+        #[derive(Reflect, FromReflect, Debug, PartialEq)]
+        struct Sprite {
+            id: Uuid,
+            image: AssetGuid,
+        }
+        let mut registry = TypeRegistry::default();
+        registry.register::<Sprite>();
+        registry.register::<Uuid>();
+        registry.register::<AssetGuid>();
+        let convert = |text: &str| {
+            from_text::<Sprite>(text, Default::default(), &registry, &Default::default())
+        };
+        let uuid = Uuid::from_u128(0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8);
+        // The hyphenated and simple forms
+        let text = r#"Sprite id="67e55044-10b1-426f-9247-bb680e5fe0c8" image="67e5504410b1426f9247bb680e5fe0c8""#;
+        let ConvertResult::Deserialized(sprite) = convert(text) else {
+            panic!("valid UUIDs must be read");
+        };
+        let sprite = Sprite::from_reflect(sprite.as_ref()).unwrap();
+        assert_eq!(sprite, Sprite { id: uuid, image: AssetGuid(uuid) });
+
+        let ConvertResult::Errors(errors) = convert(r#"Sprite id="67e55044" image="nope""#) else {
+            panic!("invalid UUIDs must not be read");
+        };
+        let errors: Vec<_> = errors.errors().collect();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(*errors[1].source, ErrorType::ValueParse { .. }));
+    }
+    #[test]
+    fn paths() {
+        use std::path::{PathBuf, MAIN_SEPARATOR};
+
+        #[derive(Reflect, FromReflect, Debug, PartialEq)]
+        struct Texture {
+            file: PathBuf,
+            label: String,
+        }
+        let mut registry = TypeRegistry::default();
+        registry.register::<Texture>();
+        registry.register::<PathBuf>();
+        registry.register::<String>();
+        let convert = |text: &str| {
+            from_text::<Texture>(text, Default::default(), &registry, &Default::default())
+        };
+        let text = r#"Texture file="textures\\./ui/../player.png" label=(path)"a/b""#;
+        let ConvertResult::Deserialized(texture) = convert(text) else {
+            panic!("relative paths must be read");
+        };
+        let texture = Texture::from_reflect(texture.as_ref()).unwrap();
+        assert_eq!(texture.file, PathBuf::from("textures").join("player.png"));
+        assert_eq!(texture.label, format!("a{MAIN_SEPARATOR}b"));
+
+        let error = |text| {
+            let ConvertResult::Errors(errors) = convert(text) else {
+                panic!("{text} must not be read");
+            };
+            let error = errors.errors().next().unwrap();
+            (*error.source.clone(), error.range())
+        };
+        let bad_path = |path: &str, reason| ErrorType::BadPath { path: path.to_owned(), reason };
+        assert_eq!(
+            error(r#"Texture file="/etc/passwd" label="""#),
+            (
+                bad_path("/etc/passwd", "absolute paths are not allowed"),
+                13..26
+            )
+        );
+        assert_eq!(
+            error(r#"Texture file="../../x" label="""#),
+            (
+                bad_path("../../x", "the path leaves the asset folder"),
+                13..22
+            )
+        );
+        assert_eq!(
+            error(r#"Texture file="x" label=(path)1"#).0,
+            ErrorType::PathNotString
+        );
+    }
+    #[test]
+    fn recovers_syntax_errors() {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Bar>();
+        registry.register::<f64>();
+        let convert = |text: &str| {
+            from_text_untyped(text, Default::default(), &registry, &Default::default())
+        };
+        assert!(matches!(convert("Bar 3.0"), ConvertResult::Deserialized(_)));
+
+        // A template with a syntax error isn't dropped, it would make the
+        // previous template the value of the document.
+        let text = "three { Bar 3.0; }\nbroken \"x\" {\n  Bar x=\n}\nthree\n";
+        let ConvertResult::Errors(errors) = convert(text) else {
+            panic!("a document with syntax errors must not convert");
+        };
+        let errors: Vec<_> = errors.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(*errors[0].source, ErrorType::Syntax { .. }));
+        assert!(errors[0].offset() > text.find("broken").unwrap());
+        assert!(errors[0].offset() < text.rfind("}").unwrap());
+    }
+    #[test]
+    fn options() {
+        let with = |options: DeserOptions| move |text| parse_kdl_with::<Volume>(text, &options);
+        let default = with(DeserOptions::new());
+        let filled = with(DeserOptions::new().fill_defaults(true));
+        assert!(default("Volume music=0.2").is_err());
+        let expected = Volume { master: 1.0, music: 0.2, muted: false };
+        assert_eq!(filled("Volume music=0.2"), Ok(expected));
+        // `Foo` doesn't register `ReflectDefault`, all its fields are required
+        let fill = DeserOptions::new().fill_defaults(true);
+        assert!(parse_kdl_with::<Foo>("Foo bar=1", &fill).is_err());
+
+        let twice = "Volume master=0.5 music=0.2 muted=false music=0.4";
+        let sources = |errors: ConvertErrors| {
+            errors
+                .errors()
+                .map(|e| (*e.source).clone())
+                .collect::<Vec<_>>()
+        };
+        let rejected = sources(default(twice).unwrap_err());
+        assert!(matches!(
+            &rejected[..],
+            [ErrorType::MultipleSameField { .. }]
+        ));
+        let rejected = default(twice).unwrap_err();
+        let labels = &rejected.errors().next().unwrap().labels;
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].span.offset(), 18);
+        let first = with(DeserOptions::new().duplicate_fields(DuplicateFields::KeepFirst));
+        assert_eq!(first(twice).unwrap().music, 0.2);
+        let last = with(DeserOptions::new().duplicate_fields(DuplicateFields::KeepLast));
+        assert_eq!(last(twice).unwrap().music, 0.4);
+
+        let wrong = "Volume master=\"a\" music=\"b\" muted=\"c\"";
+        assert!(default(wrong).unwrap_err().errors().count() > 2);
+        let capped = with(DeserOptions::new().max_errors(Some(2)));
+        assert_eq!(capped(wrong).unwrap_err().errors().count(), 2);
+
+        let mut registry = TypeRegistry::default();
+        registry.register::<C>();
+        registry.register::<f32>();
+        let convert = |text: &str, options| {
+            from_doc_untyped(
+                text.parse().unwrap(),
+                Default::default(),
+                &registry,
+                &options,
+            )
+        };
+        let lossy = "C 3943.13456";
+        let strict = DeserOptions::new().strict_floats(true);
+        assert!(matches!(convert(lossy, strict), ConvertResult::Errors(_)));
+        let lenient = DeserOptions::new().strict_floats(false);
+        assert!(matches!(convert(lossy, lenient), ConvertResult::Warned(..)));
+        let deny = DeserOptions::new().strict_floats(false).deny_warnings(true);
+        assert!(matches!(convert(lossy, deny), ConvertResult::Errors(_)));
+
+        let nan = r#"C "NaN""#;
+        let reject = DeserOptions::new().reject_nan(true);
+        assert!(matches!(convert(nan, reject), ConvertResult::Errors(_)));
+        let accept = DeserOptions::new().reject_nan(false);
+        assert!(matches!(convert(nan, accept), ConvertResult::Deserialized(_)));
+
+        let long_name = "bevy_kdl_reflect_deser::test::C 0.5";
+        let no_short = DeserOptions::new().short_names(false);
+        assert!(matches!(
+            convert(long_name, no_short.clone()),
+            ConvertResult::Deserialized(_)
+        ));
+        assert!(matches!(
+            convert("C 0.5", no_short),
+            ConvertResult::Errors(_)
+        ));
+    }
+    #[test]
+    fn max_depth() {
+        type Nested = (u8, (u8, String));
+        let text = r#"Tuple (u8)25 { (Tuple)- (u8)3 (String)"x"; }"#;
+        let with = |max_depth| {
+            let options = DeserOptions::new().max_depth(max_depth);
+            parse_kdl_with::<Nested>(text, &options)
+        };
+        assert_eq!(with(3), Ok((25, (3, "x".to_owned()))));
+        let errors = with(2).unwrap_err();
+        let errors: Vec<_> = errors.errors().collect();
+        // The fields of the nested tuple are too deep
+        assert_eq!(errors.len(), 2);
+        assert_eq!(*errors[0].source, ErrorType::MaxDepthExceeded(2));
+        assert!(errors[0].offset() > text.find("(Tuple)").unwrap());
+    }
+    #[cfg(feature = "fancy-errors")]
+    #[test]
+    fn int_domain_help() {
+        use miette::Diagnostic;
+        let help = |text| {
+            let errors = parse_kdl::<Tiny>(text).unwrap_err();
+            let error = errors.errors().next().unwrap();
+            let help = error.help().unwrap().to_string();
+            help
+        };
+        assert_eq!(
+            help("Tiny 300"),
+            "300 doesn't fit in u8 (the field of bevy_kdl_reflect_deser::test::Tiny), \
+            the smallest integer type it fits in is u16."
+        );
+        assert!(help("Tiny -3").ends_with("the smallest integer type it fits in is i8."));
+        assert!(help("Tiny 0x1_00").starts_with("0x1_00 (256) doesn't fit in u8"));
+    }
+    #[test]
+    fn int_domain_repr() {
+        let errors = parse_kdl::<Tiny>("Tiny 0xFF_FF").unwrap_err();
+        let error = errors.errors().next().unwrap();
+        let ErrorType::IntDomain { value, repr, .. } = &*error.source else {
+            panic!("0xFF_FF doesn't fit in a u8, it must be an IntDomain error");
+        };
+        assert_eq!((*value, repr.as_str()), (0xFFFF, "0xFF_FF"));
+        assert_eq!(error.range(), 5..12);
+    }
     #[rustfmt::skip]
     #[test]
     fn more_test() -> Result<()> {
         // TODO: Enum variants n' stuff
-        // assert_eq!(parse_kdl::<E>("E \"Y\""), Ok(E::Y));
+        assert_eq!(parse_kdl::<E>("E \"Y\"")?, E::Y);
+        assert!(parse_kdl::<E>("E \"Z\"").is_err());
+
+        assert_eq!(parse_kdl::<Layers>("Layers \"Player\" \"Walls\"")?, Layers(5));
+        assert_eq!(parse_kdl::<Layers>("Layers \"Enemies\"")?, Layers(2));
+        assert_eq!(parse_kdl::<Layers>("Layers")?, Layers(0));
+        assert!(parse_kdl::<Layers>("Layers \"Player\" \"Enemy\"").is_err());
+
+        let h_v = H { count: NonZeroU8::new(3).unwrap(), offset: NonZeroI32::new(-30).unwrap() };
+        assert_eq!(parse_kdl::<H>("H count=3 offset=-30")?, h_v);
+        assert!(parse_kdl::<H>("H count=0 offset=-30").is_err());
+        assert!(parse_kdl::<H>("H count=256 offset=-30").is_err());
 
         assert_eq!(parse_kdl::<D>("D x=10;")?, D { x: 10 });
         assert_eq!(parse_kdl::<D>("D 10;")?, D { x: 10 });
 
         assert_eq!(parse_kdl::<C>("C 22.0;")?, C(22.0));
+        assert_eq!(parse_kdl::<C>("C \"inf\";")?, C(f32::INFINITY));
+        #[cfg(not(feature = "reject-nan"))]
+        assert!(parse_kdl::<C>("C \"NaN\";")?.0.is_nan());
+        #[cfg(feature = "reject-nan")]
+        assert!(parse_kdl::<C>("C \"NaN\";").is_err());
+        assert!(parse_kdl::<C>("C \"infinity\";").is_err());
+        let range_v = Range { start: f64::NEG_INFINITY, end: Some(f32::INFINITY) };
+        assert_eq!(parse_kdl::<Range>("Range start=(f64)\"-inf\" end=\"inf\"")?, range_v);
 
         assert_eq!(parse_kdl::<B>("B")?, B);
 
@@ -167,6 +1411,8 @@ mod test {
             d: (-34234552, 3943.13456, "I am a foo".to_owned(), 65431.25543243, 0b101010101010101010101010),
             b: Some(255),
         };
+        // Those floats are not representable as f32
+        #[cfg(not(feature = "strict-floats"))]
         assert_eq!(parse_kdl::<F>(f)?, f_v);
         let g = r#"
         G {
@@ -178,6 +1424,7 @@ mod test {
             y: string_vec!["hello", "this", "is", "a", "series", "of", "worlds"],
             z: map!{"pi" => 3.14, "e" => 2.7182818, "tau" => 6.28, "ln2" => 0.69314},
         };
+        #[cfg(not(feature = "strict-floats"))]
         assert_eq!(parse_kdl::<G>(g)?, g_v);
         Ok(())
     }
@@ -1,37 +1,80 @@
 use std::{
     any::{self, TypeId},
     fmt, mem,
+    path::{PathBuf, MAIN_SEPARATOR},
 };
 
-use bevy_reflect::{
-    DynamicStruct, DynamicTuple, DynamicTupleStruct, TypeInfo, TypeRegistration, TypeRegistry,
-};
 use kdl::KdlValue;
 use multierr_span::{Smarc, Span, Spanned};
 use template_kdl::{
-    multi_err::{MultiError, MultiErrorTrait, MultiResult},
-    multi_try,
-    navigate::{Navigable, ThunkField, Value as Nvalue},
+    multi_err::{MultiError, MultiErrorTrait, MultiResult, OrAccumulate},
+    navigate::{Navigable, Value as Nvalue},
 };
 
 use crate::{
-    dyn_wrappers,
-    err::{Error, ErrorType as ErrTy, ErrorType::GenericUnsupported as TODO, MResult},
+    compat::{
+        DynamicStruct, DynamicTuple, DynamicTupleStruct, TypeIdentity, TypeInfo, TypeRegistration,
+        Typed,
+    },
+    dyn_wrappers::{self, ANON_TUPLE},
+    err::{Error, ErrorType as ErrTy, MResult},
+    options::Context,
+    shape,
+    value::{self, ReflectSet},
+    visit::Field,
     DynRefl,
 };
 
-type Field = ThunkField;
-type Reg = TypeRegistry;
+type Reg<'r> = Context<'r>;
+/// The type name of the unit type.
+const UNIT: &str = "()";
+
+/// Annotations declaring how to read a bare value, rather than its type.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReadAs {
+    /// `(env)"NAME"`, the value of an environment variable.
+    Env,
+    /// `(path)"dir/file"`, a path relative to the asset folder.
+    Path,
+}
+impl ReadAs {
+    fn new(annotation: Option<&str>) -> Option<Self> {
+        match annotation? {
+            "env" => Some(ReadAs::Env),
+            "path" => Some(ReadAs::Path),
+            _ => None,
+        }
+    }
+}
+
+/// The `()` value, declared as `null` or a node without entries or children.
+fn unit() -> DynamicTuple {
+    let mut unit = DynamicTuple::default();
+    unit.set_name(UNIT.to_owned());
+    unit
+}
 
 pub(crate) fn make_dyn(reg: &Reg, expected: Option<&str>, field: Field) -> MResult<DynRefl> {
     let ty = field.ty();
     let ty_span = ty.as_ref().map_or_else(|| field.span(), |ty| ty.span());
-    make_declared_dyn(reg, ty.as_deref(), expected, ty_span, field)
+    let name = field.name();
+    let result = make_declared_dyn(reg, ty.as_deref(), expected, ty_span, field);
+    within(name.as_deref(), result)
 }
 pub(crate) fn make_named_dyn(reg: &Reg, expected: Option<&str>, field: Field) -> MResult<DynRefl> {
     let ty = field.ty().or(field.name());
     let ty_span = ty.as_ref().map_or_else(|| field.span(), |ty| ty.span());
-    make_declared_dyn(reg, ty.as_deref(), expected, ty_span, field)
+    let name = field.name();
+    let result = make_declared_dyn(reg, ty.as_deref(), expected, ty_span, field);
+    within(name.as_deref(), result)
+}
+/// Add the node `name` to the path of the errors of `result`, errors bubble
+/// up, so each node prepends its own name.
+fn within(name: Option<&str>, result: MResult<DynRefl>) -> MResult<DynRefl> {
+    match name {
+        Some(name) => result.map_err(|err| err.within(name)),
+        None => result,
+    }
 }
 fn make_declared_dyn(
     reg: &Reg,
@@ -40,31 +83,84 @@ fn make_declared_dyn(
     ty_span: Span,
     field: Field,
 ) -> MResult<DynRefl> {
-    let mut errs = MultiError::default();
-    let expected = multi_try!(errs, ExpectedType::new(reg, declared, expected, ty_span));
-    expected.make_dyn(field).combine(errs)
+    // `(env)"NAME"` and `(path)"file"` declare how to read the value, not its type
+    let read_as = ReadAs::new(declared).filter(|_| matches!(field.value(), Nvalue::Bare(_)));
+    let declared = declared.filter(|_| read_as.is_none());
+    // Type annotations and node names declaring the type of a value
+    let declared_at = declared.is_some().then_some(ty_span);
+    reg.nested(field.span(), || {
+        MultiError::scope(|errs| {
+            let expected =
+                ExpectedType::new(reg, declared, expected, ty_span).or_accumulate(errs)?;
+            expected
+                .make_dyn(field, declared_at, read_as)
+                .or_accumulate(errs)
+        })
+    })
 }
 
 struct ExpectedType<'r> {
     // The potential types a X can be declared as in KDL
     tys: Vec<&'r TypeInfo>,
-    reg: &'r TypeRegistry,
+    reg: &'r Reg<'r>,
 }
 impl<'r> ExpectedType<'r> {
     // TODO(PERF): this is extremely inneficient for deeply nested newtypes that are
     // declared as the topmost type (ie: not using the shortcut syntax) since
     // for each level of nest, we visit all inner nests one more time.
-    fn make_dyn(self, field: Field) -> MResult<DynRefl> {
+    /// `declared_at` is where the type of `field` is declared, if it is.
+    /// `read_as` is how to read bare values, `PathBuf`s are always read as
+    /// paths.
+    fn make_dyn(
+        self,
+        field: Field,
+        declared_at: Option<Span>,
+        read_as: Option<ReadAs>,
+    ) -> MResult<DynRefl> {
         use MultiResult::Ok as MultiOk;
         use Nvalue::{Bare, List as Vlist};
 
+        // The newtypes wrapping the most inner type, from the most outer one.
+        let wrapper_count = self.tys.len().saturating_sub(1);
+        let wrappers: Vec<_> = self.tys[..wrapper_count]
+            .iter()
+            .map(|t| t.full_name())
+            .collect();
+        let concrete = |value: Smarc<KdlValue>, expected: &TypeInfo| {
+            let concrete = KdlConcrete::from(value)
+                .with_repr(field.value_repr())
+                .declared_at(declared_at);
+            let concrete = match read_as {
+                Some(ReadAs::Env) => concrete.read_env(expected, self.reg)?,
+                _ => concrete,
+            };
+            if read_as == Some(ReadAs::Path) || is_path(expected.reflected_id()) {
+                concrete.relative_path()
+            } else {
+                Ok(concrete)
+            }
+        };
         let into_dyn = |expected| match (field.value(), expected) {
             (Vlist(_), info) => dyn_wrappers::from_expected(info, &field, self.reg),
-            (Bare(value), Some(expected)) => KdlConcrete::from(value).into_dyn(expected).into(),
-            (_, info) => {
-                let msg = format!("cannot turn field into type: {field:?} \n {info:?}");
-                MResult::Err(vec![TODO(msg).spanned(&field)])
+            (Bare(value), Some(expected)) => {
+                match self
+                    .reg
+                    .get_type_data::<ReflectSet>(expected.reflected_id())
+                {
+                    // A set of a single item
+                    Some(set) => match concrete(value.clone(), set.item()) {
+                        Ok(item) => item
+                            .into_dyn(set.item(), self.reg, &[])
+                            .and_then(|item| value::from_set_item(set, item, &value)),
+                        Err(err) => MResult::Err(vec![err]),
+                    },
+                    None => match concrete(value, expected) {
+                        Ok(value) => value.into_dyn(expected, self.reg, &wrappers),
+                        Err(err) => MResult::Err(vec![err]),
+                    },
+                }
             }
+            (Bare(value), None) => MResult::Err(vec![ErrTy::UntypedTupleField.spanned(&value)]),
         };
         if self.tys.is_empty() {
             return into_dyn(None);
@@ -78,30 +174,34 @@ impl<'r> ExpectedType<'r> {
         // unwrap: only constructor has at least one element to tys
         let first = tys.next().unwrap();
         let mut inner = into_dyn(Some(first));
+        // A bare value can only be the most inner type, retrying with the
+        // wrapper types would replace the error with a less useful type mismatch
+        let is_bare = matches!(field.value(), Bare(_));
         for ty in tys {
             match (&mut inner, ty) {
                 (MultiOk(ref mut inner), TypeInfo::Struct(info)) => {
                     let field = info.field_at(0).unwrap().name();
                     let mut acc = DynamicStruct::default();
-                    acc.set_name(info.type_name().to_owned());
+                    acc.set_name(info.full_name().to_owned());
                     let old_inner = mem::replace(inner, Box::new(()));
                     acc.insert_boxed(field, old_inner);
                     *inner = Box::new(acc);
                 }
                 (MultiOk(ref mut inner), TypeInfo::Tuple(info)) => {
                     let mut acc = DynamicTuple::default();
-                    acc.set_name(info.type_name().to_owned());
+                    acc.set_name(info.full_name().to_owned());
                     let old_inner = mem::replace(inner, Box::new(()));
                     acc.insert_boxed(old_inner);
                     *inner = Box::new(acc);
                 }
                 (MultiOk(ref mut inner), TypeInfo::TupleStruct(info)) => {
                     let mut acc = DynamicTupleStruct::default();
-                    acc.set_name(info.type_name().to_owned());
+                    acc.set_name(info.full_name().to_owned());
                     let old_inner = mem::replace(inner, Box::new(()));
                     acc.insert_boxed(old_inner);
                     *inner = Box::new(acc);
                 }
+                _ if is_bare => {}
                 _ => {
                     inner = into_dyn(Some(ty));
                 }
@@ -110,32 +210,25 @@ impl<'r> ExpectedType<'r> {
         inner
     }
 
-    fn registered(expected: &'r TypeRegistration, reg: &'r TypeRegistry) -> Self {
+    fn registered(expected: &'r TypeRegistration, reg: &'r Reg<'r>) -> Self {
         use TypeInfo::{Struct, Tuple, TupleStruct};
         let mut tys = Vec::with_capacity(1);
         let mut expected = expected.type_info();
         loop {
             tys.push(expected);
-            match expected {
-                Struct(info) if info.field_len() == 1 => {
-                    // unwrap: We just checked the length is 1
-                    let field = info.field_at(0).unwrap();
-                    // TODO: unwrap
-                    expected = reg.get_type_info(field.type_id()).unwrap();
-                }
-                Tuple(info) if info.field_len() == 1 => {
-                    // unwrap: We just checked the length is 1
-                    let field = info.field_at(0).unwrap();
-                    // TODO: unwrap
-                    expected = reg.get_type_info(field.type_id()).unwrap();
-                }
+            let inner = match expected {
+                Struct(info) if info.field_len() == 1 => info.field_at(0).map(|f| f.reflected_id()),
+                Tuple(info) if info.field_len() == 1 => info.field_at(0).map(|f| f.reflected_id()),
                 TupleStruct(info) if info.field_len() == 1 => {
-                    // unwrap: We just checked the length is 1
-                    let field = info.field_at(0).unwrap();
-                    // TODO: unwrap
-                    expected = reg.get_type_info(field.type_id()).unwrap();
+                    info.field_at(0).map(|f| f.reflected_id())
                 }
-                _ => return Self { tys, reg },
+                _ => None,
+            };
+            // A newtype of an unregistered type is read as a regular struct,
+            // which reports the missing registration of its field.
+            match inner.and_then(|inner| reg.get_type_info(inner)) {
+                Some(inner) => expected = inner,
+                None => return Self { tys, reg },
             }
         }
     }
@@ -147,36 +240,57 @@ impl<'r> ExpectedType<'r> {
     // * Any combination of the above
     // * Fatal: only if expected is not registered and (either declared is None or not registered)
     fn new(
-        reg: &'r TypeRegistry,
+        reg: &'r Reg<'r>,
         declared: Option<&str>,
         expected: Option<&str>,
         span: Span,
     ) -> MResult<Self> {
         let get_named = |name| {
-            reg.get_with_name(name)
-                .or_else(|| reg.get_with_short_name(name))
-                .ok_or(ErrTy::NoSuchType(name.to_owned()).spanned(&span))
+            let registered = reg.get_with_name(name).or_else(|| {
+                let short_names = reg.options.resolves_short_names();
+                short_names.then(|| reg.get_with_short_name(name)).flatten()
+            });
+            registered.ok_or(ErrTy::NoSuchType(name.to_owned()).spanned(&span))
         };
+        // `()` is rarely registered, yet fields of generic types often are `()`
+        let is_unit = matches!(declared, None | Some(ANON_TUPLE)) && expected == Some(UNIT);
+        if is_unit && reg.get_with_name(UNIT).is_none() {
+            return MultiResult::Ok(Self { tys: vec![<()>::type_info()], reg });
+        }
         let mut errs = MultiError::default();
+        // Deprecated type names are read as their replacement, with a warning
+        let declared = declared.map(|name| match reg.options.replacement(name) {
+            Some(replacement) if get_named(name).is_err() => {
+                let (name, new) = (name.to_owned(), replacement.to_owned());
+                errs.add_error(ErrTy::Deprecated { name, replacement: new }.spanned(&span));
+                replacement
+            }
+            _ => name,
+        });
         let expected = expected.and_then(|e| errs.optionally(get_named(e)));
+        // `Tuple` stands for the expected type, or an anonymous tuple if unknown
         match (declared, expected) {
-            (Some("Tuple"), Some(expected)) => {
+            (Some(ANON_TUPLE), Some(expected)) => {
                 return errs.into_result(Self::registered(expected, reg))
             }
-            (Some("Tuple"), None) => {
+            (Some(ANON_TUPLE), None) => {
                 return errs.into_result(Self { tys: vec![], reg });
             }
             _ => {}
         }
+        let is_declared = declared.is_some();
         let declared = declared.and_then(|e| errs.optionally(get_named(e)));
         match (declared, expected) {
             // Both declared and expected are registered, but they are not equal
             // We chose `declared` since that's what is in the file, so we expect that
             // the rest of the file uses the declaredly stated type.
-            (Some(declared), Some(expected)) if declared.type_id() != expected.type_id() => {
-                let expected = expected.type_name();
-                let actual = declared.type_name().to_owned();
-                errs.add_error(ErrTy::TypeMismatch { expected, actual }.spanned(&span));
+            (Some(declared), Some(expected))
+                if declared.reflected_id() != expected.reflected_id() =>
+            {
+                let shape = shape::of_compound(expected.type_info());
+                let expected = expected.full_name();
+                let actual = declared.full_name().to_owned();
+                errs.add_error(ErrTy::TypeMismatch { expected, actual, shape }.spanned(&span));
                 errs.into_result(Self::registered(declared, reg))
             }
             // Either declared was not provided, or it was not registered (in which case
@@ -186,6 +300,7 @@ impl<'r> ExpectedType<'r> {
             // Either declared was not provided, or it was not registered (in which case
             // the error is already in `errors`) and expected is not registered
             // NOTE: This is the only Fatal error preventing any validation of what's inside.
+            (None, None) if is_declared => errs.into_many_errors([]),
             (None, None) => errs.into_errors(ErrTy::UntypedTupleField.spanned(&span)),
             // declared type exists, but is not equal to expected one, and the
             // expected one is not registered. This is an error, but we continue,
@@ -196,7 +311,9 @@ impl<'r> ExpectedType<'r> {
 }
 /// A proxy for [`KdlValue`] that doesn't care about the format of declaration.
 enum KdlType {
-    Int(i64),
+    Int(i128),
+    /// An integer past `i128::MAX`, only declared as a string, see [`big_int`].
+    U128(u128),
     Float(f64),
     Bool(bool),
     Str(String),
@@ -205,6 +322,10 @@ enum KdlType {
 struct KdlConcrete {
     ty: KdlType,
     span: Span,
+    /// How the value is written in the document, such as `0xFF_FF`.
+    repr: Option<String>,
+    /// The type annotation or node name declaring the type of the value.
+    declared_at: Option<Span>,
 }
 impl From<Smarc<KdlValue>> for KdlConcrete {
     fn from(value: Smarc<KdlValue>) -> Self {
@@ -213,26 +334,112 @@ impl From<Smarc<KdlValue>> for KdlConcrete {
         };
         let span = value.span();
         let ty = match &*value {
-            Base10(i) | Base2(i) | Base16(i) | Base8(i) => KdlType::Int(*i),
+            Base10(i) | Base2(i) | Base16(i) | Base8(i) => KdlType::Int((*i).into()),
             Base10Float(f) => KdlType::Float(*f),
             VString(s) | RawString(s) => KdlType::Str(s.clone()),
             Bool(b) => KdlType::Bool(*b),
             Null => KdlType::Null,
         };
-        KdlConcrete { ty, span }
+        KdlConcrete { ty, span, repr: None, declared_at: None }
     }
 }
 impl KdlConcrete {
-    fn into_dyn(self, expected: &TypeInfo) -> Result<DynRefl, Error> {
-        self.ty
-            .into_dyn(expected)
-            .map_err(|e| Error::new(&self.span, e))
+    /// Declared as `repr`, errors point to and show the literal as written.
+    fn with_repr(mut self, repr: Option<String>) -> Self {
+        if let Some(repr) = &repr {
+            self.span.size = repr.len() as u32;
+        }
+        self.repr = repr;
+        self
+    }
+    /// Declared with a type at `declared_at`, type mismatches also point there.
+    fn declared_at(mut self, declared_at: Option<Span>) -> Self {
+        self.declared_at = declared_at;
+        self
+    }
+    /// The value of the environment variable this `(env)"NAME"` value names,
+    /// as `expected`.
+    fn read_env(mut self, expected: &TypeInfo, reg: &Reg) -> Result<Self, Error> {
+        let KdlType::Str(name) = &self.ty else {
+            return Err(ErrTy::EnvNotString.spanned(&self.span));
+        };
+        if !reg.options.allows_env(name) {
+            return Err(ErrTy::EnvNotAllowed(name.clone()).spanned(&self.span));
+        }
+        let not_set = || ErrTy::EnvNotSet(name.clone()).spanned(&self.span);
+        let value = std::env::var(name).map_err(|_| not_set())?;
+        self.ty = KdlType::from_env(value, expected);
+        self.repr = None;
+        Ok(self)
+    }
+    /// This string as a path relative to the asset folder, see
+    /// [`relative_path`].
+    fn relative_path(mut self) -> Result<Self, Error> {
+        let KdlType::Str(path) = &self.ty else {
+            return Err(ErrTy::PathNotString.spanned(&self.span));
+        };
+        let bad_path = |reason| ErrTy::BadPath { path: path.clone(), reason };
+        let relative =
+            relative_path(path).map_err(|reason| bad_path(reason).spanned(&self.span))?;
+        self.ty = KdlType::Str(relative);
+        Ok(self)
+    }
+    fn into_dyn(
+        self,
+        expected: &TypeInfo,
+        reg: &Reg,
+        wrappers: &[&'static str],
+    ) -> MResult<DynRefl> {
+        let span = self.span;
+        let lossy = self.lossy_f32(expected);
+        let (declared, declared_at) = (self.repr, self.declared_at);
+        let result = self.ty.into_dyn(expected, reg).map_err(|mut e| {
+            if let ErrTy::IntDomain { wrappers: ref mut in_wrappers, ref mut repr, .. } = e {
+                *in_wrappers = wrappers.to_vec();
+                if let Some(declared) = declared {
+                    *repr = declared;
+                }
+            }
+            let is_mismatch = matches!(e, ErrTy::TypeMismatch { .. });
+            match declared_at {
+                Some(at) if is_mismatch => {
+                    Error::new(&span, e).with_label(&at, "type declared here")
+                }
+                _ => Error::new(&span, e),
+            }
+        });
+        match (result, lossy) {
+            (Ok(value), Some(lossy)) => {
+                let lossy = lossy.spanned(&span);
+                if reg.options.is_warning(&lossy) {
+                    MultiResult::OkErr(value, vec![lossy])
+                } else {
+                    MultiResult::Err(vec![lossy])
+                }
+            }
+            (result, _) => result.into(),
+        }
+    }
+    /// A [`ErrTy::LossyFloat`] if this is narrowed into a `f32` it can't be
+    /// represented as.
+    fn lossy_f32(&self, expected: &TypeInfo) -> Option<ErrTy> {
+        let is_f32 = |ty| ty == TypeId::of::<f32>() || ty == TypeId::of::<Option<f32>>();
+        match self.ty {
+            // Compare the shortest representation of the f32 with the literal,
+            // so that `0.1` is not considered lossy.
+            KdlType::Float(f) if is_f32(expected.reflected_id()) && f.is_finite() => {
+                let narrowed: f64 = (f as f32).to_string().parse().ok()?;
+                (narrowed != f).then_some(ErrTy::LossyFloat(f))
+            }
+            _ => None,
+        }
     }
 }
 impl fmt::Display for KdlType {
     fn fmt(&self, fm: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self {
             KdlType::Int(i) => write!(fm, "int({i})"),
+            KdlType::U128(i) => write!(fm, "int({i})"),
             KdlType::Float(f) => write!(fm, "float({f})"),
             KdlType::Str(s) => write!(fm, "string(\"{s}\")"),
             KdlType::Bool(b) => write!(fm, "bool({b})"),
@@ -240,22 +447,116 @@ impl fmt::Display for KdlType {
         }
     }
 }
+/// Floats kdl can't express as numbers, declared as strings instead.
+/// `None` if `value` is not one of them, `"NaN"` is an error if `reject_nan`.
+fn special_float(value: &str, reject_nan: bool) -> Option<Result<f64, ErrTy>> {
+    match value {
+        "inf" | "+inf" => Some(Ok(f64::INFINITY)),
+        "-inf" => Some(Ok(f64::NEG_INFINITY)),
+        "NaN" if reject_nan => Some(Err(ErrTy::RejectedNan)),
+        "NaN" => Some(Ok(f64::NAN)),
+        _ => None,
+    }
+}
+/// Whether `ty` is a `PathBuf`, or an optional one.
+fn is_path(ty: TypeId) -> bool {
+    ty == TypeId::of::<PathBuf>() || ty == TypeId::of::<Option<PathBuf>>()
+}
+/// `path` with the separators of the platform, whether it was written with
+/// `/` or `\`, without `.` and with its `..` resolved.
+///
+/// Fails if `path` is absolute or `..` leaves the asset folder.
+fn relative_path(path: &str) -> Result<String, &'static str> {
+    let is_separator = |c: char| c == '/' || c == '\\';
+    let mut chars = path.chars();
+    let is_drive =
+        matches!((chars.next(), chars.next()), (Some(c), Some(':')) if c.is_ascii_alphabetic());
+    if path.starts_with(is_separator) || is_drive {
+        return Err("absolute paths are not allowed");
+    }
+    let mut components = Vec::new();
+    for component in path.split(is_separator) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    return Err("the path leaves the asset folder");
+                }
+            }
+            component => components.push(component),
+        }
+    }
+    Ok(components.join(&MAIN_SEPARATOR.to_string()))
+}
+/// Whether `ty` is an integer type, or an optional one.
+fn is_int(ty: TypeId) -> bool {
+    macro_rules! ints {
+        ($($int:ty),*) => ([$(TypeId::of::<$int>(), TypeId::of::<Option<$int>>(),)*])
+    }
+    let ints = ints!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+    ints.contains(&ty)
+}
+/// Integers kdl can't express as numbers, out of the `i64` range, declared as
+/// strings instead. `None` if `value` is not one of them.
+fn big_int(value: &str) -> Option<KdlType> {
+    let value = value.replace('_', "");
+    match value.parse::<i128>() {
+        Ok(i) if i64::try_from(i).is_err() => Some(KdlType::Int(i)),
+        Ok(_) => None,
+        // Only `u128` fits integers past `i128::MAX`
+        Err(_) => value.parse::<u128>().ok().map(KdlType::U128),
+    }
+}
 impl KdlType {
+    /// The environment variable `value`, a string unless it is a number or a
+    /// boolean and `expected` isn't a string.
+    fn from_env(value: String, expected: &TypeInfo) -> Self {
+        let string_types = [TypeId::of::<String>(), TypeId::of::<Option<String>>()];
+        if string_types.contains(&expected.reflected_id()) {
+            return KdlType::Str(value);
+        }
+        if let Ok(int) = value.parse() {
+            KdlType::Int(int)
+        } else if let Ok(float) = value.parse() {
+            KdlType::Float(float)
+        } else if let Ok(boolean) = value.parse() {
+            KdlType::Bool(boolean)
+        } else {
+            KdlType::Str(value)
+        }
+    }
     // TODO: this probably works better if we implemnt Deserialize on template-kdl
-    fn into_dyn(self, expected: &TypeInfo) -> Result<DynRefl, ErrTy> {
+    fn into_dyn(self, expected: &TypeInfo, reg: &Reg) -> Result<DynRefl, ErrTy> {
         use KdlType::*;
         let actual = self.to_string();
-        let mismatch = || ErrTy::TypeMismatch { expected: expected.type_name(), actual };
+        let mismatch = || ErrTy::TypeMismatch {
+            expected: expected.full_name(),
+            actual,
+            shape: shape::of_compound(expected),
+        };
         macro_rules! int2dyn {
             (@opt $int_type:ty, $int_value:expr) => {{
                 Ok(Box::new(<$int_type>::try_from($int_value).ok()))
             }};
             ($int_type:ty, $int_value:expr) => {
                 <$int_type>::try_from($int_value)
-                    .map_err(|_| ErrTy::IntDomain($int_value, any::type_name::<$int_type>()))
+                    .map_err(|_| ErrTy::IntDomain {
+                        value: $int_value,
+                        repr: $int_value.to_string(),
+                        ty: any::type_name::<$int_type>(),
+                        wrappers: Vec::new(),
+                    })
                     .map::<DynRefl, _>(|i| Box::new(i))
             };
         }
+        macro_rules! str2float {
+            ($convert:expr, $str_value:expr) => {
+                match special_float(&$str_value, reg.options.rejects_nan()) {
+                    Some(f) => f.map::<DynRefl, _>(|f| Box::new($convert(f))),
+                    None => Err(mismatch()),
+                }
+            };
+        }
         macro_rules! null2dyn {
             ($ty_id:expr, $($convert_to:ty,)*) => {
                 $(  if $ty_id == TypeId::of::<Option<$convert_to>>() {
@@ -266,12 +567,12 @@ impl KdlType {
                 }
             };
         }
-        match (self, expected.type_id()) {
+        match (self, expected.reflected_id()) {
             (Int(i), ty) if ty == TypeId::of::<i8>() => int2dyn!(i8, i),
             (Int(i), ty) if ty == TypeId::of::<i16>() => int2dyn!(i16, i),
             (Int(i), ty) if ty == TypeId::of::<i32>() => int2dyn!(i32, i),
-            (Int(i), ty) if ty == TypeId::of::<i64>() => Ok(Box::new(i)),
-            (Int(i), ty) if ty == TypeId::of::<i128>() => int2dyn!(i128, i),
+            (Int(i), ty) if ty == TypeId::of::<i64>() => int2dyn!(i64, i),
+            (Int(i), ty) if ty == TypeId::of::<i128>() => Ok(Box::new(i)),
             (Int(i), ty) if ty == TypeId::of::<isize>() => int2dyn!(isize, i),
             (Int(i), ty) if ty == TypeId::of::<u8>() => int2dyn!(u8, i),
             (Int(i), ty) if ty == TypeId::of::<u16>() => int2dyn!(u16, i),
@@ -282,8 +583,8 @@ impl KdlType {
             (Int(i), ty) if ty == TypeId::of::<Option<i8>>() => int2dyn!(@opt i8, i),
             (Int(i), ty) if ty == TypeId::of::<Option<i16>>() => int2dyn!(@opt i16, i),
             (Int(i), ty) if ty == TypeId::of::<Option<i32>>() => int2dyn!(@opt i32, i),
-            (Int(i), ty) if ty == TypeId::of::<Option<i64>>() => Ok(Box::new(Some(i))),
-            (Int(i), ty) if ty == TypeId::of::<Option<i128>>() => int2dyn!(@opt i128, i),
+            (Int(i), ty) if ty == TypeId::of::<Option<i64>>() => int2dyn!(@opt i64, i),
+            (Int(i), ty) if ty == TypeId::of::<Option<i128>>() => Ok(Box::new(Some(i))),
             (Int(i), ty) if ty == TypeId::of::<Option<isize>>() => int2dyn!(@opt isize, i),
             (Int(i), ty) if ty == TypeId::of::<Option<u8>>() => int2dyn!(@opt u8, i),
             (Int(i), ty) if ty == TypeId::of::<Option<u16>>() => int2dyn!(@opt u16, i),
@@ -291,7 +592,13 @@ impl KdlType {
             (Int(i), ty) if ty == TypeId::of::<Option<u64>>() => int2dyn!(@opt u64, i),
             (Int(i), ty) if ty == TypeId::of::<Option<u128>>() => int2dyn!(@opt u128, i),
             (Int(i), ty) if ty == TypeId::of::<Option<usize>>() => int2dyn!(@opt usize, i),
-            (Int(_), _) => Err(mismatch()),
+            (Int(i), ty) => i64::try_from(i)
+                .ok()
+                .and_then(|i| value::from_int(reg, ty, expected.full_name(), i))
+                .unwrap_or_else(|| Err(mismatch())),
+            (U128(i), ty) if ty == TypeId::of::<u128>() => Ok(Box::new(i)),
+            (U128(i), ty) if ty == TypeId::of::<Option<u128>>() => Ok(Box::new(Some(i))),
+            (U128(_), _) => Err(mismatch()),
             (Float(f), ty) if ty == TypeId::of::<f32>() => Ok(Box::new(f as f32)),
             (Float(f), ty) if ty == TypeId::of::<f64>() => Ok(Box::new(f)),
             (Float(f), ty) if ty == TypeId::of::<Option<f32>>() => Ok(Box::new(Some(f as f32))),
@@ -302,8 +609,23 @@ impl KdlType {
             (Bool(_), _) => Err(mismatch()),
             (Str(s), ty) if ty == TypeId::of::<String>() => Ok(Box::new(s)),
             (Str(s), ty) if ty == TypeId::of::<Option<String>>() => Ok(Box::new(Some(s))),
-            (Str(_), _) => Err(mismatch()),
+            (Str(s), ty) if ty == TypeId::of::<PathBuf>() => Ok(Box::new(PathBuf::from(s))),
+            (Str(s), ty) if ty == TypeId::of::<Option<PathBuf>>() => {
+                Ok(Box::new(Some(PathBuf::from(s))))
+            }
+            (Str(s), ty) if ty == TypeId::of::<f32>() => str2float!(|f| f as f32, s),
+            (Str(s), ty) if ty == TypeId::of::<f64>() => str2float!(|f: f64| f, s),
+            (Str(s), ty) if ty == TypeId::of::<Option<f32>>() => str2float!(|f| Some(f as f32), s),
+            (Str(s), ty) if ty == TypeId::of::<Option<f64>>() => str2float!(Some::<f64>, s),
+            (Str(s), ty) if is_int(ty) => match big_int(&s) {
+                Some(int) => int.into_dyn(expected, reg),
+                None => Err(mismatch()),
+            },
+            (Str(s), ty) => {
+                value::from_str(reg, ty, expected.full_name(), s).unwrap_or_else(|| Err(mismatch()))
+            }
 
+            (Null, ty) if ty == TypeId::of::<()>() => Ok(Box::new(unit())),
             (Null, ty) => null2dyn!(
                 ty, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, bool,
                 String,
@@ -0,0 +1,246 @@
+//! Configuration of the conversion from kdl to `Reflect`.
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet},
+    ops::Deref,
+    sync::Arc,
+};
+
+use bevy_reflect::TypeRegistry;
+use multierr_span::Span;
+use template_kdl::{multi_err::MultiResult, Limits};
+
+use crate::{
+    err::{ErrorType, MResult},
+    Error,
+};
+
+/// What to do with a field declared several times in the same struct or map.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateFields {
+    /// It is an error to declare a field several times.
+    #[default]
+    Reject,
+    /// Use the first declaration, ignore the following ones.
+    KeepFirst,
+    /// Use the last declaration, it overrides the previous ones.
+    KeepLast,
+}
+
+/// Options of [`from_doc`](crate::from_doc) and the other conversion
+/// functions.
+///
+/// The `Default` options keep the behavior of the crate features.
+///
+/// ```
+/// use bevy_kdl_reflect_deser::{DeserOptions, DuplicateFields};
+///
+/// let options = DeserOptions::new()
+///     .fill_defaults(true)
+///     .duplicate_fields(DuplicateFields::KeepLast)
+///     .max_errors(Some(10));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserOptions {
+    fill_defaults: bool,
+    strict_floats: bool,
+    reject_nan: bool,
+    duplicate_fields: DuplicateFields,
+    max_errors: Option<usize>,
+    deny_warnings: bool,
+    short_names: bool,
+    expansion_limits: Limits,
+    max_depth: u32,
+    features: Arc<BTreeSet<String>>,
+    env_vars: Arc<BTreeSet<String>>,
+    deprecated_names: Arc<BTreeMap<String, String>>,
+}
+impl Default for DeserOptions {
+    fn default() -> Self {
+        Self {
+            fill_defaults: false,
+            strict_floats: cfg!(feature = "strict-floats"),
+            reject_nan: cfg!(feature = "reject-nan"),
+            duplicate_fields: DuplicateFields::Reject,
+            max_errors: None,
+            deny_warnings: false,
+            short_names: true,
+            expansion_limits: Limits::default(),
+            max_depth: 128,
+            features: Arc::default(),
+            env_vars: Arc::default(),
+            deprecated_names: Arc::default(),
+        }
+    }
+}
+impl DeserOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Build structs, tuples and collections that register `ReflectDefault`
+    /// from their `Default`, as if they were all declared with `@default`,
+    /// unless declared with `@default=false`. Defaults to `false`.
+    pub fn fill_defaults(mut self, fill_defaults: bool) -> Self {
+        self.fill_defaults = fill_defaults;
+        self
+    }
+    /// Fail on float literals that can't be exactly represented as the `f32`
+    /// they are narrowed into. Defaults to `true` with the `strict-floats`
+    /// feature, `false` otherwise.
+    pub fn strict_floats(mut self, strict_floats: bool) -> Self {
+        self.strict_floats = strict_floats;
+        self
+    }
+    /// Fail on `"NaN"` where a float is expected. Defaults to `true` with the
+    /// `reject-nan` feature, `false` otherwise.
+    pub fn reject_nan(mut self, reject_nan: bool) -> Self {
+        self.reject_nan = reject_nan;
+        self
+    }
+    /// What to do with fields declared several times in structs and maps
+    /// declared with named fields. Defaults to [`DuplicateFields::Reject`].
+    ///
+    /// Maps declared with pair-style `- key value` nodes always keep the last
+    /// declared pair.
+    pub fn duplicate_fields(mut self, duplicate_fields: DuplicateFields) -> Self {
+        self.duplicate_fields = duplicate_fields;
+        self
+    }
+    /// Report at most `max_errors` errors, the first ones found. Defaults to
+    /// `None`, reporting all errors.
+    pub fn max_errors(mut self, max_errors: Option<usize>) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+    /// Fail on warnings, see [`Error::is_warning`]. Defaults to `false`.
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.deny_warnings = deny_warnings;
+        self
+    }
+    /// Resolve type names not found in the registry as short names, such as
+    /// `Transform` for `bevy_transform::components::Transform`. Defaults to
+    /// `true`.
+    pub fn short_names(mut self, short_names: bool) -> Self {
+        self.short_names = short_names;
+        self
+    }
+    /// Stop expanding templates past `expansion_limits`, with an error.
+    /// Defaults to no limits.
+    pub fn expansion_limits(mut self, expansion_limits: Limits) -> Self {
+        self.expansion_limits = expansion_limits;
+        self
+    }
+    /// Fail on values nested deeper than `max_depth`, with an error, rather
+    /// than overflowing the stack. Defaults to `128`.
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+    /// Include the children of `only-if feature="name"` nodes when `name` is
+    /// one of `features`, the `only-if` nodes of other features are skipped.
+    /// Defaults to no features.
+    pub fn features<S: Into<String>>(mut self, features: impl IntoIterator<Item = S>) -> Self {
+        self.features = Arc::new(features.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Replace `(env)"NAME"` values with the `NAME` environment variable,
+    /// when `NAME` is one of `env_vars`. Reading other variables is an error,
+    /// so that documents can't read any variable. Defaults to no variables.
+    pub fn env_vars<S: Into<String>>(mut self, env_vars: impl IntoIterator<Item = S>) -> Self {
+        self.env_vars = Arc::new(env_vars.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Read the type and field names of `deprecated_names`, pairs of a
+    /// deprecated name and its replacement, as their replacement, with a
+    /// [`ErrorType::Deprecated`] warning. Names of registered types and
+    /// existing fields are never replaced. Defaults to no names.
+    ///
+    /// ```
+    /// use bevy_kdl_reflect_deser::DeserOptions;
+    ///
+    /// let options = DeserOptions::new()
+    ///     .deprecated_names([("Colour", "Color"), ("pos", "translation")]);
+    /// ```
+    pub fn deprecated_names<S: Into<String>>(
+        mut self,
+        deprecated_names: impl IntoIterator<Item = (S, S)>,
+    ) -> Self {
+        let names = deprecated_names.into_iter();
+        let names = names.map(|(name, replacement)| (name.into(), replacement.into()));
+        self.deprecated_names = Arc::new(names.collect());
+        self
+    }
+
+    pub(crate) fn fills_defaults(&self) -> bool {
+        self.fill_defaults
+    }
+    pub(crate) fn on_duplicate(&self) -> DuplicateFields {
+        self.duplicate_fields
+    }
+    pub(crate) fn rejects_nan(&self) -> bool {
+        self.reject_nan
+    }
+    pub(crate) fn resolves_short_names(&self) -> bool {
+        self.short_names
+    }
+    pub(crate) fn enabled_features(&self) -> Arc<BTreeSet<String>> {
+        self.features.clone()
+    }
+    /// The replacement of the deprecated type or field name `name`.
+    pub(crate) fn replacement(&self, name: &str) -> Option<&str> {
+        self.deprecated_names.get(name).map(String::as_str)
+    }
+    pub(crate) fn allows_env(&self, name: &str) -> bool {
+        self.env_vars.contains(name)
+    }
+    #[cfg(feature = "templating")]
+    pub(crate) fn limits(&self) -> Limits {
+        self.expansion_limits
+    }
+    /// Whether `error` is a warning with those options.
+    pub(crate) fn is_warning(&self, error: &Error) -> bool {
+        match &*error.source {
+            _ if self.deny_warnings => false,
+            ErrorType::LossyFloat(_) => !self.strict_floats,
+            _ => error.is_warning(),
+        }
+    }
+    /// Drop the errors in excess of `max_errors`.
+    pub(crate) fn truncate(&self, errors: &mut Vec<Error>) {
+        if let Some(max) = self.max_errors {
+            errors.truncate(max);
+        }
+    }
+}
+
+/// The registry and options of a conversion.
+pub(crate) struct Context<'r> {
+    registry: &'r TypeRegistry,
+    pub(crate) options: &'r DeserOptions,
+    /// How many values the value being converted is nested in.
+    depth: Cell<u32>,
+}
+impl<'r> Context<'r> {
+    pub(crate) fn new(registry: &'r TypeRegistry, options: &'r DeserOptions) -> Self {
+        Self { registry, options, depth: Cell::new(0) }
+    }
+    /// Run `convert` one value deeper, an error at `span` instead if it is
+    /// past the `max_depth` option.
+    pub(crate) fn nested<T>(&self, span: Span, convert: impl FnOnce() -> MResult<T>) -> MResult<T> {
+        let depth = self.depth.get();
+        let max = self.options.max_depth;
+        if depth >= max {
+            return MultiResult::Err(vec![ErrorType::MaxDepthExceeded(max).spanned(&span)]);
+        }
+        self.depth.set(depth + 1);
+        let result = convert();
+        self.depth.set(depth);
+        result
+    }
+}
+impl Deref for Context<'_> {
+    type Target = TypeRegistry;
+    fn deref(&self) -> &TypeRegistry {
+        self.registry
+    }
+}
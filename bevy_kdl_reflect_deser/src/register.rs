@@ -0,0 +1,198 @@
+//! Register the types a type is made of.
+use std::{any::TypeId, collections::HashSet};
+
+#[cfg(feature = "bevy-collections")]
+use bevy_utils::HashMap;
+
+use crate::compat::{
+    GetTypeRegistration, ItemTypes, KeyType, TypeIdentity, TypeInfo, TypeRegistration,
+    TypeRegistry, VariantInfo,
+};
+
+type Register = fn(&mut TypeRegistry);
+
+macro_rules! registered {
+    ($ty:ty) => {
+        (
+            TypeId::of::<$ty>(),
+            (|r: &mut TypeRegistry| r.add_registration(TypeRegistration::of::<$ty>())) as Register,
+        )
+    };
+}
+/// `vec![$apply!($($prefix)* ty), ...]` for each primitive that can be mixed
+/// in tuples. The list is shorter than the one of [`std_types`], each tuple
+/// type adds to build times.
+macro_rules! for_mixed {
+    ($apply:ident!($($prefix:tt)*)) => {
+        vec![
+            $apply!($($prefix)* bool),
+            $apply!($($prefix)* u8),
+            $apply!($($prefix)* u16),
+            $apply!($($prefix)* u32),
+            $apply!($($prefix)* u64),
+            $apply!($($prefix)* i32),
+            $apply!($($prefix)* i64),
+            $apply!($($prefix)* f32),
+            $apply!($($prefix)* f64),
+            $apply!($($prefix)* String),
+        ]
+    };
+}
+/// The tuples of two or three primitives starting with `$a` and `$b`.
+macro_rules! mixed_tuples {
+    ($a:ty) => {
+        for_mixed!(mixed_tuples!($a,)).concat()
+    };
+    ($a:ty, $b:ty) => {{
+        let mut tuples = for_mixed!(registered_triple!($a, $b,));
+        tuples.push(registered!(($a, $b)));
+        tuples
+    }};
+}
+macro_rules! registered_triple {
+    ($a:ty, $b:ty, $c:ty) => {
+        registered!(($a, $b, $c))
+    };
+}
+
+/// Types that can be registered knowing only their `TypeId`.
+fn std_types() -> Vec<(TypeId, Register)> {
+    macro_rules! std_types {
+        ($($ty:ty),* $(,)?) => {{
+            let types = vec![$(
+                (TypeId::of::<$ty>(), (|r: &mut TypeRegistry| r.register::<$ty>()) as Register),
+                registered!(Option<$ty>),
+                registered!(Vec<$ty>),
+                registered!(($ty, $ty)),
+                registered!(($ty, $ty, $ty)),
+            )*];
+            #[cfg(feature = "bevy-collections")]
+            let types = [types, vec![$(registered!(HashMap<String, $ty>),)*]].concat();
+            types
+        }};
+    }
+    let mut types = std_types!(
+        bool, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, String,
+    );
+    // Such as `(u64, u32, u32)`. The tuples of a single primitive are in
+    // `types` already, they are registered the same way.
+    types.extend(for_mixed!(mixed_tuples!()).concat());
+    types
+}
+
+/// The types of the fields of `info`, with their names.
+fn field_types(info: &TypeInfo) -> Vec<(TypeId, &'static str)> {
+    macro_rules! fields {
+        ($info:expr) => {
+            $info
+                .iter()
+                .map(|f| (f.reflected_id(), f.full_name()))
+                .collect()
+        };
+    }
+    match info {
+        TypeInfo::Struct(info) => fields!(info),
+        TypeInfo::TupleStruct(info) => fields!(info),
+        TypeInfo::Tuple(info) => fields!(info),
+        TypeInfo::List(info) => vec![(info.item_id(), info.item_name())],
+        TypeInfo::Array(info) => vec![(info.item_id(), info.item_name())],
+        TypeInfo::Map(info) => vec![
+            (info.key_id(), info.key_name()),
+            (info.item_id(), info.item_name()),
+        ],
+        TypeInfo::Enum(info) => info
+            .iter()
+            .flat_map(|variant| -> Vec<_> {
+                match variant {
+                    VariantInfo::Struct(variant) => fields!(variant),
+                    VariantInfo::Tuple(variant) => fields!(variant),
+                    VariantInfo::Unit(_) => Vec::new(),
+                }
+            })
+            .collect(),
+        TypeInfo::Value(_) | TypeInfo::Dynamic(_) => Vec::new(),
+    }
+}
+
+/// Register the types reachable from the fields of the registered type `id`.
+///
+/// Only primitives, `String`, their `Option`, `Vec`, `HashMap<String, _>`
+/// (with the `bevy-collections` feature), tuples of two or three of the same
+/// primitive, and tuples of two or three of `bool`, `u8`, `u16`, `u32`, `u64`,
+/// `i32`, `i64`, `f32`, `f64` and `String` can be registered without knowing them statically. Returns the
+/// names of the other types not already registered, their fields are not
+/// visited.
+pub(crate) fn register_dependencies(registry: &mut TypeRegistry, id: TypeId) -> Vec<&'static str> {
+    let std_types = std_types();
+    let mut missing = Vec::new();
+    let mut visited = HashSet::default();
+    let mut to_visit = vec![(id, "")];
+    while let Some((id, name)) = to_visit.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        if registry.get(id).is_none() {
+            match std_types.iter().find(|(std_id, _)| *std_id == id) {
+                Some((_, register)) => register(registry),
+                None => {
+                    missing.push(name);
+                    continue;
+                }
+            }
+        }
+        if let Some(info) = registry.get_type_info(id) {
+            to_visit.extend(field_types(info));
+        }
+    }
+    missing
+}
+
+/// Register `T` and the types reachable from its fields.
+///
+/// Fields are visited through tuples, `Option`s, lists, arrays, maps and enum
+/// variants. Types that can't be registered without knowing them statically
+/// (see the crate README) and aren't already registered are returned by name,
+/// register them before calling this to visit their fields as well.
+#[must_use = "the returned types are not registered, register them to deserialize T"]
+pub fn register_recursive<T: GetTypeRegistration>(
+    registry: &mut TypeRegistry,
+) -> Vec<&'static str> {
+    registry.register::<T>();
+    register_dependencies(registry, TypeId::of::<T>())
+}
+
+#[cfg(test)]
+mod test {
+    use bevy_reflect::{FromReflect, Reflect};
+
+    use super::*;
+
+    #[derive(Reflect, FromReflect)]
+    struct Inner(u8);
+
+    #[derive(Reflect, FromReflect)]
+    struct Outer {
+        size: Option<u16>,
+        position: (f32, f32, f32),
+        populations: (u64, u32, u32),
+        names: Vec<String>,
+        scores: HashMap<String, u32>,
+        inner: Inner,
+    }
+
+    #[test]
+    fn registers_std_fields() {
+        let mut registry = TypeRegistry::default();
+        let missing = register_recursive::<Outer>(&mut registry);
+        assert_eq!(missing, vec![std::any::type_name::<Inner>()]);
+        assert!(registry.get(TypeId::of::<(f32, f32, f32)>()).is_some());
+        assert!(registry.get(TypeId::of::<(u64, u32, u32)>()).is_some());
+        assert!(registry.get(TypeId::of::<HashMap<String, u32>>()).is_some());
+        assert!(registry.get(TypeId::of::<Option<u16>>()).is_some());
+        assert!(registry.get(TypeId::of::<u8>()).is_none());
+
+        registry.register::<Inner>();
+        assert!(register_recursive::<Outer>(&mut registry).is_empty());
+        assert!(registry.get(TypeId::of::<u8>()).is_some());
+    }
+}
@@ -0,0 +1,521 @@
+//! A standalone description of the registered types, for tools running
+//! outside of the bevy app.
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io, mem,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+use kdl::{KdlDocument, KdlError, KdlNode};
+use multierr_span::Spanned;
+use template_kdl::navigate::{Navigable, Value};
+
+use crate::{
+    compat::{ItemTypes, KeyType, TypeIdentity, TypeInfo, TypeRegistry, VariantInfo},
+    dyn_wrappers::{declared_fields, ANON_TUPLE, DEFAULT_MARKER},
+    err::{Error, ErrorType},
+    visit::Field,
+    DeserOptions,
+};
+
+/// The shape of a [`TypeSchema`], following bevy's `TypeInfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Struct,
+    TupleStruct,
+    Tuple,
+    List,
+    Array,
+    Map,
+    Enum,
+    /// Opaque types, such as primitives and `reflect_value` types.
+    Value,
+}
+impl TypeKind {
+    fn of(info: &TypeInfo) -> Self {
+        match info {
+            TypeInfo::Struct(_) => Self::Struct,
+            TypeInfo::TupleStruct(_) => Self::TupleStruct,
+            TypeInfo::Tuple(_) => Self::Tuple,
+            TypeInfo::List(_) => Self::List,
+            TypeInfo::Array(_) => Self::Array,
+            TypeInfo::Map(_) => Self::Map,
+            TypeInfo::Enum(_) => Self::Enum,
+            TypeInfo::Value(_) | TypeInfo::Dynamic(_) => Self::Value,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Self::Struct => "struct",
+            Self::TupleStruct => "tuple-struct",
+            Self::Tuple => "tuple",
+            Self::List => "list",
+            Self::Array => "array",
+            Self::Map => "map",
+            Self::Enum => "enum",
+            Self::Value => "value",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        let kinds = [
+            Self::Struct,
+            Self::TupleStruct,
+            Self::Tuple,
+            Self::List,
+            Self::Array,
+            Self::Map,
+            Self::Enum,
+            Self::Value,
+        ];
+        kinds.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// A field of a type, or the item type of a collection.
+///
+/// Fields of tuples and the items of lists and arrays have no name, the key
+/// and value types of maps are named `key` and `value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSchema {
+    pub name: Option<String>,
+    pub type_name: String,
+    pub docs: Option<String>,
+}
+impl FieldSchema {
+    fn new(name: Option<&str>, type_name: &str) -> Self {
+        Self {
+            name: name.map(str::to_owned),
+            type_name: type_name.to_owned(),
+            docs: None,
+        }
+    }
+}
+
+/// A variant of an enum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantSchema {
+    pub name: String,
+    pub fields: Vec<FieldSchema>,
+}
+
+/// A registered type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeSchema {
+    pub name: String,
+    pub short_name: String,
+    pub kind: TypeKind,
+    /// The doc comment of the type, `bevy_reflect` doesn't record it, set it
+    /// with [`RegistrySchema::set_docs`] before dumping the schema.
+    pub docs: Option<String>,
+    pub fields: Vec<FieldSchema>,
+    /// The variants of enums, empty for other kinds.
+    pub variants: Vec<VariantSchema>,
+}
+impl TypeSchema {
+    fn of(short_name: &str, info: &TypeInfo) -> Self {
+        macro_rules! fields {
+            ($info:expr, named) => {
+                $info
+                    .iter()
+                    .map(|f| FieldSchema::new(Some(f.name()), f.full_name()))
+                    .collect()
+            };
+            ($info:expr, unnamed) => {
+                $info
+                    .iter()
+                    .map(|f| FieldSchema::new(None, f.full_name()))
+                    .collect()
+            };
+        }
+        let fields = match info {
+            TypeInfo::Struct(info) => fields!(info, named),
+            TypeInfo::TupleStruct(info) => fields!(info, unnamed),
+            TypeInfo::Tuple(info) => fields!(info, unnamed),
+            TypeInfo::List(info) => vec![FieldSchema::new(None, info.item_name())],
+            TypeInfo::Array(info) => vec![FieldSchema::new(None, info.item_name())],
+            TypeInfo::Map(info) => vec![
+                FieldSchema::new(Some("key"), info.key_name()),
+                FieldSchema::new(Some("value"), info.item_name()),
+            ],
+            TypeInfo::Enum(_) | TypeInfo::Value(_) | TypeInfo::Dynamic(_) => Vec::new(),
+        };
+        let variants = match info {
+            TypeInfo::Enum(info) => info
+                .iter()
+                .map(|variant| {
+                    let fields = match variant {
+                        VariantInfo::Struct(variant) => fields!(variant, named),
+                        VariantInfo::Tuple(variant) => fields!(variant, unnamed),
+                        VariantInfo::Unit(_) => Vec::new(),
+                    };
+                    VariantSchema { name: variant.name().to_owned(), fields }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Self {
+            name: info.full_name().to_owned(),
+            short_name: short_name.to_owned(),
+            kind: TypeKind::of(info),
+            docs: None,
+            fields,
+            variants,
+        }
+    }
+    /// The field named `name`.
+    pub fn field(&self, name: &str) -> Option<&FieldSchema> {
+        self.fields.iter().find(|f| f.name.as_deref() == Some(name))
+    }
+}
+
+/// An error reading a [`RegistrySchema`].
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Kdl(#[from] KdlError),
+    #[error("Invalid schema node at {offset}: {reason}")]
+    Invalid { offset: usize, reason: &'static str },
+}
+impl SchemaError {
+    fn invalid(node: &KdlNode, reason: &'static str) -> Self {
+        Self::Invalid { offset: node.span().offset(), reason }
+    }
+}
+
+/// The string arguments of `node`.
+fn arguments(node: &KdlNode) -> Vec<&str> {
+    let arguments = node.entries().iter().filter(|e| e.name().is_none());
+    arguments.filter_map(|e| e.value().as_string()).collect()
+}
+/// The string value of the `key` property of `node`.
+fn property<'n>(node: &'n KdlNode, key: &str) -> Option<&'n str> {
+    let mut properties = node.entries().iter();
+    let property = properties.find(|e| e.name().map(|n| n.value()) == Some(key));
+    property.and_then(|e| e.value().as_string())
+}
+fn children(node: &KdlNode) -> &[KdlNode] {
+    node.children().map_or(&[], KdlDocument::nodes)
+}
+
+fn field_node(field: &FieldSchema) -> KdlNode {
+    let mut node = KdlNode::new("field");
+    if let Some(name) = &field.name {
+        node.push(name.clone());
+    }
+    node.push(field.type_name.clone());
+    if let Some(docs) = &field.docs {
+        node.push(("docs", docs.clone()));
+    }
+    node
+}
+fn read_fields(nodes: &[KdlNode]) -> Result<Vec<FieldSchema>, SchemaError> {
+    let fields = nodes.iter().filter(|node| node.name().value() == "field");
+    let read_field = |node: &KdlNode| {
+        let invalid = || SchemaError::invalid(node, "expected a type, or a name and a type");
+        let mut field = match arguments(node)[..] {
+            [type_name] => FieldSchema::new(None, type_name),
+            [name, type_name] => FieldSchema::new(Some(name), type_name),
+            _ => return Err(invalid()),
+        };
+        field.docs = property(node, "docs").map(str::to_owned);
+        Ok(field)
+    };
+    fields.map(read_field).collect()
+}
+
+/// A snapshot of the types of a `TypeRegistry`.
+///
+/// Tools running outside of the bevy app, such as a command line validator or
+/// a language server, don't have access to its `TypeRegistry`. The app dumps
+/// it with [`dump_registry_schema`], and the tools read it back with
+/// [`RegistrySchema::load`] to check documents with
+/// [`validate_with_schema`](crate::validate_with_schema).
+///
+/// The schema is itself a kdl document:
+///
+/// ```kdl
+/// type "my_game::Line" short="Line" kind="struct" {
+///     field "start" "my_game::Point"
+///     field "end" "my_game::Point"
+/// }
+/// type "my_game::Meters" short="Meters" kind="tuple-struct" docs="A distance" {
+///     field "f32"
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegistrySchema {
+    types: Vec<TypeSchema>,
+    by_name: HashMap<String, usize>,
+    /// `None` when several types have the same short name.
+    by_short_name: HashMap<String, Option<usize>>,
+}
+impl RegistrySchema {
+    pub fn new(types: Vec<TypeSchema>) -> Self {
+        let mut by_name = HashMap::default();
+        let mut by_short_name = HashMap::default();
+        for (i, ty) in types.iter().enumerate() {
+            by_name.insert(ty.name.clone(), i);
+            by_short_name
+                .entry(ty.short_name.clone())
+                .and_modify(|index| *index = None)
+                .or_insert(Some(i));
+        }
+        Self { types, by_name, by_short_name }
+    }
+    /// The schema of all types registered in `registry`.
+    pub fn of(registry: &TypeRegistry) -> Self {
+        let mut types: Vec<_> = registry
+            .iter()
+            .map(|reg| TypeSchema::of(reg.short_name(), reg.type_info()))
+            .collect();
+        types.sort_by(|a, b| a.name.cmp(&b.name));
+        Self::new(types)
+    }
+    pub fn types(&self) -> &[TypeSchema] {
+        &self.types
+    }
+    pub fn get_with_name(&self, name: &str) -> Option<&TypeSchema> {
+        self.by_name.get(name).map(|i| &self.types[*i])
+    }
+    /// Set the doc comment of the type named `name`, `false` if there is no
+    /// such type.
+    pub fn set_docs(&mut self, name: &str, docs: impl Into<String>) -> bool {
+        let Some(i) = self.by_name.get(name) else {
+            return false;
+        };
+        self.types[*i].docs = Some(docs.into());
+        true
+    }
+    /// Set the doc comment of the field `field` of the type named `name`,
+    /// `false` if there is no such field.
+    pub fn set_field_docs(&mut self, name: &str, field: &str, docs: impl Into<String>) -> bool {
+        let Some(i) = self.by_name.get(name) else {
+            return false;
+        };
+        let mut fields = self.types[*i].fields.iter_mut();
+        let Some(field) = fields.find(|f| f.name.as_deref() == Some(field)) else {
+            return false;
+        };
+        field.docs = Some(docs.into());
+        true
+    }
+    /// The type with the short name `name`, `None` if several types have it.
+    pub fn get_with_short_name(&self, name: &str) -> Option<&TypeSchema> {
+        let index = self.by_short_name.get(name).copied().flatten();
+        index.map(|i| &self.types[i])
+    }
+    pub fn to_kdl(&self) -> KdlDocument {
+        let mut document = KdlDocument::new();
+        for ty in &self.types {
+            let mut node = KdlNode::new("type");
+            node.push(ty.name.clone());
+            node.push(("short", ty.short_name.clone()));
+            node.push(("kind", ty.kind.name()));
+            if let Some(docs) = &ty.docs {
+                node.push(("docs", docs.clone()));
+            }
+            let mut children: Vec<_> = ty.fields.iter().map(field_node).collect();
+            for variant in &ty.variants {
+                let mut variant_node = KdlNode::new("variant");
+                variant_node.push(variant.name.clone());
+                let fields = variant.fields.iter().map(field_node);
+                variant_node.ensure_children().nodes_mut().extend(fields);
+                children.push(variant_node);
+            }
+            if !children.is_empty() {
+                node.ensure_children().nodes_mut().extend(children);
+            }
+            document.nodes_mut().push(node);
+        }
+        document.fmt();
+        document
+    }
+    pub fn from_kdl(document: &KdlDocument) -> Result<Self, SchemaError> {
+        let mut types = Vec::with_capacity(document.nodes().len());
+        for node in document.nodes() {
+            if node.name().value() != "type" {
+                return Err(SchemaError::invalid(node, "expected a `type` node"));
+            }
+            let name = match arguments(node)[..] {
+                [name] => name,
+                _ => return Err(SchemaError::invalid(node, "expected the type name")),
+            };
+            let kind = property(node, "kind").and_then(TypeKind::from_name);
+            let kind = kind.ok_or_else(|| SchemaError::invalid(node, "unknown type kind"))?;
+            let variant_nodes = children(node)
+                .iter()
+                .filter(|n| n.name().value() == "variant");
+            let read_variant = |node: &KdlNode| match arguments(node)[..] {
+                [name] => Ok(VariantSchema {
+                    name: name.to_owned(),
+                    fields: read_fields(children(node))?,
+                }),
+                _ => Err(SchemaError::invalid(node, "expected the variant name")),
+            };
+            types.push(TypeSchema {
+                name: name.to_owned(),
+                short_name: property(node, "short").unwrap_or(name).to_owned(),
+                kind,
+                docs: property(node, "docs").map(str::to_owned),
+                fields: read_fields(children(node))?,
+                variants: variant_nodes.map(read_variant).collect::<Result<_, _>>()?,
+            });
+        }
+        Ok(Self::new(types))
+    }
+    /// Read the schema written by [`dump_registry_schema`] at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SchemaError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+impl FromStr for RegistrySchema {
+    type Err = SchemaError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_kdl(&s.parse()?)
+    }
+}
+
+/// Write the schema of the types registered in `registry` at `path`.
+///
+/// In a bevy app, use the `AppTypeRegistry` resource:
+/// `dump_registry_schema(&app_registry.read(), "types.kdl")`.
+pub fn dump_registry_schema(registry: &TypeRegistry, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, RegistrySchema::of(registry).to_kdl().to_string())
+}
+
+/// Check the type and field names of a document against a [`RegistrySchema`].
+pub(crate) struct Validator<'s> {
+    schema: &'s RegistrySchema,
+    short_names: bool,
+    /// The features of `only-if` nodes to check the children of.
+    features: Arc<BTreeSet<String>>,
+    pub(crate) errors: Vec<Error>,
+}
+impl<'s> Validator<'s> {
+    pub(crate) fn new(schema: &'s RegistrySchema, options: &DeserOptions) -> Self {
+        Self {
+            schema,
+            short_names: options.resolves_short_names(),
+            features: options.enabled_features(),
+            errors: Vec::new(),
+        }
+    }
+    fn get(&mut self, name: &str, span: &impl Spanned) -> Option<&'s TypeSchema> {
+        let mut schema = self.schema.get_with_name(name);
+        if schema.is_none() && self.short_names {
+            schema = self.schema.get_with_short_name(name);
+        }
+        if schema.is_none() {
+            let error = ErrorType::NoSuchType(name.to_owned());
+            self.errors.push(error.spanned(span));
+        }
+        schema
+    }
+    /// The single-field types `ty` wraps, from `ty` to the most inner one.
+    fn newtypes(&self, mut ty: &'s TypeSchema) -> Vec<&'s TypeSchema> {
+        use TypeKind::{Struct, Tuple, TupleStruct};
+        let mut newtypes = vec![ty];
+        while let (Struct | Tuple | TupleStruct, [inner]) = (ty.kind, &ty.fields[..]) {
+            match self.schema.get_with_name(&inner.type_name) {
+                Some(inner) if !newtypes.contains(&inner) => ty = inner,
+                _ => break,
+            }
+            newtypes.push(ty);
+        }
+        newtypes
+    }
+    /// Check `field`, `named` if its name is its type rather than a field
+    /// name, like the deserializer does.
+    pub(crate) fn check(&mut self, field: Field, expected: Option<&str>, named: bool) {
+        let declared = if named { field.ty().or(field.name()) } else { field.ty() };
+        let span = declared.as_ref().map_or(field.span(), |ty| ty.span());
+        let expected = expected.and_then(|name| self.get(name, &span));
+        let ty = match declared.as_deref() {
+            Some(ANON_TUPLE) => expected,
+            Some(declared) => self.get(declared, &span),
+            None => expected,
+        };
+        let Value::List(children) = field.value() else {
+            return;
+        };
+        let features = self.features.clone();
+        let children = children.flat_map(move |child| declared_fields(child, features.clone()));
+        let Some(ty) = ty else {
+            // An anonymous tuple, its fields declare their type
+            if declared.as_deref() == Some(ANON_TUPLE) {
+                children.for_each(|child| self.check(child, None, true));
+            }
+            return;
+        };
+        // Like when deserializing, a newtype can be declared as any of the
+        // types it wraps, starting from the most inner one.
+        let outer_errors = mem::take(&mut self.errors);
+        let mut errors = Vec::new();
+        for ty in self.newtypes(ty).into_iter().rev() {
+            self.check_fields(ty, &field);
+            errors = mem::take(&mut self.errors);
+            if errors.is_empty() {
+                break;
+            }
+        }
+        self.errors = outer_errors;
+        self.errors.extend(errors);
+    }
+    fn check_fields(&mut self, ty: &'s TypeSchema, field: &Field) {
+        let Value::List(children) = field.value() else {
+            return;
+        };
+        let features = self.features.clone();
+        let children = children.flat_map(move |child| declared_fields(child, features.clone()));
+        match ty.kind {
+            TypeKind::Struct => {
+                for child in children {
+                    let Some(name) = child.name() else { continue };
+                    if &*name == DEFAULT_MARKER {
+                        continue;
+                    }
+                    match ty.field(&name) {
+                        Some(field) => self.check(child, Some(&field.type_name), false),
+                        None => {
+                            let available = ty.fields.iter().filter_map(|f| f.name.clone());
+                            let error = ErrorType::NoSuchSchemaField {
+                                requested: String::from(&*name),
+                                name: ty.name.clone(),
+                                available: available.collect(),
+                            };
+                            self.errors.push(error.spanned(&name));
+                        }
+                    }
+                }
+            }
+            TypeKind::Tuple | TypeKind::TupleStruct => {
+                for (child, field) in children.zip(&ty.fields) {
+                    self.check(child, Some(&field.type_name), false);
+                }
+            }
+            TypeKind::List | TypeKind::Array => {
+                let Some(item) = ty.fields.first() else {
+                    return;
+                };
+                for child in children {
+                    self.check(child, Some(&item.type_name), false);
+                }
+            }
+            // Pair style declarations are not checked
+            TypeKind::Map => {
+                let Some(value) = ty.field("value") else {
+                    return;
+                };
+                let named = children.filter(|child| child.name().map_or(false, |n| &*n != "-"));
+                for child in named {
+                    self.check(child, Some(&value.type_name), false);
+                }
+            }
+            TypeKind::Enum | TypeKind::Value => {}
+        }
+    }
+}
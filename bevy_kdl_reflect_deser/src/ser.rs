@@ -0,0 +1,266 @@
+//! Write reflected values as kdl nodes, declared so that they convert back
+//! into the same values.
+use std::cmp::Ordering;
+
+use kdl::{KdlNode, KdlValue};
+
+use crate::{
+    compat::{Reflect, ReflectRef, TypeIdentity, TypeRegistry},
+    ReflectSet,
+};
+
+/// The node name of items and fields declared by position.
+const POSITIONAL: &str = "-";
+
+/// Why a value can't be declared in kdl.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SerError {
+    #[error("{0} is an enum, only `Option`s of values can be declared")]
+    Enum(String),
+    #[error("{0} is an opaque `reflect_value` type, it can't be declared")]
+    Opaque(String),
+}
+
+/// `value` as a kdl value, `None` if it can't be declared as one.
+fn kdl_value(value: &dyn Reflect) -> Option<KdlValue> {
+    macro_rules! downcast {
+        ($($ty:ty => $convert:expr),* $(,)?) => {
+            $( if let Some(v) = value.downcast_ref::<$ty>() {
+                return Some($convert(*v));
+            } )*
+        };
+    }
+    // kdl can't parse integers out of the i64 range, they are strings.
+    let big_int = |i: i128| -> KdlValue {
+        i64::try_from(i).map_or_else(|_| i.to_string().into(), Into::into)
+    };
+    let float = |f: f64| -> KdlValue {
+        match () {
+            () if f.is_nan() => "NaN".into(),
+            () if f.is_infinite() => (if f > 0.0 { "inf" } else { "-inf" }).into(),
+            () => KdlValue::Base10Float(f),
+        }
+    };
+    downcast! {
+        i8 => |i| KdlValue::Base10(i.into()), i16 => |i| KdlValue::Base10(i.into()),
+        i32 => |i| KdlValue::Base10(i.into()), i64 => KdlValue::Base10,
+        u8 => |i| KdlValue::Base10(i.into()), u16 => |i| KdlValue::Base10(i.into()),
+        u32 => |i| KdlValue::Base10(i.into()),
+        isize => |i| big_int(i as i128), usize => |i| big_int(i as i128),
+        u64 => |i| big_int(i as i128), i128 => big_int,
+        u128 => |i: u128| i128::try_from(i).map_or_else(|_| i.to_string().into(), big_int),
+        // The shortest representation of the f32, so that it isn't lossy.
+        f32 => |f: f32| float(f.to_string().parse().unwrap_or(f as f64)),
+        f64 => float,
+        bool => KdlValue::Bool,
+    }
+    if let Some(string) = value.downcast_ref::<String>() {
+        return Some(string.clone().into());
+    }
+    match value.reflect_ref() {
+        ReflectRef::Tuple(unit) if unit.field_len() == 0 => Some(KdlValue::Null),
+        ReflectRef::Enum(option) if is_option(value) => match option.field_at(0) {
+            None => Some(KdlValue::Null),
+            Some(some) => kdl_value(some),
+        },
+        _ => None,
+    }
+}
+fn is_option(value: &dyn Reflect) -> bool {
+    value.type_name().starts_with("core::option::Option<")
+}
+
+/// How [`serialize_value`] orders the fields of structs.
+///
+/// Map entries and set items are always sorted, so that serializing the same
+/// value twice gives the same document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldOrder {
+    /// In the order they are declared in the rust type.
+    #[default]
+    Declaration,
+    /// Sorted by name.
+    Alphabetical,
+}
+
+/// The order of map pairs and set items: numerically by their first value if
+/// it is a number, then by how they are printed.
+fn canonical_cmp(a: &KdlNode, b: &KdlNode) -> Ordering {
+    let first = |node: &KdlNode| node.entries().first().map(|e| e.value());
+    let by_number = match (first(a), first(b)) {
+        (Some(a), Some(b)) => cmp_numbers(a, b).unwrap_or(Ordering::Equal),
+        _ => Ordering::Equal,
+    };
+    by_number.then_with(|| a.to_string().cmp(&b.to_string()))
+}
+/// The order of `a` and `b` if they are both numbers, integers are compared
+/// exactly, not as the `f64` they round to.
+fn cmp_numbers(a: &KdlValue, b: &KdlValue) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        return Some(a.cmp(&b));
+    }
+    let float = |value: &KdlValue| value.as_f64().or_else(|| value.as_i64().map(|i| i as f64));
+    Some(float(a)?.total_cmp(&float(b)?))
+}
+
+struct Serializer<'r> {
+    registry: &'r TypeRegistry,
+    order: FieldOrder,
+}
+impl Serializer<'_> {
+    /// Add `fields` to `node`, as entries if they are all values, otherwise
+    /// as children named after the name paired with each field.
+    ///
+    /// A node with a single entry is read as that value, so a single field is
+    /// only an entry if `is_newtype`.
+    fn push_fields<'a>(
+        &self,
+        node: &mut KdlNode,
+        fields: impl Iterator<Item = (&'a str, &'a dyn Reflect)>,
+        is_newtype: bool,
+    ) -> Result<(), SerError> {
+        let fields: Vec<_> = fields.collect();
+        let values: Option<Vec<_>> = fields.iter().map(|(_, f)| kdl_value(*f)).collect();
+        match values {
+            Some(values) if values.len() != 1 || is_newtype => {
+                values.into_iter().for_each(|value| node.push(value));
+            }
+            _ => {
+                let children = node.ensure_children().nodes_mut();
+                for (name, field) in fields {
+                    children.push(self.node(name, field)?);
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Add the named `fields` to `node`, values as properties and other
+    /// fields as children.
+    fn push_named<'a>(
+        &self,
+        node: &mut KdlNode,
+        fields: impl Iterator<Item = (&'a str, &'a dyn Reflect)>,
+    ) -> Result<(), SerError> {
+        for (name, field) in fields {
+            match kdl_value(field) {
+                Some(field) => node.push((name, field)),
+                None => {
+                    let field = self.node(name, field)?;
+                    node.ensure_children().nodes_mut().push(field);
+                }
+            }
+        }
+        Ok(())
+    }
+    /// The node named `name` declaring `value`.
+    fn node(&self, name: &str, value: &dyn Reflect) -> Result<KdlNode, SerError> {
+        let mut node = KdlNode::new(name);
+        if let Some(value) = kdl_value(value) {
+            node.push(value);
+            return Ok(node);
+        }
+        let positional = |len| (0..len).map(|_| POSITIONAL);
+        match value.reflect_ref() {
+            // Named fields can mix entries and children.
+            ReflectRef::Struct(value) => {
+                // unwrap: `i` is the index of a field
+                let name = |i| value.name_at(i).unwrap();
+                let mut fields: Vec<_> = value
+                    .iter_fields()
+                    .enumerate()
+                    .map(|(i, field)| (name(i), field))
+                    .collect();
+                if self.order == FieldOrder::Alphabetical {
+                    fields.sort_by_key(|(name, _)| *name);
+                }
+                self.push_named(&mut node, fields.into_iter())?;
+            }
+            ReflectRef::TupleStruct(value) => {
+                let fields = positional(value.field_len()).zip(value.iter_fields());
+                self.push_fields(&mut node, fields, true)?;
+            }
+            ReflectRef::Tuple(value) => {
+                let fields = positional(value.field_len()).zip(value.iter_fields());
+                self.push_fields(&mut node, fields, true)?;
+            }
+            ReflectRef::List(value) => {
+                let items = positional(value.len()).zip(value.iter());
+                self.push_fields(&mut node, items, false)?;
+            }
+            ReflectRef::Array(value) => {
+                let items = positional(value.len()).zip(value.iter());
+                self.push_fields(&mut node, items, false)?;
+            }
+            ReflectRef::Map(value) => {
+                let mut named = Vec::new();
+                let mut pairs = Vec::new();
+                for (key, item) in value.iter() {
+                    match key.downcast_ref::<String>() {
+                        Some(key) => named.push((key.as_str(), item)),
+                        // Other keys are declared as `(key, value)` pairs.
+                        None => {
+                            let mut pair = KdlNode::new(POSITIONAL);
+                            let fields = positional(2).zip([key, item]);
+                            self.push_fields(&mut pair, fields, false)?;
+                            pairs.push(pair);
+                        }
+                    }
+                }
+                named.sort_by_key(|(key, _)| *key);
+                pairs.sort_by(canonical_cmp);
+                self.push_named(&mut node, named.into_iter())?;
+                if !pairs.is_empty() {
+                    node.ensure_children().nodes_mut().extend(pairs);
+                }
+            }
+            ReflectRef::Enum(_) => return Err(SerError::Enum(value.type_name().to_owned())),
+            ReflectRef::Value(set) => {
+                let type_id = set.get_type_info().reflected_id();
+                let set_data = self.registry.get_type_data::<ReflectSet>(type_id);
+                let items = set_data.and_then(|data| data.items(set));
+                let Some(items) = items else {
+                    return Err(SerError::Opaque(value.type_name().to_owned()));
+                };
+                let mut items = items
+                    .iter()
+                    .map(|item| Ok((self.node(POSITIONAL, item.as_ref())?, item)))
+                    .collect::<Result<Vec<_>, SerError>>()?;
+                items.sort_by(|(a, _), (b, _)| canonical_cmp(a, b));
+                let items = items
+                    .into_iter()
+                    .map(|(_, item)| (POSITIONAL, item.as_ref()));
+                self.push_fields(&mut node, items, false)?;
+            }
+        }
+        Ok(node)
+    }
+}
+
+/// The name declaring the type of `value`: its short name if it is registered
+/// and no other registered type has it, its full name otherwise.
+fn type_name(value: &dyn Reflect, registry: &TypeRegistry) -> String {
+    let full_name = value.type_name();
+    let short_name = registry
+        .get_with_name(full_name)
+        .map(|registration| registration.short_name())
+        .filter(|short| {
+            let with_short = registry.get_with_short_name(short);
+            with_short.map_or(false, |r| r.full_name() == full_name)
+        });
+    short_name.unwrap_or(full_name).to_owned()
+}
+
+/// `value` as a kdl node, which converts back into `value` with
+/// [`from_doc`](crate::from_doc).
+///
+/// The node is named after the type of `value`, struct fields are in `order`.
+/// Enums, other than `Option`s of values, and opaque `reflect_value` types
+/// can't be declared, except for sets registering [`ReflectSet`].
+pub fn serialize_value(
+    value: &dyn Reflect,
+    registry: &TypeRegistry,
+    order: FieldOrder,
+) -> Result<KdlNode, SerError> {
+    let serializer = Serializer { registry, order };
+    serializer.node(&type_name(value, registry), value)
+}
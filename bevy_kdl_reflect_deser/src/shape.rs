@@ -0,0 +1,106 @@
+//! Compact descriptions of rust types and kdl declarations, for error messages.
+use std::fmt::Write;
+
+use template_kdl::navigate::{Navigable, Value};
+
+use crate::{
+    compat::{ItemTypes, KeyType, TypeIdentity, TypeInfo},
+    visit::Field,
+};
+
+/// `full_name` without the module paths of the types it names, such as
+/// `Vec<Entity>` for `alloc::vec::Vec<bevy_ecs::entity::Entity>`.
+pub(crate) fn short_name(full_name: &str) -> String {
+    fn last_segment(path: &str) -> &str {
+        path.rsplit("::").next().unwrap_or(path)
+    }
+    let mut short = String::with_capacity(full_name.len());
+    let mut path_start = 0;
+    for (i, c) in full_name.char_indices() {
+        if matches!(c, '<' | '>' | '(' | ')' | '[' | ']' | ',' | ';' | ' ' | '&') {
+            short.push_str(last_segment(&full_name[path_start..i]));
+            short.push(c);
+            path_start = i + c.len_utf8();
+        }
+    }
+    short.push_str(last_segment(&full_name[path_start..]));
+    short
+}
+/// The short `names`, separated by commas.
+fn short_names<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    names.map(short_name).collect::<Vec<_>>().join(", ")
+}
+/// The shape of `info`, with the types of its fields, one level deep.
+///
+/// Such as `Foo { bar: u32, baz: String }` or `list of u8`.
+pub(crate) fn of_type(info: &TypeInfo) -> String {
+    use TypeInfo::*;
+    let short = short_name;
+    match info {
+        Struct(info) => {
+            let fields: Vec<_> = info
+                .iter()
+                .map(|f| format!("{}: {}", f.name(), short(f.full_name())))
+                .collect();
+            format!("{} {{ {} }}", short(info.full_name()), fields.join(", "))
+        }
+        TupleStruct(info) => {
+            let fields = short_names(info.iter().map(|f| f.full_name()));
+            format!("{}({fields})", short(info.full_name()))
+        }
+        Tuple(info) => format!("({})", short_names(info.iter().map(|f| f.full_name()))),
+        List(info) => format!("list of {}", short(info.item_name())),
+        Array(info) => format!("array of {} {}", info.capacity(), short(info.item_name())),
+        Map(info) => {
+            let (key, value) = (short(info.key_name()), short(info.item_name()));
+            format!("map of {key} to {value}")
+        }
+        Enum(info) => {
+            let variants = info.variant_names().join(", ");
+            format!("enum {} {{ {variants} }}", short(info.full_name()))
+        }
+        Value(info) => short(info.full_name()),
+        Dynamic(info) => short(info.full_name()),
+    }
+}
+/// The shape of `info` if it has fields, `None` if it is a value type.
+pub(crate) fn of_compound(info: &TypeInfo) -> Option<String> {
+    let is_value = matches!(info, TypeInfo::Value(_) | TypeInfo::Dynamic(_));
+    (!is_value).then(|| of_type(info))
+}
+
+/// The shape of `field` as declared in kdl, its children one level deep.
+///
+/// Such as `Foo 1 bar=2 { baz …; - …; }`.
+pub(crate) fn of_field(field: &Field) -> String {
+    let mut shape = field
+        .name()
+        .map_or_else(|| "-".to_owned(), |n| n.to_string());
+    let fields = match field.value() {
+        Value::Bare(value) => {
+            write!(&mut shape, " {}", &*value).unwrap();
+            return shape;
+        }
+        Value::List(fields) => fields,
+    };
+    let mut children = Vec::new();
+    for field in fields {
+        let ty = field
+            .ty()
+            .map_or_else(String::new, |ty| format!("({})", &*ty));
+        match (field.is_entry(), field.name(), field.value()) {
+            (true, Some(name), Value::Bare(value)) => {
+                write!(&mut shape, " {}={ty}{}", &*name, &*value).unwrap()
+            }
+            (true, None, Value::Bare(value)) => write!(&mut shape, " {ty}{}", &*value).unwrap(),
+            (_, name, _) => {
+                let name = name.map_or_else(|| "-".to_owned(), |n| n.to_string());
+                children.push(format!("{ty}{name} …;"));
+            }
+        }
+    }
+    if !children.is_empty() {
+        write!(&mut shape, " {{ {} }}", children.join(" ")).unwrap();
+    }
+    shape
+}
@@ -0,0 +1,181 @@
+//! Helpers to test the conversion of kdl documents into rust types, enabled
+//! with the `test-utils` feature.
+use std::fmt::Write;
+
+use bevy_reflect::{
+    FromReflect, GetTypeRegistration, Reflect, ReflectRef, TypeRegistry, Typed, VariantType,
+};
+use kdl::KdlDocument;
+
+use crate::{from_doc, register_recursive, shape::short_name, ConvertErrors, ConvertResult};
+
+fn show(errors: ConvertErrors) -> String {
+    #[cfg(feature = "fancy-errors")]
+    return format!("{:?}", miette::Report::new(errors));
+    #[cfg(not(feature = "fancy-errors"))]
+    return errors.show_for();
+}
+
+/// `value` as an indented tree of its fields, with their names and types.
+///
+/// The `Debug` output of the dynamic values conversion returns doesn't show
+/// their type, this makes failed assertions on them readable. Values are
+/// followed by their type, compound values are preceded by it.
+///
+/// ```
+/// # use bevy_reflect::{FromReflect, Reflect};
+/// # use bevy_kdl_reflect_deser::test_utils::pretty_print;
+/// #[derive(Reflect, FromReflect, Debug)]
+/// struct Point { x: f32, y: f32 }
+///
+/// let printed = pretty_print(&Point { x: 1.0, y: 2.0 });
+/// assert_eq!(printed, "Point {\n    x: 1.0 (f32),\n    y: 2.0 (f32),\n}");
+/// ```
+pub fn pretty_print(value: &dyn Reflect) -> String {
+    let mut printed = String::new();
+    write_pretty(&mut printed, value, 0);
+    printed
+}
+fn write_pretty(out: &mut String, value: &dyn Reflect, depth: usize) {
+    let ty = short_name(value.type_name());
+    let named = |name: &str| format!("{name}: ");
+    // The fields of compound values, with their label
+    let (header, open, close, fields): (_, _, _, Vec<(String, &dyn Reflect)>) =
+        match value.reflect_ref() {
+            ReflectRef::Struct(value) => {
+                let field = |i| Some((named(value.name_at(i)?), value.field_at(i)?));
+                (
+                    ty,
+                    " {",
+                    "}",
+                    (0..value.field_len()).filter_map(field).collect(),
+                )
+            }
+            ReflectRef::TupleStruct(value) => {
+                let fields = value.iter_fields().map(|f| (String::new(), f));
+                (ty, "(", ")", fields.collect())
+            }
+            ReflectRef::Tuple(value) => {
+                let fields = value.iter_fields().map(|f| (String::new(), f));
+                (String::new(), "(", ")", fields.collect())
+            }
+            ReflectRef::List(value) => (
+                ty,
+                " [",
+                "]",
+                value.iter().map(|f| (String::new(), f)).collect(),
+            ),
+            ReflectRef::Array(value) => (
+                ty,
+                " [",
+                "]",
+                value.iter().map(|f| (String::new(), f)).collect(),
+            ),
+            ReflectRef::Map(value) => {
+                let fields = value.iter().map(|(k, v)| (format!("{k:?} => "), v));
+                (ty, " {", "}", fields.collect())
+            }
+            ReflectRef::Enum(value) => {
+                let header = format!("{ty}::{}", value.variant_name());
+                let indices = 0..value.field_len();
+                match value.variant_type() {
+                    VariantType::Struct => {
+                        let field = |i| Some((named(value.name_at(i)?), value.field_at(i)?));
+                        (header, " {", "}", indices.filter_map(field).collect())
+                    }
+                    VariantType::Tuple => {
+                        let field = |i| Some((String::new(), value.field_at(i)?));
+                        (header, "(", ")", indices.filter_map(field).collect())
+                    }
+                    VariantType::Unit => (header, "", "", Vec::new()),
+                }
+            }
+            ReflectRef::Value(value) => {
+                // unwrap: writing to a String never fails
+                write!(out, "{value:?} ({ty})").unwrap();
+                return;
+            }
+        };
+    out.push_str(&header);
+    out.push_str(open);
+    if !fields.is_empty() {
+        let indent = "    ".repeat(depth + 1);
+        for (label, field) in fields {
+            write!(out, "\n{indent}{label}").unwrap();
+            write_pretty(out, field, depth + 1);
+            out.push(',');
+        }
+        write!(out, "\n{}", "    ".repeat(depth)).unwrap();
+    }
+    out.push_str(close);
+}
+
+/// Convert `text` into a `T`, panicking with the conversion errors if it
+/// fails.
+///
+/// `T` is registered with [`register_recursive`](crate::register_recursive),
+/// `register` registers the other types `T` is made of. Panics listing the
+/// types not registered.
+///
+/// Until there is a serializer, this checks that `T` built from the
+/// converted value is equal to it.
+pub fn assert_roundtrip<T>(text: &str, register: impl FnOnce(&mut TypeRegistry)) -> T
+where
+    T: FromReflect + GetTypeRegistration + Typed,
+{
+    let type_name = T::type_info().type_name();
+    let mut registry = TypeRegistry::default();
+    register(&mut registry);
+    let missing = register_recursive::<T>(&mut registry);
+    assert!(
+        missing.is_empty(),
+        "Those types must be registered to convert into {type_name}: {missing:?}"
+    );
+    let document: KdlDocument = match text.parse() {
+        Ok(document) => document,
+        Err(error) => panic!("{text:?} is not a valid kdl document: {error}"),
+    };
+    let value = match from_doc::<T>(document, Default::default(), &registry, &Default::default()) {
+        ConvertResult::Deserialized(value) | ConvertResult::Warned(value, _) => value,
+        ConvertResult::Errors(errors) => panic!("{text:?} is not a {type_name}:{}", show(errors)),
+        ConvertResult::Exports(_) => panic!("{text:?} exports templates, it is not a value"),
+    };
+    let Some(typed) = T::from_reflect(value.as_ref()) else {
+        panic!("{value:?} can't be converted into {type_name}");
+    };
+    let equal = typed.reflect_partial_eq(value.as_ref());
+    let (value, typed_value) = (
+        pretty_print(value.as_ref()),
+        pretty_print(typed.as_reflect()),
+    );
+    let unequal = format!("{text:?} converts into {value}, but {type_name} is {typed_value}");
+    assert_ne!(equal, Some(false), "{unequal}");
+    typed
+}
+
+/// Assert that a kdl document converts into a type, returns the converted
+/// value.
+///
+/// The type and the primitive types it is made of are registered
+/// automatically, other types it is made of must be listed after the
+/// document. Conversion errors are shown on failure.
+///
+/// ```
+/// # use bevy_reflect::{FromReflect, Reflect};
+/// # use bevy_kdl_reflect_deser::assert_kdl_roundtrip;
+/// #[derive(Reflect, FromReflect, Debug)]
+/// struct Point { x: f32, y: f32 }
+/// #[derive(Reflect, FromReflect, Debug)]
+/// struct Line { start: Point, end: Point }
+///
+/// let line = assert_kdl_roundtrip!(Line, "Line { start x=1.0 y=2.0; end x=3.0 y=4.0; }", Point);
+/// assert_eq!(line.end.x, 3.0);
+/// ```
+#[macro_export]
+macro_rules! assert_kdl_roundtrip {
+    ($ty:ty, $text:expr $(, $dependency:ty)* $(,)?) => {
+        $crate::test_utils::assert_roundtrip::<$ty>($text, |_registry| {
+            $(_registry.register::<$dependency>();)*
+        })
+    };
+}
@@ -0,0 +1,270 @@
+//! Check converted values against the type they should build, pointing at
+//! the field that doesn't match it, and build them.
+use kdl::{KdlDocument, KdlEntry, KdlIdentifier, KdlNode};
+use multierr_span::Span;
+
+use crate::{
+    compat::{
+        FromReflect, FromType, ItemTypes, Reflect, ReflectRef, TypeIdentity, TypeInfo, TypeRegistry,
+    },
+    err::{Error, ErrorType},
+    DynRefl,
+};
+
+/// Type data to build a value of a registered type from the dynamic value
+/// converted from kdl, with its [`FromReflect`] implementation.
+///
+/// Register it with `#[reflect(FromReflect)]`, having `ReflectFromReflect` in
+/// scope, or with `registry.register_type_data::<T, ReflectFromReflect>()`.
+#[derive(Clone)]
+pub struct ReflectFromReflect {
+    from_reflect: fn(&dyn Reflect) -> Option<DynRefl>,
+}
+impl ReflectFromReflect {
+    /// The concrete value `value` represents, `None` if `FromReflect` rejects
+    /// it.
+    pub fn from_reflect(&self, value: &dyn Reflect) -> Option<DynRefl> {
+        (self.from_reflect)(value)
+    }
+}
+impl<T: FromReflect> FromType<T> for ReflectFromReflect {
+    fn from_type() -> Self {
+        ReflectFromReflect {
+            from_reflect: |value| T::from_reflect(value).map(|t| Box::new(t) as DynRefl),
+        }
+    }
+}
+
+/// How a converted field differs from its type.
+enum Found {
+    /// The field was converted into another type.
+    Type(String),
+    /// The field is not in the converted value.
+    Missing,
+    /// The converted value has a field its type doesn't have.
+    Extra,
+}
+
+/// Where a converted value differs from the type it should build.
+pub(crate) struct Mismatch {
+    /// The fields leading to the differing field, from the converted value.
+    path: Vec<String>,
+    /// The expected type, or for missing and extra fields, the type they are
+    /// fields of.
+    expected: &'static str,
+    found: Found,
+}
+
+/// The first field of `value` not of the type `info` expects.
+///
+/// `None` if `value` looks like a `info`, then `FromReflect` rejected it for
+/// another reason, such as a manual `FromReflect` implementation.
+pub(crate) fn find_mismatch(
+    value: &dyn Reflect,
+    info: &TypeInfo,
+    registry: &TypeRegistry,
+) -> Option<Mismatch> {
+    let mismatch = |name: Option<String>, found| Mismatch {
+        path: name.into_iter().collect(),
+        expected: info.full_name(),
+        found,
+    };
+    // The field named `name` of type `type_id`, an error if it's missing.
+    let field = |name: String, field: Option<&dyn Reflect>, type_id| {
+        let Some(field) = field else {
+            return Some(mismatch(Some(name), Found::Missing));
+        };
+        let mut found = find_mismatch(field, registry.get_type_info(type_id)?, registry)?;
+        found.path.insert(0, name);
+        Some(found)
+    };
+    let items = |items: &mut dyn Iterator<Item = &dyn Reflect>, type_id| {
+        let info = registry.get_type_info(type_id)?;
+        items.enumerate().find_map(|(i, item)| {
+            let mut found = find_mismatch(item, info, registry)?;
+            found.path.insert(0, i.to_string());
+            Some(found)
+        })
+    };
+    // The first of `actual` fields past the `expected` ones.
+    let extra = |actual: usize, expected: usize| {
+        (actual > expected).then(|| mismatch(Some(expected.to_string()), Found::Extra))
+    };
+    match (value.reflect_ref(), info) {
+        (ReflectRef::Struct(value), TypeInfo::Struct(info)) => {
+            let mut fields = info.iter();
+            let differing = fields
+                .find_map(|f| field(f.name().to_owned(), value.field(f.name()), f.reflected_id()));
+            differing.or_else(|| {
+                let extra = (0..value.field_len()).filter_map(|i| value.name_at(i));
+                let mut extra = extra.filter(|name| info.field(name).is_none());
+                extra
+                    .next()
+                    .map(|name| mismatch(Some(name.to_owned()), Found::Extra))
+            })
+        }
+        (ReflectRef::TupleStruct(value), TypeInfo::TupleStruct(info)) => info
+            .iter()
+            .find_map(|f| {
+                field(
+                    f.index().to_string(),
+                    value.field(f.index()),
+                    f.reflected_id(),
+                )
+            })
+            .or_else(|| extra(value.field_len(), info.field_len())),
+        (ReflectRef::Tuple(value), TypeInfo::Tuple(info)) => info
+            .iter()
+            .find_map(|f| {
+                field(
+                    f.index().to_string(),
+                    value.field(f.index()),
+                    f.reflected_id(),
+                )
+            })
+            .or_else(|| extra(value.field_len(), info.field_len())),
+        (ReflectRef::List(value), TypeInfo::List(info)) => items(&mut value.iter(), info.item_id()),
+        (ReflectRef::Array(value), TypeInfo::Array(info)) => {
+            items(&mut value.iter(), info.item_id())
+        }
+        (ReflectRef::Map(value), TypeInfo::Map(info)) => {
+            items(&mut value.iter().map(|(_, v)| v), info.item_id())
+        }
+        (ReflectRef::Value(value), TypeInfo::Value(info)) => {
+            let is_expected = value.get_type_info().reflected_id() == info.reflected_id();
+            (!is_expected).then(|| mismatch(None, Found::Type(value.type_name().to_owned())))
+        }
+        (ReflectRef::Enum(_), TypeInfo::Enum(_)) | (_, TypeInfo::Dynamic(_)) => None,
+        _ => Some(mismatch(None, Found::Type(value.type_name().to_owned()))),
+    }
+}
+
+/// The span of the node or entry at `path` in `node`, or of the deepest one
+/// found following it.
+///
+/// Numeric path segments are positional fields: the arguments of the node,
+/// then its children. Templates are not expanded, fields declared by
+/// templates point at the template invocation.
+fn span_at(node: &KdlNode, path: &[String]) -> Span {
+    let name_span = |node: &KdlNode| {
+        let span = node.name().span();
+        Span {
+            offset: span.offset() as u32,
+            size: span.len() as u32,
+        }
+    };
+    let entry_span = |entry: &KdlEntry| {
+        let span = entry.span();
+        Span {
+            offset: span.offset() as u32,
+            size: span.len() as u32,
+        }
+    };
+    let Some((first, rest)) = path.split_first() else {
+        return name_span(node);
+    };
+    let children = node.children().map_or(&[][..], |c| c.nodes());
+    let mut entries = node.entries().iter();
+    let (entry, child) = match first.parse::<usize>() {
+        Ok(index) => {
+            let mut arguments = entries.filter(|e| e.name().is_none());
+            let argument_count = arguments.clone().count();
+            let child = index
+                .checked_sub(argument_count)
+                .and_then(|i| children.get(i));
+            (arguments.nth(index), child)
+        }
+        Err(_) => {
+            let is_first = |name: &KdlIdentifier| name.value() == first.as_str();
+            let entry = entries.find(|e| e.name().map_or(false, is_first));
+            (entry, children.iter().find(|c| is_first(c.name())))
+        }
+    };
+    match (entry, child) {
+        (Some(entry), _) => entry_span(entry),
+        (None, Some(child)) => span_at(child, rest),
+        (None, None) => name_span(node),
+    }
+}
+
+/// The error of `mismatch`, in the value declared by `node`.
+fn mismatch_error(node: &KdlNode, mismatch: Mismatch) -> Error {
+    let Mismatch { path, expected, found } = mismatch;
+    let field = || path.last().cloned().unwrap_or_default();
+    let error = match found {
+        Found::Type(actual) => ErrorType::FromReflect { expected, actual: Some(actual) },
+        Found::Missing => ErrorType::UndeclaredField { name: expected, field: field() },
+        Found::Extra => ErrorType::UnexpectedField { name: expected, field: field() },
+    };
+    let error = error.spanned(&span_at(node, &path));
+    let path = std::iter::once(node.name().value()).chain(path.iter().map(String::as_str));
+    path.rev().fold(error, Error::within)
+}
+
+/// The error of `FromReflect` rejecting the value converted from `doc`.
+pub(crate) fn from_reflect_error(
+    doc: &KdlDocument,
+    mismatch: Option<Mismatch>,
+    ty: &'static str,
+) -> Error {
+    let Some(node) = doc.nodes().last() else {
+        let error = ErrorType::FromReflect { expected: ty, actual: None };
+        return error.spanned(&Span { offset: 0, size: 0 });
+    };
+    match mismatch {
+        Some(mismatch) => mismatch_error(node, mismatch),
+        None => {
+            let error = ErrorType::FromReflect { expected: ty, actual: None };
+            error
+                .spanned(&span_at(node, &[]))
+                .within(node.name().value())
+        }
+    }
+}
+
+/// Check that `value`, converted from `node`, has the fields of its
+/// registered type, all of the expected type.
+///
+/// Applying a value with missing fields, for example with
+/// `ReflectComponent::apply`, keeps the previous value of those fields, and
+/// extra fields are silently ignored. `None` if `value` matches its type, or
+/// if its type is not registered.
+pub fn check_declared(
+    node: &KdlNode,
+    value: &dyn Reflect,
+    registry: &TypeRegistry,
+) -> Option<Error> {
+    let info = registry.get_with_name(value.type_name())?.type_info();
+    let mismatch = find_mismatch(value, info, registry)?;
+    Some(mismatch_error(node, mismatch))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The text at `path` in the node `text`, without surrounding whitespace.
+    fn at<'a>(text: &'a str, path: &[&str]) -> &'a str {
+        let node: KdlNode = text.parse().unwrap();
+        let path: Vec<_> = path.iter().map(|s| s.to_string()).collect();
+        let span = span_at(&node, &path);
+        text[span.offset as usize..span.end() as usize].trim()
+    }
+
+    #[test]
+    fn positional_paths() {
+        let text = "Pair 1 { - 2; Even 3; }";
+        assert_eq!(at(text, &["0"]), "1");
+        // Children are after the arguments
+        assert_eq!(at(text, &["1"]), "-");
+        assert_eq!(at(text, &["2"]), "Even");
+        assert_eq!(at(text, &["3"]), "Pair");
+    }
+    #[test]
+    fn named_paths() {
+        let text = "Foo y=2 { x { - 1 2; }; }";
+        assert_eq!(at(text, &["y"]), "y=2");
+        assert_eq!(at(text, &["x", "0", "1"]), "2");
+        assert_eq!(at(text, &["z"]), "Foo");
+    }
+}
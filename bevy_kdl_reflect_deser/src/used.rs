@@ -0,0 +1,75 @@
+//! Find which registered types deserialized values are made of.
+use std::{any::TypeId, collections::HashSet};
+
+use crate::compat::{Reflect, ReflectRef, TypeIdentity, TypeRegistration, TypeRegistry};
+
+/// The registered types deserialized values are made of.
+///
+/// This is useful to spot types that are registered but never declared in
+/// any file, or to create a registry with only the types files use.
+///
+/// Anonymous tuples are not registered types, but their fields are tracked.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UsedTypes(HashSet<TypeId>);
+impl UsedTypes {
+    /// The types of `value` and all its fields, recursively.
+    pub fn of(value: &dyn Reflect, registry: &TypeRegistry) -> Self {
+        let mut used = Self::default();
+        used.add(value, registry);
+        used
+    }
+    /// Add the types of `value` and all its fields, recursively.
+    pub fn add(&mut self, value: &dyn Reflect, registry: &TypeRegistry) {
+        // Dynamic values have the name of the type they represent
+        if let Some(registration) = registry.get_with_name(value.type_name()) {
+            self.0.insert(registration.reflected_id());
+        }
+        match value.reflect_ref() {
+            ReflectRef::Struct(value) => value.iter_fields().for_each(|f| self.add(f, registry)),
+            ReflectRef::TupleStruct(value) => {
+                value.iter_fields().for_each(|f| self.add(f, registry))
+            }
+            ReflectRef::Tuple(value) => value.iter_fields().for_each(|f| self.add(f, registry)),
+            ReflectRef::List(value) => value.iter().for_each(|f| self.add(f, registry)),
+            ReflectRef::Array(value) => value.iter().for_each(|f| self.add(f, registry)),
+            ReflectRef::Map(value) => value.iter().for_each(|(key, value)| {
+                self.add(key, registry);
+                self.add(value, registry);
+            }),
+            ReflectRef::Enum(value) => value
+                .iter_fields()
+                .for_each(|f| self.add(f.value(), registry)),
+            ReflectRef::Value(_) => {}
+        }
+    }
+    /// Add all types used in `other`.
+    pub fn merge(&mut self, other: &UsedTypes) {
+        self.0.extend(&other.0);
+    }
+    pub fn contains(&self, id: TypeId) -> bool {
+        self.0.contains(&id)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.0.iter().copied()
+    }
+    /// The registrations of `registry` that are not used.
+    pub fn unused<'r>(
+        &'r self,
+        registry: &'r TypeRegistry,
+    ) -> impl Iterator<Item = &'r TypeRegistration> + 'r {
+        registry
+            .iter()
+            .filter(|reg| !self.contains(reg.reflected_id()))
+    }
+    /// A registry with only the registrations of `registry` that are used.
+    pub fn snapshot(&self, registry: &TypeRegistry) -> TypeRegistry {
+        let mut snapshot = TypeRegistry::empty();
+        for registration in registry
+            .iter()
+            .filter(|reg| self.contains(reg.reflected_id()))
+        {
+            snapshot.add_registration(registration.clone());
+        }
+        snapshot
+    }
+}
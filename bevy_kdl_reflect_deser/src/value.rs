@@ -0,0 +1,367 @@
+//! Declare opaque `reflect_value` types as kdl strings or integers.
+use std::{
+    any::{self, TypeId},
+    fmt::Display,
+    hash::Hash,
+    num::{
+        NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+        NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+    },
+    ops::BitOr,
+    str::FromStr,
+};
+
+#[cfg(feature = "bevy-collections")]
+use bevy_utils::HashSet;
+use multierr_span::{Span, Spanned};
+use serde::de::value::{Error as DeError, StrDeserializer};
+use template_kdl::{
+    multi_err::{MultiError, MultiErrorTrait},
+    navigate::{Navigable, Value as Nvalue},
+};
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+
+use crate::{
+    compat::{
+        FromReflect, FromType, Reflect, ReflectDeserialize, TypeIdentity, TypeInfo, TypeRegistry,
+        Typed, ValueInfo,
+    },
+    err::{ErrorType as ErrTy, MResult},
+    newtype,
+    options::Context,
+    shape,
+    visit::Field,
+    DynRefl,
+};
+
+/// Type data to declare a `reflect_value` type as a kdl string, using its
+/// [`FromStr`] implementation.
+///
+/// Register it with `#[reflect_value(FromStr)]`, having `ReflectFromStr` in
+/// scope. Types with [`ReflectDeserialize`] are also declarable as strings,
+/// `ReflectFromStr` takes precedence if both are registered.
+#[derive(Clone)]
+pub struct ReflectFromStr {
+    from_str: fn(&str) -> Result<DynRefl, String>,
+}
+impl ReflectFromStr {
+    pub fn from_str(&self, s: &str) -> Result<DynRefl, String> {
+        (self.from_str)(s)
+    }
+}
+impl<T: FromStr + Reflect> FromType<T> for ReflectFromStr
+where
+    T::Err: Display,
+{
+    fn from_type() -> Self {
+        ReflectFromStr {
+            from_str: |s| match s.parse::<T>() {
+                Ok(value) => Ok(Box::new(value)),
+                Err(err) => Err(err.to_string()),
+            },
+        }
+    }
+}
+
+/// Type data to declare a `reflect_value` type as a UUID string, such as an
+/// asset reference by GUID.
+///
+/// Register it with `#[reflect_value(FromUuid)]` for types implementing
+/// `From<Uuid>`, having `ReflectFromUuid` in scope. `Uuid` itself is
+/// declarable as a string without it. Both the hyphenated and the simple
+/// (hyphenless) forms are accepted.
+#[cfg(feature = "uuid")]
+#[derive(Clone)]
+pub struct ReflectFromUuid {
+    from_uuid: fn(Uuid) -> DynRefl,
+}
+#[cfg(feature = "uuid")]
+impl ReflectFromUuid {
+    pub fn from_uuid(&self, uuid: Uuid) -> DynRefl {
+        (self.from_uuid)(uuid)
+    }
+}
+#[cfg(feature = "uuid")]
+impl<T: From<Uuid> + Reflect> FromType<T> for ReflectFromUuid {
+    fn from_type() -> Self {
+        ReflectFromUuid { from_uuid: |uuid| Box::new(T::from(uuid)) }
+    }
+}
+/// How to convert a UUID into the type of `ty`, `None` if it isn't declared
+/// as a UUID.
+#[cfg(feature = "uuid")]
+fn uuid_data(reg: &TypeRegistry, ty: TypeId) -> Option<fn(Uuid) -> DynRefl> {
+    if ty == TypeId::of::<Uuid>() {
+        return Some(|uuid| Box::new(uuid));
+    }
+    let data = reg.get_type_data::<ReflectFromUuid>(ty)?;
+    Some(data.from_uuid)
+}
+
+/// A bitflags type, declarable as a list of flag names, such as
+/// `layers "Player" "Enemies"`.
+///
+/// Register the [`ReflectBitflags`] type data of implementors with
+/// `#[reflect_value(Bitflags)]`.
+pub trait Bitflags: Reflect + Copy + Default + BitOr<Output = Self> {
+    /// The name of each individual flag, and their value.
+    fn flags() -> &'static [(&'static str, Self)];
+}
+
+/// Type data to declare a [`Bitflags`] type as a list of flag names.
+#[derive(Clone)]
+pub struct ReflectBitflags {
+    names: Vec<&'static str>,
+    from_indices: fn(&[usize]) -> DynRefl,
+}
+impl ReflectBitflags {
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+    fn flag(&self, requested: &str, ty: &'static str) -> Result<usize, ErrTy> {
+        let index = self.names.iter().position(|name| *name == requested);
+        index.ok_or_else(|| ErrTy::NoSuchFlag {
+            requested: requested.to_owned(),
+            name: ty,
+            available: self.names.clone(),
+        })
+    }
+}
+impl<T: Bitflags> FromType<T> for ReflectBitflags {
+    fn from_type() -> Self {
+        ReflectBitflags {
+            names: T::flags().iter().map(|(name, _)| *name).collect(),
+            from_indices: |indices| {
+                let flags = T::flags();
+                Box::new(
+                    indices
+                        .iter()
+                        .fold(T::default(), |acc, i| acc | flags[*i].1),
+                )
+            },
+        }
+    }
+}
+
+/// Combine the flags named in `fields` if `info` is a [`Bitflags`] type,
+/// `None` if it isn't.
+pub(crate) fn from_flags(
+    reg: &TypeRegistry,
+    info: &ValueInfo,
+    fields: impl Iterator<Item = Field>,
+) -> Option<MResult<DynRefl>> {
+    let flags = reg.get_type_data::<ReflectBitflags>(info.reflected_id())?;
+    let mut errs = MultiError::default();
+    let mut indices = Vec::new();
+    for field in fields {
+        let index = match field.value() {
+            Nvalue::Bare(value) => match value.as_string() {
+                Some(requested) => flags.flag(requested, info.full_name()),
+                None => {
+                    let actual = value.to_string();
+                    Err(ErrTy::TypeMismatch { expected: "String", actual, shape: None })
+                }
+            }
+            .map_err(|err| err.spanned(&value)),
+            Nvalue::List(_) => {
+                let actual = shape::of_field(&field);
+                Err(ErrTy::TypeMismatch { expected: "String", actual, shape: None }.spanned(&field))
+            }
+        };
+        indices.extend(errs.optionally(index));
+    }
+    Some(errs.into_result((flags.from_indices)(&indices)))
+}
+
+/// Type data to declare a set as a list of its items, such as `ids 1 2 3`.
+///
+/// `bevy_reflect` reflects `HashSet`s as opaque values, with the
+/// `bevy-collections` feature, register this type data for them with
+/// `registry.register_type_data::<HashSet<u32>, ReflectSet>()`.
+#[derive(Clone)]
+pub struct ReflectSet {
+    name: &'static str,
+    item: &'static TypeInfo,
+    /// The set of `items`, and the indices of items already in the set. Fails
+    /// with the index of the first item not of the item type.
+    from_items: fn(&[DynRefl]) -> Result<(DynRefl, Vec<usize>), usize>,
+    /// The items of a set, `None` if it isn't of the set type.
+    items: fn(&dyn Reflect) -> Option<Vec<DynRefl>>,
+}
+impl ReflectSet {
+    pub fn item(&self) -> &'static TypeInfo {
+        self.item
+    }
+    /// The items of `set`, `None` if it isn't of the type of this set.
+    pub fn items(&self, set: &dyn Reflect) -> Option<Vec<DynRefl>> {
+        (self.items)(set)
+    }
+}
+#[cfg(feature = "bevy-collections")]
+impl<T> FromType<HashSet<T>> for ReflectSet
+where
+    T: FromReflect + Typed + Hash + Eq + Clone + Send + Sync,
+{
+    fn from_type() -> Self {
+        ReflectSet {
+            name: any::type_name::<HashSet<T>>(),
+            item: T::type_info(),
+            from_items: |items| {
+                let mut set = HashSet::default();
+                let mut duplicates = Vec::new();
+                for (i, item) in items.iter().enumerate() {
+                    if !set.insert(T::from_reflect(item.as_ref()).ok_or(i)?) {
+                        duplicates.push(i);
+                    }
+                }
+                Ok((Box::new(set), duplicates))
+            },
+            items: |set| {
+                let set = set.downcast_ref::<HashSet<T>>()?;
+                Some(
+                    set.iter()
+                        .map(|item| Box::new(item.clone()) as DynRefl)
+                        .collect(),
+                )
+            },
+        }
+    }
+}
+
+/// The set of items declared in `fields` if `info` is a set with
+/// [`ReflectSet`], `None` if it isn't.
+///
+/// Duplicate items are reported as warnings, at the repeated item.
+pub(crate) fn from_set(
+    reg: &Context,
+    info: &ValueInfo,
+    fields: impl Iterator<Item = Field>,
+) -> Option<MResult<DynRefl>> {
+    let set = reg.get_type_data::<ReflectSet>(info.reflected_id())?;
+    let mut errs = MultiError::default();
+    let mut items = Vec::new();
+    let mut spans = Vec::new();
+    for field in fields {
+        let span = field.span();
+        let item = newtype::make_named_dyn(reg, Some(set.item.full_name()), field);
+        if let Some(item) = errs.optionally(item) {
+            items.push(item);
+            spans.push(span);
+        }
+    }
+    Some(collect_set(set, items, &spans).combine(errs))
+}
+/// The set with the single item `item`, declared as a bare value at `item_at`.
+pub(crate) fn from_set_item(
+    set: &ReflectSet,
+    item: DynRefl,
+    item_at: &impl Spanned,
+) -> MResult<DynRefl> {
+    collect_set(set, vec![item], &[item_at.span()])
+}
+fn collect_set(set: &ReflectSet, items: Vec<DynRefl>, spans: &[Span]) -> MResult<DynRefl> {
+    let mut errs = MultiError::default();
+    match (set.from_items)(&items) {
+        Ok((value, duplicates)) => {
+            for i in duplicates {
+                errs.add_error(ErrTy::DuplicateSetItem(set.name).spanned(&spans[i]));
+            }
+            errs.into_result(value)
+        }
+        Err(i) => {
+            let expected = set.item.full_name();
+            let actual = items[i].type_name().to_owned();
+            let shape = shape::of_compound(set.item);
+            errs.into_errors(ErrTy::TypeMismatch { expected, actual, shape }.spanned(&spans[i]))
+        }
+    }
+}
+
+/// Convert `value` into the type of `ty` with the string type data registered
+/// for it, `None` if there is none.
+pub(crate) fn from_str(
+    reg: &TypeRegistry,
+    ty: TypeId,
+    ty_name: &'static str,
+    value: String,
+) -> Option<Result<DynRefl, ErrTy>> {
+    let parse_error = |reason| ErrTy::ValueParse { value: value.clone(), ty: ty_name, reason };
+    #[cfg(feature = "uuid")]
+    if let Some(from_uuid) = uuid_data(reg, ty) {
+        let uuid = Uuid::parse_str(&value).map_err(|err| parse_error(err.to_string()));
+        return Some(uuid.map(from_uuid));
+    }
+    if let Some(flags) = reg.get_type_data::<ReflectBitflags>(ty) {
+        let index = flags.flag(&value, ty_name);
+        Some(index.map(|i| (flags.from_indices)(&[i])))
+    } else if let Some(data) = reg.get_type_data::<ReflectFromStr>(ty) {
+        Some(data.from_str(&value).map_err(parse_error))
+    } else if let Some(data) = reg.get_type_data::<ReflectDeserialize>(ty) {
+        let deserializer = StrDeserializer::<DeError>::new(&value);
+        let result = data.deserialize(deserializer);
+        Some(result.map_err(|err| parse_error(err.to_string())))
+    } else {
+        None
+    }
+}
+
+/// Type data to declare a `reflect_value` type as a kdl integer, for integer
+/// types with a restricted domain.
+///
+/// The std `NonZero*` integers are handled without registering it.
+#[derive(Clone)]
+pub struct ReflectFromInt {
+    /// The allowed values, shown in error messages.
+    pub domain: &'static str,
+    /// Convert the integer, `None` if it is out of `domain`.
+    pub from_int: fn(i64) -> Option<DynRefl>,
+}
+impl ReflectFromInt {
+    pub fn new(domain: &'static str, from_int: fn(i64) -> Option<DynRefl>) -> Self {
+        Self { domain, from_int }
+    }
+}
+fn builtin_int(ty: TypeId) -> Option<ReflectFromInt> {
+    macro_rules! nonzero {
+        ($($nonzero:ident($int:ty)),* $(,)?) => {
+            $(  if ty == TypeId::of::<$nonzero>() {
+                return Some(ReflectFromInt::new(
+                    concat!("any non-zero ", stringify!($int)),
+                    |i| Some(Box::new(<$int>::try_from(i).ok().and_then($nonzero::new)?)),
+                ));
+            } )*
+        };
+    }
+    nonzero!(
+        NonZeroI8(i8),
+        NonZeroI16(i16),
+        NonZeroI32(i32),
+        NonZeroI64(i64),
+        NonZeroI128(i128),
+        NonZeroIsize(isize),
+        NonZeroU8(u8),
+        NonZeroU16(u16),
+        NonZeroU32(u32),
+        NonZeroU64(u64),
+        NonZeroU128(u128),
+        NonZeroUsize(usize),
+    );
+    None
+}
+
+/// Convert `value` into the type of `ty` with the integer type data registered
+/// for it or the built-in constrained integers, `None` if there is none.
+pub(crate) fn from_int(
+    reg: &TypeRegistry,
+    ty: TypeId,
+    ty_name: &'static str,
+    value: i64,
+) -> Option<Result<DynRefl, ErrTy>> {
+    let data = reg
+        .get_type_data::<ReflectFromInt>(ty)
+        .cloned()
+        .or_else(|| builtin_int(ty))?;
+    let out_of_domain = || ErrTy::IntConstraint { value, ty: ty_name, domain: data.domain };
+    Some((data.from_int)(value).ok_or_else(out_of_domain))
+}
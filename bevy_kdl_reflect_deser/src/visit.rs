@@ -1,48 +1,392 @@
-use kdl::KdlDocument;
+use std::sync::Arc;
 
-use bevy_reflect::{TypeRegistry, Typed};
-use template_kdl::{multi_err::MultiResult, navigate::ThunkField, Document, RequiredBindings};
+use kdl::{KdlDocument, KdlNode};
 
-use crate::{err::Error, newtype, ConvertResult, DynRefl};
+use multierr_span::Span;
+#[cfg(not(feature = "templating"))]
+use template_kdl::navigate::SpannedField;
+use template_kdl::{analysis::parse_recovering, multi_err::MultiResult};
+#[cfg(feature = "templating")]
+use template_kdl::{multi_err::MultiErrorTrait, navigate::ThunkField, Document};
+
+use crate::{
+    compat::{FromReflect, Reflect, TypeIdentity, TypeRegistry, Typed},
+    err::{Error, ErrorType},
+    newtype,
+    options::Context,
+    schema::{RegistrySchema, Validator},
+    typed::{find_mismatch, from_reflect_error},
+    ConvertErrors, ConvertResult, DeserOptions, DynRefl, ExportedBindings, RequiredBindings,
+};
+
+/// Without templating, there are no templates to make available to a
+/// document, see [`read_doc`].
+#[cfg(not(feature = "templating"))]
+#[derive(Debug, Default, Clone)]
+pub struct RequiredBindings;
+/// Without templating, documents export nothing, see [`read_exports`].
+#[cfg(not(feature = "templating"))]
+#[derive(Debug, Default, Clone)]
+pub struct ExportedBindings;
+
+/// The kdl node representation the deserializer navigates.
+#[cfg(feature = "templating")]
+pub(crate) type Field = ThunkField;
+/// The kdl node representation the deserializer navigates.
+#[cfg(not(feature = "templating"))]
+pub(crate) type Field = SpannedField;
 
 pub fn read_navigable(
-    field: ThunkField,
+    field: Field,
     expected: Option<&str>,
     registry: &TypeRegistry,
+    options: &DeserOptions,
 ) -> MultiResult<DynRefl, Error> {
-    newtype::make_named_dyn(registry, expected, field)
+    newtype::make_named_dyn(&Context::new(registry, options), expected, field)
 }
+/// Convert `doc`, it is only printed to show errors.
+#[cfg(feature = "templating")]
 pub fn read_doc(
-    doc: KdlDocument,
+    doc: Arc<KdlDocument>,
     expected: Option<&str>,
     registry: &TypeRegistry,
     required: RequiredBindings,
+    options: &DeserOptions,
 ) -> ConvertResult {
-    let doc_repr = doc.to_string();
-    let result = template_kdl::read_document(doc, required).map_err(Error::from);
+    let doc_repr = || doc.to_string();
+    let result = {
+        enter_span!("kdl_read_document", nodes = doc.nodes().len());
+        template_kdl::read_shared_document(doc.clone(), required, options.limits())
+            .map_err(Error::from)
+    };
     match result.into_result() {
-        Err(errs) => ConvertResult::errors(doc_repr, errs),
+        Err(errs) => ConvertResult::errors(doc_repr(), errs, options),
         Ok(Document::Exports(exports)) => ConvertResult::Exports(exports),
-        Ok(Document::Node(node)) => {
-            match read_navigable(ThunkField::node(node), expected, registry).into_result() {
-                Ok(dyn_value) => ConvertResult::Deserialized(dyn_value),
-                Err(errs) => ConvertResult::errors(doc_repr, errs),
-            }
+        // The exports of a document read as a value are not used.
+        Ok(Document::Node(node) | Document::NodeAndExports(node, _)) => {
+            // Templates are expanded lazily, while converting
+            enter_span!("kdl_convert", expected);
+            let expansion = node.clone();
+            let mut result = read_navigable(ThunkField::node(node), expected, registry, options);
+            let expansion_errors = expansion.take_expansion_errors().into_iter();
+            result.extend_errors(expansion_errors.map(Error::from));
+            ConvertResult::deserialized(doc_repr, result, options)
         }
     }
 }
+/// Without templating, the document must be a single node, it is read as-is
+/// and `required` bindings are ignored. Like with templating, an empty document
+/// exports nothing.
+#[cfg(not(feature = "templating"))]
+pub fn read_doc(
+    doc: Arc<KdlDocument>,
+    expected: Option<&str>,
+    registry: &TypeRegistry,
+    _required: RequiredBindings,
+    options: &DeserOptions,
+) -> ConvertResult {
+    enter_span!("kdl_convert", expected, nodes = doc.nodes().len());
+    let doc_repr = || doc.to_string();
+    let mut nodes = template_kdl::read_shared_plain(doc.clone());
+    let result = match (nodes.next(), nodes.next()) {
+        (None, _) => return ConvertResult::Exports(Default::default()),
+        (Some(_), Some(extra)) => {
+            MultiResult::Err(vec![ErrorType::TemplatingDisabled.spanned(&extra)])
+        }
+        (Some(node), None) => read_navigable(node, expected, registry, options),
+    };
+    ConvertResult::deserialized(doc_repr, result, options)
+}
 pub fn from_doc_untyped(
     doc: KdlDocument,
     bindings: RequiredBindings,
     registry: &TypeRegistry,
+    options: &DeserOptions,
 ) -> ConvertResult {
-    read_doc(doc, None, registry, bindings)
+    read_doc(Arc::new(doc), None, registry, bindings, options)
 }
 pub fn from_doc<T: Typed>(
     doc: KdlDocument,
     bindings: RequiredBindings,
     registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> ConvertResult {
+    from_shared_doc::<T>(Arc::new(doc), bindings, registry, options)
+}
+/// Like [`from_doc_untyped`], but reads a document shared with something
+/// else, such as an editor, without cloning it.
+pub fn from_shared_doc_untyped(
+    doc: Arc<KdlDocument>,
+    bindings: RequiredBindings,
+    registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> ConvertResult {
+    read_doc(doc, None, registry, bindings, options)
+}
+/// Like [`from_doc`], but reads a document shared with something else, such
+/// as an editor, without cloning it.
+pub fn from_shared_doc<T: Typed>(
+    doc: Arc<KdlDocument>,
+    bindings: RequiredBindings,
+    registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> ConvertResult {
+    let expected = Some(T::type_info().full_name());
+    read_doc(doc, expected, registry, bindings, options)
+}
+/// Like [`from_doc_untyped`], but parses `text`, recovering from syntax
+/// errors with [`template_kdl::analysis::parse_recovering`].
+///
+/// Syntax errors are reported along with the conversion errors of the rest of
+/// the document, so that editors keep showing them while `text` is edited.
+pub fn from_text_untyped(
+    text: &str,
+    bindings: RequiredBindings,
+    registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> ConvertResult {
+    read_text(text, None, registry, bindings, options)
+}
+/// Like [`from_doc`], but parses `text`, see [`from_text_untyped`].
+pub fn from_text<T: Typed>(
+    text: &str,
+    bindings: RequiredBindings,
+    registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> ConvertResult {
+    let expected = Some(T::type_info().full_name());
+    read_text(text, expected, registry, bindings, options)
+}
+fn read_text(
+    text: &str,
+    expected: Option<&str>,
+    registry: &TypeRegistry,
+    bindings: RequiredBindings,
+    options: &DeserOptions,
+) -> ConvertResult {
+    let (doc, mut errors) = match parse_recovering(text) {
+        Ok(doc) => (doc, Vec::new()),
+        Err((error, Some(doc))) => (doc, vec![error.into()]),
+        Err((error, None)) => return ConvertResult::errors(text, vec![error.into()], options),
+    };
+    let result = read_doc(Arc::new(doc), expected, registry, bindings, options);
+    if errors.is_empty() {
+        return result;
+    }
+    // A document with syntax errors is never converted.
+    match result {
+        ConvertResult::Errors(others) | ConvertResult::Warned(_, others) => errors.extend(others),
+        ConvertResult::Deserialized(_) | ConvertResult::Exports(_) => {}
+    }
+    errors.sort_by_key(|error| error.span.offset());
+    ConvertResult::errors(text, errors, options)
+}
+
+/// Convert `node`, declared at `offset` of `source`, such as a node extracted
+/// from a larger document, so that errors point to where it is in `source`.
+///
+/// `offset` is where `node` starts, after its leading whitespace and comments.
+/// `node` can invoke the `bindings` templates, but can't declare any.
+#[cfg(feature = "templating")]
+pub fn convert_node_at(
+    node: KdlNode,
+    offset: usize,
+    source: &str,
+    expected: Option<&str>,
+    registry: &TypeRegistry,
+    bindings: RequiredBindings,
+    options: &DeserOptions,
+) -> ConvertResult {
+    enter_span!("kdl_convert", expected);
+    let node = template_kdl::read_node_at(node, offset, bindings, options.limits());
+    let expansion = node.clone();
+    let mut result = read_navigable(ThunkField::node(node), expected, registry, options);
+    let expansion_errors = expansion.take_expansion_errors().into_iter();
+    result.extend_errors(expansion_errors.map(Error::from));
+    ConvertResult::deserialized(|| source.to_owned(), result, options)
+}
+/// Without templating, `node` is read as-is and `bindings` are ignored.
+#[cfg(not(feature = "templating"))]
+pub fn convert_node_at(
+    node: KdlNode,
+    offset: usize,
+    source: &str,
+    expected: Option<&str>,
+    registry: &TypeRegistry,
+    _bindings: RequiredBindings,
+    options: &DeserOptions,
 ) -> ConvertResult {
-    let expected = Some(T::type_info().type_name());
-    read_doc(doc, expected, registry, bindings)
+    enter_span!("kdl_convert", expected);
+    let node = template_kdl::read_plain_node_at(node, offset);
+    let result = read_navigable(node, expected, registry, options);
+    ConvertResult::deserialized(|| source.to_owned(), result, options)
+}
+/// Check the type and field names of `doc` against `schema`, without a
+/// `TypeRegistry`.
+///
+/// Values are not checked, a document passing validation may still fail to
+/// convert. Like with [`from_doc`], `expected` is the type of the value, and
+/// documents only exporting templates pass validation.
+#[cfg(feature = "templating")]
+pub fn validate_with_schema(
+    doc: KdlDocument,
+    expected: Option<&str>,
+    schema: &RegistrySchema,
+    required: RequiredBindings,
+    options: &DeserOptions,
+) -> Result<(), ConvertErrors> {
+    let doc_repr = doc.to_string();
+    let result = template_kdl::read_document_with_limits(doc, required, options.limits());
+    let errors = match result.map_err(Error::from).into_result() {
+        Err(errors) => errors,
+        Ok(Document::Exports(_)) => return Ok(()),
+        Ok(Document::Node(node) | Document::NodeAndExports(node, _)) => {
+            let expansion = node.clone();
+            let mut validator = Validator::new(schema, options);
+            validator.check(ThunkField::node(node), expected, true);
+            let expansion_errors = expansion.take_expansion_errors().into_iter();
+            validator.errors.extend(expansion_errors.map(Error::from));
+            validator.errors
+        }
+    };
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(ConvertErrors::new(doc_repr, errors))
+}
+/// Without templating, the document must be a single node, see
+/// [`read_doc`].
+#[cfg(not(feature = "templating"))]
+pub fn validate_with_schema(
+    doc: KdlDocument,
+    expected: Option<&str>,
+    schema: &RegistrySchema,
+    _required: RequiredBindings,
+    options: &DeserOptions,
+) -> Result<(), ConvertErrors> {
+    let doc_repr = doc.to_string();
+    let mut nodes = template_kdl::read_shared_plain(Arc::new(doc));
+    let mut validator = Validator::new(schema, options);
+    match (nodes.next(), nodes.next()) {
+        (None, _) => return Ok(()),
+        (Some(_), Some(extra)) => {
+            let error = ErrorType::TemplatingDisabled.spanned(&extra);
+            validator.errors.push(error);
+        }
+        (Some(node), None) => validator.check(node, expected, true),
+    }
+    if validator.errors.is_empty() {
+        return Ok(());
+    }
+    Err(ConvertErrors::new(doc_repr, validator.errors))
+}
+
+/// A value deserialized with [`deserialize_value`], or built with
+/// [`from_doc_typed`].
+#[derive(Debug)]
+pub struct Converted<T = DynRefl> {
+    pub value: T,
+    /// Suspicious declarations in the document, see [`Error::is_warning`].
+    pub warnings: Option<ConvertErrors>,
+}
+/// The name of the last node of `doc`, where a value or `export` node is.
+fn last_node_span(doc: &KdlDocument) -> Span {
+    let span = doc.nodes().last().map(|node| *node.name().span());
+    let (offset, size) = span.map_or((0, 0), |s| (s.offset() as u32, s.len() as u32));
+    Span { offset, size }
+}
+/// Deserialize `doc` as a `T`, it is an error for `doc` to only export
+/// templates.
+pub fn deserialize_value<T: Typed>(
+    doc: KdlDocument,
+    bindings: RequiredBindings,
+    registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> Result<Converted, ConvertErrors> {
+    deserialize_shared::<T>(Arc::new(doc), bindings, registry, options)
+}
+fn deserialize_shared<T: Typed>(
+    doc: Arc<KdlDocument>,
+    bindings: RequiredBindings,
+    registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> Result<Converted, ConvertErrors> {
+    let expected = Some(T::type_info().full_name());
+    let span = last_node_span(&doc);
+    match read_doc(doc.clone(), expected, registry, bindings, options) {
+        ConvertResult::Deserialized(value) => Ok(Converted { value, warnings: None }),
+        ConvertResult::Warned(value, warnings) => Ok(Converted { value, warnings: Some(warnings) }),
+        ConvertResult::Errors(errors) => Err(errors),
+        ConvertResult::Exports(_) => {
+            let error = ErrorType::NotValue.spanned(&span);
+            Err(ConvertErrors::new(doc.to_string(), vec![error]))
+        }
+    }
+}
+/// Like [`deserialize_value`], but also builds the `T` with `FromReflect`.
+///
+/// Conversion can succeed with a value `FromReflect` rejects, for example if
+/// `T` implements `FromReflect` manually. The error then points at the field
+/// `FromReflect` couldn't build, rather than failing later on an `unwrap`.
+pub fn from_doc_typed<T: FromReflect + Typed>(
+    doc: KdlDocument,
+    bindings: RequiredBindings,
+    registry: &TypeRegistry,
+    options: &DeserOptions,
+) -> Result<Converted<T>, ConvertErrors> {
+    let doc = Arc::new(doc);
+    let converted = deserialize_shared::<T>(doc.clone(), bindings, registry, options)?;
+    let Converted { value, warnings } = converted;
+    let value = build_typed(&doc, value.as_ref(), registry)?;
+    Ok(Converted { value, warnings })
+}
+/// Build the `T` converted from `doc` with `FromReflect`, the error points at
+/// the field `FromReflect` rejects, like [`from_doc_typed`].
+///
+/// For values converted with [`from_doc`] or [`from_shared_doc`], from
+/// documents that may also only export templates.
+pub fn build_typed<T: FromReflect + Typed>(
+    doc: &KdlDocument,
+    value: &dyn Reflect,
+    registry: &TypeRegistry,
+) -> Result<T, ConvertErrors> {
+    T::from_reflect(value).ok_or_else(|| {
+        let mismatch = find_mismatch(value, T::type_info(), registry);
+        let error = from_reflect_error(doc, mismatch, T::type_info().full_name());
+        ConvertErrors::new(doc.to_string(), vec![error])
+    })
+}
+/// The templates exported by `doc`, it is an error for `doc` to not have an
+/// `export` node.
+///
+/// An empty document exports nothing.
+#[cfg(feature = "templating")]
+pub fn read_exports(
+    doc: KdlDocument,
+    bindings: RequiredBindings,
+) -> Result<ExportedBindings, ConvertErrors> {
+    let doc_repr = doc.to_string();
+    let span = last_node_span(&doc);
+    let result = template_kdl::read_document(doc, bindings).map_err(Error::from);
+    match result.into_result() {
+        Err(errs) => Err(ConvertErrors::new(doc_repr, errs)),
+        Ok(Document::Exports(exports) | Document::NodeAndExports(_, exports)) => Ok(exports),
+        Ok(Document::Node(_)) => {
+            let error = ErrorType::NotExports.spanned(&span);
+            Err(ConvertErrors::new(doc_repr, vec![error]))
+        }
+    }
+}
+/// Without templating, only empty documents are export documents, they
+/// export nothing.
+#[cfg(not(feature = "templating"))]
+pub fn read_exports(
+    doc: KdlDocument,
+    _bindings: RequiredBindings,
+) -> Result<ExportedBindings, ConvertErrors> {
+    if doc.nodes().is_empty() {
+        return Ok(ExportedBindings::default());
+    }
+    let error = ErrorType::NotExports.spanned(&last_node_span(&doc));
+    Err(ConvertErrors::new(doc.to_string(), vec![error]))
 }
@@ -0,0 +1,86 @@
+//! Containers reflected by `bevy_reflect` are declarable whatever their
+//! concrete type, as long as they are registered.
+//!
+//! `bevy_reflect` 0.9 doesn't implement `Reflect` for `VecDeque`, `BinaryHeap`
+//! and `BTreeSet`, so they can't be declared.
+use std::fmt;
+
+use bevy_kdl_reflect_deser::{deserialize_value, ConvertErrors, ReflectSet};
+use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry, Typed};
+use bevy_utils::{HashMap, HashSet};
+
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Inventory {
+    slots: [u8; 3],
+    items: Vec<String>,
+    grid: Vec<[u8; 2]>,
+    counts: HashMap<String, u8>,
+    tags: HashSet<u32>,
+    nested: Vec<Vec<u8>>,
+}
+
+fn registry() -> TypeRegistry {
+    let mut reg = TypeRegistry::default();
+    macro_rules! register_more {
+        ($($ty_name:ty ),* $(,)? ) => ({$(
+            reg.add_registration(TypeRegistration::of::<$ty_name>());
+        )*})
+    }
+    reg.register::<Inventory>();
+    reg.register::<u8>();
+    reg.register::<u32>();
+    reg.register::<String>();
+    register_more!(
+        [u8; 3], [u8; 2], Vec<String>, Vec<[u8; 2]>, HashMap<String, u8>, HashSet<u32>,
+        Vec<Vec<u8>>, Vec<u8>,
+    );
+    reg.register_type_data::<HashSet<u32>, ReflectSet>();
+    reg
+}
+
+fn read<T: FromReflect + Typed + fmt::Debug>(text: &str) -> Result<T, ConvertErrors> {
+    let converted = deserialize_value::<T>(
+        text.parse().unwrap(),
+        Default::default(),
+        &registry(),
+        &Default::default(),
+    )?;
+    Ok(T::from_reflect(converted.value.as_ref()).unwrap())
+}
+
+#[test]
+fn all_containers() {
+    let text = r#"Inventory {
+        slots 1 2 3
+        items "sword" "shield"
+        grid { - 0 1; - 2 3; }
+        counts arrows=20 bombs=3
+        tags 7 9
+        nested { - 1 2; - { - 3; }; }
+    }"#;
+    let expected = Inventory {
+        slots: [1, 2, 3],
+        items: vec!["sword".to_owned(), "shield".to_owned()],
+        grid: vec![[0, 1], [2, 3]],
+        counts: [("arrows".to_owned(), 20), ("bombs".to_owned(), 3)]
+            .into_iter()
+            .collect(),
+        tags: [7, 9].into_iter().collect(),
+        nested: vec![vec![1, 2], vec![3]],
+    };
+    assert_eq!(read::<Inventory>(text).unwrap(), expected);
+}
+
+#[test]
+fn array_length() {
+    let text =
+        |slots| format!(r#"Inventory {{ slots {slots}; items; grid; counts; tags; nested; }}"#);
+    assert_eq!(read::<Inventory>(&text("1 2 3")).unwrap().slots, [1, 2, 3]);
+    let missing = read::<Inventory>(&text("1 2")).unwrap_err().show_for();
+    assert!(
+        missing.contains("[u8; 3] has 3 items, but only 2 were declared"),
+        "{missing}"
+    );
+    let extra = read::<Inventory>(&text("1 2 3 4")).unwrap_err().show_for();
+    assert!(extra.contains("[u8; 3] has 3 fields"), "{extra}");
+}
@@ -0,0 +1,132 @@
+//! Random documents must convert into errors, never panic.
+use bevy_kdl_reflect_deser::{from_doc, from_doc_untyped};
+use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry};
+use bevy_utils::HashMap;
+use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+use proptest::prelude::*;
+
+#[derive(Reflect, FromReflect)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+#[derive(Reflect, FromReflect)]
+struct Wrap(Point);
+#[derive(Reflect, FromReflect)]
+struct Named {
+    inner: Wrap,
+    opt: Option<i64>,
+}
+#[derive(Reflect, FromReflect)]
+struct Items(Vec<Point>);
+#[derive(Reflect, FromReflect)]
+struct Table {
+    map: HashMap<String, u8>,
+}
+#[derive(Reflect, FromReflect)]
+struct Pair(u8, String);
+#[derive(Reflect, FromReflect)]
+struct Unit;
+/// Its field type is not registered.
+#[derive(Reflect, FromReflect)]
+struct Secret(u16);
+
+fn registry() -> TypeRegistry {
+    let mut reg = TypeRegistry::default();
+    macro_rules! register_all {
+        ($($ty_name:ty ),* $(,)? ) => ({$(
+            reg.register::<$ty_name>();
+        )*})
+    }
+    macro_rules! register_more {
+        ($($ty_name:ty ),* $(,)? ) => ({$(
+            reg.add_registration(TypeRegistration::of::<$ty_name>());
+        )*})
+    }
+    register_all!(Point, Wrap, Named, Items, Table, Pair, Unit, Secret, f32, i64, u8, String, bool);
+    register_more!(Option<i64>, Vec<Point>, HashMap<String, u8>, (u8, String), (Pair, Point));
+    reg
+}
+
+const NAMES: &[&str] = &[
+    "Point", "Wrap", "Named", "Items", "Table", "Pair", "Unit", "Secret", "Tuple", "f32", "i64",
+    "u8", "String", "bool", "x", "y", "inner", "opt", "map", "-", "expand", "export", "import",
+    "@default", "tpl", "@p", "",
+];
+
+fn name() -> impl Strategy<Value = String> {
+    prop::sample::select(NAMES).prop_map(str::to_owned)
+}
+fn value() -> impl Strategy<Value = KdlValue> {
+    prop_oneof![
+        any::<i64>().prop_map(KdlValue::Base10),
+        any::<f64>().prop_map(KdlValue::Base10Float),
+        name().prop_map(KdlValue::String),
+        prop::sample::select(&["inf", "NaN", "tpl/@p", "a.kdl/tpl"][..])
+            .prop_map(|s| KdlValue::String(s.to_owned())),
+        any::<bool>().prop_map(KdlValue::Bool),
+        Just(KdlValue::Null),
+    ]
+}
+fn entry() -> impl Strategy<Value = KdlEntry> {
+    let ty = prop::option::weighted(0.2, name());
+    (prop::option::of(name()), ty, value()).prop_map(|(name, ty, value)| {
+        let mut entry = match name {
+            Some(name) if !name.is_empty() => KdlEntry::new_prop(name, value),
+            _ => KdlEntry::new(value),
+        };
+        if let Some(ty) = ty.filter(|ty| !ty.is_empty()) {
+            entry.set_ty(ty);
+        }
+        entry
+    })
+}
+fn node() -> impl Strategy<Value = KdlNode> {
+    let leaf = (name(), prop::collection::vec(entry(), 0..4)).prop_map(|(name, entries)| {
+        let mut node = KdlNode::new(if name.is_empty() { "-".to_owned() } else { name });
+        node.entries_mut().extend(entries);
+        node
+    });
+    leaf.prop_recursive(4, 32, 4, |inner| {
+        let children = prop::option::of(prop::collection::vec(inner, 0..4));
+        (name(), prop::collection::vec(entry(), 0..3), children).prop_map(
+            |(name, entries, children)| {
+                let mut node = KdlNode::new(if name.is_empty() { "-".to_owned() } else { name });
+                node.entries_mut().extend(entries);
+                if let Some(children) = children {
+                    let mut document = KdlDocument::new();
+                    document.nodes_mut().extend(children);
+                    node.set_children(document);
+                }
+                node
+            },
+        )
+    })
+}
+fn document() -> impl Strategy<Value = String> {
+    prop::collection::vec(node(), 0..5).prop_map(|nodes| {
+        let mut document = KdlDocument::new();
+        document.nodes_mut().extend(nodes);
+        document.to_string()
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1024))]
+    #[test]
+    fn converter_never_panics(text in document()) {
+        // Spans are computed from the text, the document must be parsed from it.
+        let Ok(document) = text.parse::<KdlDocument>() else { return Ok(()) };
+        let registry = registry();
+        let _ = from_doc_untyped(document.clone(), Default::default(), &registry, &Default::default());
+        let _ = from_doc::<Named>(document, Default::default(), &registry, &Default::default());
+    }
+    #[cfg(feature = "templating")]
+    #[test]
+    fn template_expansion_never_panics(text in document()) {
+        let Ok(document) = text.parse::<KdlDocument>() else { return Ok(()) };
+        if let Ok(thunk) = template_kdl::read_thunk(document).into_result() {
+            let _ = thunk.evaluate();
+        }
+    }
+}
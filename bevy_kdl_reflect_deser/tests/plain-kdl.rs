@@ -0,0 +1,69 @@
+//! Without the `templating` feature, documents are read as plain kdl.
+//!
+//! Run with `cargo test -p bevy_kdl_reflect_deser --no-default-features`.
+#![cfg(not(feature = "templating"))]
+use bevy_kdl_reflect_deser::{deserialize_value, read_exports, ConvertErrors, RequiredBindings};
+use bevy_reflect::{FromReflect, Reflect, TypeRegistry};
+
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Line {
+    points: Vec<Point>,
+    closed: bool,
+}
+
+fn registry() -> TypeRegistry {
+    let mut reg = TypeRegistry::default();
+    reg.register::<f32>();
+    reg.register::<bool>();
+    reg.register::<Point>();
+    reg.register::<Vec<Point>>();
+    reg.register::<Line>();
+    reg
+}
+fn read(text: &str) -> Result<Line, ConvertErrors> {
+    let converted = deserialize_value::<Line>(
+        text.parse().unwrap(),
+        RequiredBindings,
+        &registry(),
+        &Default::default(),
+    )?;
+    Ok(Line::from_reflect(converted.value.as_ref()).unwrap())
+}
+fn messages(errors: ConvertErrors) -> Vec<String> {
+    errors.errors().map(|e| e.source.to_string()).collect()
+}
+
+#[test]
+fn single_node() {
+    let text = r#"Line closed=true {
+        points {
+            - x=1.0 y=2.0
+            - { x 3.0; y 4.0; }
+        }
+    }"#;
+    let expected = Line {
+        points: vec![Point { x: 1.0, y: 2.0 }, Point { x: 3.0, y: 4.0 }],
+        closed: true,
+    };
+    assert_eq!(read(text).unwrap(), expected);
+}
+
+#[test]
+fn template_declarations_are_rejected() {
+    let text = "Origin { Point x=0.0 y=0.0; }\nLine closed=false { points { Origin; }; }";
+    let disabled = "Templating is disabled, the document must contain a single node";
+    assert_eq!(messages(read(text).unwrap_err()), [disabled]);
+}
+
+#[test]
+fn nothing_is_exported() {
+    assert!(read_exports("".parse().unwrap(), RequiredBindings).is_ok());
+    let errors = read_exports("export \"Origin\"".parse().unwrap(), RequiredBindings);
+    let not_exports = "This document declares a value, it doesn't export templates";
+    assert_eq!(messages(errors.unwrap_err()), [not_exports]);
+}
@@ -1,8 +1,8 @@
 use std::any::{type_name, Any};
 use std::fmt;
 
-use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry, Typed};
 use bevy_kdl_reflect_deser::{from_doc, from_doc_untyped, ConvertErrors, ConvertResult};
+use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry, Typed};
 use bevy_utils::HashMap;
 use miette::GraphicalReportHandler;
 
@@ -105,9 +105,16 @@ fn assert_all_lines_eq_kdl<T: FromReflect + PartialEq + fmt::Debug + Typed>(
     println!("in section {section_no}");
     for (i, line) in text.lines().enumerate() {
         println!("########### line {i} ###############\n---------------------");
-        let converted = match from_doc::<T>(line.parse().unwrap(), &reg) {
+        let converted = match from_doc::<T>(
+            line.parse().unwrap(),
+            Default::default(),
+            &reg,
+            &Default::default(),
+        ) {
             ConvertResult::Errors(errs) => return Err(errs),
-            ConvertResult::Deserialized(val) => T::from_reflect(val.as_ref()).unwrap(),
+            ConvertResult::Deserialized(val) | ConvertResult::Warned(val, _) => {
+                T::from_reflect(val.as_ref()).unwrap()
+            }
             ConvertResult::Exports(_) => panic!("Never call parse_kdl with an export node"),
         };
         assert_eq!(&converted, value, "in {line}");
@@ -122,9 +129,16 @@ fn assert_eq_kdl<T: FromReflect + PartialEq + fmt::Debug>(
     reg: &TypeRegistry,
 ) -> Result<(), ConvertErrors> {
     println!("in section {section_no}");
-    let converted = match from_doc_untyped(text.parse().unwrap(), &reg) {
+    let converted = match from_doc_untyped(
+        text.parse().unwrap(),
+        Default::default(),
+        &reg,
+        &Default::default(),
+    ) {
         ConvertResult::Errors(errs) => return Err(errs),
-        ConvertResult::Deserialized(val) => T::from_reflect(val.as_ref()).unwrap(),
+        ConvertResult::Deserialized(val) | ConvertResult::Warned(val, _) => {
+            T::from_reflect(val.as_ref()).unwrap()
+        }
         ConvertResult::Exports(_) => panic!("Never call parse_kdl with an export node"),
     };
     assert_eq!(&converted, value, "in {text}");
@@ -136,9 +150,16 @@ fn assert_fails_kdl<T: FromReflect + fmt::Debug + Any>(
     reg: &TypeRegistry,
 ) -> Result<(), ConvertErrors> {
     println!("in section {section_no}");
-    let converted = match from_doc_untyped(text.parse().unwrap(), &reg) {
+    let converted = match from_doc_untyped(
+        text.parse().unwrap(),
+        Default::default(),
+        &reg,
+        &Default::default(),
+    ) {
         ConvertResult::Errors(errs) => Err(errs),
-        ConvertResult::Deserialized(val) => Ok(T::from_reflect(val.as_ref()).unwrap()),
+        ConvertResult::Deserialized(val) | ConvertResult::Warned(val, _) => {
+            Ok(T::from_reflect(val.as_ref()).unwrap())
+        }
         ConvertResult::Exports(_) => panic!("Never call parse_kdl with an export node"),
     };
     assert!(
@@ -183,7 +204,7 @@ fn readme_examples_inner() -> Result<(), ConvertErrors> {
     register_more!(
         MyTuple, Example2, (u64, u32, u32), Vec<String>, Vec<u8>, Vec<Fancy>,
         HashMap<String, SimpleFields>, HashMap<String, u32>, Vec<usize>,
-        HashMap<u32, Fancy>, HashMap<u32, StringNewtype>
+        HashMap<u32, Fancy>, HashMap<u32, StringNewtype>, (u8, (u8, String), String)
     );
 
     let mut sections: Vec<_> = extract_kdls().collect();
@@ -249,6 +270,12 @@ fn readme_examples_inner() -> Result<(), ConvertErrors> {
         3 => StringNewtype("Hallo Welt".to_owned()),
         4 => StringNewtype("Ahoj svĕte".to_owned()),
     };
+    let s31 = (25u8, (3u8, "nested".to_owned()), "Tuple String".to_owned());
+    let s32 = vec![
+        Fancy("One thousand".to_owned(), 1000),
+        Fancy("Two thousand".to_owned(), 2000),
+        Fancy("Three thousand".to_owned(), 3000),
+    ];
 
     assert_eq_kdl(1, sections[0].content, &s1, &reg)?;
     assert_eq_kdl(2, sections[1].content, &s2, &reg)?;
@@ -280,5 +307,7 @@ fn readme_examples_inner() -> Result<(), ConvertErrors> {
     assert_fails_kdl::<MyTuple>(28, sections[27].content, &reg)?;
     assert_eq_kdl(29, sections[28].content, &s13_29.0, &reg)?;
     assert_eq_kdl(30, sections[29].content, &s30, &reg)?;
+    assert_eq_kdl(31, sections[30].content, &s31, &reg)?;
+    assert_eq_kdl(32, sections[31].content, &s32, &reg)?;
     Ok(())
 }
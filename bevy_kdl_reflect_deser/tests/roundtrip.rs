@@ -0,0 +1,54 @@
+//! `assert_kdl_roundtrip!` registers the types a type is made of.
+#![cfg(feature = "test-utils")]
+use bevy_kdl_reflect_deser::{assert_kdl_roundtrip, test_utils::assert_roundtrip};
+use bevy_reflect::{FromReflect, Reflect};
+
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Stats {
+    health: u32,
+    speed: f32,
+    name: String,
+    tags: Vec<String>,
+    shield: Option<u8>,
+}
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Unit {
+    stats: Stats,
+    level: i64,
+}
+
+#[test]
+fn primitives_are_registered() {
+    let text = r#"Stats health=10 speed=1.5 name="Pierre" { tags "a" "b"; shield 3; }"#;
+    let stats = assert_kdl_roundtrip!(Stats, text);
+    let tags = vec!["a".to_owned(), "b".to_owned()];
+    let expected = Stats {
+        health: 10,
+        speed: 1.5,
+        name: "Pierre".to_owned(),
+        tags,
+        shield: Some(3),
+    };
+    assert_eq!(stats, expected);
+}
+
+#[test]
+fn listed_dependencies() {
+    let text = r#"Unit level=3 { stats health=1 speed=2.0 name="" { tags "x" "y"; shield 0; }; }"#;
+    let unit = assert_kdl_roundtrip!(Unit, text, Stats);
+    assert_eq!(unit.level, 3);
+    assert_eq!(unit.stats.shield, Some(0));
+}
+
+#[test]
+#[should_panic(expected = "Stats")]
+fn missing_dependency() {
+    let text = r#"Unit level=3 { stats health=1 speed=2.0 name="" { tags "x" "y"; shield 0; }; }"#;
+    assert_roundtrip::<Unit>(text, |_| {});
+}
+
+#[test]
+#[should_panic(expected = "is not a")]
+fn conversion_error() {
+    assert_kdl_roundtrip!(Stats, r#"Stats health="ten""#);
+}
@@ -0,0 +1,172 @@
+//! Serialized values convert back into the same values.
+use std::fmt;
+
+use bevy_kdl_reflect_deser::{
+    deserialize_value, serialize_value, FieldOrder, ReflectSet, SerError,
+};
+use bevy_reflect::{FromReflect, Reflect, TypeRegistration, TypeRegistry, Typed};
+use bevy_utils::{HashMap, HashSet};
+use kdl::KdlDocument;
+
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Meters(f32);
+
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Stats {
+    health: u64,
+    speed: Meters,
+    name: String,
+    shield: Option<u8>,
+}
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+struct Unit {
+    stats: Stats,
+    tags: Vec<String>,
+    lone: Vec<u8>,
+    counts: HashMap<String, u8>,
+    ids: HashSet<u32>,
+    pos: (i32, i32),
+}
+#[derive(Reflect, FromReflect, Debug, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+fn registry() -> TypeRegistry {
+    let mut reg = TypeRegistry::default();
+    macro_rules! register_more {
+        ($($ty_name:ty ),* $(,)? ) => ({$(
+            reg.add_registration(TypeRegistration::of::<$ty_name>());
+        )*})
+    }
+    reg.register::<Unit>();
+    reg.register::<Stats>();
+    reg.register::<Meters>();
+    reg.register::<Side>();
+    reg.register::<u8>();
+    reg.register::<u32>();
+    reg.register::<u64>();
+    reg.register::<i32>();
+    reg.register::<i64>();
+    reg.register::<f32>();
+    reg.register::<String>();
+    register_more!(
+        Option<u8>, Vec<String>, Vec<u8>, HashMap<String, u8>, HashSet<u32>, HashSet<i64>,
+        (i32, i32),
+    );
+    reg.register_type_data::<HashSet<u32>, ReflectSet>();
+    reg.register_type_data::<HashSet<i64>, ReflectSet>();
+    reg
+}
+
+fn roundtrip<T: FromReflect + Typed + fmt::Debug>(value: &T) -> T {
+    let registry = registry();
+    let mut document = KdlDocument::new();
+    document
+        .nodes_mut()
+        .push(serialize_value(value, &registry, FieldOrder::Declaration).unwrap());
+    // Print the document, to check that it parses back.
+    let text = document.to_string();
+    let converted = deserialize_value::<T>(
+        text.parse().unwrap(),
+        Default::default(),
+        &registry,
+        &Default::default(),
+    );
+    let converted = converted.unwrap_or_else(|errs| panic!("{text}{}", errs.show_for()));
+    assert!(converted.warnings.is_none(), "{text}");
+    T::from_reflect(converted.value.as_ref()).unwrap()
+}
+
+#[test]
+fn values_roundtrip() {
+    let stats = Stats {
+        health: u64::MAX,
+        speed: Meters(0.1),
+        name: "Pierre \"the\" Unit".to_owned(),
+        shield: None,
+    };
+    let unit = Unit {
+        stats,
+        tags: vec!["a".to_owned(), "b c".to_owned()],
+        lone: vec![3],
+        counts: [("arrows".to_owned(), 20), ("big bombs".to_owned(), 3)]
+            .into_iter()
+            .collect(),
+        ids: [7, 9].into_iter().collect(),
+        pos: (-1, 2),
+    };
+    let roundtripped = roundtrip(&unit);
+    assert_eq!(roundtripped, unit);
+
+    let stats = Stats {
+        health: 0,
+        speed: Meters(f32::INFINITY),
+        name: String::new(),
+        shield: Some(5),
+    };
+    assert_eq!(roundtrip(&stats), stats);
+}
+
+#[test]
+fn enums_are_errors() {
+    let side = serialize_value(&Side::Left, &registry(), FieldOrder::Declaration);
+    let name = Side::type_info().type_name().to_owned();
+    assert_eq!(side, Err(SerError::Enum(name)));
+}
+
+#[test]
+fn canonical_order() {
+    let stats = Stats {
+        health: 3,
+        speed: Meters(1.5),
+        name: "a".to_owned(),
+        shield: None,
+    };
+    let registry = registry();
+    let alphabetical = serialize_value(&stats, &registry, FieldOrder::Alphabetical).unwrap();
+    let names: Vec<_> = alphabetical
+        .entries()
+        .iter()
+        .map(|e| e.name().unwrap().value())
+        .collect();
+    assert_eq!(names, ["health", "name", "shield"]);
+    let speed = alphabetical.children().unwrap().nodes();
+    assert_eq!(speed[0].name().value(), "speed");
+
+    let counts: HashMap<String, u8> = [("c", 1), ("a", 2), ("b", 3)]
+        .map(|(k, v)| (k.to_owned(), v))
+        .into_iter()
+        .collect();
+    let ids: HashSet<u32> = [30, 1, 200, 7].into_iter().collect();
+    let counts = serialize_value(&counts, &registry, FieldOrder::Declaration).unwrap();
+    let keys: Vec<_> = counts
+        .entries()
+        .iter()
+        .map(|e| e.name().unwrap().value())
+        .collect();
+    assert_eq!(keys, ["a", "b", "c"]);
+    let ids = serialize_value(&ids, &registry, FieldOrder::Declaration).unwrap();
+    let ids: Vec<_> = ids
+        .entries()
+        .iter()
+        .map(|e| e.value().as_i64().unwrap())
+        .collect();
+    assert_eq!(ids, [1, 7, 30, 200]);
+}
+
+#[test]
+fn exact_integer_order() {
+    // Both round to the same `f64`, and print in the opposite order
+    let ids: HashSet<i64> = [-9_007_199_254_740_992, -9_007_199_254_740_993]
+        .into_iter()
+        .collect();
+    let ids = serialize_value(&ids, &registry(), FieldOrder::Declaration).unwrap();
+    let ids: Vec<_> = ids
+        .entries()
+        .iter()
+        .map(|e| e.value().as_i64().unwrap())
+        .collect();
+    assert_eq!(ids, [-9_007_199_254_740_993, -9_007_199_254_740_992]);
+}
@@ -0,0 +1,237 @@
+//! Spawn components that can't be reflected, such as components holding
+//! `Handle`s or foreign types, with a function reading their kdl node.
+//!
+//! A [`ComponentBuilder`] is registered for a node name. `bundle` children
+//! with that name are kept as-is when converting the scene, and the builder is
+//! called with the node on each entity spawned with it:
+//!
+//! ```kdl
+//! scene {
+//!   entity { bundle { Transform; Sprite texture="player.png"; }; }
+//! }
+//! ```
+//!
+//! Template arguments are not substituted in builder nodes, they are passed
+//! to the builder as written in the file.
+use std::{any, error::Error};
+
+use bevy::{
+    ecs::world::{EntityMut, EntityRef},
+    prelude::*,
+    utils::HashMap,
+};
+use kdl::{KdlDocument, KdlEntry, KdlIdentifier, KdlNode};
+
+use crate::config::SceneConfig;
+
+/// The name of the node declaring the components of an entity.
+const BUNDLE: &str = "bundle";
+/// The name of the node declaring components only with a feature enabled.
+const ONLY_IF: &str = "only-if";
+
+/// Insert the component declared by `node` into the entity.
+pub type ComponentBuilder = fn(&KdlNode, &mut EntityMut) -> Result<(), Box<dyn Error>>;
+
+/// A node to pass to the [`ComponentBuilder`] of a name once spawned: the
+/// name and the node.
+///
+/// Its fields are positional so that the replaced node reads without
+/// printing any property name, see [`ComponentBuilders::replace_nodes`].
+#[derive(Reflect, FromReflect, Clone, Debug, Default)]
+pub(crate) struct BuiltComponent(String, String);
+impl BuiltComponent {
+    /// The node declaring `node`, printed as `node` was.
+    ///
+    /// The name of the node is the `BuiltComponent` type, its repr the whole
+    /// text of `node`, and its entries print nothing, so that the spans of
+    /// the document are those of the file.
+    fn replacing(node: &KdlNode) -> KdlNode {
+        let mut declared = node.clone();
+        declared.set_leading("");
+        declared.set_trailing("");
+        let declared = declared.to_string();
+        let mut name = KdlIdentifier::from(any::type_name::<Self>());
+        name.set_repr(declared.clone());
+        let mut built = KdlNode::new(name);
+        for value in [node.name().value().to_owned(), declared] {
+            let mut entry = KdlEntry::new(value);
+            entry.set_leading("");
+            entry.set_value_repr("");
+            entry.set_trailing("");
+            built.push(entry);
+        }
+        if let Some(leading) = node.leading() {
+            built.set_leading(leading);
+        }
+        if let Some(trailing) = node.trailing() {
+            built.set_trailing(trailing);
+        }
+        built
+    }
+    /// The `BuiltComponent` declared in a scene, if `declared` is one.
+    pub(crate) fn from_declared(declared: &dyn Reflect) -> Option<Self> {
+        let is_built = declared.type_name() == any::type_name::<Self>();
+        is_built.then(|| Self::from_reflect(declared)).flatten()
+    }
+}
+
+/// The nodes to build on a spawned entity, removed once built.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub(crate) struct BuiltComponents(pub(crate) Vec<BuiltComponent>);
+
+/// The [`ComponentBuilder`]s per node name.
+#[derive(Resource, Default, Clone)]
+pub struct ComponentBuilders {
+    builders: HashMap<String, ComponentBuilder>,
+}
+impl ComponentBuilders {
+    /// Build the nodes named `name` with `builder`, returns the builder
+    /// previously registered for `name`.
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        builder: ComponentBuilder,
+    ) -> Option<ComponentBuilder> {
+        self.builders.insert(name.into(), builder)
+    }
+    /// Replace the `bundle` children of `document` with a registered builder
+    /// by a [`BuiltComponent`], so that they convert without being reflected.
+    ///
+    /// The replaced nodes print as they were written, the spans of the
+    /// document are unchanged.
+    pub(crate) fn replace_nodes(&self, document: &mut KdlDocument) {
+        if self.builders.is_empty() {
+            return;
+        }
+        for node in document.nodes_mut() {
+            let is_bundle = node.name().value() == BUNDLE;
+            if let Some(children) = node.children_mut() {
+                if is_bundle {
+                    self.replace_components(children);
+                } else {
+                    self.replace_nodes(children);
+                }
+            }
+        }
+    }
+    /// Replace the components of a bundle with a registered builder, including
+    /// those of its `only-if` nodes.
+    fn replace_components(&self, bundle: &mut KdlDocument) {
+        for node in bundle.nodes_mut() {
+            let name = node.name().value();
+            let is_only_if = name == ONLY_IF;
+            if self.builders.contains_key(name) {
+                *node = BuiltComponent::replacing(node);
+            } else if let (true, Some(children)) = (is_only_if, node.children_mut()) {
+                self.replace_components(children);
+            }
+        }
+    }
+    /// Run the builders of the [`BuiltComponents`] of `declared` on `entity`.
+    ///
+    /// Nodes failing to build are skipped with a warning.
+    pub(crate) fn run(&self, declared: EntityRef, mut entity: EntityMut, config: &SceneConfig) {
+        let Some(BuiltComponents(built)) = declared.get::<BuiltComponents>() else {
+            return;
+        };
+        for BuiltComponent(name, node) in built {
+            let Some(builder) = self.builders.get(name) else {
+                config.warn(format_args!(
+                    "No component builder for {name}, it is skipped"
+                ));
+                continue;
+            };
+            let built = node.parse::<KdlNode>().map_err(Into::into);
+            if let Err(error) = built.and_then(|node| builder(&node, &mut entity)) {
+                config.warn(format_args!("Couldn't build {name}: {error}"));
+            }
+        }
+        entity.remove::<BuiltComponents>();
+    }
+}
+
+/// Register [`ComponentBuilder`]s from a plugin.
+pub trait AddComponentBuilder {
+    /// Spawn nodes named `name` in scene bundles with `builder`.
+    fn add_component_builder(&mut self, name: &str, builder: ComponentBuilder) -> &mut Self;
+}
+impl AddComponentBuilder for App {
+    fn add_component_builder(&mut self, name: &str, builder: ComponentBuilder) -> &mut Self {
+        let mut builders = self
+            .world
+            .get_resource_or_insert_with(ComponentBuilders::default);
+        builders.add(name, builder);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sprite(_: &KdlNode, _: &mut EntityMut) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn replaced(text: &str) -> KdlDocument {
+        let mut builders = ComponentBuilders::default();
+        builders.add("Sprite", sprite);
+        let mut document: KdlDocument = text.parse().unwrap();
+        builders.replace_nodes(&mut document);
+        document
+    }
+    /// The names of the nodes of `document`, depth first.
+    fn names(document: &KdlDocument, out: &mut Vec<String>) {
+        for node in document.nodes() {
+            out.push(node.name().value().to_owned());
+            if let Some(children) = node.children() {
+                names(children, out);
+            }
+        }
+    }
+
+    #[test]
+    fn only_bundle_children() {
+        let text = r#"Sprite
+entity {
+    bundle { Transform; Sprite texture="a.png"; }
+    only-if feature="debug" { Sprite; }
+    entity { bundle { only-if feature="debug" { Sprite { size 2; }; }; }; }
+}"#;
+        let document = replaced(text);
+        let mut replaced_names = Vec::new();
+        names(&document, &mut replaced_names);
+        let built = any::type_name::<BuiltComponent>();
+        let expected = [
+            "Sprite",
+            "entity",
+            "bundle",
+            "Transform",
+            built,
+            "only-if",
+            "Sprite",
+            "entity",
+            "bundle",
+            "only-if",
+            built,
+        ];
+        assert_eq!(replaced_names, expected);
+    }
+
+    #[test]
+    fn spans_kept() {
+        let text = "entity {\n  bundle {\n    Sprite \"a.png\" {\n      size 2\n    }\n  }\n}\n";
+        let document = replaced(text);
+        assert_eq!(document.to_string(), text);
+
+        let bundle = document.nodes()[0].children().unwrap().nodes()[0].children();
+        let built = &bundle.unwrap().nodes()[0];
+        let values: Vec<_> = built
+            .entries()
+            .iter()
+            .map(|e| e.value().as_string())
+            .collect();
+        let node = "Sprite \"a.png\" {\n      size 2\n    }";
+        assert_eq!(values, [Some("Sprite"), Some(node)]);
+    }
+}
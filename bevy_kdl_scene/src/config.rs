@@ -0,0 +1,63 @@
+//! Configuration of the scene plugin, see [`crate::Plug`].
+use std::{fmt, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy_kdl_reflect_deser::DeserOptions;
+
+/// Where warnings and errors of scene loading are reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Diagnostics {
+    /// With bevy's `warn!` and `error!` logging macros.
+    #[default]
+    Log,
+    /// Printed to the standard error output.
+    Stderr,
+    /// Not reported, use [`crate::KdlManager::files`] to check for errors.
+    Silent,
+}
+
+/// The configuration set with the [`crate::Plug`] builder methods.
+///
+/// Files already loaded are not affected by changes to this resource. All the
+/// warnings and errors of the crate are reported according to
+/// [`SceneConfig::diagnostics`].
+#[derive(Resource, Debug, Clone)]
+pub struct SceneConfig {
+    /// The folder, in the asset folder, scene files are read from.
+    pub asset_folder: Option<PathBuf>,
+    /// Whether conversion warnings, such as inexact floats, fail the scene.
+    pub strict_mode: bool,
+    pub diagnostics: Diagnostics,
+    /// Whether entities named with a string get it as their `Name` component.
+    pub insert_names: bool,
+}
+impl Default for SceneConfig {
+    fn default() -> Self {
+        SceneConfig {
+            asset_folder: None,
+            strict_mode: false,
+            diagnostics: Diagnostics::Log,
+            insert_names: true,
+        }
+    }
+}
+impl SceneConfig {
+    /// `options` with the strictness of this configuration.
+    pub(crate) fn options(&self, options: DeserOptions) -> DeserOptions {
+        options.deny_warnings(self.strict_mode)
+    }
+    pub(crate) fn warn(&self, message: fmt::Arguments) {
+        match self.diagnostics {
+            Diagnostics::Log => warn!("{message}"),
+            Diagnostics::Stderr => eprintln!("warning: {message}"),
+            Diagnostics::Silent => {}
+        }
+    }
+    pub(crate) fn error(&self, message: fmt::Arguments) {
+        match self.diagnostics {
+            Diagnostics::Log => error!("{message}"),
+            Diagnostics::Stderr => eprintln!("error: {message}"),
+            Diagnostics::Silent => {}
+        }
+    }
+}
@@ -1,42 +1,78 @@
-use std::{io::Read, marker::PhantomData, path::PathBuf, str::Utf8Error};
+use std::{fmt, fs, io, marker::PhantomData, path::Path, str::Utf8Error, sync::Arc};
 
 use bevy::{
     asset::FileAssetIo,
     ecs::system::SystemParam,
     prelude::*,
-    reflect::{ReflectRef, TypeRegistryInternal},
+    reflect::{ReflectRef, TypeRegistration, TypeRegistryInternal},
     utils::{HashMap, HashSet},
 };
-use bevy_kdl_reflect_deser::{from_doc, ConvertErrors, ConvertResult};
-use kdl::{KdlDocument, KdlError};
+use bevy_kdl_reflect_deser::{
+    build_typed, check_declared, enter_span, from_shared_doc, ConvertErrors, ConvertResult,
+    Error as DeserError, ReflectFromReflect, UsedTypes,
+};
+use kdl::{KdlDocument, KdlError, KdlNode};
 use slotmap::{new_key_type, SecondaryMap, SlotMap};
-use template_kdl::ExportedBindings;
+use template_kdl::{
+    multi_err::OrAccumulate, ExportedBindings, ExportedBindingsList, Import, Imports,
+};
 use thiserror::Error;
 
-use crate::reload::{self, AssetManager};
+use crate::{
+    builders::{BuiltComponent, BuiltComponents, ComponentBuilders},
+    config::SceneConfig,
+    features::SceneFeatures,
+    library::TemplateLibraries,
+    names::{name_properties, ScenePath},
+    patch::{apply_patches, take_patches, ConvertedPatch, Patches},
+    reload::AssetManager,
+    roots::{export_root, gather_roots},
+    script::SceneScripts,
+};
 
-pub struct BoxedReflect(pub Box<dyn Reflect>);
+/// A declared component, shared by the entities and scenes it is cloned into.
+///
+/// Cloning it only counts a reference. Spawning applies it by reference,
+/// mutating it, for example when a patch changes it, clones the component
+/// first if it is shared, so that the others keep the declared value.
+#[derive(Clone)]
+pub struct SharedReflect(pub Arc<dyn Reflect>);
+impl SharedReflect {
+    /// The component, cloned first if something else shares it.
+    fn make_mut(&mut self) -> &mut dyn Reflect {
+        if Arc::get_mut(&mut self.0).is_none() {
+            self.0 = Arc::from(self.0.clone_value());
+        }
+        // unwrap: `self.0` is not shared anymore
+        Arc::get_mut(&mut self.0).unwrap()
+    }
+}
 
 #[rustfmt::skip]
-impl Reflect for BoxedReflect {
-    fn set(&mut self, value: Box<dyn Reflect>) -> Result<(), Box<dyn Reflect>> { self.0.set(value) }
-    fn apply(&mut self, value: &dyn Reflect) { self.0.apply(value) }
+impl Reflect for SharedReflect {
+    fn set(&mut self, value: Box<dyn Reflect>) -> Result<(), Box<dyn Reflect>> { self.make_mut().set(value) }
+    fn apply(&mut self, value: &dyn Reflect) { self.make_mut().apply(value) }
     fn as_any(&self) -> &dyn std::any::Any { self.0.as_any() }
-    fn into_any(self: Box<Self>) -> Box<dyn std::any::Any> { self.0.into_any() }
+    fn into_any(self: Box<Self>) -> Box<dyn std::any::Any> { self.0.clone_value().into_any() }
     fn type_name(&self) -> &str { self.0.type_name() }
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self.0.as_any_mut() }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self.make_mut().as_any_mut() }
     fn as_reflect(&self) -> &dyn Reflect { self.0.as_reflect() }
     fn reflect_ref(&self) -> bevy::reflect::ReflectRef { self.0.reflect_ref() }
-    fn reflect_mut(&mut self) -> bevy::reflect::ReflectMut { self.0.reflect_mut() }
+    fn reflect_mut(&mut self) -> bevy::reflect::ReflectMut { self.make_mut().reflect_mut() }
     fn clone_value(&self) -> Box<dyn Reflect> { self.0.clone_value() }
-    fn into_reflect(self: Box<Self>) -> Box<dyn Reflect> { self.0.into_reflect() }
+    fn into_reflect(self: Box<Self>) -> Box<dyn Reflect> { self.0.clone_value() }
     fn get_type_info(&self) -> &'static bevy::reflect::TypeInfo { self.0.get_type_info() }
-    fn reflect_owned(self: Box<Self>) -> bevy::reflect::ReflectOwned { self.0.reflect_owned() }
-    fn as_reflect_mut(&mut self) -> &mut dyn Reflect { self.0.as_reflect_mut() }
+    fn reflect_owned(self: Box<Self>) -> bevy::reflect::ReflectOwned { self.0.clone_value().reflect_owned() }
+    fn as_reflect_mut(&mut self) -> &mut dyn Reflect { self.make_mut().as_reflect_mut() }
+}
+impl fmt::Debug for SharedReflect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
 }
-impl FromReflect for BoxedReflect {
+impl FromReflect for SharedReflect {
     fn from_reflect(reflect: &dyn Reflect) -> Option<Self> {
-        Some(BoxedReflect(reflect.clone_value()))
+        Some(SharedReflect(Arc::from(reflect.clone_value())))
     }
 }
 impl FromReflect for DeserEntity {
@@ -50,8 +86,9 @@ impl FromReflect for DeserEntity {
     }
 }
 
+/// Why a component of a scene couldn't be spawned, see [`SpawnReport`].
 #[derive(Error, Debug)]
-pub(crate) enum SpawnError {
+pub enum SpawnError {
     #[error(
         "scene contains the unregistered type `{0}`. \
         Consider registering the type using `app.register_type::<{0}>()`"
@@ -63,48 +100,185 @@ pub(crate) enum SpawnError {
     )]
     MissingComponent(String),
 }
-#[derive(Reflect, FromReflect, Clone, PartialEq, Eq, Hash)]
+#[derive(Reflect, FromReflect, Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum ReferBy {
     Name(String),
     Id(u32),
 }
-#[derive(Reflect)]
+/// An entity declared in a scene, cloning it shares its components.
+#[derive(Reflect, Clone, Debug)]
 pub(crate) struct DeserEntity {
     pub(crate) refer_by: Option<ReferBy>,
-    pub(crate) components: Vec<BoxedReflect>,
+    pub(crate) components: Vec<SharedReflect>,
     pub(crate) children: Vec<DeserEntity>,
 }
-impl DeserEntity {
-    pub(crate) fn spawn_hierarchy<'a>(
-        &'a self,
+/// The entities spawned from a scene file, and the components that couldn't
+/// be spawned, the rest of the scene is still spawned.
+///
+/// It is inserted into the [`KdlScene`] entity once its scene is spawned, and
+/// replaced when it is hot reloaded:
+///
+/// ```ignore
+/// fn check_scenes(reports: Query<&SpawnReport, Changed<SpawnReport>>) {
+///     for report in &reports {
+///         for (path, error) in &report.errors {
+///             error!("{path}: {error}");
+///         }
+///     }
+/// }
+/// ```
+#[derive(Component, Debug, Default)]
+pub struct SpawnReport {
+    /// The spawned entities and their path, parents before their children.
+    pub spawned: Vec<(Entity, ScenePath)>,
+    /// The components that couldn't be spawned, by the path of their entity.
+    pub errors: Vec<(ScenePath, SpawnError)>,
+}
+/// Spawns [`DeserEntity`]s, see [`DeserEntity::spawn_hierarchy`].
+struct Spawner<'a, 'r> {
+    entity_references: &'r mut HashMap<&'a ReferBy, Entity>,
+    registry: &'r TypeRegistryInternal,
+    insert_names: bool,
+    report: SpawnReport,
+}
+impl<'a, 'r> Spawner<'a, 'r> {
+    fn spawn(
+        &mut self,
+        declared: &'a DeserEntity,
         world: &mut World,
         current: Entity,
-        entity_references: &mut HashMap<&'a ReferBy, Entity>,
-        registry: &TypeRegistryInternal,
-    ) -> Result<(), SpawnError> {
-        if let Some(reference) = &self.refer_by {
-            entity_references.insert(reference, current);
+        path: ScenePath,
+    ) {
+        self.report.spawned.push((current, path.clone()));
+        if let Some(reference) = &declared.refer_by {
+            self.entity_references.insert(reference, current);
         }
-        for component in &self.components {
+        if let (Some(name), true) = (declared.name(), self.insert_names) {
+            world.entity_mut(current).insert(Name::new(name.to_owned()));
+        }
+        let mut built = Vec::new();
+        for component in &declared.components {
+            if let Some(component) = BuiltComponent::from_declared(component.as_reflect()) {
+                built.push(component);
+                continue;
+            }
             let get_name = || component.type_name().to_string();
-            let registration = registry
+            let reflect_component = self
+                .registry
                 .get_with_name(component.type_name())
-                .ok_or_else(|| SpawnError::Missing(get_name()))?;
-
-            let reflect_component = registration
-                .data::<ReflectComponent>()
-                .ok_or_else(|| SpawnError::MissingComponent(get_name()))?;
-
-            reflect_component.apply_or_insert(world, current, component.0.as_ref());
+                .ok_or_else(|| SpawnError::Missing(get_name()))
+                .and_then(|registration| {
+                    let data = registration.data::<ReflectComponent>();
+                    data.ok_or_else(|| SpawnError::MissingComponent(get_name()))
+                })
+                .map_err(|err| (path.clone(), err))
+                .or_accumulate(&mut self.report.errors);
+            if let Some(reflect_component) = reflect_component {
+                reflect_component.apply_or_insert(world, current, component.0.as_ref());
+            }
+        }
+        if !built.is_empty() {
+            world.entity_mut(current).insert(BuiltComponents(built));
         }
 
-        for child in &self.children {
+        let paths = path.children(&declared.children);
+        for (child, child_path) in declared.children.iter().zip(paths) {
             let new_child = world.spawn_empty().id();
-            child.spawn_hierarchy(world, new_child, entity_references, registry)?;
+            self.spawn(child, world, new_child, child_path);
             let mut entity = world.entity_mut(current);
             entity.push_children(&[new_child]);
         }
-        Ok(())
+    }
+}
+impl DeserEntity {
+    /// Spawn this entity as `current` and its children, skipping the
+    /// components that can't be spawned. Entities referred to by a string get
+    /// it as their [`Name`] if `insert_names`.
+    pub(crate) fn spawn_hierarchy<'a>(
+        &'a self,
+        world: &mut World,
+        current: Entity,
+        entity_references: &mut HashMap<&'a ReferBy, Entity>,
+        registry: &TypeRegistryInternal,
+        insert_names: bool,
+    ) -> SpawnReport {
+        let report = SpawnReport::default();
+        let mut spawner = Spawner { entity_references, registry, insert_names, report };
+        spawner.spawn(self, world, current, ScenePath::root(self));
+        spawner.report
+    }
+    /// Add the types of the components of this entity and its descendants.
+    fn add_used_types(&self, used: &mut UsedTypes, registry: &TypeRegistryInternal) {
+        for component in &self.components {
+            used.add(component.0.as_ref(), registry);
+        }
+        for child in &self.children {
+            child.add_used_types(used, registry);
+        }
+    }
+    /// Replace the components of this entity and its children by their
+    /// concrete type, built with their [`ReflectFromReflect`].
+    ///
+    /// Components without it stay dynamic values. Concrete values hash and
+    /// compare like the type they are, unlike dynamic ones.
+    fn make_concrete(&mut self, registry: &TypeRegistryInternal) {
+        for component in &mut self.components {
+            let registration = registry.get_with_name(component.type_name());
+            let from_reflect = registration.and_then(|r| r.data::<ReflectFromReflect>());
+            let concrete = from_reflect.and_then(|data| data.from_reflect(component.0.as_ref()));
+            if let Some(concrete) = concrete {
+                component.0 = Arc::from(concrete);
+            }
+        }
+        for child in &mut self.children {
+            child.make_concrete(registry);
+        }
+    }
+    /// Check that the components of this entity and its children, declared
+    /// by `node`, have all the fields of their type, with the right type.
+    ///
+    /// Components declared by templates are not expanded in `node`, their
+    /// errors point at the template invocation.
+    fn check_components(
+        &self,
+        node: &KdlNode,
+        registry: &TypeRegistryInternal,
+        errors: &mut Vec<DeserError>,
+    ) {
+        let children = node.children().map_or(&[][..], |c| c.nodes());
+        let (bundle, entities) = match children {
+            [bundle, entities @ ..] if bundle.name().value() == "bundle" => {
+                (bundle.children().map_or(&[][..], |c| c.nodes()), entities)
+            }
+            entities => (&[][..], entities),
+        };
+        // Templates, `only-if` and `@disabled` nodes change which nodes declare
+        // components, find them by name rather than by position. Components
+        // declared by templates or through an alias point at `node`.
+        let declared_node = |type_name: &str| {
+            let is_declared = |declared: &&KdlNode| {
+                let name = declared.name().value();
+                let prefix = type_name.strip_suffix(name);
+                name == type_name || prefix.map_or(false, |p| p.ends_with("::"))
+            };
+            let mut declared = bundle.iter().filter(is_declared);
+            match (declared.next(), declared.next()) {
+                (Some(declared), None) => declared,
+                _ => node,
+            }
+        };
+        for component in &self.components {
+            if BuiltComponent::from_declared(component.as_reflect()).is_some() {
+                continue;
+            }
+            let declared = declared_node(component.type_name());
+            errors.extend(check_declared(declared, component.0.as_ref(), registry));
+        }
+        let same_children = entities.len() == self.children.len();
+        for (i, child) in self.children.iter().enumerate() {
+            let declared = if same_children { &entities[i] } else { node };
+            child.check_components(declared, registry, errors);
+        }
     }
 }
 
@@ -120,11 +294,177 @@ pub enum CuddlyError {
     KdlError(#[from] KdlError),
     #[error("Utf8 validation error when reading the kdl file: {0}")]
     Utf8(#[from] Utf8Error),
+    #[error("Circular import: {}", .0.join(" imports "))]
+    CircularImport(Vec<String>),
+    #[error("{file} imports from {dependency}, which is a scene, not an export file")]
+    NotExports { file: String, dependency: String },
+    #[error("{file} imports from {dependency}, which failed to load: {reason}")]
+    FailedDependency {
+        file: String,
+        dependency: String,
+        reason: String,
+    },
+    #[error("{file} imports {}, but {dependency} doesn't export them", .bindings.join(", "))]
+    MissingBindings {
+        file: String,
+        dependency: String,
+        bindings: Vec<String>,
+    },
+    #[error("{file} has no top level `entity \"{root}\"` node")]
+    MissingRoot { file: String, root: String },
+    #[error("{file} doesn't list `{root}` in its `export-nodes` node")]
+    PrivateRoot { file: String, root: String },
+    #[error("{file} has an invalid `patch` node: {reason}")]
+    BadPatch { file: String, reason: &'static str },
 }
 
+/// Spawn the scene declared in `file` as children of this entity.
 #[derive(Component)]
 pub struct KdlScene {
+    /// The path of the file, `file.kdl#name` selects the top level `entity`
+    /// node `name` like [`KdlScene::root`] does.
     pub file: String,
+    /// The content of `file`, when it is not read from the asset folder.
+    pub source: Option<String>,
+    /// Only spawn the top level `entity` node of `file` with this name, when
+    /// it declares several.
+    pub root: Option<String>,
+}
+impl KdlScene {
+    /// Spawn the scene declared in `source`, without touching the filesystem.
+    ///
+    /// `virtual_path` is the name of the scene in error messages and
+    /// [`KdlOrigin`](crate::KdlOrigin), the source is also registered
+    /// in [`VirtualFiles`] under that name when loaded, so that hot reloading
+    /// and other files can refer to it.
+    pub fn from_string(source: impl Into<String>, virtual_path: impl Into<String>) -> Self {
+        KdlScene {
+            file: virtual_path.into(),
+            source: Some(source.into()),
+            root: None,
+        }
+    }
+    /// The path of `file` without its `#name` anchor, and the root to spawn.
+    pub(crate) fn path_and_root(&self) -> (&str, Option<&str>) {
+        match self.file.split_once('#') {
+            Some((path, anchor)) => (path, Some(self.root.as_deref().unwrap_or(anchor))),
+            None => (&self.file, self.root.as_deref()),
+        }
+    }
+}
+
+/// In-memory kdl documents, `import` nodes resolve files against those before
+/// looking into the asset folder.
+///
+/// ```ignore
+/// fn setup(mut files: ResMut<VirtualFiles>) {
+///     files.insert("base.kdl", r#"character "@id" { entity "@id"; }; export "character""#);
+/// }
+/// ```
+#[derive(Resource, Default, Debug)]
+pub struct VirtualFiles {
+    files: HashMap<String, String>,
+}
+impl VirtualFiles {
+    /// Register `source` as the content of the file at `path`, returns the
+    /// previously registered content.
+    pub fn insert(&mut self, path: impl Into<String>, source: impl Into<String>) -> Option<String> {
+        self.files.insert(path.into(), source.into())
+    }
+    pub fn remove(&mut self, path: &str) -> Option<String> {
+        self.files.remove(path)
+    }
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.files.get(path).map(String::as_str)
+    }
+}
+
+/// Where to read kdl files from.
+pub(crate) struct Sources<'a> {
+    /// The asset folder, `None` when the asset server doesn't read from the
+    /// filesystem.
+    asset_path: Option<&'a Path>,
+    libraries: &'a TemplateLibraries,
+    virtual_files: &'a VirtualFiles,
+    pub(crate) config: &'a SceneConfig,
+}
+impl<'a> Sources<'a> {
+    pub(crate) fn new(
+        asset_server: &'a AssetServer,
+        libraries: &'a TemplateLibraries,
+        virtual_files: &'a VirtualFiles,
+        config: &'a SceneConfig,
+    ) -> Self {
+        // TODO(COMPAT): wasm support
+        let asset_io = asset_server.asset_io().downcast_ref::<FileAssetIo>();
+        let asset_path = asset_io.map(FileAssetIo::root_path);
+        Sources { asset_path, libraries, virtual_files, config }
+    }
+    fn read(&self, file: &str) -> io::Result<String> {
+        if let Some(source) = self
+            .libraries
+            .get(file)
+            .or_else(|| self.virtual_files.get(file))
+        {
+            return Ok(source.to_owned());
+        }
+        match self.asset_path {
+            Some(asset_path) => match &self.config.asset_folder {
+                Some(folder) => fs::read_to_string(asset_path.join(folder).join(file)),
+                None => fs::read_to_string(asset_path.join(file)),
+            },
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{file} is not in VirtualFiles and there is no asset folder"),
+            )),
+        }
+    }
+}
+
+/// What converting files needs, besides reading them.
+pub(crate) struct LoadContext<'a> {
+    pub(crate) registry: &'a TypeRegistryInternal,
+    /// Only the `only-if` nodes of the enabled features are converted.
+    pub(crate) features: &'a SceneFeatures,
+    pub(crate) builders: &'a ComponentBuilders,
+    pub(crate) scripts: &'a SceneScripts,
+}
+
+/// The resources to read and convert scene files.
+#[derive(SystemParam)]
+pub(crate) struct SceneLoader<'w, 's> {
+    asset_server: Res<'w, AssetServer>,
+    app_registry: Res<'w, AppTypeRegistry>,
+    libraries: Res<'w, TemplateLibraries>,
+    features: Res<'w, SceneFeatures>,
+    builders: Res<'w, ComponentBuilders>,
+    scripts: Res<'w, SceneScripts>,
+    pub(crate) config: Res<'w, SceneConfig>,
+    #[system_param(ignore)]
+    _p: PhantomData<&'s ()>,
+}
+impl SceneLoader<'_, '_> {
+    pub(crate) fn sources<'a>(&'a self, virtual_files: &'a VirtualFiles) -> Sources<'a> {
+        Sources::new(
+            &self.asset_server,
+            &self.libraries,
+            virtual_files,
+            &self.config,
+        )
+    }
+    pub(crate) fn registry(&self) -> &AppTypeRegistry {
+        &self.app_registry
+    }
+    /// The context to convert files with, `registry` is read from
+    /// [`Self::registry`].
+    pub(crate) fn context<'a>(&'a self, registry: &'a TypeRegistryInternal) -> LoadContext<'a> {
+        LoadContext {
+            registry,
+            features: &self.features,
+            builders: &self.builders,
+            scripts: &self.scripts,
+        }
+    }
 }
 
 /// Stored in `LoadManager::graph` to manage dependencies.
@@ -138,70 +478,242 @@ pub(crate) struct LoadStatus {
 #[derive(Debug)]
 pub(crate) enum LoadState {
     ExportsReady(ExportedBindings),
-    // TODO: use concrete type DeserEntity here instead
-    SceneReady(Box<dyn Reflect>),
+    /// The scene, and the commands to run once it is spawned.
+    SceneReady(DeserEntity, Vec<KdlNode>),
     // TODO(ERR): probably need to accumulate several errors.
     Failed(CuddlyError),
 }
 
 // TODO(ERR): Accumulate errors
 fn load_kdl_template(
-    asset_path: &PathBuf,
+    sources: &Sources,
+    context: &LoadContext,
     current: &str,
-    registry: &TypeRegistryInternal,
+    root: Option<&str>,
     instances: &mut KdlInstances,
 ) -> Result<KdlInstanceKey, CuddlyError> {
-    let mut file = std::fs::File::open(asset_path.join(current))?;
-    let mut file_content = String::new();
-    file.read_to_string(&mut file_content)?;
-    let document: KdlDocument = file_content.parse()?;
+    let (state, dependencies) = convert_file(sources, context, current, root, instances)?;
+    let key = instances.states.insert(LoadStatus {
+        dependencies: dependencies.into_iter().collect(),
+        state,
+        source: current.to_owned(),
+    });
+    // Only the whole file can be imported
+    if root.is_none() {
+        instances.keys.insert(current.to_string(), key);
+    }
+    Ok(key)
+}
+/// Read and convert `current`, loading its not yet loaded dependencies.
+///
+/// With a `root`, only the top level `entity` node with that name is
+/// converted, see [`gather_roots`]. An anchored `current`, `file.kdl#name`,
+/// is the `file.kdl` export file of the `name` template, see [`export_root`].
+/// Only the `only-if` nodes of the enabled features of `context` are
+/// converted, nodes with a component builder are kept for its builders, and,
+/// when `current` is spawned rather than imported, the commands allowed by its
+/// scripts are removed from the scene to run once it is spawned.
+pub(crate) fn convert_file(
+    sources: &Sources,
+    context: &LoadContext,
+    current: &str,
+    root: Option<&str>,
+    instances: &mut KdlInstances,
+) -> Result<(LoadState, HashSet<KdlInstanceKey>), CuddlyError> {
+    let LoadContext { registry, features, builders, scripts } = *context;
+    let (path, anchor) = match current.split_once('#') {
+        Some((path, anchor)) => (path, Some(anchor)),
+        None => (current, None),
+    };
+    let file_content = sources.read(path)?;
+    let mut document: KdlDocument = {
+        enter_span!("kdl_parse", file = current, bytes = file_content.len());
+        file_content.parse()?
+    };
+    builders.replace_nodes(&mut document);
+    name_properties(document.nodes_mut());
+    // Commands only run for the file spawned as a scene, not for its imports
+    let is_spawned = anchor.is_none() && instances.loading.is_empty();
+    let script = if is_spawned {
+        scripts.take_commands(&mut document)
+    } else {
+        Vec::new()
+    };
+    // Patches only apply to the file spawned as a scene, not to its imports
+    let patches = take_patches(&mut document, current)?;
+    let patches = match anchor {
+        Some(anchor) => {
+            export_root(&mut document, path, anchor)?;
+            Patches::default()
+        }
+        None => {
+            gather_roots(&mut document, current, root)?;
+            patches
+        }
+    };
     let deps = template_kdl::get_imports(&document)?;
-    let mut list = std::collections::HashMap::new();
+    let mut list = ExportedBindingsList::new();
     let mut dependencies = HashSet::new();
     for dep in deps.required_files() {
         let exports_key = if let Some(already_loaded_key) = instances.keys.get(dep) {
             *already_loaded_key
         } else {
-            load_kdl_template(asset_path, dep, registry, instances)?
+            instances.loading.push(current.to_owned());
+            if let Some(start) = instances.loading.iter().position(|file| file == dep) {
+                let mut cycle = instances.loading[start..].to_vec();
+                cycle.push(dep.to_owned());
+                instances.loading.pop();
+                return Err(CuddlyError::CircularImport(cycle));
+            }
+            let loaded = load_kdl_template(sources, context, dep, None, instances);
+            instances.loading.pop();
+            loaded?
         };
+        let (file, dependency) = (current.to_owned(), dep.to_owned());
+        // unwrap: `exports_key` was returned by `load_kdl_template`
         let exports = match &instances.states.get(exports_key).unwrap().state {
-            LoadState::SceneReady(_) | LoadState::Failed(_) => panic!("TODO(ERR)"),
             LoadState::ExportsReady(exports) => exports.clone(),
+            LoadState::SceneReady(..) => return Err(CuddlyError::NotExports { file, dependency }),
+            LoadState::Failed(err) => {
+                let reason = err.to_string();
+                return Err(CuddlyError::FailedDependency { file, dependency, reason });
+            }
         };
         dependencies.insert(exports_key);
         list.insert(dep, exports);
     }
-    let required = deps.bindings(&template_kdl::ExportedBindingsList { list })?;
-    let state = match from_doc::<DeserEntity>(document, required, registry) {
+    let required = deps
+        .bindings(&list)
+        .map_err(|err| missing_bindings(current, &deps, &list, err))?;
+    let options = sources.config.options(features.options());
+    let is_patched = !patches.is_empty();
+    let patches = match patches.convert(registry, &required, &options, sources.config, current) {
+        Ok(patches) => patches,
+        Err(errors) => return Ok((LoadState::Failed(errors.into()), dependencies)),
+    };
+    let document = Arc::new(document);
+    let shared = document.clone();
+    let scene = match from_shared_doc::<DeserEntity>(shared, required, registry, &options) {
         // TODO: return value of from_doc should be the type given as argument.
-        ConvertResult::Deserialized(reflect) => LoadState::SceneReady(reflect),
-        ConvertResult::Exports(bindings) => LoadState::ExportsReady(bindings),
-        ConvertResult::Errors(errs) => LoadState::Failed(errs.into()),
+        ConvertResult::Deserialized(reflect) => reflect,
+        ConvertResult::Warned(reflect, warnings) => {
+            let warnings = warnings.show_for();
+            sources.config.warn(format_args!("{current}:{warnings}"));
+            reflect
+        }
+        ConvertResult::Exports(_) if is_patched => {
+            let reason = "there is no scene to patch";
+            let bad_patch = CuddlyError::BadPatch { file: current.to_owned(), reason };
+            return Ok((LoadState::Failed(bad_patch), dependencies));
+        }
+        ConvertResult::Exports(bindings) => {
+            return Ok((LoadState::ExportsReady(bindings), dependencies));
+        }
+        ConvertResult::Errors(errs) => return Ok((LoadState::Failed(errs.into()), dependencies)),
     };
-    let key = instances.states.insert(LoadStatus {
-        dependencies: dependencies.into_iter().collect(),
-        state,
-        source: current.to_owned(),
-    });
-    instances.keys.insert(current.to_string(), key);
-    Ok(key)
+    let converted = ConvertedScene {
+        document: &document,
+        script,
+        patches,
+        file: current,
+    };
+    let state = converted.checked(scene, registry, sources.config);
+    Ok((state, dependencies))
+}
+/// The error of `file` importing bindings from `imports` that are not in
+/// `list`, grouped by the first file missing some, `err` if it isn't about
+/// missing bindings.
+fn missing_bindings(
+    file: &str,
+    imports: &Imports,
+    list: &ExportedBindingsList,
+    err: template_kdl::err::Error,
+) -> CuddlyError {
+    let missing = || {
+        let entries = imports.entries().iter();
+        entries.filter(move |i| i.missing(list).is_some())
+    };
+    let Some(dependency) = missing().next().and_then(Import::file) else {
+        return err.into();
+    };
+    let from_dependency = missing().filter(|i| i.file() == Some(dependency));
+    let bindings = from_dependency.map(|i| i.binding().unwrap_or(&i.path).to_owned());
+    CuddlyError::MissingBindings {
+        file: file.to_owned(),
+        dependency: dependency.to_owned(),
+        bindings: bindings.collect(),
+    }
+}
+/// A scene file, with what was taken out of it before conversion.
+struct ConvertedScene<'a> {
+    document: &'a KdlDocument,
+    script: Vec<KdlNode>,
+    patches: Vec<ConvertedPatch>,
+    file: &'a str,
+}
+impl ConvertedScene<'_> {
+    /// The `scene` converted from the document, with its patches applied,
+    /// failed if any of its components doesn't match its type.
+    ///
+    /// Spawning applies components with `ReflectComponent`, which silently
+    /// skips fields missing from the declared value or absent from the type.
+    /// Patches only declare the fields they change, they are not checked.
+    fn checked(
+        self,
+        scene: Box<dyn Reflect>,
+        registry: &TypeRegistryInternal,
+        config: &SceneConfig,
+    ) -> LoadState {
+        let ConvertedScene { document, script, patches, file } = self;
+        let mut entity = match build_typed::<DeserEntity>(document, scene.as_ref(), registry) {
+            Ok(entity) => entity,
+            Err(errors) => return LoadState::Failed(errors.into()),
+        };
+        let node = document.nodes().last();
+        let node = node.cloned().unwrap_or_else(|| KdlNode::new("entity"));
+        let mut errors = Vec::new();
+        entity.check_components(&node, registry, &mut errors);
+        if errors.is_empty() {
+            for unmatched in apply_patches(&mut entity, &patches) {
+                config.warn(format_args!("{file}: patch {unmatched} matches no entity"));
+            }
+            entity.make_concrete(registry);
+            LoadState::SceneReady(entity, script)
+        } else {
+            LoadState::Failed(ConvertErrors::new(document.to_string(), errors).into())
+        }
+    }
 }
 // TODO(PERF): async (see `1_nonbevy_loader.md`)
 fn load_scene(
-    asset_server: Res<AssetServer>,
-    app_registry: Res<AppTypeRegistry>,
+    loader: SceneLoader,
     mut instances: ResMut<KdlInstances>,
+    mut virtual_files: ResMut<VirtualFiles>,
     scenes: Query<(Entity, &KdlScene), Changed<KdlScene>>,
     mut cmds: Commands,
 ) {
+    let config = &loader.config;
     for (entity, scene) in &scenes {
-        // TODO(COMPAT): wasm support
-        let asset_io: &FileAssetIo = asset_server.asset_io().downcast_ref().unwrap();
-        let root = asset_io.root_path();
-        let registry = app_registry.read();
-        // TODO(ERR): gahhhh
-        let instance = load_kdl_template(root, &scene.file, &registry, &mut instances).unwrap();
-        cmds.entity(entity).insert(KdlInstance(instance));
+        if let Some(source) = &scene.source {
+            virtual_files.insert(scene.path_and_root().0, source);
+        }
+        let sources = loader.sources(&virtual_files);
+        let registry = loader.registry().read();
+        let context = loader.context(&registry);
+        let file = &scene.file;
+        let (path, root) = scene.path_and_root();
+        let instance = load_kdl_template(&sources, &context, path, root, &mut instances);
+        // unwrap: `load_kdl_template` returns keys of `instances.states`
+        let state = instance.map(|key| (key, &instances.states.get(key).unwrap().state));
+        match state {
+            Ok((_, LoadState::Failed(err))) => {
+                config.error(format_args!("Couldn't load {file}: {err}"));
+            }
+            Ok((instance, _)) => {
+                cmds.entity(entity).insert(KdlInstance(instance));
+            }
+            Err(err) => config.error(format_args!("Couldn't load {file}: {err}")),
+        }
     }
 }
 new_key_type! { pub(crate) struct KdlInstanceKey; }
@@ -217,10 +729,14 @@ pub struct KdlInstances {
     pub(crate) spawned: SecondaryMap<KdlInstanceKey, Vec<Entity>>,
     pub(crate) states: SlotMap<KdlInstanceKey, LoadStatus>,
     pub(crate) keys: HashMap<String, KdlInstanceKey>,
+    /// The files being loaded, each importing the next one, to detect
+    /// circular imports.
+    pub(crate) loading: Vec<String>,
 }
 #[derive(SystemParam)]
 pub struct KdlManager<'w, 's> {
     kdl_instances: Res<'w, KdlInstances>,
+    config: Res<'w, SceneConfig>,
     #[system_param(ignore)]
     _p: PhantomData<&'s ()>,
 }
@@ -233,7 +749,92 @@ impl<'w, 's> AssetManager for KdlManager<'w, 's> {
     }
 
     fn load_marker(&self, path: &str) -> Self::LoadMarker {
-        KdlScene { file: path.to_string() }
+        KdlScene { file: path.to_string(), source: None, root: None }
+    }
+}
+
+/// How a scene file loaded, see [`KdlManager::files`].
+#[derive(Debug, Clone, Copy)]
+pub enum FileState<'a> {
+    /// The file exports templates for other files.
+    Exports,
+    /// The file declares a scene, ready to spawn.
+    Scene,
+    Failed(&'a CuddlyError),
+}
+/// A scene file and the files it imports, see [`KdlManager::files`].
+#[derive(Debug, Clone)]
+pub struct LoadedFile<'a> {
+    pub path: &'a str,
+    pub state: FileState<'a>,
+    /// The paths of the files imported by this one.
+    pub dependencies: Vec<&'a str>,
+}
+impl<'w, 's> KdlManager<'w, 's> {
+    /// The scene files loaded so far and their imports, for example to show
+    /// the load graph in a debug overlay.
+    ///
+    /// ```ignore
+    /// fn list_files(manager: KdlManager) {
+    ///     for file in manager.files() {
+    ///         info!("{}: {:?} imports {:?}", file.path, file.state, file.dependencies);
+    ///     }
+    /// }
+    /// ```
+    pub fn files(&self) -> impl Iterator<Item = LoadedFile<'_>> {
+        let states = &self.kdl_instances.states;
+        states.values().map(move |status| LoadedFile {
+            path: &status.source,
+            state: match &status.state {
+                LoadState::ExportsReady(_) => FileState::Exports,
+                LoadState::SceneReady(..) => FileState::Scene,
+                LoadState::Failed(err) => FileState::Failed(err),
+            },
+            dependencies: status
+                .dependencies
+                .iter()
+                .filter_map(|key| states.get(*key))
+                .map(|dependency| dependency.source.as_str())
+                .collect(),
+        })
+    }
+    /// The registered types the components of the loaded scenes are made of.
+    pub fn used_types(&self, registry: &TypeRegistryInternal) -> UsedTypes {
+        let mut used = UsedTypes::default();
+        for status in self.kdl_instances.states.values() {
+            if let LoadState::SceneReady(scene, _) = &status.state {
+                scene.add_used_types(&mut used, registry);
+            }
+        }
+        used
+    }
+    /// Warn about the registered components that `filter` accepts and no
+    /// loaded scene declares.
+    ///
+    /// Bevy registers many components scenes never declare, restrict `filter`
+    /// to the components of your game:
+    ///
+    /// ```ignore
+    /// fn warn_unused(manager: KdlManager, registry: Res<AppTypeRegistry>) {
+    ///     let is_mine = |reg: &TypeRegistration| reg.type_name().starts_with("my_game::");
+    ///     manager.warn_unused_components(&registry.read(), is_mine);
+    /// }
+    /// ```
+    pub fn warn_unused_components(
+        &self,
+        registry: &TypeRegistryInternal,
+        mut filter: impl FnMut(&TypeRegistration) -> bool,
+    ) {
+        let used = self.used_types(registry);
+        let is_component = |reg: &&TypeRegistration| reg.data::<ReflectComponent>().is_some();
+        for registration in used.unused(registry).filter(is_component) {
+            if filter(registration) {
+                let name = registration.type_name();
+                self.config.warn(format_args!(
+                    "{name} is registered, but no loaded scene uses it"
+                ));
+            }
+        }
     }
 }
 
@@ -244,7 +845,76 @@ pub enum Systems {
 pub struct Plug;
 impl Plugin for Plug {
     fn build(&self, app: &mut App) {
-        app.add_plugin(reload::Plug::<KdlManager>::new())
+        app.init_resource::<SceneConfig>()
+            .init_resource::<VirtualFiles>()
+            .init_resource::<TemplateLibraries>()
+            .init_resource::<SceneFeatures>()
+            .init_resource::<ComponentBuilders>()
+            .init_resource::<SceneScripts>()
+            .register_type::<BuiltComponent>()
+            .register_type::<BuiltComponents>()
             .add_system(load_scene.label(Systems::LoadScene));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use bevy::reflect::DynamicStruct;
+
+    use super::*;
+
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component)]
+    struct Hp(u32);
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component)]
+    struct Mana(u32);
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component)]
+    struct Speed {
+        value: f32,
+    }
+
+    #[test]
+    fn used_types() {
+        let mut registry = TypeRegistryInternal::default();
+        registry.register::<Hp>();
+        registry.register::<Mana>();
+        registry.register::<u32>();
+        let components = vec![SharedReflect(Arc::new(Hp(3)))];
+        let child = DeserEntity { refer_by: None, components, children: vec![] };
+        let scene = DeserEntity {
+            refer_by: None,
+            components: vec![],
+            children: vec![child],
+        };
+        let mut used = UsedTypes::default();
+        scene.add_used_types(&mut used, &registry);
+        assert!(used.contains(TypeId::of::<Hp>()));
+        assert!(used.contains(TypeId::of::<u32>()));
+        assert!(!used.contains(TypeId::of::<Mana>()));
+    }
+    #[test]
+    fn components_found_by_name() {
+        let mut registry = TypeRegistryInternal::default();
+        registry.register::<Hp>();
+        registry.register::<Speed>();
+        registry.register::<f32>();
+        // `Speed` misses its `value` field, the scene doesn't have the `Hp`
+        // declared before it, for example because of `only-if`.
+        let mut speed = DynamicStruct::default();
+        speed.set_name(std::any::type_name::<Speed>().to_owned());
+        let components = vec![SharedReflect(Arc::new(speed))];
+        let entity = DeserEntity { refer_by: None, components, children: vec![] };
+        let text = "entity { bundle { Hp 3; Speed; }; }";
+        let mut errors = Vec::new();
+        entity.check_components(&text.parse().unwrap(), &registry, &mut errors);
+        let [error] = &errors[..] else {
+            panic!("expected a single error, got {errors:?}");
+        };
+        let (offset, len) = (error.span.offset(), error.span.len());
+        assert_eq!(&text[offset..offset + len], "Speed");
+    }
+}
@@ -0,0 +1,138 @@
+//! Write entities of a live world as a scene file, the counterpart of loading
+//! scenes, for save games or level editors.
+//!
+//! Only components registering `ReflectComponent` are exported. The hierarchy
+//! is declared by nesting `entity` nodes, like in hand-written scenes.
+use std::any::TypeId;
+
+use bevy::{ecs::world::EntityRef, prelude::*, reflect::TypeRegistryInternal};
+use bevy_kdl_reflect_deser::{serialize_value, FieldOrder};
+use kdl::{KdlDocument, KdlNode};
+
+use crate::config::SceneConfig;
+
+/// The hierarchy components, declared by nesting `entity` nodes instead.
+fn is_hierarchy(type_id: TypeId) -> bool {
+    type_id == TypeId::of::<Parent>() || type_id == TypeId::of::<Children>()
+}
+
+/// The `bundle` node of the components of `entity`.
+///
+/// Components are sorted by their full type name with
+/// [`FieldOrder::Declaration`], by their declared name otherwise, not in the
+/// order the world happened to register them. Components that can't be
+/// declared in kdl, such as enums, are skipped with a warning.
+fn bundle(
+    entity: EntityRef,
+    registry: &TypeRegistryInternal,
+    order: FieldOrder,
+    config: &SceneConfig,
+) -> KdlNode {
+    let world = entity.world();
+    let mut bundle = KdlNode::new("bundle");
+    let mut components = Vec::new();
+    for component_id in entity.archetype().components() {
+        let type_id = world
+            .components()
+            .get_info(component_id)
+            .and_then(|info| info.type_id())
+            .filter(|type_id| !is_hierarchy(*type_id));
+        let registration = type_id.and_then(|type_id| registry.get(type_id));
+        let reflect = registration
+            .and_then(|r| r.data::<ReflectComponent>())
+            .and_then(|reflect| reflect.reflect(world, entity.id()));
+        let Some(value) = reflect else {
+            continue;
+        };
+        match serialize_value(value, registry, order) {
+            Ok(component) => components.push((value.type_name().to_owned(), component)),
+            Err(error) => config.warn(format_args!("{error}, the component isn't exported")),
+        }
+    }
+    match order {
+        FieldOrder::Declaration => components.sort_by(|a, b| a.0.cmp(&b.0)),
+        FieldOrder::Alphabetical => {
+            components.sort_by(|(_, a), (_, b)| a.name().value().cmp(b.name().value()));
+        }
+    }
+    let nodes = bundle.ensure_children().nodes_mut();
+    nodes.extend(components.into_iter().map(|(_, component)| component));
+    bundle
+}
+
+struct Exporter<'w, F> {
+    world: &'w World,
+    registry: &'w TypeRegistryInternal,
+    order: FieldOrder,
+    config: SceneConfig,
+    filter: F,
+}
+impl<'w, F: FnMut(EntityRef) -> bool> Exporter<'w, F> {
+    fn is_exported(&mut self, entity: Entity) -> bool {
+        self.world
+            .get_entity(entity)
+            .map_or(false, &mut self.filter)
+    }
+    /// The `entity` node of `entity`, with its exported children.
+    fn entity(&mut self, entity: Entity) -> KdlNode {
+        let world = self.world;
+        let mut node = KdlNode::new("entity");
+        let nodes = node.ensure_children().nodes_mut();
+        let bundle = bundle(
+            world.entity(entity),
+            self.registry,
+            self.order,
+            &self.config,
+        );
+        nodes.push(bundle);
+        let children = world.get::<Children>(entity);
+        for child in children.into_iter().flat_map(|c| c.iter()) {
+            if self.is_exported(*child) {
+                nodes.push(self.entity(*child));
+            }
+        }
+        node
+    }
+}
+
+/// The entities of `world` for which `filter` is true, as a scene file.
+///
+/// Exported entities are declared as children of the exported entity they
+/// are a child of, if any, otherwise at the root of the scene. Loading the
+/// scene spawns them as children of the [`KdlScene`](crate::KdlScene)
+/// entity.
+///
+/// The output is stable, so that saving the same world twice gives the same
+/// file, even if its entities were spawned in another order: root entities
+/// are sorted by how they are declared, the fields of components are in
+/// `order`, and so are the components themselves.
+pub fn export_world_to_kdl(
+    world: &mut World,
+    filter: impl FnMut(EntityRef) -> bool,
+    order: FieldOrder,
+) -> KdlDocument {
+    let entities: Vec<Entity> = world.query::<Entity>().iter(world).collect();
+    let world = &*world;
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+    // Worlds without the scene plugin report with the default configuration
+    let config = world
+        .get_resource::<SceneConfig>()
+        .cloned()
+        .unwrap_or_default();
+    let mut exporter = Exporter { world, registry: &registry, order, config, filter };
+
+    let mut scene = KdlNode::new("scene");
+    let roots = scene.ensure_children().nodes_mut();
+    for entity in entities {
+        let parent = world.get::<Parent>(entity).map(Parent::get);
+        let is_root = parent.map_or(true, |parent| !exporter.is_exported(parent));
+        if is_root && exporter.is_exported(entity) {
+            roots.push(exporter.entity(entity));
+        }
+    }
+    roots.sort_by_cached_key(|root| root.to_string());
+    let mut document = KdlDocument::new();
+    document.nodes_mut().push(scene);
+    document
+}
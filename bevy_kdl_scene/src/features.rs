@@ -0,0 +1,56 @@
+//! Features enabled by the app, to include nodes only in some builds.
+//!
+//! The children of an `only-if feature="name"` node are spawned only when the
+//! `name` feature is enabled, so that debug gizmos or platform specific
+//! entities can live in the same file as the rest of the scene:
+//!
+//! ```kdl
+//! scene {
+//!   entity "player" { bundle { Player; }; }
+//!   only-if feature="debug-ui" {
+//!     entity "fps-counter" { bundle { FpsCounter; }; }
+//!   }
+//! }
+//! ```
+use bevy::{prelude::*, utils::HashSet};
+use bevy_kdl_reflect_deser::DeserOptions;
+
+/// The features enabled for `only-if` nodes of scenes.
+///
+/// Scenes already loaded are not affected by changes to this resource.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct SceneFeatures {
+    features: HashSet<String>,
+}
+impl SceneFeatures {
+    /// Enable `feature`, returns whether it was disabled.
+    pub fn enable(&mut self, feature: impl Into<String>) -> bool {
+        self.features.insert(feature.into())
+    }
+    /// Disable `feature`, returns whether it was enabled.
+    pub fn disable(&mut self, feature: &str) -> bool {
+        self.features.remove(feature)
+    }
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        self.features.contains(feature)
+    }
+    /// The options to convert scenes with.
+    pub(crate) fn options(&self) -> DeserOptions {
+        DeserOptions::new().features(self.features.iter().cloned())
+    }
+}
+
+/// Enable scene features from a plugin.
+pub trait EnableSceneFeature {
+    /// Spawn the children of `only-if feature="{feature}"` nodes.
+    fn enable_scene_feature(&mut self, feature: &str) -> &mut Self;
+}
+impl EnableSceneFeature for App {
+    fn enable_scene_feature(&mut self, feature: &str) -> &mut Self {
+        let mut features = self
+            .world
+            .get_resource_or_insert_with(SceneFeatures::default);
+        features.enable(feature);
+        self
+    }
+}
@@ -0,0 +1,67 @@
+//! Post-process components after they are spawned.
+//!
+//! Some data can't be expressed in the scene file, for example a mesh AABB or
+//! an entry in a spatial index. A spawn hook registered for a component type
+//! is called on every entity spawned with this component, once it is added to
+//! the app world.
+use std::any::TypeId;
+
+use bevy::{
+    ecs::world::{EntityMut, EntityRef},
+    prelude::*,
+    reflect::TypeRegistryInternal,
+    utils::HashMap,
+};
+
+/// Fix up an entity, given the component value declared in the scene file.
+pub type SpawnHook = fn(&mut EntityMut, &dyn Reflect);
+
+/// The [`SpawnHook`]s to run per component type.
+///
+/// Hooks of a single component type run in registration order.
+#[derive(Resource, Default, Clone)]
+pub struct SpawnHooks {
+    hooks: HashMap<TypeId, Vec<SpawnHook>>,
+}
+impl SpawnHooks {
+    pub fn add<T: Component>(&mut self, hook: SpawnHook) {
+        self.hooks.entry(TypeId::of::<T>()).or_default().push(hook);
+    }
+    /// Run the hooks of all components of `declared` on `entity`.
+    ///
+    /// Hooks of components without `ReflectComponent` are skipped.
+    pub(crate) fn run(
+        &self,
+        declared: EntityRef,
+        mut entity: EntityMut,
+        registry: &TypeRegistryInternal,
+    ) {
+        for (type_id, hooks) in &self.hooks {
+            let reflect_component = registry
+                .get(*type_id)
+                .and_then(|r| r.data::<ReflectComponent>());
+            let Some(reflect_component) = reflect_component else {
+                continue;
+            };
+            let Some(value) = reflect_component.reflect(declared.world(), declared.id()) else {
+                continue;
+            };
+            for hook in hooks {
+                hook(&mut entity, value);
+            }
+        }
+    }
+}
+
+/// Register [`SpawnHook`]s from a plugin.
+pub trait AddSpawnHook {
+    /// Call `hook` on entities spawned from a scene with a `T` component.
+    fn add_spawn_hook<T: Component>(&mut self, hook: SpawnHook) -> &mut Self;
+}
+impl AddSpawnHook for App {
+    fn add_spawn_hook<T: Component>(&mut self, hook: SpawnHook) -> &mut Self {
+        let mut hooks = self.world.get_resource_or_insert_with(SpawnHooks::default);
+        hooks.add::<T>(hook);
+        self
+    }
+}
@@ -1,3 +1,104 @@
+mod builders;
+mod config;
 mod depends;
+mod export;
+mod features;
+mod hooks;
+mod library;
 mod load;
+#[cfg(feature = "asset-meta")]
+pub mod meta;
+mod names;
+mod patch;
+#[cfg(any(feature = "schedule-config", feature = "asset-meta"))]
+mod read;
 mod reload;
+mod roots;
+mod save;
+#[cfg(feature = "schedule-config")]
+pub mod schedule;
+mod script;
+#[cfg(feature = "validate")]
+pub mod validate;
+
+pub use bevy_kdl_reflect_deser::{FieldOrder, ReflectFromReflect};
+pub use builders::{AddComponentBuilder, ComponentBuilder, ComponentBuilders};
+pub use config::{Diagnostics, SceneConfig};
+pub use depends::{
+    CuddlyError, FileState, KdlManager, KdlScene, LoadedFile, SpawnError, SpawnReport, VirtualFiles,
+};
+pub use export::export_world_to_kdl;
+pub use features::{EnableSceneFeature, SceneFeatures};
+pub use hooks::{AddSpawnHook, SpawnHook, SpawnHooks};
+pub use library::{RegisterTemplateLibrary, TemplateLibraries};
+pub use load::KdlOrigin;
+pub use names::ScenePath;
+pub use save::{Migration, SaveError, SaveGames};
+pub use script::{AllowSceneCommand, SceneScripts, ScriptCommand};
+
+use std::path::PathBuf;
+
+use bevy::prelude::{App, Plugin};
+
+/// Load, spawn and hot reload [`KdlScene`]s.
+///
+/// The builder methods set the [`SceneConfig`] resource, except for hot
+/// reload, which adds a plugin:
+///
+/// ```ignore
+/// app.add_plugin(
+///     Plug::default()
+///         .with_hot_reload(false)
+///         .with_asset_folder("scenes")
+///         .with_strict_mode(true),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Plug {
+    hot_reload: bool,
+    config: SceneConfig,
+}
+impl Default for Plug {
+    fn default() -> Self {
+        Plug { hot_reload: true, config: SceneConfig::default() }
+    }
+}
+impl Plug {
+    /// Reload spawned scenes when their file changes, enabled by default.
+    pub fn with_hot_reload(mut self, hot_reload: bool) -> Self {
+        self.hot_reload = hot_reload;
+        self
+    }
+    /// Read scene files from `folder` in the asset folder, rather than from
+    /// the asset folder itself.
+    pub fn with_asset_folder(mut self, folder: impl Into<PathBuf>) -> Self {
+        self.config.asset_folder = Some(folder.into());
+        self
+    }
+    /// Fail scenes with conversion warnings, disabled by default.
+    pub fn with_strict_mode(mut self, strict_mode: bool) -> Self {
+        self.config.strict_mode = strict_mode;
+        self
+    }
+    /// Where to report scene loading warnings and errors, the log by default.
+    pub fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.config.diagnostics = diagnostics;
+        self
+    }
+    /// Insert a `Name` component into entities named with a string, such as
+    /// `entity "hud"`, enabled by default.
+    pub fn with_entity_names(mut self, insert_names: bool) -> Self {
+        self.config.insert_names = insert_names;
+        self
+    }
+}
+impl Plugin for Plug {
+    fn build(&self, app: &mut App) {
+        if self.hot_reload {
+            app.add_plugin(reload::Plug::<KdlManager>::new());
+        }
+        app.insert_resource(self.config.clone())
+            .add_plugin(depends::Plug)
+            .add_plugin(load::Plug);
+    }
+}
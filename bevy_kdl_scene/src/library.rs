@@ -0,0 +1,58 @@
+//! Template libraries shared by all scenes.
+//!
+//! A plugin can register a set of templates, such as a UI widget set, under a
+//! name. Scenes import it like any other file, without it existing in the
+//! asset folder:
+//!
+//! ```kdl
+//! import "builtin:ui"
+//! scene {
+//!   button "Start"
+//! }
+//! ```
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::config::SceneConfig;
+
+/// Template libraries, `import` nodes resolve files against those before
+/// [`VirtualFiles`](crate::VirtualFiles) and the asset folder.
+#[derive(Resource, Default, Debug)]
+pub struct TemplateLibraries {
+    libraries: HashMap<String, String>,
+}
+impl TemplateLibraries {
+    /// Register `source` as the library `name`, returns the previously
+    /// registered library.
+    ///
+    /// `source` must be a kdl file with an `export` node.
+    pub fn insert(&mut self, name: impl Into<String>, source: impl Into<String>) -> Option<String> {
+        self.libraries.insert(name.into(), source.into())
+    }
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.libraries.get(name).map(String::as_str)
+    }
+}
+
+/// Register template libraries from a plugin.
+pub trait RegisterTemplateLibrary {
+    /// Make the templates exported by `source` available to all scenes
+    /// importing `name`.
+    fn register_template_library(&mut self, name: &str, source: &str) -> &mut Self;
+}
+impl RegisterTemplateLibrary for App {
+    fn register_template_library(&mut self, name: &str, source: &str) -> &mut Self {
+        let mut libraries = self
+            .world
+            .get_resource_or_insert_with(TemplateLibraries::default);
+        let replaced = libraries.insert(name, source).is_some();
+        // The scene plugin may not be added yet
+        let config = self.world.get_resource::<SceneConfig>();
+        if replaced {
+            let config = config.cloned().unwrap_or_default();
+            config.warn(format_args!(
+                "Template library {name} was registered twice, using the last one"
+            ));
+        }
+        self
+    }
+}
@@ -6,8 +6,15 @@ use bevy::{
     prelude::*,
     utils::HashMap,
 };
+use bevy_kdl_reflect_deser::enter_span;
 
-use crate::depends::{self, DeserEntity, KdlInstance, KdlInstances, LoadState};
+use crate::{
+    builders::ComponentBuilders,
+    config::SceneConfig,
+    depends::{self, KdlInstance, KdlInstances, LoadState, SpawnReport},
+    hooks::SpawnHooks,
+    script::SceneScripts,
+};
 
 #[derive(Component)]
 pub struct KdlOrigin {
@@ -21,29 +28,74 @@ fn load_instance(world: &mut World) {
         let mut state: SystemState<(
             Res<KdlInstances>,
             Res<AppTypeRegistry>,
+            Res<SceneConfig>,
             // TODO: Changed<KdlInstance> (read "Warning" section of SystemState doc first)
             Query<(Entity, &KdlInstance), Added<KdlInstance>>,
         )> = SystemState::new(world);
-        let (instances, app_registry, added) = state.get_mut(world);
+        let (instances, app_registry, config, added) = state.get_mut(world);
 
         for (entity, instance) in &added {
             // TODO: Do not filthy up change detection by prematurely &mut instances
             let KdlInstances { states, .. } = &*instances;
             let status = states.get(instance.0).unwrap();
-            let foo = match &status.state {
-                LoadState::SceneReady(scene) => DeserEntity::from_reflect(scene.as_ref()).unwrap(),
-                any_else => panic!("A spawned KdlInstance wasn't a node file: {any_else:?}"),
+            let (foo, script) = match &status.state {
+                LoadState::SceneReady(scene, script) => (scene, script.clone()),
+                LoadState::ExportsReady(_) => {
+                    let file = &status.source;
+                    config.warn(format_args!(
+                        "{file} doesn't contain a scene, nothing to spawn"
+                    ));
+                    continue;
+                }
+                // `load_scene` doesn't insert `KdlInstance` for failed files,
+                // they may still fail when reloaded
+                LoadState::Failed(err) => {
+                    let file = &status.source;
+                    config.error(format_args!("Couldn't load {file}: {err}"));
+                    continue;
+                }
             };
+            enter_span!("kdl_spawn", file = %status.source, children = foo.children.len());
             let mut refs = HashMap::new();
             let mut sub_world = World::new();
-            foo.spawn_hierarchy(&mut sub_world, entity, &mut refs, &app_registry.read());
-            to_spawn.push((Scene::new(sub_world), entity, status.source.clone()));
+            let registry = app_registry.read();
+            let insert_names = config.insert_names;
+            let report =
+                foo.spawn_hierarchy(&mut sub_world, entity, &mut refs, &registry, insert_names);
+            // The rest of the scene is still spawned
+            let file = &status.source;
+            for (path, error) in &report.errors {
+                config.error(format_args!("{file}: {path}: {error}"));
+            }
+            if !report.errors.is_empty() {
+                let (spawned, skipped) = (report.spawned.len(), report.errors.len());
+                config.warn(format_args!(
+                    "{file}: spawned {spawned} entities without {skipped} of their components"
+                ));
+            }
+            let source = status.source.clone();
+            to_spawn.push((Scene::new(sub_world), entity, source, script, report));
         }
     }
+    let hooks = world.resource::<SpawnHooks>().clone();
+    let builders = world.resource::<ComponentBuilders>().clone();
+    let scripts = world.resource::<SceneScripts>().clone();
+    let config = world.resource::<SceneConfig>().clone();
     world.resource_scope(|world, registry: Mut<AppTypeRegistry>| {
-        for (scene, parent, source) in to_spawn.into_iter() {
+        for (scene, parent, source, script, report) in to_spawn.into_iter() {
             // TODO(ERR)
             let infos = scene.write_to_world_with(world, &registry).unwrap();
+            // The report names the entities of the scene world
+            let spawned = report.spawned.into_iter();
+            let spawned =
+                spawned.filter_map(|(e, path)| Some((infos.entity_map.get(e).ok()?, path)));
+            let report = SpawnReport { spawned: spawned.collect(), errors: report.errors };
+            for scene_entity in infos.entity_map.keys() {
+                let entity = infos.entity_map.get(scene_entity).unwrap();
+                let declared = scene.world.entity(scene_entity);
+                builders.run(declared, world.entity_mut(entity), &config);
+                hooks.run(declared, world.entity_mut(entity), &registry.read());
+            }
             for entity in infos.entity_map.values() {
                 let mut entity_mut = world.entity_mut(entity);
                 entity_mut.insert(KdlOrigin { file: source.clone() });
@@ -54,12 +106,15 @@ fn load_instance(world: &mut World) {
                     AddChild { parent, child: entity }.write(world);
                 }
             }
+            world.entity_mut(parent).insert(report);
+            scripts.run(&script, parent, world, &config);
         }
     });
 }
 pub struct Plug;
 impl Plugin for Plug {
     fn build(&self, app: &mut App) {
-        app.add_system(load_instance.after(depends::Systems::LoadScene));
+        app.init_resource::<SpawnHooks>()
+            .add_system(load_instance.after(depends::Systems::LoadScene));
     }
 }
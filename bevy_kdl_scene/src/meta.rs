@@ -0,0 +1,109 @@
+//! Asset processing settings declared in kdl side-car files.
+//!
+//! For an asset at `textures/grass.png`, the settings are read from
+//! `textures/grass.png.meta.kdl`. The file contains a single node of the
+//! settings type, deserialized with `bevy_kdl_reflect_deser`:
+//!
+//! ```kdl
+//! TextureSettings filter="Nearest" repeat=true
+//! ```
+//!
+//! Settings are applied with a user-provided hook every time the asset is
+//! loaded or reloaded. Assets without side-car files are left untouched.
+use std::{
+    ffi::OsString,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    asset::{Asset, AssetPath, FileAssetIo, HandleId},
+    prelude::*,
+    reflect::{GetTypeRegistration, Typed},
+    utils::HashSet,
+};
+
+use crate::{config::SceneConfig, read::read_value};
+
+/// The extension appended to an asset path to get its side-car file path.
+pub const META_EXTENSION: &str = "meta.kdl";
+
+/// Apply the settings `S` to the asset `A`.
+pub type Hook<A, S> = fn(&S, &mut A);
+
+#[derive(Resource)]
+struct MetaHook<A: Asset, S> {
+    hook: Hook<A, S>,
+    /// Assets modified by the hook itself, so that we do not re-apply the hook
+    /// on the `AssetEvent::Modified` it caused.
+    just_applied: HashSet<HandleId>,
+}
+
+fn meta_path(root: &Path, asset: &AssetPath) -> PathBuf {
+    let mut path = OsString::from(root.join(asset.path()));
+    path.push(".");
+    path.push(META_EXTENSION);
+    path.into()
+}
+
+fn apply_meta<A: Asset, S: FromReflect + Typed + Send + Sync + 'static>(
+    mut events: EventReader<AssetEvent<A>>,
+    mut assets: ResMut<Assets<A>>,
+    mut hook: ResMut<MetaHook<A, S>>,
+    asset_server: Res<AssetServer>,
+    app_registry: Res<AppTypeRegistry>,
+    config: Res<SceneConfig>,
+) {
+    // TODO(COMPAT): wasm support
+    let asset_io: &FileAssetIo = asset_server.asset_io().downcast_ref().unwrap();
+    let root = asset_io.root_path();
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if hook.just_applied.remove(&handle.id()) {
+            continue;
+        }
+        let Some(asset_path) = asset_server.get_handle_path(handle) else {
+            continue;
+        };
+        let path = meta_path(root, &asset_path);
+        if !path.exists() {
+            continue;
+        }
+        let settings: S = match read_value(&path, &app_registry.read(), &config) {
+            Ok(settings) => settings,
+            Err(err) => {
+                config.error(format_args!("Invalid asset settings {path:?}: {err}"));
+                continue;
+            }
+        };
+        if let Some(asset) = assets.get_mut(handle) {
+            (hook.hook)(&settings, asset);
+            hook.just_applied.insert(handle.id());
+        }
+    }
+}
+
+/// Apply settings `S` declared in side-car files to assets of type `A`.
+///
+/// `S` and all its fields must be registered in the app type registry.
+pub struct Plug<A, S>(Hook<A, S>, PhantomData<fn(S)>);
+impl<A: Asset, S> Plug<A, S> {
+    pub fn new(hook: Hook<A, S>) -> Self {
+        Self(hook, PhantomData)
+    }
+}
+impl<A: Asset, S> Plugin for Plug<A, S>
+where
+    S: FromReflect + Typed + GetTypeRegistration + Send + Sync + 'static,
+{
+    fn build(&self, app: &mut App) {
+        let hook = MetaHook::<A, S> { hook: self.0, just_applied: HashSet::default() };
+        app.register_type::<S>()
+            .insert_resource(hook)
+            .init_resource::<SceneConfig>()
+            .add_system_to_stage(CoreStage::PostUpdate, apply_meta::<A, S>);
+    }
+}
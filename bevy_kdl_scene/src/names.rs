@@ -0,0 +1,186 @@
+//! Entity names: `entity "hud"` or `entity name="hud"` nodes spawn entities
+//! with a [`Name`](bevy::prelude::Name), and entities are reported by their path in the scene,
+//! such as `root/hud/healthbar`.
+use std::fmt;
+
+use kdl::{KdlEntry, KdlNode};
+
+use crate::depends::{DeserEntity, ReferBy};
+
+/// Replace the `name="hud"` property of `entity` nodes in `nodes` and their
+/// descendants by the `"hud"` argument, unless they already have one.
+///
+/// The argument is printed as the property was written, so that conversion
+/// errors show and point to the file as is.
+pub(crate) fn name_properties(nodes: &mut [KdlNode]) {
+    for node in nodes {
+        if let Some(children) = node.children_mut() {
+            name_properties(children.nodes_mut());
+        }
+        if node.name().value() != "entity" {
+            continue;
+        }
+        let entries = node.entries_mut();
+        let is_name = |e: &KdlEntry| e.name().map_or(false, |n| n.value() == "name");
+        let has_argument = entries.iter().any(|e| e.name().is_none());
+        if let (Some(index), false) = (entries.iter().position(is_name), has_argument) {
+            let property = &entries[index];
+            let mut argument = KdlEntry::new(property.value().clone());
+            if let Some(leading) = property.leading() {
+                argument.set_leading(leading);
+            }
+            if let Some(trailing) = property.trailing() {
+                argument.set_trailing(trailing);
+            }
+            let mut written = property.clone();
+            written.set_leading("");
+            written.set_trailing("");
+            argument.set_value_repr(written.to_string());
+            entries[index] = argument;
+        }
+    }
+}
+
+impl DeserEntity {
+    /// The name of this entity, if it is referred to by a string.
+    pub(crate) fn name(&self) -> Option<&str> {
+        match &self.refer_by {
+            Some(ReferBy::Name(name)) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// How `entity` is referred to in a [`ScenePath`], if it is.
+fn segment(entity: &DeserEntity) -> Option<String> {
+    match entity.refer_by.as_ref()? {
+        ReferBy::Name(name) => Some(name.clone()),
+        ReferBy::Id(id) => Some(id.to_string()),
+    }
+}
+/// Where an entity is in a scene: how it and its ancestors are referred to,
+/// from the root. Entities without an argument are `#i`, their position
+/// among siblings without argument, the root `scene`.
+///
+/// It displays as its segments separated by `/`, such as `scene/hud/#0`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ScenePath(Vec<String>);
+impl ScenePath {
+    /// How the entity and its ancestors are referred to, from the root.
+    pub fn segments(&self) -> &[String] {
+        &self.0
+    }
+    pub(crate) fn root(root: &DeserEntity) -> Self {
+        ScenePath(vec![segment(root).unwrap_or_else(|| "scene".to_owned())])
+    }
+    /// The paths of `children`, the children of the entity at this path.
+    pub(crate) fn children(&self, children: &[DeserEntity]) -> Vec<Self> {
+        let mut unnamed = 0..;
+        let path = |child| {
+            // unwrap: `unnamed` is infinite
+            let segment = segment(child).unwrap_or_else(|| format!("#{}", unnamed.next().unwrap()));
+            ScenePath(self.0.iter().cloned().chain([segment]).collect())
+        };
+        children.iter().map(path).collect()
+    }
+    /// Whether this is the path of the scene root.
+    pub(crate) fn is_root(&self) -> bool {
+        self.0.len() == 1
+    }
+    /// Whether the last segments of this path are `pattern`, `*` matching
+    /// any segment.
+    pub(crate) fn ends_with(&self, pattern: &[String]) -> bool {
+        let Some(start) = self.0.len().checked_sub(pattern.len()) else {
+            return false;
+        };
+        let matches = |(segment, pattern): (&String, &String)| pattern == "*" || segment == pattern;
+        self.0[start..].iter().zip(pattern).all(matches)
+    }
+}
+impl fmt::Display for ScenePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use kdl::{KdlDocument, KdlValue};
+
+    use super::*;
+
+    fn path(segments: &[&str]) -> ScenePath {
+        ScenePath(segments.iter().map(|s| s.to_string()).collect())
+    }
+    fn pattern(pattern: &str) -> Vec<String> {
+        pattern.split('/').map(str::to_owned).collect()
+    }
+    fn entity(refer_by: Option<ReferBy>) -> DeserEntity {
+        DeserEntity { refer_by, components: vec![], children: vec![] }
+    }
+
+    #[test]
+    fn name_property_as_argument() {
+        let text = r#"entity name="hud" {
+    bundle { Label name="title"; }
+    entity name="healthbar" visible=true
+    entity 3 name="ignored"
+    entity
+}
+Other name="kept""#;
+        let mut document: KdlDocument = text.parse().unwrap();
+        name_properties(document.nodes_mut());
+        // Printed as written
+        assert_eq!(document.to_string(), text);
+        let hud = &document.nodes()[0];
+        let healthbar = &hud.children().unwrap().nodes()[1];
+        let arguments = |node: &KdlNode| {
+            let entries = node.entries().iter().filter(|e| e.name().is_none());
+            entries.map(|e| e.value().clone()).collect::<Vec<_>>()
+        };
+        let name = |name: &str| vec![KdlValue::String(name.to_owned())];
+        assert_eq!(arguments(hud), name("hud"));
+        assert_eq!(arguments(healthbar), name("healthbar"));
+        let ignored = &hud.children().unwrap().nodes()[2];
+        assert_eq!(arguments(ignored), [KdlValue::Base10(3)]);
+    }
+
+    #[test]
+    fn children_paths() {
+        let children = [
+            entity(None),
+            entity(Some(ReferBy::Name("hud".to_owned()))),
+            entity(Some(ReferBy::Id(3))),
+            entity(None),
+        ];
+        let root = ScenePath::root(&entity(None));
+        let paths: Vec<_> = root
+            .children(&children)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(paths, ["scene/#0", "scene/hud", "scene/3", "scene/#1"]);
+
+        let named = ScenePath::root(&entity(Some(ReferBy::Name("menu".to_owned()))));
+        let paths = named.children(&children[..1]);
+        assert_eq!(paths[0].segments(), ["menu", "#0"]);
+        assert!(named.is_root());
+    }
+
+    #[test]
+    fn ends_with() {
+        let healthbar = path(&["scene", "hud", "healthbar"]);
+        assert!(healthbar.ends_with(&pattern("healthbar")));
+        assert!(healthbar.ends_with(&pattern("hud/healthbar")));
+        assert!(healthbar.ends_with(&pattern("scene/hud/healthbar")));
+        assert!(healthbar.ends_with(&pattern("*/healthbar")));
+        assert!(healthbar.ends_with(&pattern("scene/*/*")));
+        assert!(healthbar.ends_with(&[]));
+        assert!(!healthbar.ends_with(&pattern("hud")));
+        assert!(!healthbar.ends_with(&pattern("menu/healthbar")));
+        // Longer than the path
+        assert!(!healthbar.ends_with(&pattern("*/scene/hud/healthbar")));
+        assert!(!healthbar.is_root());
+        assert!(path(&["scene"]).is_root());
+    }
+}
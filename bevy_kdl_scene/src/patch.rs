@@ -0,0 +1,364 @@
+//! `patch` nodes, overriding the components of some entities of the scene,
+//! typically of a scene instantiated from an imported template:
+//!
+//! ```kdl
+//! import "level.kdl"
+//! patch "hud/healthbar" { Style width=200; }
+//! patch with="Enemy" { Hp 5; }
+//! entity { bundle; level; }
+//! ```
+//!
+//! The selector matches the end of the path of entities in the scene, see
+//! [`ScenePath`], `*` matches any entity. `with="Component"` only selects
+//! entities with this component. Without path, all entities but the root of
+//! the scene are selected.
+//!
+//! Components of the patch are applied to the selected entities' component of
+//! the same type, only overwriting the declared fields, or added if they
+//! don't have it.
+use std::{fmt, sync::Arc};
+
+use bevy::{
+    reflect::{Reflect, TypeRegistryInternal},
+    utils::get_short_name,
+};
+use bevy_kdl_reflect_deser::{
+    convert_node_at, ConvertErrors, ConvertResult, DeserOptions, Spanned,
+};
+use kdl::{KdlDocument, KdlNode};
+use template_kdl::{navigate::SpannedField, read_plain, RequiredBindings};
+
+use crate::{
+    config::SceneConfig,
+    depends::{DeserEntity, SharedReflect},
+    names::ScenePath,
+    CuddlyError,
+};
+
+/// Which entities a `patch` node applies to.
+#[derive(Debug, Clone)]
+struct Selector {
+    path: Vec<String>,
+    with: Option<String>,
+}
+impl Selector {
+    fn new(node: &KdlNode, file: &str) -> Result<Self, CuddlyError> {
+        let bad_patch = |reason| CuddlyError::BadPatch { file: file.to_owned(), reason };
+        let mut path = Vec::new();
+        let mut with = None;
+        for entry in node.entries() {
+            let value = entry.value().as_string();
+            match (entry.name().map(|n| n.value()), value) {
+                (None, Some(selected)) if path.is_empty() => {
+                    path = selected.split('/').map(str::to_owned).collect();
+                }
+                (Some("with"), Some(component)) => with = Some(component.to_owned()),
+                (None, _) => return Err(bad_patch("the selector must be a single string")),
+                (Some(_), _) => return Err(bad_patch("only a `with=\"Component\"` can be set")),
+            }
+        }
+        Ok(Selector { path, with })
+    }
+    fn matches(&self, entity: &DeserEntity, path: &ScenePath) -> bool {
+        let has_component = |with: &String| {
+            let names = entity.components.iter().map(|c| c.type_name());
+            names.any(|name| name == with || get_short_name(name) == *with)
+        };
+        // The root is the scene itself, only patched when explicitly selected
+        let selected = match &self.path[..] {
+            [] => !path.is_root(),
+            pattern => path.ends_with(pattern),
+        };
+        selected && self.with.as_ref().map_or(true, has_component)
+    }
+}
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self.path.join("/"))?;
+        match &self.with {
+            Some(with) => write!(f, " with=\"{with}\""),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A `patch` node, taken out of the scene file before converting it.
+struct Patch {
+    selector: Selector,
+    /// The component nodes of the patch, and where they start in the file.
+    components: Vec<(KdlNode, usize)>,
+}
+/// The `patch` nodes of a scene file.
+#[derive(Default)]
+pub(crate) struct Patches {
+    /// The file the patches were taken from, as printed.
+    source: String,
+    patches: Vec<Patch>,
+}
+/// A [`Patch`] with its components converted.
+pub(crate) struct ConvertedPatch {
+    selector: Selector,
+    components: Vec<SharedReflect>,
+}
+impl Patches {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+    /// Convert the components of the patches, each component on its own,
+    /// with [`convert_node_at`] so that errors point to where it is in the
+    /// file.
+    pub(crate) fn convert(
+        self,
+        registry: &TypeRegistryInternal,
+        required: &RequiredBindings,
+        options: &DeserOptions,
+        config: &SceneConfig,
+        file: &str,
+    ) -> Result<Vec<ConvertedPatch>, ConvertErrors> {
+        let Patches { source, patches } = self;
+        let mut errors = Vec::new();
+        let mut converted = Vec::with_capacity(patches.len());
+        for Patch { selector, components } in patches {
+            let mut patch = ConvertedPatch { selector, components: Vec::new() };
+            for (node, offset) in components {
+                let bindings = required.clone();
+                match convert_node_at(node, offset, &source, None, registry, bindings, options) {
+                    ConvertResult::Deserialized(component) => {
+                        patch.components.push(SharedReflect(Arc::from(component)));
+                    }
+                    ConvertResult::Warned(component, warnings) => {
+                        let warnings = warnings.show_for();
+                        config.warn(format_args!("{file}:{warnings}"));
+                        patch.components.push(SharedReflect(Arc::from(component)));
+                    }
+                    ConvertResult::Errors(errs) => errors.extend(errs),
+                    // A single node is never an export node
+                    ConvertResult::Exports(_) => {}
+                }
+            }
+            converted.push(patch);
+        }
+        if errors.is_empty() {
+            Ok(converted)
+        } else {
+            Err(ConvertErrors::new(source, errors))
+        }
+    }
+}
+
+/// Remove the top level `patch` nodes of `document`.
+pub(crate) fn take_patches(document: &mut KdlDocument, file: &str) -> Result<Patches, CuddlyError> {
+    let is_patch = |node: &KdlNode| node.name().value() == "patch";
+    if !document.nodes().iter().any(is_patch) {
+        return Ok(Patches::default());
+    }
+    let source = document.to_string();
+    let mut patches = Vec::new();
+    for node in read_plain(document.clone()) {
+        let SpannedField::Node(node) = node else {
+            continue;
+        };
+        if !is_patch(&node) {
+            continue;
+        }
+        let selector = Selector::new(&node, file)?;
+        let children = node.children().into_iter().flat_map(|c| c.nodes());
+        let components = children.map(|c| (KdlNode::clone(&c), c.span().offset as usize));
+        patches.push(Patch { selector, components: components.collect() });
+    }
+    document.nodes_mut().retain(|node| !is_patch(node));
+    Ok(Patches { source, patches })
+}
+/// Apply `patches` to `scene`, returns the selectors that didn't match any
+/// entity.
+pub(crate) fn apply_patches(scene: &mut DeserEntity, patches: &[ConvertedPatch]) -> Vec<String> {
+    let mut unmatched = Vec::new();
+    for ConvertedPatch { selector, components } in patches {
+        let mut matched = false;
+        let path = ScenePath::root(scene);
+        visit(scene, &path, &mut |entity, path| {
+            if selector.matches(entity, path) {
+                matched = true;
+                patch_entity(entity, components);
+            }
+        });
+        if !matched {
+            unmatched.push(selector.to_string());
+        }
+    }
+    unmatched
+}
+fn patch_entity(entity: &mut DeserEntity, components: &[SharedReflect]) {
+    for component in components {
+        let type_name = component.type_name();
+        let existing = entity
+            .components
+            .iter_mut()
+            .find(|c| c.type_name() == type_name);
+        match existing {
+            Some(existing) => existing.apply(component.0.as_ref()),
+            None => entity.components.push(component.clone()),
+        }
+    }
+}
+/// Call `f` on `entity` and its descendants, with their path.
+fn visit(
+    entity: &mut DeserEntity,
+    path: &ScenePath,
+    f: &mut impl FnMut(&mut DeserEntity, &ScenePath),
+) {
+    f(entity, path);
+    let paths = path.children(&entity.children);
+    for (child, path) in entity.children.iter_mut().zip(&paths) {
+        visit(child, path, f);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::type_name;
+
+    use bevy::reflect::DynamicStruct;
+
+    use super::*;
+    use crate::depends::ReferBy;
+
+    #[derive(Reflect, Clone, Debug, PartialEq)]
+    struct Hp {
+        value: u32,
+        max: u32,
+    }
+    fn hp() -> Vec<SharedReflect> {
+        vec![SharedReflect(Arc::new(Hp { value: 1, max: 10 }))]
+    }
+    fn entity(
+        name: Option<&str>,
+        components: Vec<SharedReflect>,
+        children: Vec<DeserEntity>,
+    ) -> DeserEntity {
+        let refer_by = name.map(|name| ReferBy::Name(name.to_owned()));
+        DeserEntity { refer_by, components, children }
+    }
+    /// A scene with the paths:
+    /// `scene`, `scene/hud`, `scene/hud/healthbar`, `scene/#0`,
+    /// `scene/enemies`, `scene/enemies/#0` and `scene/enemies/boss`.
+    fn scene() -> DeserEntity {
+        let healthbar = entity(Some("healthbar"), hp(), vec![]);
+        let hud = entity(Some("hud"), vec![], vec![healthbar]);
+        let boss = entity(Some("boss"), vec![], vec![]);
+        let enemies = entity(
+            Some("enemies"),
+            vec![],
+            vec![entity(None, hp(), vec![]), boss],
+        );
+        entity(None, hp(), vec![hud, entity(None, hp(), vec![]), enemies])
+    }
+    fn selector(text: &str) -> Result<Selector, CuddlyError> {
+        let node: KdlNode = text.parse().unwrap();
+        Selector::new(&node, "test.kdl")
+    }
+    /// The paths of the entities of [`scene`] selected by the `patch` node
+    /// `text`.
+    fn selected(text: &str) -> Vec<String> {
+        let selector = selector(text).unwrap();
+        let mut scene = scene();
+        let mut selected = Vec::new();
+        let path = ScenePath::root(&scene);
+        visit(&mut scene, &path, &mut |entity, path| {
+            if selector.matches(entity, path) {
+                selected.push(path.to_string());
+            }
+        });
+        selected
+    }
+
+    #[test]
+    fn selectors() {
+        assert_eq!(
+            selected(r#"patch "hud/healthbar""#),
+            ["scene/hud/healthbar"]
+        );
+        assert_eq!(selected(r#"patch "healthbar""#), ["scene/hud/healthbar"]);
+        assert_eq!(selected(r#"patch "hud""#), ["scene/hud"]);
+        let enemies = ["scene/enemies/#0", "scene/enemies/boss"];
+        assert_eq!(selected(r#"patch "enemies/*""#), enemies);
+        assert_eq!(selected(r#"patch "scene""#), ["scene"]);
+        let with_hp = [
+            "scene",
+            "scene/hud/healthbar",
+            "scene/#0",
+            "scene/enemies/#0",
+        ];
+        assert_eq!(selected(r#"patch "*" with="Hp""#), with_hp);
+        let full_name = format!(r#"patch "*" with="{}""#, type_name::<Hp>());
+        assert_eq!(selected(&full_name), with_hp);
+        assert!(selected(r#"patch "hud/*/boss""#).is_empty());
+    }
+    #[test]
+    fn no_path_skips_root() {
+        let all = [
+            "scene/hud",
+            "scene/hud/healthbar",
+            "scene/#0",
+            "scene/enemies",
+            "scene/enemies/#0",
+            "scene/enemies/boss",
+        ];
+        assert_eq!(selected("patch"), all);
+        let with_hp = ["scene/hud/healthbar", "scene/#0", "scene/enemies/#0"];
+        assert_eq!(selected(r#"patch with="Hp""#), with_hp);
+    }
+    #[test]
+    fn bad_selectors() {
+        assert!(selector("patch 1").is_err());
+        assert!(selector(r#"patch "hud" "enemies""#).is_err());
+        assert!(selector(r#"patch "hud" at="enemies""#).is_err());
+        let selector = selector(r#"patch "enemies/*" with="Hp""#).unwrap();
+        assert_eq!(selector.to_string(), r#""enemies/*" with="Hp""#);
+    }
+    #[test]
+    fn patch_declared_fields() {
+        let mut patch = DynamicStruct::default();
+        patch.set_name(type_name::<Hp>().to_owned());
+        patch.insert("value", 5_u32);
+        let patch = [SharedReflect(Arc::new(patch))];
+
+        let mut hurt = entity(None, hp(), vec![]);
+        patch_entity(&mut hurt, &patch);
+        assert_eq!(hurt.components.len(), 1);
+        let hp = hurt.components[0].0.downcast_ref::<Hp>();
+        assert_eq!(hp, Some(&Hp { value: 5, max: 10 }));
+
+        // Entities without the component share the one of the patch
+        let mut healthy = entity(None, vec![], vec![]);
+        patch_entity(&mut healthy, &patch);
+        assert_eq!(healthy.components.len(), 1);
+        assert_eq!(Arc::strong_count(&patch[0].0), 2);
+    }
+    #[test]
+    fn patch_shared_components() {
+        let mut patch = DynamicStruct::default();
+        patch.set_name(type_name::<Hp>().to_owned());
+        patch.insert("value", 5_u32);
+        let patch = [SharedReflect(Arc::new(patch))];
+
+        let mut hurt = entity(None, hp(), vec![]);
+        let healthy = hurt.clone();
+        patch_entity(&mut hurt, &patch);
+        let hp = |entity: &DeserEntity| entity.components[0].0.downcast_ref::<Hp>().cloned();
+        assert_eq!(hp(&hurt), Some(Hp { value: 5, max: 10 }));
+        assert_eq!(hp(&healthy), Some(Hp { value: 1, max: 10 }));
+    }
+    #[test]
+    fn unmatched_patches() {
+        let patch = |text| ConvertedPatch {
+            selector: selector(text).unwrap(),
+            components: hp(),
+        };
+        let patches = [patch(r#"patch "boss""#), patch(r#"patch "minion""#)];
+        let mut scene = scene();
+        assert_eq!(apply_patches(&mut scene, &patches), [r#""minion""#]);
+        let boss = &scene.children[2].children[1];
+        assert_eq!(boss.components.len(), 1);
+    }
+}
@@ -0,0 +1,34 @@
+//! Read a single rust value from a standalone kdl file.
+use std::{fs, path::Path};
+
+use bevy::reflect::{FromReflect, TypeRegistryInternal, Typed};
+use bevy_kdl_reflect_deser::{from_doc_typed, ConvertErrors};
+use kdl::{KdlDocument, KdlError};
+use thiserror::Error;
+
+use crate::config::SceneConfig;
+
+#[derive(Error, Debug)]
+pub(crate) enum ReadError {
+    #[error("file error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Parsing error: {0}")]
+    Kdl(#[from] KdlError),
+    #[error("Conversion error: {}", .0.show_for())]
+    Convert(#[from] ConvertErrors),
+}
+
+pub(crate) fn read_value<T: FromReflect + Typed>(
+    path: &Path,
+    registry: &TypeRegistryInternal,
+    config: &SceneConfig,
+) -> Result<T, ReadError> {
+    let document: KdlDocument = fs::read_to_string(path)?.parse()?;
+    // `FromReflect` errors point at the field it rejects
+    let converted =
+        from_doc_typed::<T>(document, Default::default(), registry, &Default::default())?;
+    if let Some(warnings) = converted.warnings {
+        config.warn(format_args!("{path:?}:{}", warnings.show_for()));
+    }
+    Ok(converted.value)
+}
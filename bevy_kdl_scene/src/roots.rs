@@ -0,0 +1,190 @@
+//! Files with several top level `entity` nodes, spawned together or selected
+//! by name.
+//!
+//! An `export-nodes "button" "slider"` node lists the entities other files and
+//! [`KdlScene`](crate::KdlScene)s can select, the others are private. Without
+//! it, all of them can be selected.
+use std::mem;
+
+use kdl::{KdlDocument, KdlNode};
+
+use crate::CuddlyError;
+
+fn is_root(node: &KdlNode) -> bool {
+    node.name().value() == "entity"
+}
+/// The name of the `entity "name"` node.
+fn root_name(node: &KdlNode) -> Option<&str> {
+    let first = node
+        .entries()
+        .first()
+        .filter(|entry| entry.name().is_none());
+    first.and_then(|entry| entry.value().as_string())
+}
+/// Remove the `export-nodes` node of `document`, returning the names it lists.
+fn take_export_nodes(document: &mut KdlDocument) -> Option<Vec<String>> {
+    let nodes = document.nodes_mut();
+    let index = nodes
+        .iter()
+        .position(|n| n.name().value() == "export-nodes")?;
+    let export_nodes = nodes.remove(index);
+    let names = export_nodes
+        .entries()
+        .iter()
+        .filter_map(|e| e.value().as_string());
+    Some(names.map(str::to_owned).collect())
+}
+/// Remove the top level `entity` nodes of `document`.
+fn take_roots(document: &mut KdlDocument) -> Vec<KdlNode> {
+    let nodes = document.nodes_mut();
+    let (roots, rest): (Vec<_>, Vec<_>) = mem::take(nodes).into_iter().partition(is_root);
+    *nodes = rest;
+    roots
+}
+/// The root of `file` named `name`, it must be in `exported` if `file` has an
+/// `export-nodes` node.
+fn select_root(
+    roots: Vec<KdlNode>,
+    exported: Option<&[String]>,
+    file: &str,
+    name: &str,
+) -> Result<KdlNode, CuddlyError> {
+    let (file, root) = (file.to_owned(), name.to_owned());
+    if exported.map_or(false, |names| !names.iter().any(|n| n == name)) {
+        return Err(CuddlyError::PrivateRoot { file, root });
+    }
+    let named = roots.into_iter().find(|node| root_name(node) == Some(name));
+    named.ok_or(CuddlyError::MissingRoot { file, root })
+}
+/// Add a `name` node with `children` at the end of `document`.
+fn push_node(document: &mut KdlDocument, name: &str, children: Vec<KdlNode>) {
+    let mut document_children = KdlDocument::new();
+    *document_children.nodes_mut() = children;
+    let mut node = KdlNode::new(name);
+    node.set_children(document_children);
+    document.nodes_mut().push(node);
+}
+
+/// Move the top level `entity` nodes of `document` in a single `scene` node
+/// at its end, so that each is a root entity of the scene, or only the one
+/// named `root`.
+///
+/// Without `root`, documents with a single top level `entity` node are left
+/// as is, that node is the scene and its components go to the
+/// [`KdlScene`](crate::KdlScene) entity.
+pub(crate) fn gather_roots(
+    document: &mut KdlDocument,
+    file: &str,
+    root: Option<&str>,
+) -> Result<(), CuddlyError> {
+    let exported = take_export_nodes(document);
+    let root_count = document.nodes().iter().filter(|node| is_root(node)).count();
+    if root.is_none() && root_count < 2 {
+        return Ok(());
+    }
+    let mut roots = take_roots(document);
+    if let Some(root) = root {
+        roots = vec![select_root(roots, exported.as_deref(), file, root)?];
+    }
+    push_node(document, "scene", roots);
+    Ok(())
+}
+/// Turn `document` in an export file of the `anchor` template, expanding to
+/// its top level `entity "anchor"` node, for imports of `file.kdl#anchor`.
+///
+/// The template can use the templates of `file`, other top level `entity`
+/// nodes and `export` node are dropped.
+pub(crate) fn export_root(
+    document: &mut KdlDocument,
+    file: &str,
+    anchor: &str,
+) -> Result<(), CuddlyError> {
+    let exported = take_export_nodes(document);
+    let root = select_root(take_roots(document), exported.as_deref(), file, anchor)?;
+    document
+        .nodes_mut()
+        .retain(|node| node.name().value() != "export");
+    push_node(document, anchor, vec![root]);
+    let mut export = KdlNode::new("export");
+    export.push(anchor);
+    document.nodes_mut().push(export);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FILE: &str = r#"
+button { entity; }
+entity "menu" { bundle; }
+entity "pause"
+export-nodes "menu"
+"#;
+
+    fn names(nodes: &[KdlNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name().value()).collect()
+    }
+    fn root_names(node: &KdlNode) -> Vec<Option<&str>> {
+        let children = node.children().map_or(&[][..], |c| c.nodes());
+        children.iter().map(root_name).collect()
+    }
+    fn gathered(text: &str, root: Option<&str>) -> Result<KdlDocument, CuddlyError> {
+        let mut document: KdlDocument = text.parse().unwrap();
+        gather_roots(&mut document, "file.kdl", root).map(|()| document)
+    }
+
+    #[test]
+    fn single_root_left_as_is() {
+        let text = "button { entity; }\nentity \"menu\" { bundle; }\n";
+        assert_eq!(gathered(text, None).unwrap().to_string(), text);
+    }
+    #[test]
+    fn roots_gathered() {
+        let document = gathered(FILE, None).unwrap();
+        assert_eq!(names(document.nodes()), ["button", "scene"]);
+        let scene = &document.nodes()[1];
+        assert_eq!(root_names(scene), [Some("menu"), Some("pause")]);
+    }
+    #[test]
+    fn root_selected() {
+        let document = gathered(FILE, Some("menu")).unwrap();
+        assert_eq!(names(document.nodes()), ["button", "scene"]);
+        assert_eq!(root_names(&document.nodes()[1]), [Some("menu")]);
+
+        let private = gathered(FILE, Some("pause")).unwrap_err();
+        assert!(matches!(private, CuddlyError::PrivateRoot { root, .. } if root == "pause"));
+        let missing = gathered("entity \"menu\"", Some("pause")).unwrap_err();
+        assert!(matches!(missing, CuddlyError::MissingRoot { root, .. } if root == "pause"));
+    }
+    #[test]
+    fn select_without_export_nodes() {
+        let roots = take_roots(&mut FILE.parse().unwrap());
+        let root = select_root(roots, None, "file.kdl", "pause").unwrap();
+        assert_eq!(root_name(&root), Some("pause"));
+
+        let roots = take_roots(&mut FILE.parse().unwrap());
+        let exported = ["menu".to_owned()];
+        let private = select_root(roots, Some(&exported[..]), "file.kdl", "pause");
+        assert!(matches!(private, Err(CuddlyError::PrivateRoot { .. })));
+    }
+    #[test]
+    fn root_exported() {
+        let text = format!("{FILE}export \"button\"\n");
+        let mut document: KdlDocument = text.parse().unwrap();
+        export_root(&mut document, "file.kdl", "menu").unwrap();
+        assert_eq!(names(document.nodes()), ["button", "menu", "export"]);
+        assert_eq!(root_names(&document.nodes()[1]), [Some("menu")]);
+        let export = &document.nodes()[2];
+        let exported: Vec<_> = export
+            .entries()
+            .iter()
+            .map(|e| e.value().as_string())
+            .collect();
+        assert_eq!(exported, [Some("menu")]);
+
+        let mut document: KdlDocument = FILE.parse().unwrap();
+        let private = export_root(&mut document, "file.kdl", "pause");
+        assert!(matches!(private, Err(CuddlyError::PrivateRoot { .. })));
+    }
+}
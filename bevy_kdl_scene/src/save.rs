@@ -0,0 +1,175 @@
+//! Save games, scenes exported from the world with a version tag, so that
+//! saves written by older versions of the game still load after its
+//! components changed.
+//!
+//! The first node of a save file is its version, the rest is the exported
+//! scene:
+//!
+//! ```kdl
+//! save-version 2
+//! scene {
+//!   entity { bundle { Player; Hp 10; }; }
+//! }
+//! ```
+//!
+//! Before conversion, the migrations registered from the version of the file
+//! up to the current version are run on the document, in order.
+use bevy::{ecs::world::EntityRef, prelude::*, utils::HashMap};
+use bevy_kdl_reflect_deser::FieldOrder;
+use kdl::{KdlDocument, KdlError, KdlNode};
+use thiserror::Error;
+
+use crate::{export_world_to_kdl, KdlScene};
+
+/// The name of the node declaring the version of a save file.
+const VERSION_NODE: &str = "save-version";
+
+/// Upgrade a save file document from the version `from` to `from + 1`.
+pub type Migration = fn(KdlDocument, u32) -> KdlDocument;
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("Parsing error: {0}")]
+    KdlError(#[from] KdlError),
+    #[error("The save file doesn't start with a `save-version` node")]
+    MissingVersion,
+    #[error("The save file version {saved} is newer than the game save version {current}")]
+    NewerVersion { saved: u32, current: u32 },
+    #[error("There is no migration from save version {from} to version {}", .from + 1)]
+    NoMigration { from: u32 },
+}
+
+/// Write and read save files of the current `version`.
+///
+/// ```ignore
+/// fn rename_health(mut document: KdlDocument, _from: u32) -> KdlDocument {
+///     // ...
+///     document
+/// }
+/// let saves = SaveGames::new(2).with_migration(1, rename_health);
+/// ```
+#[derive(Resource, Clone, Debug)]
+pub struct SaveGames {
+    version: u32,
+    order: FieldOrder,
+    /// The migration from each version to the next one.
+    migrations: HashMap<u32, Migration>,
+}
+impl SaveGames {
+    pub fn new(version: u32) -> Self {
+        SaveGames {
+            version,
+            order: FieldOrder::default(),
+            migrations: HashMap::default(),
+        }
+    }
+    /// Upgrade save files from the version `from` to `from + 1` with
+    /// `migration`.
+    pub fn with_migration(mut self, from: u32, migration: Migration) -> Self {
+        self.migrations.insert(from, migration);
+        self
+    }
+    /// Order the fields and components of saved entities with `order`,
+    /// [`FieldOrder::Declaration`] by default.
+    pub fn with_field_order(mut self, order: FieldOrder) -> Self {
+        self.order = order;
+        self
+    }
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+    /// The save file of the entities of `world` for which `filter` is true,
+    /// see [`export_world_to_kdl`].
+    pub fn save(&self, world: &mut World, filter: impl FnMut(EntityRef) -> bool) -> KdlDocument {
+        let mut document = export_world_to_kdl(world, filter, self.order);
+        let mut version = KdlNode::new(VERSION_NODE);
+        version.push(i64::from(self.version));
+        document.nodes_mut().insert(0, version);
+        document
+    }
+    /// The scene of the save file `document`, upgraded to the current version.
+    pub fn migrate(&self, mut document: KdlDocument) -> Result<KdlDocument, SaveError> {
+        let nodes = document.nodes_mut();
+        let is_version = |node: &KdlNode| node.name().value() == VERSION_NODE;
+        if !nodes.first().map_or(false, is_version) {
+            return Err(SaveError::MissingVersion);
+        }
+        let version = nodes.remove(0);
+        let saved = version.get(0).and_then(|v| v.value().as_i64());
+        let saved = saved.and_then(|v| u32::try_from(v).ok());
+        let saved = saved.ok_or(SaveError::MissingVersion)?;
+        if saved > self.version {
+            return Err(SaveError::NewerVersion { saved, current: self.version });
+        }
+        for from in saved..self.version {
+            let migration = self.migrations.get(&from);
+            let migration = migration.ok_or(SaveError::NoMigration { from })?;
+            document = migration(document, from);
+        }
+        Ok(document)
+    }
+    /// Load the save file `source`, spawning it as children of the entity
+    /// the returned [`KdlScene`] is added to.
+    ///
+    /// `virtual_path` is the name of the save in error messages, see
+    /// [`KdlScene::from_string`].
+    pub fn load(&self, source: &str, virtual_path: &str) -> Result<KdlScene, SaveError> {
+        let document = self.migrate(source.parse()?)?;
+        Ok(KdlScene::from_string(document.to_string(), virtual_path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Append the version it migrates from to the `scene` node.
+    fn mark(mut document: KdlDocument, from: u32) -> KdlDocument {
+        let scene = document.nodes_mut().last_mut().unwrap();
+        scene.push(i64::from(from));
+        document
+    }
+    /// The versions the `scene` node of `text` was migrated from.
+    fn migrate(saves: &SaveGames, text: &str) -> Result<Vec<i64>, SaveError> {
+        let document = saves.migrate(text.parse().unwrap())?;
+        let [scene] = document.nodes() else {
+            panic!("the version node is not removed");
+        };
+        let entries = scene.entries().iter();
+        Ok(entries.filter_map(|e| e.value().as_i64()).collect())
+    }
+
+    #[test]
+    fn migrations_in_order() {
+        let saves = SaveGames::new(3)
+            .with_migration(1, mark)
+            .with_migration(2, mark);
+        assert_eq!(migrate(&saves, "save-version 1\nscene").unwrap(), [1, 2]);
+        assert_eq!(migrate(&saves, "save-version 2\nscene").unwrap(), [2]);
+        assert!(migrate(&saves, "save-version 3\nscene").unwrap().is_empty());
+    }
+    #[test]
+    fn missing_version() {
+        let saves = SaveGames::new(1);
+        let missing = |text: &str| matches!(migrate(&saves, text), Err(SaveError::MissingVersion));
+        assert!(missing("scene"));
+        assert!(missing("scene\nsave-version 1"));
+        assert!(missing("save-version \"one\"\nscene"));
+        assert!(missing("save-version -1\nscene"));
+    }
+    #[test]
+    fn newer_version() {
+        let saves = SaveGames::new(2);
+        let error = migrate(&saves, "save-version 3\nscene").unwrap_err();
+        assert!(matches!(
+            error,
+            SaveError::NewerVersion { saved: 3, current: 2 }
+        ));
+    }
+    #[test]
+    fn no_migration() {
+        let saves = SaveGames::new(3).with_migration(1, mark);
+        let error = migrate(&saves, "save-version 1\nscene").unwrap_err();
+        assert!(matches!(error, SaveError::NoMigration { from: 2 }));
+    }
+}
@@ -0,0 +1,161 @@
+//! Tweak system scheduling from a kdl file.
+//!
+//! The file is a single [`ScheduleConfig`] node, deserialized with
+//! `bevy_kdl_reflect_deser`:
+//!
+//! ```kdl
+//! ScheduleConfig {
+//!   enabled debug_draw=false enemy_ai=true
+//!   values spawn_rate=0.5
+//!   fixed_timesteps physics=0.016
+//! }
+//! ```
+//!
+//! All three fields must be declared, but they can be left empty. Systems opt
+//! into the config with the [`flag`] and [`fixed_timestep`] run criteria. The
+//! file is re-read whenever it is modified.
+use std::{fs, time::SystemTime};
+
+use bevy::{asset::FileAssetIo, ecs::schedule::ShouldRun, prelude::*, utils::HashMap};
+
+use crate::{config::SceneConfig, read::read_value};
+
+/// Schedule tweaks read from a kdl file.
+#[derive(Resource, Reflect, FromReflect, Default, Debug, Clone, PartialEq)]
+pub struct ScheduleConfig {
+    /// Whether a system gated by [`flag`] should run. Missing flags run.
+    pub enabled: HashMap<String, bool>,
+    /// Arbitrary named values, for systems to read directly.
+    pub values: HashMap<String, f64>,
+    /// Duration in seconds of named [`fixed_timestep`]s.
+    pub fixed_timesteps: HashMap<String, f64>,
+}
+impl ScheduleConfig {
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.get(name).copied().unwrap_or(true)
+    }
+    pub fn value(&self, name: &str) -> Option<f64> {
+        self.values.get(name).copied()
+    }
+    pub fn timestep(&self, name: &str) -> Option<f64> {
+        self.fixed_timesteps.get(name).copied()
+    }
+    /// Remove the timesteps that are not positive, they would run their
+    /// systems forever, returns their names.
+    fn remove_invalid_timesteps(&mut self) -> Vec<String> {
+        let mut invalid = Vec::new();
+        self.fixed_timesteps.retain(|name, step| {
+            let valid = *step > 0.0;
+            if !valid {
+                invalid.push(name.clone());
+            }
+            valid
+        });
+        invalid
+    }
+}
+
+/// Run criteria running the system only if `name` is not disabled in the
+/// [`ScheduleConfig`].
+pub fn flag(name: &'static str) -> impl FnMut(Res<ScheduleConfig>) -> ShouldRun {
+    move |config: Res<ScheduleConfig>| config.is_enabled(name).into()
+}
+
+/// Run criteria running the system once every `fixed_timesteps.name` seconds,
+/// using `default` if the value is not specified in the [`ScheduleConfig`].
+///
+/// Similarly to bevy's `FixedTimestep`, this may run the system several times
+/// per frame to catch up. The system never runs if `default` is not positive
+/// and the config doesn't specify a value.
+pub fn fixed_timestep(
+    name: &'static str,
+    default: f64,
+) -> impl FnMut(Local<(f64, bool)>, Res<Time>, Res<ScheduleConfig>) -> ShouldRun {
+    move |mut state: Local<(f64, bool)>, time: Res<Time>, config: Res<ScheduleConfig>| {
+        let step = config.timestep(name).unwrap_or(default);
+        if step.is_nan() || step <= 0.0 {
+            return ShouldRun::No;
+        }
+        let (accumulator, looping) = &mut *state;
+        // ShouldRun::YesAndCheckAgain re-runs the criteria within the same
+        // frame, we must only accumulate on the first check of a frame.
+        if !*looping {
+            *accumulator += time.delta_seconds_f64();
+        }
+        *looping = *accumulator >= step;
+        if *looping {
+            *accumulator -= step;
+            ShouldRun::YesAndCheckAgain
+        } else {
+            ShouldRun::No
+        }
+    }
+}
+
+/// Where to read the [`ScheduleConfig`] from.
+#[derive(Resource)]
+struct ScheduleFile {
+    /// Path relative to the asset folder.
+    path: String,
+    /// The modification time of the file when last read, `Some(None)` if
+    /// it was missing, `None` before the first read.
+    last_read: Option<Option<SystemTime>>,
+}
+
+fn reload_config(
+    asset_server: Res<AssetServer>,
+    registry: Res<AppTypeRegistry>,
+    mut file: ResMut<ScheduleFile>,
+    mut config: ResMut<ScheduleConfig>,
+    scene_config: Res<SceneConfig>,
+) {
+    // TODO(COMPAT): wasm support
+    let asset_io: &FileAssetIo = asset_server.asset_io().downcast_ref().unwrap();
+    let path = asset_io.root_path().join(&file.path);
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    // A missing file is only reported once, until it is created
+    if file.last_read == Some(modified) {
+        return;
+    }
+    file.last_read = Some(modified);
+    match read_value::<ScheduleConfig>(&path, &registry.read(), &scene_config) {
+        Ok(mut new_config) => {
+            for name in new_config.remove_invalid_timesteps() {
+                scene_config.error(format_args!(
+                    "Invalid schedule config {path:?}: timestep {name} must be positive"
+                ));
+            }
+            *config = new_config;
+        }
+        Err(err) => scene_config.error(format_args!("Invalid schedule config {path:?}: {err}")),
+    }
+}
+
+#[derive(SystemLabel)]
+pub enum Systems {
+    ReloadConfig,
+}
+
+/// Load and hot-reload the [`ScheduleConfig`] from `path` in the asset folder.
+pub struct Plug {
+    pub path: String,
+}
+impl Plug {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+impl Plugin for Plug {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ScheduleConfig>()
+            .register_type::<HashMap<String, bool>>()
+            .register_type::<HashMap<String, f64>>()
+            .register_type::<String>()
+            .register_type::<bool>()
+            .register_type::<f64>()
+            .init_resource::<ScheduleConfig>()
+            .init_resource::<SceneConfig>()
+            .insert_resource(ScheduleFile { path: self.path.clone(), last_read: None })
+            .add_system_to_stage(CoreStage::First, reload_config.label(Systems::ReloadConfig));
+    }
+}
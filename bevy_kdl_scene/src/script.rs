@@ -0,0 +1,251 @@
+//! Commands declared in scene files, run on the world once the scene is
+//! spawned.
+//!
+//! The app allows each command by name, other nodes are converted as usual.
+//! Commands are declared among the entities of the scene:
+//!
+//! ```kdl
+//! scene {
+//!   insert-resource { Gravity 9.8; }
+//!   set-state "MainMenu"
+//!   spawn-from "menu/background.kdl"
+//!   entity "title" { bundle { Title; }; }
+//! }
+//! ```
+//!
+//! Only the file spawned as a scene runs commands, the files it imports don't.
+//! Commands run once per spawned scene, hot reloading the scene doesn't run
+//! them again.
+//!
+//! `SceneScripts` provides the `insert-resource`, `set-state` and
+//! `spawn-from` commands, allow them with [`AllowSceneCommand`].
+use std::{error::Error, str::FromStr};
+
+use bevy::{ecs::schedule::StateData, prelude::*, utils::HashMap};
+use bevy_kdl_reflect_deser::{from_doc_untyped, ConvertResult};
+use kdl::{KdlDocument, KdlNode};
+
+use crate::{config::SceneConfig, KdlScene};
+
+/// Run the command declared by `node`, in the scene spawned as children of
+/// the `scene` entity.
+pub type ScriptCommand = fn(&KdlNode, Entity, &mut World) -> Result<(), Box<dyn Error>>;
+
+/// Marks scene entities which already ran their commands.
+#[derive(Component)]
+struct RanScript;
+
+/// The [`ScriptCommand`]s scene files are allowed to run, per node name.
+#[derive(Resource, Default, Clone)]
+pub struct SceneScripts {
+    commands: HashMap<String, ScriptCommand>,
+}
+impl SceneScripts {
+    /// Run the nodes named `name` with `command`, returns the command
+    /// previously allowed as `name`.
+    pub fn allow(
+        &mut self,
+        name: impl Into<String>,
+        command: ScriptCommand,
+    ) -> Option<ScriptCommand> {
+        self.commands.insert(name.into(), command)
+    }
+    /// Remove the commands of `document` from its scene, the children of its
+    /// last node.
+    pub(crate) fn take_commands(&self, document: &mut KdlDocument) -> Vec<KdlNode> {
+        let scene = document.nodes_mut().last_mut();
+        let Some(nodes) = scene.and_then(|s| s.children_mut()).map(|c| c.nodes_mut()) else {
+            return Vec::new();
+        };
+        let is_command = |node: &KdlNode| self.commands.contains_key(node.name().value());
+        let commands = nodes.iter().filter(|n| is_command(n)).cloned().collect();
+        nodes.retain(|node| !is_command(node));
+        commands
+    }
+    /// Run `script` on `world`, for the scene spawned as children of `scene`.
+    ///
+    /// Commands failing are skipped with a warning. Nothing runs if `scene`
+    /// already ran its commands, such as when it is hot reloaded.
+    pub(crate) fn run(
+        &self,
+        script: &[KdlNode],
+        scene: Entity,
+        world: &mut World,
+        config: &SceneConfig,
+    ) {
+        match world.get_entity_mut(scene) {
+            Some(entity) if entity.contains::<RanScript>() => return,
+            Some(mut entity) => {
+                entity.insert(RanScript);
+            }
+            None => {}
+        }
+        for node in script {
+            let name = node.name().value();
+            // unwrap: `take_commands` only keeps nodes with a command
+            let command = self.commands.get(name).unwrap();
+            if let Err(error) = command(node, scene, world) {
+                config.warn(format_args!("Scene command `{name}` failed: {error}"));
+            }
+        }
+    }
+
+    /// Insert the resources declared in the children of `node`, they must
+    /// register `ReflectResource`.
+    pub fn insert_resource(
+        node: &KdlNode,
+        _: Entity,
+        world: &mut World,
+    ) -> Result<(), Box<dyn Error>> {
+        let registry = world.resource::<AppTypeRegistry>().clone();
+        let registry = registry.read();
+        let resources = node.children().map_or(&[][..], |c| c.nodes());
+        for resource in resources {
+            let mut document = KdlDocument::new();
+            document.nodes_mut().push(resource.clone());
+            let options = Default::default();
+            let value = match from_doc_untyped(document, Default::default(), &registry, &options) {
+                ConvertResult::Deserialized(value) | ConvertResult::Warned(value, _) => value,
+                ConvertResult::Exports(_) => return Err("resources can't be exports".into()),
+                ConvertResult::Errors(errors) => return Err(errors.show_for().into()),
+            };
+            let name = value.type_name();
+            let reflect_resource = registry
+                .get_with_name(name)
+                .and_then(|r| r.data::<ReflectResource>())
+                .ok_or_else(|| format!("{name} doesn't register `ReflectResource`"))?;
+            reflect_resource.insert(world, value.as_ref());
+        }
+        Ok(())
+    }
+    /// Spawn the scene file named by the first argument of `node` as a child
+    /// of `scene`.
+    pub fn spawn_from(
+        node: &KdlNode,
+        scene: Entity,
+        world: &mut World,
+    ) -> Result<(), Box<dyn Error>> {
+        let file = node.get(0).and_then(|file| file.value().as_string());
+        let file = file.ok_or("the first argument must be the file to spawn")?;
+        let child = world
+            .spawn(KdlScene { file: file.to_owned(), source: None, root: None })
+            .id();
+        world.entity_mut(scene).push_children(&[child]);
+        Ok(())
+    }
+    /// Set the `S` state to the first argument of `node`, parsed with
+    /// `S::from_str`. Nothing changes if `S` already is in that state.
+    pub fn set_state<S: StateData + FromStr>(
+        node: &KdlNode,
+        _: Entity,
+        world: &mut World,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = node.get(0).and_then(|state| state.value().as_string());
+        let name = name.ok_or("the first argument must be the state to set")?;
+        let state = S::from_str(name).map_err(|_| format!("{name} is not a state"))?;
+        let mut current = world
+            .get_resource_mut::<State<S>>()
+            .ok_or("the state is not added to the app")?;
+        if *current.current() != state {
+            current.set(state)?;
+        }
+        Ok(())
+    }
+}
+
+/// Allow scene files to run commands.
+pub trait AllowSceneCommand {
+    /// Run nodes named `name` at the root of scenes with `command`.
+    ///
+    /// ```ignore
+    /// app.allow_scene_command("set-state", SceneScripts::set_state::<AppState>)
+    ///     .allow_scene_command("spawn-from", SceneScripts::spawn_from);
+    /// ```
+    fn allow_scene_command(&mut self, name: &str, command: ScriptCommand) -> &mut Self;
+}
+impl AllowSceneCommand for App {
+    fn allow_scene_command(&mut self, name: &str, command: ScriptCommand) -> &mut Self {
+        let mut scripts = self
+            .world
+            .get_resource_or_insert_with(SceneScripts::default);
+        scripts.allow(name, command);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    enum AppState {
+        Menu,
+        Game,
+    }
+    impl FromStr for AppState {
+        type Err = ();
+        fn from_str(s: &str) -> Result<Self, ()> {
+            match s {
+                "Menu" => Ok(AppState::Menu),
+                "Game" => Ok(AppState::Game),
+                _ => Err(()),
+            }
+        }
+    }
+    #[derive(Resource, Default)]
+    struct Count(u32);
+
+    fn count(_: &KdlNode, _: Entity, world: &mut World) -> Result<(), Box<dyn Error>> {
+        world.resource_mut::<Count>().0 += 1;
+        Ok(())
+    }
+    fn scripts() -> SceneScripts {
+        let mut scripts = SceneScripts::default();
+        scripts.allow("count", count);
+        scripts.allow("set-state", SceneScripts::set_state::<AppState>);
+        scripts
+    }
+    fn node(text: &str) -> KdlNode {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn commands_taken_from_scene() {
+        let text = "count { Hp 1; }\nscene { count; entity { bundle { count; }; }; other; }";
+        let mut document: KdlDocument = text.parse().unwrap();
+        let script = scripts().take_commands(&mut document);
+        let names: Vec<_> = script.iter().map(|n| n.name().value()).collect();
+        assert_eq!(names, ["count"]);
+        let expected = "count { Hp 1; }\nscene { entity { bundle { count; }; }; other; }";
+        assert_eq!(document.to_string(), expected);
+    }
+
+    #[test]
+    fn commands_run_once() {
+        let mut world = World::new();
+        world.init_resource::<Count>();
+        let scene = world.spawn_empty().id();
+        let script = [node("count"), node("count")];
+        let config = SceneConfig::default();
+        scripts().run(&script, scene, &mut world, &config);
+        // Hot reloading spawns the scene again on the same entity
+        scripts().run(&script, scene, &mut world, &config);
+        assert_eq!(world.resource::<Count>().0, 2);
+
+        let other = world.spawn_empty().id();
+        scripts().run(&script, other, &mut world, &config);
+        assert_eq!(world.resource::<Count>().0, 4);
+    }
+
+    #[test]
+    fn set_current_state() {
+        let mut world = World::new();
+        world.insert_resource(State::new(AppState::Menu));
+        let scene = world.spawn_empty().id();
+        let set_state = SceneScripts::set_state::<AppState>;
+        assert!(set_state(&node("set-state \"Menu\""), scene, &mut world).is_ok());
+        assert!(set_state(&node("set-state \"Game\""), scene, &mut world).is_ok());
+        assert!(set_state(&node("set-state \"Pause\""), scene, &mut world).is_err());
+        assert!(set_state(&node("set-state"), scene, &mut world).is_err());
+    }
+}
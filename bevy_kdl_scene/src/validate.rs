@@ -0,0 +1,177 @@
+//! Detect systems mutating authored data.
+//!
+//! Scene files are converted again, and the declared component values are
+//! compared with the components of the entities spawned from them. Components
+//! that differ are logged as warnings. This is useful to catch systems that
+//! unexpectedly overwrite values tweaked while hot-reloading.
+//!
+//! Validation runs periodically when the plugin is created with a period, and
+//! whenever a [`ValidateScenes`] event is sent.
+use std::time::Duration;
+
+use bevy::{
+    ecs::{schedule::ShouldRun, system::SystemState},
+    prelude::*,
+    reflect::TypeRegistryInternal,
+};
+
+use crate::{
+    config::SceneConfig,
+    depends::{convert_file, DeserEntity, KdlInstance, KdlInstances, LoadState, SceneLoader},
+    load::KdlOrigin,
+    names::ScenePath,
+    KdlScene, VirtualFiles,
+};
+
+/// Send this event to validate all spawned scenes at the end of the frame.
+pub struct ValidateScenes;
+
+#[derive(Resource)]
+struct ValidateTimer(Timer);
+
+/// Whether to validate scenes this frame.
+fn should_validate(
+    mut requests: EventReader<ValidateScenes>,
+    timer: Option<ResMut<ValidateTimer>>,
+    time: Res<Time>,
+) -> ShouldRun {
+    let requested = requests.iter().count() != 0;
+    let elapsed = timer.map_or(false, |mut timer| {
+        timer.0.tick(time.delta()).just_finished()
+    });
+    if requested || elapsed {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Convert again the source of each spawned scene.
+fn reconvert(world: &mut World) -> Vec<(String, Entity, DeserEntity)> {
+    let mut state: SystemState<(
+        SceneLoader,
+        ResMut<KdlInstances>,
+        Res<VirtualFiles>,
+        Query<(Entity, &KdlScene), With<KdlInstance>>,
+    )> = SystemState::new(world);
+    let (loader, mut instances, virtual_files, scenes) = state.get_mut(world);
+    let sources = loader.sources(&virtual_files);
+    let registry = loader.registry().read();
+    let context = loader.context(&registry);
+    let config = &loader.config;
+    let mut declared = Vec::new();
+    for (entity, scene) in &scenes {
+        let file = &scene.file;
+        let (path, root) = scene.path_and_root();
+        let converted = convert_file(&sources, &context, path, root, &mut instances);
+        match converted {
+            Ok((LoadState::SceneReady(scene, _), _)) => {
+                declared.push((file.clone(), entity, scene));
+            }
+            Ok((LoadState::Failed(err), _)) | Err(err) => {
+                config.warn(format_args!("Couldn't validate {file}: {err}"));
+            }
+            Ok((LoadState::ExportsReady(_), _)) => {}
+        }
+    }
+    declared
+}
+
+/// The children of `entity` spawned from the scene `file`, in spawn order.
+fn spawned_children<'w>(
+    world: &'w World,
+    entity: Entity,
+    file: &'w str,
+) -> impl Iterator<Item = Entity> + 'w {
+    let children = world.get::<Children>(entity).map_or(&[][..], |c| &**c);
+    children.iter().copied().filter(move |child| {
+        let origin = world.get::<KdlOrigin>(*child);
+        origin.map_or(false, |origin| origin.file == file)
+    })
+}
+
+/// Log components of `live` and its descendants that differ from `declared`.
+fn log_drift(
+    world: &World,
+    registry: &TypeRegistryInternal,
+    config: &SceneConfig,
+    file: &str,
+    declared: &DeserEntity,
+    live: Entity,
+    path: &ScenePath,
+) {
+    for component in &declared.components {
+        let type_name = component.type_name();
+        let registration = registry.get_with_name(type_name);
+        let reflect_component = registration.and_then(|r| r.data::<ReflectComponent>());
+        let Some(reflect_component) = reflect_component else {
+            continue;
+        };
+        match reflect_component.reflect(world, live) {
+            None => config.warn(format_args!(
+                "{file}: {path} lost its `{type_name}` component"
+            )),
+            Some(value) if value.reflect_partial_eq(component.0.as_ref()) == Some(false) => {
+                let declared = component.0.as_ref();
+                config.warn(format_args!(
+                    "{file}: {path} `{type_name}` is {value:?}, but declared as {declared:?}"
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    let children: Vec<_> = spawned_children(world, live, file).collect();
+    if children.len() != declared.children.len() {
+        let (spawned, declared) = (children.len(), declared.children.len());
+        config.warn(format_args!(
+            "{file}: {path} has {spawned} children, but {declared} are declared"
+        ));
+    }
+    let paths = path.children(&declared.children);
+    for ((child, live), path) in declared.children.iter().zip(children).zip(paths) {
+        log_drift(world, registry, config, file, child, live, &path);
+    }
+}
+
+fn validate_scenes(world: &mut World) {
+    let declared = reconvert(world);
+    let registry = world.resource::<AppTypeRegistry>().read();
+    let config = world.resource::<SceneConfig>();
+    for (file, instance, scene) in &declared {
+        let Some(root) = spawned_children(world, *instance, file).next() else {
+            config.warn(format_args!(
+                "{file}: the scene of {instance:?} is not spawned"
+            ));
+            continue;
+        };
+        let path = ScenePath::root(scene);
+        log_drift(world, &registry, config, file, scene, root, &path);
+    }
+}
+
+/// Log drift between scene files and the entities spawned from them.
+///
+/// Reading all scene files is slow, this is intended for development only.
+#[derive(Default)]
+pub struct Plug {
+    period: Option<Duration>,
+}
+impl Plug {
+    /// Validate scenes every `period`, in addition to when a
+    /// [`ValidateScenes`] event is sent.
+    pub fn every(period: Duration) -> Self {
+        Plug { period: Some(period) }
+    }
+}
+impl Plugin for Plug {
+    fn build(&self, app: &mut App) {
+        if let Some(period) = self.period {
+            let timer = Timer::new(period, TimerMode::Repeating);
+            app.insert_resource(ValidateTimer(timer));
+        }
+        app.add_event::<ValidateScenes>().add_system_to_stage(
+            CoreStage::Last,
+            validate_scenes.with_run_criteria(should_validate),
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Declare bevy scenes and UIs in kdl files, with templates.
+//!
+//! This crate re-exports the crates of the workspace under a single name,
+//! their features are enabled through the features of this crate. Most apps
+//! only need the [`prelude`]:
+//!
+//! ```ignore
+//! use bevy::prelude::*;
+//! use bevy_kdl_ui::prelude::*;
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugin(Plug::default())
+//!         .add_startup_system(|mut cmds: Commands| {
+//!             cmds.spawn(KdlScene { file: "menu.kdl".to_owned(), source: None, root: None });
+//!         })
+//!         .run();
+//! }
+//! ```
+pub use bevy_kdl_reflect_deser as reflect_deser;
+pub use bevy_kdl_scene as scene;
+pub use template_kdl;
+
+/// The types and traits to load scenes and convert kdl documents.
+pub mod prelude {
+    pub use bevy_kdl_reflect_deser::{
+        from_doc, from_text, ConvertErrors, ConvertResult, DeserOptions,
+    };
+    pub use bevy_kdl_scene::{
+        AddComponentBuilder, AddSpawnHook, AllowSceneCommand, EnableSceneFeature, KdlOrigin,
+        KdlScene, Plug, RegisterTemplateLibrary, VirtualFiles,
+    };
+}
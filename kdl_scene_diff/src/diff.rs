@@ -0,0 +1,355 @@
+//! Compare scenes by entity, component and field rather than by line.
+//!
+//! Entities are matched with the entity of the same name in the other scene,
+//! or the one at the same position among unnamed siblings. Components are
+//! matched by type name, and their fields by name or position.
+//!
+//! With the [`RegistrySchema`] of the app, components are matched by type
+//! whether they are written with their full or short name, and the arguments
+//! of struct components are the fields they set.
+use std::{collections::HashMap, fmt};
+
+use bevy_kdl_reflect_deser::{RegistrySchema, TypeKind, TypeSchema};
+use kdl::{KdlDocument, KdlEntry, KdlNode};
+
+/// How an item is matched with its counterpart in the other scene.
+#[derive(Clone, PartialEq, Eq)]
+enum Key {
+    /// The `n`th item with this name, `0` being the first.
+    Named(String, usize),
+    /// The `n`th item without a name.
+    Nth(usize),
+}
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Named(name, 0) => write!(f, "{name}"),
+            Key::Named(name, n) => write!(f, "{name}[{n}]"),
+            Key::Nth(n) => write!(f, "#{n}"),
+        }
+    }
+}
+
+fn keyed<T>(items: impl IntoIterator<Item = (Option<String>, T)>) -> Vec<(Key, T)> {
+    let mut unnamed = 0;
+    let mut named = HashMap::<String, usize>::new();
+    let mut key = |name: Option<String>| match name {
+        None => {
+            unnamed += 1;
+            Key::Nth(unnamed - 1)
+        }
+        Some(name) => {
+            let count = named.entry(name.clone()).or_default();
+            *count += 1;
+            Key::Named(name, *count - 1)
+        }
+    };
+    items
+        .into_iter()
+        .map(|(name, item)| (key(name), item))
+        .collect()
+}
+
+/// Pair items with the same key, in `old` order, followed by the ones only
+/// in `new`.
+fn pair<T>(old: Vec<(Key, T)>, new: Vec<(Key, T)>) -> Vec<(Key, Option<T>, Option<T>)> {
+    let mut new: Vec<_> = new.into_iter().map(Some).collect();
+    let mut pairs: Vec<_> = old
+        .into_iter()
+        .map(|(key, old)| {
+            let same_key = new
+                .iter_mut()
+                .find(|n| matches!(n, Some((k, _)) if *k == key));
+            let new = same_key.and_then(Option::take).map(|(_, new)| new);
+            (key, Some(old), new)
+        })
+        .collect();
+    pairs.extend(
+        new.into_iter()
+            .flatten()
+            .map(|(key, new)| (key, None, Some(new))),
+    );
+    pairs
+}
+
+fn nodes(node: &KdlNode) -> &[KdlNode] {
+    node.children().map_or(&[], KdlDocument::nodes)
+}
+
+/// The type of `component` in `schema`, by its full or short name.
+fn component_type<'s>(
+    schema: Option<&'s RegistrySchema>,
+    component: &KdlNode,
+) -> Option<&'s TypeSchema> {
+    let (schema, name) = (schema?, component.name().value());
+    schema
+        .get_with_name(name)
+        .or_else(|| schema.get_with_short_name(name))
+}
+/// The name `component` is matched by: the short name of its type if no
+/// other type has it, its full name otherwise, and the node name without
+/// schema.
+fn component_name(schema: Option<&RegistrySchema>, component: &KdlNode) -> String {
+    let Some(ty) = component_type(schema, component) else {
+        return component.name().value().to_owned();
+    };
+    let is_unique = |schema: &RegistrySchema| schema.get_with_short_name(&ty.short_name).is_some();
+    match schema.map_or(false, is_unique) {
+        true => ty.short_name.clone(),
+        false => ty.name.clone(),
+    }
+}
+
+/// An entity of an expanded scene.
+struct Entity<'a> {
+    /// The argument or `name` property of the `entity` node, used to refer
+    /// to it.
+    refer_by: Option<String>,
+    components: &'a [KdlNode],
+    children: Vec<Entity<'a>>,
+    schema: Option<&'a RegistrySchema>,
+}
+impl<'a> Entity<'a> {
+    fn new(node: &'a KdlNode, schema: Option<&'a RegistrySchema>) -> Self {
+        // Named like bevy_kdl_scene names entities: strings as is
+        let name = |e: &KdlEntry| {
+            e.value()
+                .as_string()
+                .map_or_else(|| e.value().to_string(), str::to_owned)
+        };
+        let entries = node.entries();
+        let argument = entries.iter().find(|e| e.name().is_none());
+        let property = entries
+            .iter()
+            .find(|e| e.name().map(|n| n.value()) == Some("name"));
+        let refer_by = argument.or(property).map(name);
+        let (components, children) = match nodes(node) {
+            [bundle, children @ ..] if bundle.name().value() == "bundle" => {
+                (nodes(bundle), children)
+            }
+            children => (&[][..], children),
+        };
+        let children = children.iter().map(|c| Entity::new(c, schema)).collect();
+        Entity { refer_by, components, children, schema }
+    }
+    fn components(&self) -> Vec<(Key, &'a KdlNode)> {
+        let name = |c: &'a KdlNode| (Some(component_name(self.schema, c)), c);
+        keyed(self.components.iter().map(name))
+    }
+    fn children(&self) -> Vec<(Key, &Self)> {
+        keyed(self.children.iter().map(|c| (c.refer_by.clone(), c)))
+    }
+    fn component_names(&self) -> String {
+        let names: Vec<_> = self
+            .components()
+            .iter()
+            .map(|(k, _)| k.to_string())
+            .collect();
+        names.join(", ")
+    }
+}
+
+/// The fields of `component`. When its type `ty` is a struct, arguments are
+/// named after its fields in declaration order, like the deserializer reads
+/// them.
+fn fields(component: &KdlNode, ty: Option<&TypeSchema>) -> Vec<(Key, String)> {
+    let struct_fields = match ty {
+        Some(ty) if ty.kind == TypeKind::Struct => &ty.fields[..],
+        _ => &[][..],
+    };
+    let mut arguments = struct_fields.iter().map(|field| field.name.clone());
+    let entries = component.entries().iter().map(|entry| {
+        let name = match entry.name() {
+            Some(name) => Some(name.value().to_owned()),
+            None => arguments.next().flatten(),
+        };
+        (name, entry.value().to_string())
+    });
+    let children = nodes(component).iter().map(|node| {
+        let name = Some(node.name().value().to_owned());
+        (name, node.to_string().trim().to_owned())
+    });
+    keyed(entries.chain(children))
+}
+
+/// A difference between two scenes.
+pub(crate) enum Change {
+    AddedEntity {
+        entity: String,
+        components: String,
+    },
+    RemovedEntity {
+        entity: String,
+        components: String,
+    },
+    AddedComponent {
+        entity: String,
+        component: String,
+    },
+    RemovedComponent {
+        entity: String,
+        component: String,
+    },
+    ChangedField {
+        entity: String,
+        component: String,
+        field: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Change::*;
+        let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "(none)".to_owned());
+        match self {
+            AddedEntity { entity, components } => write!(f, "+ {entity} ({components})"),
+            RemovedEntity { entity, components } => write!(f, "- {entity} ({components})"),
+            AddedComponent { entity, component } => write!(f, "+ {entity} {component}"),
+            RemovedComponent { entity, component } => write!(f, "- {entity} {component}"),
+            ChangedField { entity, component, field, old, new } => {
+                let (old, new) = (or_none(old), or_none(new));
+                write!(f, "~ {entity} {component}.{field}: {old} -> {new}")
+            }
+        }
+    }
+}
+
+fn diff_component(
+    schema: Option<&RegistrySchema>,
+    entity: &str,
+    component: String,
+    old: &KdlNode,
+    new: &KdlNode,
+) -> Vec<Change> {
+    let (old_type, new_type) = (component_type(schema, old), component_type(schema, new));
+    pair(fields(old, old_type), fields(new, new_type))
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| Change::ChangedField {
+            entity: entity.to_owned(),
+            component: component.clone(),
+            field: field.to_string(),
+            old,
+            new,
+        })
+        .collect()
+}
+
+fn diff_entity(path: &str, old: &Entity, new: &Entity, changes: &mut Vec<Change>) {
+    for (key, old_c, new_c) in pair(old.components(), new.components()) {
+        let (entity, component) = (path.to_owned(), key.to_string());
+        match (old_c, new_c) {
+            (Some(old_c), Some(new_c)) => {
+                let fields = diff_component(old.schema, path, component, old_c, new_c);
+                changes.extend(fields);
+            }
+            (Some(_), None) => changes.push(Change::RemovedComponent { entity, component }),
+            (None, _) => changes.push(Change::AddedComponent { entity, component }),
+        }
+    }
+    for (key, old, new) in pair(old.children(), new.children()) {
+        let entity = format!("{path}/{key}");
+        match (old, new) {
+            (Some(old), Some(new)) => diff_entity(&entity, old, new, changes),
+            (Some(old), None) => {
+                let components = old.component_names();
+                changes.push(Change::RemovedEntity { entity, components });
+            }
+            (None, new) => {
+                // unwrap: `pair` never returns both sides as `None`
+                let components = new.unwrap().component_names();
+                changes.push(Change::AddedEntity { entity, components });
+            }
+        }
+    }
+}
+
+/// The differences between the `old` and `new` expanded scenes, reading
+/// their components with `schema` if any.
+pub(crate) fn scene_diff(
+    old: &KdlNode,
+    new: &KdlNode,
+    schema: Option<&RegistrySchema>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let (old, path) = (Entity::new(old, schema), old.name().value().to_owned());
+    diff_entity(&path, &old, &Entity::new(new, schema), &mut changes);
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use bevy_kdl_reflect_deser::FieldSchema;
+
+    use super::*;
+
+    fn diff(old: &str, new: &str, schema: Option<&RegistrySchema>) -> Vec<String> {
+        let (old, new): (KdlNode, KdlNode) = (old.parse().unwrap(), new.parse().unwrap());
+        let changes = scene_diff(&old, &new, schema);
+        changes.iter().map(Change::to_string).collect()
+    }
+    fn hp_schema() -> RegistrySchema {
+        let field = |name: &str| FieldSchema {
+            name: Some(name.to_owned()),
+            type_name: "u32".to_owned(),
+            docs: None,
+        };
+        RegistrySchema::new(vec![TypeSchema {
+            name: "game::Hp".to_owned(),
+            short_name: "Hp".to_owned(),
+            kind: TypeKind::Struct,
+            docs: None,
+            fields: vec![field("value"), field("max")],
+            variants: Vec::new(),
+        }])
+    }
+
+    const SCENE: &str = r#"entity {
+        bundle { Transform x=1.0; }
+        entity "player" { bundle { Player; Shield; }; }
+        entity { bundle { Hp 5; }; }
+        entity { bundle { Hp 3; }; }
+    }"#;
+
+    #[test]
+    fn reordered_is_same() {
+        let reordered = r#"entity {
+            bundle { Transform x=1.0; }
+            entity { bundle { Hp 5; }; }
+            entity { bundle { Hp 3; }; }
+            entity name="player" { bundle { Shield; Player; }; }
+        }"#;
+        assert!(diff(SCENE, reordered, None).is_empty());
+    }
+    #[test]
+    fn changes() {
+        let changed = r#"entity {
+            bundle { Transform x=1.5; }
+            entity "player" { bundle { Player; }; }
+            entity { bundle { Hp 7; Enemy; }; }
+            entity "boss" { bundle { Enemy; Hp 50; }; }
+        }"#;
+        let expected = [
+            "~ entity Transform.x: 1.0 -> 1.5",
+            "- entity/player Shield",
+            "~ entity/#0 Hp.#0: 5 -> 7",
+            "+ entity/#0 Enemy",
+            "- entity/#1 (Hp)",
+            "+ entity/boss (Enemy, Hp)",
+        ];
+        assert_eq!(diff(SCENE, changed, None), expected);
+    }
+    #[test]
+    fn with_schema() {
+        let old = "entity { bundle { Hp 5 10; }; }";
+        let new = "entity { bundle { game::Hp max=10 value=5; }; }";
+        let schema = hp_schema();
+        assert!(diff(old, new, Some(&schema)).is_empty());
+        assert_eq!(diff(old, new, None).len(), 2);
+
+        let new = "entity { bundle { Hp 6 max=10; }; }";
+        let expected = ["~ entity Hp.value: 5 -> 6"];
+        assert_eq!(diff(old, new, Some(&schema)), expected);
+    }
+}
@@ -0,0 +1,75 @@
+//! Read scene files and expand their templates.
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use kdl::{KdlDocument, KdlNode};
+use template_kdl::{
+    err::Error, graph::ImportGraph, multi_err::MultiResult, Document, ExportedBindingsList,
+};
+
+/// Exports of the files already read, by path relative to the asset folder.
+type Loaded = ExportedBindingsList;
+
+fn show_errors(file: &str, errors: Vec<Error>) -> anyhow::Error {
+    let show = |err: &Error| format!("{file}@{}: {err}", err.span.offset);
+    anyhow!(errors.iter().map(show).collect::<Vec<_>>().join("\n"))
+}
+
+fn read_document(assets: &Path, file: &str, loaded: &mut Loaded) -> Result<Document> {
+    let text = fs::read_to_string(assets.join(file)).with_context(|| format!("reading {file}"))?;
+    let document: KdlDocument = text.parse().with_context(|| format!("parsing {file}"))?;
+    let imports = template_kdl::get_imports(&document).map_err(|e| show_errors(file, vec![e]))?;
+    for dep in imports.required_files() {
+        if loaded.get(dep).is_some() {
+            continue;
+        }
+        match read_document(assets, dep, loaded)?.exports() {
+            Some(exports) => loaded.insert(dep, exports.clone()),
+            None => bail!("{file} imports from {dep}, which doesn't export anything"),
+        };
+    }
+    let required = imports.bindings(loaded).map_err(|_| {
+        let entries = imports.entries().iter();
+        show_errors(file, entries.filter_map(|i| i.missing(loaded)).collect())
+    })?;
+    template_kdl::read_document(document, required)
+        .into_result()
+        .map_err(|errs| show_errors(file, errs))
+}
+
+/// Read the scene at `file`, relative to `assets`, with all templates expanded.
+pub(crate) fn read_scene(assets: &Path, file: &str) -> Result<KdlNode> {
+    match read_document(assets, file, &mut Loaded::new())?.node() {
+        None => bail!("{file} is an `export` file, not a scene"),
+        Some(node) => node
+            .evaluate()
+            .into_result()
+            .map_err(|errs| show_errors(file, errs)),
+    }
+}
+
+/// The import graph of `files` and the files they import, relative to
+/// `assets`.
+///
+/// Files that can't be read are in the graph, without their own imports.
+pub(crate) fn import_graph(assets: &Path, files: &[String]) -> ImportGraph {
+    let read_files = template_kdl::parse_all(assets, files.iter().map(String::as_str));
+    let mut exports = ExportedBindingsList::new();
+    for (file, read_file) in &read_files {
+        if let MultiResult::Ok(document) | MultiResult::OkErr(document, _) = &read_file.document {
+            if let Some(exported) = document.exports() {
+                exports.insert(file.as_str(), exported.clone());
+            }
+        }
+    }
+    let mut read: Vec<_> = read_files.iter().collect();
+    read.sort_unstable_by_key(|(file, _)| *file);
+    let mut graph = ImportGraph::new();
+    for (file, read_file) in read {
+        match &read_file.imports {
+            Some(imports) => graph.add(file, imports, &exports),
+            None => graph.add_file(file),
+        }
+    }
+    graph
+}
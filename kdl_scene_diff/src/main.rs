@@ -0,0 +1,120 @@
+//! Compare two bevy_kdl_scene files after template expansion.
+//!
+//! Prints one line per added or removed entity and component, and per
+//! changed component field. Exits with `1` if the scenes differ, `2` on error.
+//! With `--schema`, components are read with the types of a registry dump.
+//!
+//! With `--write`, formats the given files in place instead. With `--graph`,
+//! prints the import graph of the given files, as DOT or mermaid. With
+//! `--migrate`, applies the rules of a migration file to the given files.
+mod diff;
+mod load;
+
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+use anyhow::{bail, Context};
+use bevy_kdl_reflect_deser::RegistrySchema;
+use kdl::KdlDocument;
+use template_kdl::migrate::Migration;
+
+const USAGE: &str = "usage: kdl-scene-diff [--assets <dir>] [--schema <file>] <old.kdl> <new.kdl>
+       kdl-scene-diff --write <file.kdl>...
+       kdl-scene-diff [--assets <dir>] --graph <dot|mermaid> <file.kdl>...
+       kdl-scene-diff --migrate <rules.kdl> <file.kdl>...";
+
+/// Format `files` in place, printing the name of those that changed.
+fn write_formatted(files: &[String]) -> anyhow::Result<()> {
+    for file in files {
+        let text = fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+        let mut document: KdlDocument = text.parse().with_context(|| format!("parsing {file}"))?;
+        template_kdl::fmt_document(&mut document);
+        let formatted = document.to_string();
+        if formatted != text {
+            fs::write(file, formatted).with_context(|| format!("writing {file}"))?;
+            println!("formatted {file}");
+        }
+    }
+    Ok(())
+}
+
+/// Apply the migration `rules` to `files` in place, printing the name of
+/// those that changed.
+fn migrate(rules: &str, files: &[String]) -> anyhow::Result<()> {
+    let text = fs::read_to_string(rules).with_context(|| format!("reading {rules}"))?;
+    let rules: KdlDocument = text.parse().with_context(|| format!("parsing {rules}"))?;
+    let migration = Migration::from_document(&rules)?;
+    for file in files {
+        let text = fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+        let mut document: KdlDocument = text.parse().with_context(|| format!("parsing {file}"))?;
+        let changes = migration.apply(&mut document);
+        if changes != 0 {
+            fs::write(file, document.to_string()).with_context(|| format!("writing {file}"))?;
+            println!("migrated {file}: {changes} changes");
+        }
+    }
+    Ok(())
+}
+
+/// Whether the scenes differ.
+fn run() -> anyhow::Result<bool> {
+    let mut assets = PathBuf::from(".");
+    let mut files = Vec::new();
+    let mut write = false;
+    let mut graph = None;
+    let mut rules = None;
+    let mut schema = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => assets = args.next().context(USAGE)?.into(),
+            "--write" => write = true,
+            "--graph" => graph = Some(args.next().context(USAGE)?),
+            "--migrate" => rules = Some(args.next().context(USAGE)?),
+            "--schema" => schema = Some(args.next().context(USAGE)?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(false);
+            }
+            _ => files.push(arg),
+        }
+    }
+    if write {
+        write_formatted(&files)?;
+        return Ok(false);
+    }
+    if let Some(rules) = rules {
+        migrate(&rules, &files)?;
+        return Ok(false);
+    }
+    if let Some(format) = graph {
+        let graph = load::import_graph(&assets, &files);
+        match format.as_str() {
+            "dot" => print!("{}", graph.to_dot()),
+            "mermaid" => print!("{}", graph.to_mermaid()),
+            _ => bail!("unknown graph format {format}, expected dot or mermaid"),
+        }
+        return Ok(false);
+    }
+    let [old, new] = &files[..] else { bail!(USAGE) };
+    let read_schema =
+        |file: String| RegistrySchema::load(&file).with_context(|| format!("reading {file}"));
+    let schema = schema.map(read_schema).transpose()?;
+    let old = load::read_scene(&assets, old)?;
+    let new = load::read_scene(&assets, new)?;
+    let changes = diff::scene_diff(&old, &new, schema.as_ref());
+    for change in &changes {
+        println!("{change}");
+    }
+    Ok(!changes.is_empty())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(false) => ExitCode::SUCCESS,
+        Ok(true) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("{err:#}");
+            ExitCode::from(2)
+        }
+    }
+}
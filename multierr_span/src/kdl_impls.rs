@@ -1,3 +1,5 @@
+use std::fmt::{self, Write};
+
 use kdl::{KdlDocument, KdlEntry, KdlIdentifier, KdlNode, KdlValue};
 
 use crate::{Length, Sbor, Sref};
@@ -5,9 +7,12 @@ use crate::{Length, Sbor, Sref};
 // TODO: consider this: a KdlNode has .trailing and .leading stuff, but the actual
 // span of the KdlNode is everything between, not containing the .trailing etc.
 
+/// Identifiers without repr, such as renamed nodes, are quoted when they
+/// aren't valid bare identifiers.
 impl Length for KdlIdentifier {
     fn length(&self) -> u32 {
-        self.repr().map_or(self.value().length(), |s| s.length())
+        self.repr()
+            .map_or_else(|| display_length(self), |s| s.length())
     }
 }
 impl Length for KdlEntry {
@@ -60,28 +65,38 @@ impl Length for KdlNode {
         self.inner_length() + Length::leading(self) + Length::trailing(self)
     }
 }
+/// The length of `value` printed with `Display`, without allocating.
+fn display_length(value: &impl fmt::Display) -> u32 {
+    struct Count(u32);
+    impl Write for Count {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len() as u32;
+            Ok(())
+        }
+    }
+    let mut count = Count(0);
+    // unwrap: `Count` never fails to write
+    write!(count, "{value}").unwrap();
+    count.0
+}
+/// The length of the value as printed when it has no repr, entries use their
+/// `value_repr` when they have one.
 impl Length for KdlValue {
     fn length(&self) -> u32 {
-        let must_escape = ['\n', '\\', '"', '\r', '\t', '\u{08}', '\u{0C}'];
         match self {
-            KdlValue::Base10Float(value) => {
-                let clean = match () {
-                    () if value == &f64::INFINITY => f64::MAX,
-                    () if value == &f64::NEG_INFINITY => -f64::MAX,
-                    () if value.is_nan() => 0.0,
-                    () => *value,
-                };
-                format!("{clean:?}").len() as u32
-            }
             KdlValue::Bool(true) => 4,
             KdlValue::Bool(false) => 5,
             KdlValue::Null => 4,
-            KdlValue::RawString(_) => format!("{self}").len() as u32,
-            KdlValue::String(s) => (s.len() + 2 + s.matches(must_escape).count()) as u32,
-            KdlValue::Base2(value) => 2 + (64 - value.leading_zeros()),
-            KdlValue::Base8(value) => 2 + ((64 - value.leading_zeros()) / 3),
-            KdlValue::Base16(value) => 2 + ((64 - value.leading_zeros()) / 4),
-            KdlValue::Base10(value) => format!("{value:?}").len() as u32,
+            // Strings print with their escapes, numbers with their sign,
+            // prefix and exponent, count them exactly rather than guessing
+            // from the value.
+            KdlValue::String(_)
+            | KdlValue::RawString(_)
+            | KdlValue::Base2(_)
+            | KdlValue::Base8(_)
+            | KdlValue::Base10(_)
+            | KdlValue::Base10Float(_)
+            | KdlValue::Base16(_) => display_length(self),
         }
     }
 }
@@ -135,10 +150,14 @@ macro_rules! method {
             Sbor::new(self.inner.$method(), offset)
         }
     );
-    ($a:lifetime fallback($_:ident) $offsets:tt $method:ident $type:ty ) => (
+    ($a:lifetime fallback($alt:ident) $offsets:tt $method:ident $type:ty ) => (
         pub fn $method(&self) -> Sref<$a, $type> {
             let offset = offset!(self $offsets);
-            Sbor::new(self.inner.$method(), offset)
+            let spanned = Sbor::new(self.inner.$method(), offset);
+            match self.inner.$alt() {
+                Some(declared) => spanned.with_size(declared.length()),
+                None => spanned,
+            }
         }
     );
 }
@@ -177,10 +196,15 @@ macro_rules! mrc_method {
             Sbor::new($rc::map(self.inner.clone(), |t| t.$method()), offset)
         }
     );
-    ($b:ident, $rc:ident, fallback($_:ident) $offsets:tt $method:ident $type:ty ) => (
+    ($b:ident, $rc:ident, fallback($alt:ident) $offsets:tt $method:ident $type:ty ) => (
         pub fn $method(&self) -> $b<$type> {
             let offset = offset!(self $offsets);
-            Sbor::new($rc::map(self.inner.clone(), |t| t.$method()), offset)
+            let declared = self.inner.$alt().map(|t| t.length());
+            let spanned = Sbor::new($rc::map(self.inner.clone(), |t| t.$method()), offset);
+            match declared {
+                Some(size) => spanned.with_size(size),
+                None => spanned,
+            }
         }
     );
     ($_3:ident, $_4:ident, hidden $_1:tt  $_2:ident) => ();
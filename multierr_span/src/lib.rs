@@ -25,16 +25,55 @@ impl Span {
     pub fn pair(&self) -> (usize, usize) {
         (self.offset as usize, self.size as usize)
     }
+    /// The offset right after the last offset of this span.
+    pub fn end(&self) -> u32 {
+        self.offset + self.size
+    }
+    /// The smallest span containing both `self` and `other`.
+    ///
+    /// ```
+    /// use multierr_span::Span;
+    ///
+    /// let first = Span { offset: 2, size: 3 };
+    /// let second = Span { offset: 10, size: 1 };
+    /// assert_eq!(first.merge(second), Span { offset: 2, size: 9 });
+    /// ```
+    pub fn merge(self, other: Span) -> Span {
+        let offset = self.offset.min(other.offset);
+        let end = self.end().max(other.end());
+        Span { offset, size: end - offset }
+    }
+    /// Whether `offset` is within this span.
+    pub fn contains(&self, offset: u32) -> bool {
+        self.offset <= offset && offset < self.end()
+    }
+    /// Whether `self` and `other` share at least one offset.
+    pub fn intersects(&self, other: &Span) -> bool {
+        self.offset < other.end() && other.offset < self.end()
+    }
 }
 
 pub trait Spanned {
     fn span(&self) -> Span;
+    /// The smallest span containing the spans of all `items`, `None` if there
+    /// are no `items`.
+    fn spanning<I: IntoIterator<Item = Self>>(items: I) -> Option<Span>
+    where
+        Self: Sized,
+    {
+        items.into_iter().map(|t| t.span()).reduce(Span::merge)
+    }
 }
 impl Spanned for Span {
     fn span(&self) -> Span {
         *self
     }
 }
+impl<T: Spanned + ?Sized> Spanned for &T {
+    fn span(&self) -> Span {
+        (*self).span()
+    }
+}
 impl<T: Length> Spanned for (T, u32) {
     fn span(&self) -> Span {
         Span {
@@ -118,23 +157,35 @@ pub type Smarc<T> = Sbor<T, mappable_rc::Marc<T>>;
 pub struct Sbor<T: ?Sized, B: Borrow<T>> {
     pub inner: B,
     offset: u32,
+    /// The size of the span, when it isn't the length of `inner`, such as a
+    /// value written differently from how it prints.
+    size: Option<u32>,
     _t: PhantomData<T>,
 }
 impl<T: ?Sized, B: Borrow<T>> Sbor<T, B> {
     pub fn new(inner: B, offset: u32) -> Self {
-        Self { inner, offset, _t: PhantomData }
+        Self { inner, offset, size: None, _t: PhantomData }
+    }
+    /// Span `size` bytes rather than the length of `inner`.
+    pub fn with_size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
     }
     pub fn borrowed(&self) -> Sref<T> {
         Sref {
             inner: self.inner.borrow(),
             offset: self.offset,
+            size: self.size,
             _t: PhantomData,
         }
     }
+    /// Map the inner value, the size of the span is then the length of the
+    /// new inner value.
     pub fn map<U: ?Sized, C: Borrow<U>, F: FnOnce(B) -> C>(self, f: F) -> Sbor<U, C> {
         Sbor {
             inner: f(self.inner),
             offset: self.offset,
+            size: None,
             _t: PhantomData,
         }
     }
@@ -144,6 +195,7 @@ impl<T: Clone, B: Borrow<T>> Sbor<T, B> {
         Sown {
             inner: self.inner.borrow().clone(),
             offset: self.offset,
+            size: self.size,
             _t: PhantomData,
         }
     }
@@ -157,7 +209,9 @@ impl<T: ?Sized, B: Borrow<T>> Deref for Sbor<T, B> {
 impl<T: ?Sized + Length, B: Borrow<T>> Spanned for Sbor<T, B> {
     fn span(&self) -> Span {
         Span {
-            size: self.inner.borrow().inner_length(),
+            size: self
+                .size
+                .unwrap_or_else(|| self.inner.borrow().inner_length()),
             offset: self.offset + self.inner.borrow().leading(),
         }
     }
@@ -0,0 +1,367 @@
+//! Analyze documents being edited, for editor tooling.
+//!
+//! Documents being edited are often incomplete: a string or a children block
+//! isn't closed yet, or a property has no value. [`parse_tolerant`] still
+//! reads what comes before the cursor in this case, and [`parse_recovering`]
+//! what comes before a syntax error.
+//!
+//! Parsing is available without the `templates` feature, completions need it.
+#[cfg(feature = "templates")]
+use std::ptr;
+
+#[cfg(feature = "templates")]
+use kdl::KdlNode;
+use kdl::{KdlDocument, KdlError};
+#[cfg(feature = "templates")]
+use miette::SourceSpan;
+
+#[cfg(feature = "templates")]
+use crate::import::RequiredBindings;
+
+/// Where the scanner is in a document.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    String {
+        escaped: bool,
+    },
+    /// A raw string closed by `"` followed by this many `#`.
+    RawString(usize),
+    LineComment,
+    /// Nested block comments.
+    BlockComment(usize),
+}
+
+/// Track strings, comments and children blocks in a document, to close them.
+struct Scanner {
+    state: State,
+    /// How many children blocks are open.
+    depth: usize,
+    /// Where the last top level node scanned starts, including the
+    /// whitespace before it.
+    node_start: usize,
+}
+impl Scanner {
+    fn new() -> Self {
+        Scanner { state: State::Normal, depth: 0, node_start: 0 }
+    }
+    /// Scan `text`, calling `on_closed` with the offset in `text` of each
+    /// `}` closing a children block.
+    fn scan(&mut self, text: &str, mut on_closed: impl FnMut(&Self, usize)) {
+        let bytes = text.as_bytes();
+        let at = |i: usize| bytes.get(i).copied().unwrap_or(0);
+        let mut i = 0;
+        while i < bytes.len() {
+            let c = bytes[i];
+            self.state = match self.state {
+                State::Normal => match c {
+                    b'"' => State::String { escaped: false },
+                    b'/' if at(i + 1) == b'/' => State::LineComment,
+                    b'/' if at(i + 1) == b'*' => {
+                        i += 1;
+                        State::BlockComment(1)
+                    }
+                    b'r' if !is_identifier(at(i.wrapping_sub(1))) => {
+                        let hashes = bytes[i + 1..].iter().take_while(|c| **c == b'#').count();
+                        if at(i + 1 + hashes) == b'"' {
+                            i += 1 + hashes;
+                            State::RawString(hashes)
+                        } else {
+                            State::Normal
+                        }
+                    }
+                    b'{' => {
+                        self.depth += 1;
+                        State::Normal
+                    }
+                    b'}' => {
+                        self.depth = self.depth.saturating_sub(1);
+                        if self.depth == 0 {
+                            self.node_start = i + 1;
+                        }
+                        on_closed(self, i);
+                        State::Normal
+                    }
+                    b'\n' | b';' if self.depth == 0 => {
+                        self.node_start = i + 1;
+                        State::Normal
+                    }
+                    _ => State::Normal,
+                },
+                State::String { escaped: false } if c == b'"' => State::Normal,
+                State::String { escaped } => State::String { escaped: !escaped && c == b'\\' },
+                State::RawString(hashes) if c == b'"' => {
+                    let closing = bytes[i + 1..].iter().take(hashes);
+                    if closing.filter(|c| **c == b'#').count() == hashes {
+                        i += hashes;
+                        State::Normal
+                    } else {
+                        State::RawString(hashes)
+                    }
+                }
+                State::LineComment if c == b'\n' => {
+                    if self.depth == 0 {
+                        self.node_start = i + 1;
+                    }
+                    State::Normal
+                }
+                State::BlockComment(depth) if c == b'*' && at(i + 1) == b'/' => {
+                    i += 1;
+                    match depth {
+                        1 => State::Normal,
+                        depth => State::BlockComment(depth - 1),
+                    }
+                }
+                State::BlockComment(depth) if c == b'/' && at(i + 1) == b'*' => {
+                    i += 1;
+                    State::BlockComment(depth + 1)
+                }
+                state => state,
+            };
+            i += 1;
+        }
+    }
+    /// The text closing the strings, comments and children blocks open at the
+    /// end of the scanned text.
+    fn closing(&self) -> String {
+        let mut closing = match self.state {
+            State::Normal | State::LineComment => String::new(),
+            State::String { .. } => "\"".to_owned(),
+            State::RawString(hashes) => format!("\"{}", "#".repeat(hashes)),
+            State::BlockComment(depth) => "*/".repeat(depth),
+        };
+        closing.push('\n');
+        closing.push_str(&"}\n".repeat(self.depth));
+        closing
+    }
+}
+fn is_identifier(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"-_.".contains(&c) || c >= 0x80
+}
+
+/// Parse `text`, a document edited at `offset`.
+///
+/// If `text` isn't a valid document, the text after `offset` is dropped and
+/// the strings, comments and children blocks open at `offset` are closed. If
+/// it still isn't valid, the line `offset` is on is dropped as well. Offsets
+/// before `offset` are the same in the parsed document and in `text`.
+///
+/// Returns `None` if no document could be parsed.
+pub fn parse_tolerant(text: &str, offset: usize) -> Option<KdlDocument> {
+    parse_at(text, offset).map(|(document, _)| document)
+}
+/// Like [`parse_tolerant`], also returns whether the top level node at
+/// `offset` is the last one of `text`, even if the text after it was dropped.
+fn parse_at(text: &str, offset: usize) -> Option<(KdlDocument, bool)> {
+    if let Ok(document) = text.parse::<KdlDocument>() {
+        let nodes = document.nodes();
+        let before_cursor = nodes.iter().filter(|n| n.span().offset() < offset);
+        let is_last = before_cursor.count() == nodes.len();
+        return Some((document, is_last));
+    }
+    let offset = (0..=offset.min(text.len()))
+        .rev()
+        .find(|i| text.is_char_boundary(*i))?;
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    [offset, line_start].into_iter().find_map(|end| {
+        let mut scanner = Scanner::new();
+        scanner.scan(&text[..end], |_, _| {});
+        let document = format!("{}{}", &text[..end], scanner.closing());
+        let document: KdlDocument = document.parse().ok()?;
+        // The top level node at `end` ends with its line if `end` isn't in
+        // its children, otherwise with the `}` closing its children.
+        let rest = &text[end..];
+        let mut node_end = None;
+        if scanner.depth == 0 {
+            node_end = Some(rest.find('\n').unwrap_or(rest.len()));
+        }
+        scanner.scan(rest, |scanner, i| {
+            if scanner.depth == 0 && node_end.is_none() {
+                node_end = Some(i + 1);
+            }
+        });
+        let is_blank = |text: &str| {
+            text.trim_matches(|c: char| c == ';' || c.is_whitespace())
+                .is_empty()
+        };
+        let is_last = node_end.map_or(true, |node_end| is_blank(&rest[node_end..]));
+        Some((document, is_last))
+    })
+}
+/// Parse `text`, or recover what comes before its syntax error with
+/// [`parse_tolerant`].
+///
+/// Nothing is recovered if the error isn't in the last top level node of
+/// `text`, or if the whole node is dropped: the node before it would take the
+/// place of the node the document represents.
+pub fn parse_recovering(text: &str) -> Result<KdlDocument, (KdlError, Option<KdlDocument>)> {
+    let error = match text.parse() {
+        Ok(document) => return Ok(document),
+        Err(error) => error,
+    };
+    let offset = error.span.offset();
+    let mut scanner = Scanner::new();
+    scanner.scan(text.get(..offset).unwrap_or_default(), |_, _| {});
+    let keeps_broken = |document: &KdlDocument| match document.nodes().last() {
+        Some(last) => last.span().offset() + last.span().len() > scanner.node_start,
+        None => false,
+    };
+    let recovered =
+        parse_at(text, offset).filter(|(document, is_last)| *is_last && keeps_broken(document));
+    Err((error, recovered.map(|(document, _)| document)))
+}
+
+#[cfg(feature = "templates")]
+/// What can be written at a position of a document.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Completions {
+    /// The templates that can be invoked, in the file or imported.
+    pub bindings: Vec<String>,
+    /// The tparameters of the template the position is in the body of.
+    pub parameters: Vec<String>,
+    /// The tparameters of the template invoked by the node the position is
+    /// in, that the node doesn't pass yet.
+    pub fields: Vec<String>,
+}
+
+#[cfg(feature = "templates")]
+fn children(node: &KdlNode) -> &[KdlNode] {
+    node.children().map_or(&[], KdlDocument::nodes)
+}
+#[cfg(feature = "templates")]
+fn contains(span: &SourceSpan, offset: usize) -> bool {
+    (span.offset()..=span.offset() + span.len()).contains(&offset)
+}
+#[cfg(feature = "templates")]
+/// The nodes containing `offset`, from the top level one to the innermost.
+fn nodes_at(document: &KdlDocument, offset: usize) -> Vec<&KdlNode> {
+    let mut path = Vec::new();
+    let mut nodes = document.nodes();
+    while let Some(node) = nodes.iter().find(|n| contains(n.span(), offset)) {
+        path.push(node);
+        nodes = children(node);
+    }
+    path
+}
+#[cfg(feature = "templates")]
+/// The tparameters of the template declared by `node`.
+fn tparameters(node: &KdlNode) -> Vec<&str> {
+    let entries = node
+        .entries()
+        .iter()
+        .filter_map(|entry| match entry.name() {
+            Some(name) => Some(name.value()),
+            None => entry.value().as_string(),
+        });
+    let param_nodes = children(node)
+        .split_last()
+        .map_or(&[][..], |(_, params)| params);
+    let param_nodes = param_nodes
+        .iter()
+        .filter_map(|param| match param.name().value() {
+            "expand" => param.entries().first()?.value().as_string(),
+            name => Some(name),
+        });
+    entries.chain(param_nodes).collect()
+}
+
+#[cfg(feature = "templates")]
+/// The bindings, tparameters and targuments valid at `offset` of `text`.
+///
+/// `text` may be an incomplete document, see [`parse_tolerant`]. `imports`
+/// are the templates `text` imports.
+pub fn completions_at(text: &str, offset: usize, imports: &RequiredBindings) -> Completions {
+    let Some((document, is_last)) = parse_at(text, offset) else {
+        return Completions::default();
+    };
+    let path = nodes_at(&document, offset);
+    let nodes = document.nodes();
+    let before = nodes.iter().filter(|n| n.span().offset() < offset);
+    let is_declaration = |node: &&KdlNode| !["import", "export"].contains(&node.name().value());
+    // Only templates declared before the cursor can be invoked.
+    let mut declarations: Vec<_> = before.filter(is_declaration).collect();
+    let mut completions = Completions::default();
+
+    if let Some(top) = path.first() {
+        declarations.retain(|n| !ptr::eq(*n, *top));
+        // The last top level node is the node the file represents, the other
+        // ones declare templates.
+        let is_template = !(is_last && nodes.last().map_or(false, |n| ptr::eq(n, *top)));
+        let body = children(top).last();
+        let in_body = path
+            .get(1)
+            .zip(body)
+            .map_or(false, |(n, body)| ptr::eq(*n, body));
+        if is_template && is_declaration(top) && in_body {
+            let params = tparameters(top).into_iter().map(str::to_owned);
+            completions.parameters = params.collect();
+        }
+    }
+    // Later declarations shadow earlier ones and imports.
+    let local = declarations.iter().rev().map(|n| n.name().value());
+    for name in local.chain(imports.0.imported_names()) {
+        if !completions.bindings.iter().any(|b| b == name) {
+            completions.bindings.push(name.to_owned());
+        }
+    }
+
+    // On the name of a node, the node is a targument of its parent.
+    let on_name = |node: &&KdlNode| contains(node.name().span(), offset);
+    let invocation = match path.split_last() {
+        Some((node, parents)) if on_name(node) => parents.last(),
+        Some((node, _)) => Some(node),
+        None => None,
+    };
+    let Some(invocation) = invocation else {
+        return completions;
+    };
+    let name = invocation.name().value();
+    let params = match declarations.iter().rev().find(|n| n.name().value() == name) {
+        Some(declaration) => tparameters(declaration),
+        None => imports.0.imported_tparameters(name).unwrap_or_default(),
+    };
+    // Like when invoking, named entries pass the tparameter with their name,
+    // other entries and children pass the tparameter at their position.
+    let entries = invocation
+        .entries()
+        .iter()
+        .map(|e| (e.name().map(|n| n.value()), false));
+    let nodes = children(invocation).iter().map(|n| (None, on_name(&n)));
+    let mut passed = Vec::new();
+    for (i, (name, typing)) in entries.chain(nodes).enumerate() {
+        let param = name
+            .filter(|n| params.contains(n))
+            .or_else(|| params.get(i).copied());
+        passed.extend(param.filter(|_| !typing));
+    }
+    let fields = params.into_iter().filter(|param| !passed.contains(param));
+    completions.fields = fields.map(str::to_owned).collect();
+    completions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn recovered(text: &str) -> Option<Vec<String>> {
+        let (_, document) = parse_recovering(text).unwrap_err();
+        let names = document?
+            .nodes()
+            .iter()
+            .map(|n| n.name().value().to_owned());
+        Some(names.collect())
+    }
+
+    #[test]
+    fn valid_document() {
+        assert!(parse_recovering("origin { Bar 3.0; }\norigin").is_ok());
+    }
+    #[test]
+    fn error_before_last_node() {
+        assert_eq!(recovered("broken {\n  Bar x=\n}\nBar 3.0\n"), None);
+    }
+    #[test]
+    fn last_node_entirely_dropped() {
+        assert_eq!(recovered("origin { Bar 3.0; }\n/*"), None);
+    }
+}
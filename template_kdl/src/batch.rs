@@ -0,0 +1,150 @@
+//! Read the files of a project at once, spreading the work over threads.
+//!
+//! Files are parsed in parallel, then read in waves: each wave reads the
+//! files whose imports are already read. Only the conversion of the documents
+//! is left to the caller.
+use std::{collections::HashMap, fs, num::NonZeroUsize, path::Path, thread};
+
+use kdl::{KdlDocument, KdlError};
+use multierr_span::Span;
+
+use crate::{
+    err::{Error, ErrorType},
+    get_imports,
+    multi_err::MultiResult,
+    read_document, Document, ExportedBindingsList, Imports,
+};
+
+/// A parsed file, with the files it imports.
+struct Parsed {
+    document: KdlDocument,
+    imports: Imports,
+}
+/// A file read by [`parse_all`].
+pub struct ReadFile {
+    /// The [`read_document`] result of the file.
+    pub document: MultiResult<Document, Error>,
+    /// The imports of the file, `None` if it couldn't be read or parsed.
+    pub imports: Option<Imports>,
+}
+
+/// `f` of all `items`, in order, spread over the available threads.
+fn par_map<T: Send, U: Send>(items: Vec<T>, f: impl Fn(T) -> U + Sync) -> Vec<U> {
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let per_thread = (items.len() + threads - 1) / threads;
+    let mut items = items.into_iter().peekable();
+    let mut chunks = Vec::new();
+    while items.peek().is_some() {
+        chunks.push(items.by_ref().take(per_thread).collect::<Vec<_>>());
+    }
+    let f = &f;
+    thread::scope(|scope| {
+        let spawn =
+            |chunk: Vec<T>| scope.spawn(move || chunk.into_iter().map(f).collect::<Vec<_>>());
+        let handles: Vec<_> = chunks.into_iter().map(spawn).collect();
+        // unwrap: forward the panics of `f`
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+fn parse(root: &Path, file: &str) -> Result<Parsed, Error> {
+    let text = fs::read_to_string(root.join(file)).map_err(|err| {
+        let source = ErrorType::Unreadable { file: file.to_owned(), message: err.to_string() };
+        Error { source, span: Span { offset: 0, size: 0 } }
+    })?;
+    let document: KdlDocument = text.parse().map_err(|err: KdlError| {
+        let span = Span {
+            offset: err.span.offset() as u32,
+            size: err.span.len() as u32,
+        };
+        Error { source: err.into(), span }
+    })?;
+    let imports = get_imports(&document)?;
+    Ok(Parsed { document, imports })
+}
+
+fn read(parsed: Parsed, exports: &ExportedBindingsList) -> ReadFile {
+    let document = match parsed.imports.bindings(exports) {
+        Ok(required) => read_document(parsed.document, required),
+        Err(error) => MultiResult::Err(vec![error]),
+    };
+    ReadFile { document, imports: Some(parsed.imports) }
+}
+
+/// Read and [`read_document`] all `files`, and the files they import, in
+/// parallel.
+///
+/// `files` and imports are relative to `root`. Each document is read with
+/// the exports of the files it imports, which are returned with it. A file
+/// that can't be read or parsed is an error, and so are the imports of it in
+/// other files.
+///
+/// The documents still need to be converted, for example with
+/// `bevy_kdl_reflect_deser`, usually on a single thread since it needs the
+/// type registry.
+pub fn parse_all<'a>(
+    root: &Path,
+    files: impl IntoIterator<Item = &'a str>,
+) -> HashMap<String, ReadFile> {
+    let mut results = HashMap::new();
+    let mut parsed = HashMap::new();
+    let mut to_parse: Vec<_> = files.into_iter().map(str::to_owned).collect();
+    // Parse the files, then the files they import that are not parsed yet
+    while !to_parse.is_empty() {
+        to_parse.sort_unstable();
+        to_parse.dedup();
+        let parsed_now = par_map(to_parse.clone(), |file| parse(root, &file));
+        let mut imported = Vec::new();
+        for (file, parse_result) in to_parse.drain(..).zip(parsed_now) {
+            match parse_result {
+                Ok(file_parsed) => {
+                    let required = file_parsed.imports.required_files();
+                    imported.extend(required.into_iter().map(str::to_owned));
+                    parsed.insert(file, file_parsed);
+                }
+                Err(error) => {
+                    let document = MultiResult::Err(vec![error]);
+                    results.insert(file, ReadFile { document, imports: None });
+                }
+            }
+        }
+        imported.retain(|file| !parsed.contains_key(file) && !results.contains_key(file));
+        to_parse = imported;
+    }
+    let mut exports = ExportedBindingsList::new();
+    while !parsed.is_empty() {
+        let is_ready = |file: &String| {
+            let mut required = parsed[file].imports.required_files().into_iter();
+            required.all(|dep| !parsed.contains_key(dep))
+        };
+        let mut ready: Vec<_> = parsed.keys().filter(|f| is_ready(f)).cloned().collect();
+        // Files importing each other are never ready, their imports of each
+        // other are reported as not loaded.
+        if ready.is_empty() {
+            ready = parsed.keys().cloned().collect();
+        }
+        let ready: Vec<_> = ready
+            .into_iter()
+            .map(|file| {
+                // unwrap: `ready` files are keys of `parsed`
+                let file_parsed = parsed.remove(&file).unwrap();
+                (file, file_parsed)
+            })
+            .collect();
+        let files: Vec<_> = ready.iter().map(|(file, _)| file.clone()).collect();
+        let read_now = par_map(ready, |(_, file_parsed)| read(file_parsed, &exports));
+        for (file, read_file) in files.into_iter().zip(read_now) {
+            if let MultiResult::Ok(document) | MultiResult::OkErr(document, _) = &read_file.document
+            {
+                if let Some(file_exports) = document.exports() {
+                    exports.insert(file.as_str(), file_exports.clone());
+                }
+            }
+            results.insert(file, read_file);
+        }
+    }
+    results
+}
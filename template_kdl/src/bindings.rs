@@ -1,11 +1,12 @@
 use std::sync::Arc;
 
+use kdl::KdlValue;
 use mappable_rc::Marc;
 
 use crate::{
-    err::Error,
+    err::{Error, ErrorType},
     span::SpannedNode,
-    template::{Context, Declaration, NodeThunk},
+    template::{Declaration, NodeThunk},
 };
 
 #[derive(Debug, Default, Clone)]
@@ -14,6 +15,9 @@ impl Export {
     pub(crate) fn get(&self, name: &str) -> Option<&Arc<Binding>> {
         self.0.iter().find(|b| &*b.name == name)
     }
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Arc<Binding>> {
+        self.0.iter()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -21,6 +25,11 @@ pub(crate) enum Bindings {
     Local(Arc<Binding>),
     Imports {
         exposed: Vec<(Marc<str>, Arc<Binding>)>,
+        /// Names exported by several files imported as a whole, with the
+        /// files exporting them, it is an error to invoke them.
+        ambiguous: Vec<(Marc<str>, Vec<String>)>,
+        /// Values injected by the host, see [`crate::RequiredBindings::with_constant`].
+        constants: Vec<(Marc<str>, KdlValue)>,
     },
     Terminal,
 }
@@ -30,9 +39,79 @@ impl Default for Bindings {
     }
 }
 impl Bindings {
-    pub(crate) fn invoke(&self, invocation: &NodeThunk) -> Option<NodeThunk> {
-        self.visit()
-            .find_map(|binding| binding.try_invoke(invocation))
+    /// The expansion of `invocation`, `None` if it doesn't invoke a template.
+    ///
+    /// Invoking a template exported by several files imported as a whole is
+    /// an error, unless it is defined in the file.
+    pub(crate) fn invoke(&self, invocation: &NodeThunk) -> Result<Option<NodeThunk>, Error> {
+        let name = invocation.name();
+        if let Some(files) = self.ambiguous(name.value()) {
+            let (name, files) = (name.value().to_owned(), files.to_vec());
+            let error = ErrorType::AmbiguousTemplate { name, files };
+            return Err(Error::new(invocation, error));
+        }
+        Ok(self
+            .visit()
+            .find_map(|binding| binding.try_invoke(invocation)))
+    }
+    /// The files exporting `name`, if several whole file imports export it and
+    /// it isn't defined in the file.
+    fn ambiguous(&self, name: &str) -> Option<&[String]> {
+        match self {
+            Bindings::Local(binding) if &*binding.name == name => None,
+            Bindings::Local(binding) => binding.bindings.ambiguous(name),
+            Bindings::Imports { ambiguous, .. } => ambiguous
+                .iter()
+                .find_map(|(ambiguous, files)| (&**ambiguous == name).then_some(&files[..])),
+            Bindings::Terminal => None,
+        }
+    }
+    /// The names the imported templates can be invoked with.
+    pub(crate) fn imported_names(&self) -> impl Iterator<Item = &str> {
+        let (exposed, ambiguous) = match self {
+            Bindings::Imports { exposed, ambiguous, .. } => (&exposed[..], &ambiguous[..]),
+            _ => (&[][..], &[][..]),
+        };
+        let exposed = exposed.iter().map(|(name, _)| &**name);
+        exposed.chain(ambiguous.iter().map(|(name, _)| &**name))
+    }
+    /// The tparameters of the imported template `name`, `None` if it isn't
+    /// imported.
+    pub(crate) fn imported_tparameters(&self, name: &str) -> Option<Vec<&str>> {
+        let Bindings::Imports { exposed, .. } = self else {
+            return None;
+        };
+        let (_, binding) = exposed.iter().find(|(exposed, _)| &**exposed == name)?;
+        let params = binding
+            .declaration
+            .iter()
+            .flat_map(Declaration::param_names);
+        Some(params.collect())
+    }
+    /// The constants injected by the host, see [`crate::RequiredBindings::with_constant`].
+    pub(crate) fn constants(&self) -> &[(Marc<str>, KdlValue)] {
+        match self {
+            Bindings::Local(binding) => binding.bindings.constants(),
+            Bindings::Imports { constants, .. } => constants,
+            Bindings::Terminal => &[],
+        }
+    }
+    /// Set the constant `name` to `value`, replacing any previous value.
+    ///
+    /// Only the end of the list holds constants, this does nothing on
+    /// [`Bindings::Local`].
+    pub(crate) fn set_constant(&mut self, name: Marc<str>, value: KdlValue) {
+        if let Bindings::Terminal = self {
+            *self = Bindings::Imports {
+                exposed: Vec::new(),
+                ambiguous: Vec::new(),
+                constants: Vec::new(),
+            };
+        }
+        if let Bindings::Imports { constants, .. } = self {
+            constants.retain(|(constant, _)| *constant != name);
+            constants.push((name, value));
+        }
     }
     fn visit(&self) -> BindingsIter {
         BindingsIter { inner: self, exported_idx: 0 }
@@ -53,9 +132,6 @@ impl Bindings {
         });
         Export(exposed.map(Arc::new).collect())
     }
-    pub(crate) fn thunk(&self, body: SpannedNode) -> NodeThunk {
-        NodeThunk { body, context: Context::new(self.clone()) }
-    }
 }
 
 pub(crate) struct BindingsIter<'a> {
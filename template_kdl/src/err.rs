@@ -9,18 +9,44 @@ pub enum ErrorType {
         provided context: {0:?}"
     )]
     MissingTemplates(Vec<String>),
+    #[error("binding `{binding}` not exported by {file}")]
+    NotExported { binding: String, file: String },
+    #[error("{0} is imported, but wasn't provided")]
+    NotLoaded(String),
+    #[error("Imported templates must be of the form `file/template`, got `{0}`")]
+    BadImportPath(String),
+    #[error("`as` must follow the import of a whole file")]
+    BadImportAlias,
+    #[error("`{name}` is exported by several imported files: {files:?}")]
+    AmbiguousTemplate { name: String, files: Vec<String> },
+    #[error("`expand` tparameters should have the name of the expansion as first argument")]
+    BadExpandParam,
     #[error("Template parameters should have an explicit name, instead got {0:?}")]
     NonstringParam(KdlValue),
     #[error("Template node parameters should have a unique child node")]
     BadTemplateNodeParam,
+    #[error("export entries must be `name` or `local=\"exported\"` pairs")]
+    BadExport,
+    #[error("A document can only have a single `export` node")]
+    MultipleExports,
     #[error("Template has no body")]
     NoBody,
+    #[error("Can't read {file}: {message}")]
+    Unreadable { file: String, message: String },
     #[error("The input is not properly formatted KDL: {0}")]
     Kdl(#[from] kdl::KdlError),
     #[error("The input to `read_thunk`, is specifically `export`, which is not a thunk")]
     NotThunk,
     #[error("The provided KdlDocument is empty")]
     Empty,
+    #[error("Expanding this template produces more than {0} nodes")]
+    TooManyNodes(u32),
+    #[error("Expanding this template produces more than {0} bytes of nodes")]
+    TooLargeExpansion(u32),
+    #[error("`{0}` is not a constant provided by the host")]
+    UnknownConstant(String),
+    #[error("Invalid migration rule: {0}")]
+    BadMigrationRule(&'static str),
 }
 #[derive(Debug, Clone, thiserror::Error, PartialEq)]
 #[error("{source}")]
@@ -48,10 +74,28 @@ impl ErrorType {
     const NO_BODY: &'static str =
         "A template definition must have a body. See how to use templates at \
         https://github.com/nicopap/bevy-kdl-ui/tree/main/template-kdl#value-templates";
+    const BAD_EXPORT: &'static str =
+        "The `export` node lists the templates to export, either as string \
+        arguments, as properties renaming them, or as child nodes. See \
+        https://github.com/nicopap/bevy-kdl-ui/tree/main/template-kdl#export-node \
+        for the accepted forms.";
+    const LIMITS: &'static str =
+        "Template expansion is limited, to avoid freezing on templates that expand \
+        into huge documents. Check that `expand` tparameters and recursive templates \
+        do not expand more than intended, or raise the `Limits`.";
+    const AMBIGUOUS: &'static str =
+        "Name the files to import the template from, with `as=\"namespace\"` \
+        following the file import and invoking it as `namespace:name`, or import \
+        the template by name, as `name=\"file.kdl/name\"`.";
     pub fn help(&self) -> Option<String> {
         match self {
             ErrorType::NonstringParam(_) => Some(Self::NONSTR_PARAM.to_owned()),
             ErrorType::NoBody => Some(Self::NO_BODY.to_owned()),
+            ErrorType::BadExport => Some(Self::BAD_EXPORT.to_owned()),
+            ErrorType::AmbiguousTemplate { .. } => Some(Self::AMBIGUOUS.to_owned()),
+            ErrorType::TooManyNodes(_) | ErrorType::TooLargeExpansion(_) => {
+                Some(Self::LIMITS.to_owned())
+            }
             _ => None,
         }
     }
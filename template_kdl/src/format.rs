@@ -0,0 +1,71 @@
+//! Consistent formatting of kdl files.
+use kdl::{KdlDocument, KdlNode};
+
+/// Whether the whitespace `gap` between two items holds anything to preserve,
+/// such as a comment or a slashdashed entry.
+fn has_comment(gap: &str) -> bool {
+    !gap.replace('\\', "").trim().is_empty()
+}
+/// `gap` with normalized spacing, `None` if it has nothing worth preserving.
+///
+/// Gaps spanning several lines are kept as-is, a `\` line continuation may be
+/// the only thing ending a `//` comment.
+fn normalize(gap: Option<&str>) -> Option<String> {
+    let gap = gap.filter(|gap| has_comment(gap))?;
+    if gap.contains('\n') {
+        Some(gap.to_owned())
+    } else {
+        Some(format!(" {} ", gap.trim()))
+    }
+}
+/// Slashdashed entries or children after the last entry, kept as the node
+/// terminator. `KdlDocument::fmt` can't handle them, they must be removed
+/// before formatting.
+fn take_slashdash(node: &mut KdlNode) -> Option<String> {
+    let trailing = node.trailing().filter(|t| t.contains("/-"))?;
+    let slashdash = trailing.trim().trim_end_matches(';').trim_end();
+    let slashdash = format!(" {slashdash}\n");
+    node.set_trailing("\n");
+    Some(slashdash)
+}
+/// The gaps `KdlDocument::fmt` drops or can't handle, in document order.
+fn take_gaps(document: &mut KdlDocument, gaps: &mut Vec<Option<String>>) {
+    for node in document.nodes_mut() {
+        gaps.extend(node.entries().iter().map(|e| normalize(e.leading())));
+        gaps.push(normalize(node.before_children()));
+        gaps.push(take_slashdash(node));
+        if let Some(children) = node.children_mut() {
+            take_gaps(children, gaps);
+        }
+    }
+}
+fn restore_gaps(nodes: &mut [KdlNode], gaps: &mut impl Iterator<Item = Option<String>>) {
+    for node in nodes {
+        for entry in node.entries_mut() {
+            if let Some(Some(gap)) = gaps.next() {
+                entry.set_leading(gap);
+            }
+        }
+        if let Some(Some(gap)) = gaps.next() {
+            node.set_before_children(gap);
+        }
+        if let Some(Some(gap)) = gaps.next() {
+            node.set_trailing(gap);
+        }
+        if let Some(children) = node.children_mut() {
+            restore_gaps(children.nodes_mut(), gaps);
+        }
+    }
+}
+
+/// Normalize the indentation and spacing of `document`, keeping comments.
+///
+/// Nodes, entries and their order are left untouched, only the whitespace
+/// around them changes. Formatting an already formatted document doesn't
+/// change it.
+pub fn fmt_document(document: &mut KdlDocument) {
+    let mut gaps = Vec::new();
+    take_gaps(document, &mut gaps);
+    document.fmt();
+    restore_gaps(document.nodes_mut(), &mut gaps.into_iter());
+}
@@ -0,0 +1,111 @@
+//! The import graph of a set of files, as DOT or mermaid, to see which
+//! files depend on which and through which templates.
+use std::fmt::Write;
+
+use crate::{ExportedBindingsList, Imports};
+
+/// The imports of `from` from the file `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportEdge {
+    pub from: String,
+    pub to: String,
+    /// The names of the imported bindings, in `to`. Files imported as a
+    /// whole import all their exports, sorted, `*` when they are not known.
+    pub bindings: Vec<String>,
+}
+
+/// Files and their imports, build it with [`ImportGraph::add`].
+///
+/// ```
+/// use template_kdl::{get_imports, graph::ImportGraph, ExportedBindingsList};
+///
+/// let document: kdl::KdlDocument = r#"import "widgets.kdl"; Root"#.parse().unwrap();
+/// let imports = get_imports(&document).unwrap();
+/// let mut graph = ImportGraph::new();
+/// graph.add("menu.kdl", &imports, &ExportedBindingsList::new());
+/// assert!(graph.to_dot().contains(r#""menu.kdl" -> "widgets.kdl""#));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ImportGraph {
+    files: Vec<String>,
+    edges: Vec<ImportEdge>,
+}
+impl ImportGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add `file`, without its imports, if it isn't in the graph yet.
+    pub fn add_file(&mut self, file: &str) {
+        if !self.files.iter().any(|f| f == file) {
+            self.files.push(file.to_owned());
+        }
+    }
+    /// Add `file` and its `imports`, `exports` lists the bindings of files
+    /// imported as a whole.
+    pub fn add(&mut self, file: &str, imports: &Imports, exports: &ExportedBindingsList) {
+        self.add_file(file);
+        for import in imports.entries() {
+            let Some(imported) = import.file() else {
+                continue;
+            };
+            self.add_file(imported);
+            let bindings: Vec<_> = match (import.binding(), exports.get(imported)) {
+                (Some(binding), _) => vec![binding.to_owned()],
+                (None, Some(exported)) => {
+                    let mut names: Vec<_> = exported.names().map(str::to_owned).collect();
+                    names.sort_unstable();
+                    names
+                }
+                (None, None) => vec!["*".to_owned()],
+            };
+            let same_edge = |e: &&mut ImportEdge| e.from == file && e.to == imported;
+            match self.edges.iter_mut().find(same_edge) {
+                Some(edge) => edge.bindings.extend(bindings),
+                None => self.edges.push(ImportEdge {
+                    from: file.to_owned(),
+                    to: imported.to_owned(),
+                    bindings,
+                }),
+            }
+        }
+    }
+    /// The files in the graph, in the order they were added.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+    /// The imports between files, in the order they were added.
+    pub fn edges(&self) -> &[ImportEdge] {
+        &self.edges
+    }
+    /// The graph in the DOT language of graphviz.
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = "digraph imports {\n".to_owned();
+        for file in &self.files {
+            // unwrap: writing to a String never fails
+            writeln!(dot, "  \"{}\";", escape(file)).unwrap();
+        }
+        for ImportEdge { from, to, bindings } in &self.edges {
+            let (from, to, label) = (escape(from), escape(to), escape(&bindings.join(", ")));
+            writeln!(dot, "  \"{from}\" -> \"{to}\" [label=\"{label}\"];").unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+    /// The graph as a mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let escape = |s: &str| s.replace('"', "#quot;");
+        // unwrap: edges only link added files
+        let id = |file: &str| self.files.iter().position(|f| f == file).unwrap();
+        let mut mermaid = "graph LR\n".to_owned();
+        for (i, file) in self.files.iter().enumerate() {
+            // unwrap: writing to a String never fails
+            writeln!(mermaid, "  f{i}[\"{}\"]", escape(file)).unwrap();
+        }
+        for ImportEdge { from, to, bindings } in &self.edges {
+            let (from, to, label) = (id(from), id(to), escape(&bindings.join(", ")));
+            writeln!(mermaid, "  f{from} -->|\"{label}\"| f{to}").unwrap();
+        }
+        mermaid
+    }
+}
@@ -1,14 +1,14 @@
 /*! handle and parse imports
 */
 
-use std::collections::{HashMap, HashSet};
+use std::{collections::BTreeSet, sync::Arc};
 
-use kdl::{KdlDocument, KdlNode};
+use kdl::{KdlDocument, KdlNode, KdlValue};
 use mappable_rc::Marc;
 use multierr_span::{Span, Spanned};
 
 use crate::{
-    bindings::Bindings,
+    bindings::{Binding, Bindings},
     err::{Error, ErrorType},
     navigate::{Navigable, Value},
     span::SpannedNode,
@@ -22,85 +22,192 @@ pub(crate) fn has_node(doc: &KdlDocument) -> bool {
         Some(node) => node.name().value() == "import",
     }
 }
+/// An entry of the `import` node.
+#[derive(Debug, Clone)]
+pub struct Import {
+    /// The import as declared, either `file/binding` or a whole `file`.
+    pub path: String,
+    /// The name the binding is invoked with in the importing file, `None`
+    /// when the whole file is imported.
+    pub name: Option<Marc<str>>,
+    /// The namespace of the bindings of a whole file import, declared with an
+    /// `as="ui"` property following it. Bindings are then invoked as
+    /// `ui:button`.
+    pub alias: Option<Marc<str>>,
+    pub span: Span,
+}
+impl Import {
+    /// The imported file, `None` if a binding import is not of the form
+    /// `file/binding`.
+    pub fn file(&self) -> Option<&str> {
+        match self.name {
+            Some(_) => self.path.rsplit_once('/').map(|k| k.0),
+            None => Some(&self.path),
+        }
+    }
+    /// The name of the imported binding in its file, `None` for whole file
+    /// imports.
+    pub fn binding(&self) -> Option<&str> {
+        self.name.as_ref()?;
+        self.path.rsplit_once('/').map(|k| k.1)
+    }
+    /// Why this import can't be resolved with `list`, if it can't.
+    pub fn missing(&self, list: &ExportedBindingsList) -> Option<Error> {
+        self.resolve(list).err()
+    }
+    /// The bindings this import exposes, by the name they are invoked with.
+    fn resolve(
+        &self,
+        list: &ExportedBindingsList,
+    ) -> Result<Vec<(Marc<str>, Arc<Binding>)>, Error> {
+        let error = |source| Error::new(&self.span, source);
+        let bad_path = || error(ErrorType::BadImportPath(self.path.clone()));
+        let file = self.file().ok_or_else(bad_path)?;
+        let not_loaded = || error(ErrorType::NotLoaded(file.to_owned()));
+        let exports = list.get(file).ok_or_else(not_loaded)?;
+        // Templates are invoked by the binding's name, so it must be the
+        // name used in this file.
+        let rename = |name: Marc<str>, binding: &Arc<Binding>| {
+            let binding = Binding { name: name.clone(), ..Binding::clone(binding) };
+            (name, Arc::new(binding))
+        };
+        let (Some(name), Some(template_name)) = (&self.name, self.binding()) else {
+            let exposed = exports.0.iter().map(|binding| match &self.alias {
+                Some(alias) => rename(format!("{}:{}", &**alias, &*binding.name).into(), binding),
+                None => (binding.name.clone(), binding.clone()),
+            });
+            return Ok(exposed.collect());
+        };
+        let not_exported = || {
+            let (binding, file) = (template_name.to_owned(), file.to_owned());
+            error(ErrorType::NotExported { binding, file })
+        };
+        let binding = exports.0.get(template_name).ok_or_else(not_exported)?;
+        Ok(vec![rename(name.clone(), binding)])
+    }
+}
 pub struct Imports {
-    /// Mapping of "template as declared in context" to "template as bound
-    /// in the file with the given `Imports`".
-    ///
-    /// Note that this is inverted compared to the text representation.
-    mapping: Option<HashMap<String, Marc<str>>>,
+    /// The entries of the `import` node, in declaration order, so that
+    /// resolution doesn't depend on hashing.
+    entries: Option<Vec<Import>>,
     node_span: Span,
 }
 impl Imports {
+    pub(crate) fn none() -> Self {
+        Imports {
+            entries: None,
+            node_span: Span { offset: 0, size: 0 },
+        }
+    }
     // TODO: do not clone all of this
-    pub(crate) fn from_node(node: &KdlNode) -> Self {
-        let zero_span = Span { offset: 0, size: 0 };
-        if node.name().value() == "import" {
-            return Imports { mapping: None, node_span: zero_span };
+    pub(crate) fn from_node(node: &KdlNode) -> Result<Self, Error> {
+        if node.name().value() != "import" {
+            return Ok(Imports::none());
         }
         let node = SpannedNode::new(Marc::new(node.clone()), 0);
-        if let Value::List(values) = node.value() {
-            let mapping: HashMap<_, _> = values
-                .filter_map(|field| {
-                    // TODO(ERR): wrong value declaration on export
-                    let name = field.name().map(|t| t.inner);
-                    let value = &field.value();
-                    let value = if let Value::Bare(kdl_value) = value {
-                        kdl_value.as_string()
-                    } else {
-                        None
-                    };
-                    let from = value.and(name.clone())?;
-                    let to = name.and(value)?;
-                    Some((to.to_owned(), from))
-                })
-                .collect();
-            Imports { mapping: Some(mapping), node_span: node.span() }
-        } else {
-            Imports { mapping: None, node_span: zero_span }
+        let Value::List(fields) = node.value() else {
+            return Ok(Imports::none());
+        };
+        let mut entries: Vec<Import> = Vec::new();
+        for field in fields {
+            // TODO(ERR): wrong value declaration on export
+            let name = field.name().map(|t| t.inner);
+            let Value::Bare(value) = field.value() else {
+                continue;
+            };
+            let Some(path) = value.as_string() else {
+                continue;
+            };
+            if name.as_deref() == Some("as") {
+                match entries.last_mut() {
+                    Some(import) if import.name.is_none() && import.alias.is_none() => {
+                        import.alias = Some(path.to_owned().into());
+                    }
+                    _ => return Err(Error::new(&field, ErrorType::BadImportAlias)),
+                }
+                continue;
+            }
+            let span = field.span();
+            entries.push(Import { path: path.to_owned(), name, alias: None, span });
         }
+        Ok(Imports { entries: Some(entries), node_span: node.span() })
+    }
+    /// The entries of the `import` node, in declaration order.
+    pub fn entries(&self) -> &[Import] {
+        self.entries.as_deref().unwrap_or(&[])
     }
     /// Return the list of external bindings required by the `Imports`.
+    ///
+    /// Files imported as a whole are not listed.
     pub fn required_bindings(&self) -> impl Iterator<Item = &str> + '_ {
-        self.mapping
-            .iter()
-            .flat_map(|m| m.keys())
-            .map(|k| k.as_ref())
+        let entries = self.entries().iter();
+        entries
+            .filter(|import| import.name.is_some())
+            .map(|import| import.path.as_ref())
     }
-    pub fn required_files(&self) -> HashSet<&str> {
+    pub fn required_files(&self) -> BTreeSet<&str> {
         // TODO(ERR): Advise when dependency is not specified as a file (foobar.kdl/template)
-        self.mapping
-            .iter()
-            .flat_map(|m| m.keys())
-            .flat_map(|f| f.rsplit_once('/'))
-            .map(|k| k.0)
-            .collect()
+        self.entries().iter().filter_map(Import::file).collect()
     }
+    /// The bindings exposed by the imports, see [`Import::missing`] for why
+    /// individual imports fail to resolve.
     // TODO: name is silly
     pub fn bindings(&self, bindings: &ExportedBindingsList) -> Result<RequiredBindings, Error> {
         let mut exposed = Vec::new();
-        if let Some(mapping) = &self.mapping {
-            let mut missing = Vec::new();
-            for (context_name, binding_name) in mapping {
-                // TODO: more granular error handling.
-                let Some((file, template_name)) = context_name.rsplit_once('/') else {
-                    missing.push(context_name.clone());
-                    continue;
-                };
-                let Some(binding) = bindings.list.get(file).and_then(|l| l.0.get(template_name)) else {
-                    missing.push(context_name.clone());
-                    continue;
-                };
-                exposed.push((binding_name.clone(), binding.clone()))
-            }
-            if !missing.is_empty() {
-                return Err(Error::new(
-                    &self.node_span,
-                    ErrorType::MissingTemplates(missing),
-                ));
+        let mut missing = Vec::new();
+        // The files exporting each name imported without a namespace by
+        // importing a whole file.
+        let mut ambiguous: Vec<(Marc<str>, Vec<String>)> = Vec::new();
+        for import in self.entries() {
+            let Ok(imported) = import.resolve(bindings) else {
+                missing.push(import.path.clone());
+                continue;
+            };
+            if import.name.is_none() && import.alias.is_none() {
+                for (name, _) in &imported {
+                    let file = import.path.clone();
+                    match ambiguous.iter_mut().find(|(source, _)| **source == **name) {
+                        Some((_, files)) if !files.contains(&file) => files.push(file),
+                        Some(_) => {}
+                        None => ambiguous.push((name.clone(), vec![file])),
+                    }
+                }
             }
+            exposed.extend(imported);
+        }
+        // Importing a template by name picks which file it comes from.
+        let by_name = |name: &str| {
+            self.entries()
+                .iter()
+                .any(|i| i.name.as_deref() == Some(name))
+        };
+        ambiguous.retain(|(name, files)| files.len() > 1 && !by_name(name));
+        // Later imports shadow earlier ones, like later template definitions
+        // do, and `Bindings::invoke` picks the first match.
+        exposed.reverse();
+        if !missing.is_empty() {
+            return Err(Error::new(
+                &self.node_span,
+                ErrorType::MissingTemplates(missing),
+            ));
         }
-        Ok(RequiredBindings(Bindings::Imports { exposed }))
+        let constants = Vec::new();
+        let bindings = Bindings::Imports { exposed, ambiguous, constants };
+        Ok(RequiredBindings(bindings))
     }
 }
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct RequiredBindings(pub(crate) Bindings);
+impl RequiredBindings {
+    /// Add the constant `name`, replacing any previous constant `name`.
+    ///
+    /// `(const)"name"` entries are substituted with `value`, anywhere in the
+    /// document, including in the bodies of imported templates.
+    ///
+    /// This lets the host provide values not known when writing the
+    /// document, such as the screen size or the build profile.
+    pub fn with_constant(mut self, name: &str, value: impl Into<KdlValue>) -> Self {
+        self.0.set_constant(name.to_owned().into(), value.into());
+        self
+    }
+}
@@ -1,27 +1,63 @@
+pub mod analysis;
+#[cfg(feature = "templates")]
+mod batch;
+#[cfg(feature = "templates")]
 mod bindings;
 pub mod err;
 mod field;
+mod format;
+#[cfg(feature = "templates")]
+pub mod graph;
+#[cfg(feature = "templates")]
 mod import;
+mod limits;
+pub mod migrate;
 pub mod multi_err;
 pub mod navigate;
+#[cfg(feature = "templates")]
+pub mod seed;
 pub mod span;
+#[cfg(feature = "templates")]
 pub mod template;
+#[cfg(feature = "templates")]
+mod tokens;
+#[cfg(feature = "trace")]
+mod trace;
 
-pub use import::Imports;
+#[cfg(feature = "templates")]
+pub use batch::{parse_all, ReadFile};
+pub use format::fmt_document;
+#[cfg(feature = "templates")]
 pub use import::RequiredBindings;
+#[cfg(feature = "templates")]
+pub use import::{Import, Imports};
+pub use limits::Limits;
+#[cfg(feature = "templates")]
+pub use tokens::{classify_tokens, Token, TokenKind};
 
-use std::{collections::HashMap, sync::Arc};
+#[cfg(feature = "templates")]
+use std::collections::HashMap;
+use std::sync::Arc;
 
-use kdl::KdlDocument;
+use kdl::{KdlDocument, KdlNode};
 
+#[cfg(feature = "templates")]
 use bindings::{Binding, Bindings};
+#[cfg(feature = "templates")]
 use err::{Error, ErrorType};
+#[cfg(feature = "templates")]
+use limits::Fuel;
 use mappable_rc::Marc;
+#[cfg(feature = "templates")]
 use multi_err::{MultiError, MultiErrorTrait, MultiResult};
+use navigate::SpannedField;
+#[cfg(feature = "templates")]
 use navigate::{Navigable, Value};
 use span::{SpannedDocument, SpannedNode};
+#[cfg(feature = "templates")]
 use template::NodeThunk;
 
+#[cfg(feature = "templates")]
 /// A parsed template KDL file.
 #[derive(Clone, Debug)]
 pub enum Document {
@@ -29,83 +65,238 @@ pub enum Document {
     Node(NodeThunk),
     /// The file exports bindings.
     Exports(ExportedBindings),
+    /// The file represents a node, and also exports bindings with an `export`
+    /// node declared before it.
+    NodeAndExports(NodeThunk, ExportedBindings),
 }
+#[cfg(feature = "templates")]
+impl Document {
+    /// The bindings exported by this file, if any.
+    pub fn exports(&self) -> Option<&ExportedBindings> {
+        match self {
+            Document::Node(_) => None,
+            Document::Exports(exports) | Document::NodeAndExports(_, exports) => Some(exports),
+        }
+    }
+    /// The node this file represents, if any.
+    pub fn node(self) -> Option<NodeThunk> {
+        match self {
+            Document::Exports(_) => None,
+            Document::Node(node) | Document::NodeAndExports(node, _) => Some(node),
+        }
+    }
+}
+#[cfg(feature = "templates")]
+/// The bindings exported by each file, to resolve the imports of a document
+/// with [`Imports::bindings`].
 #[derive(Debug, Default, Clone)]
-pub struct ExportedBindingsList<'b> {
-    pub list: HashMap<&'b str, ExportedBindings>,
+pub struct ExportedBindingsList {
+    pub list: HashMap<Arc<str>, ExportedBindings>,
 }
+#[cfg(feature = "templates")]
+impl ExportedBindingsList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the bindings exported by `file`, replacing the previous ones.
+    pub fn insert(&mut self, file: impl Into<Arc<str>>, exports: ExportedBindings) {
+        self.list.insert(file.into(), exports);
+    }
+    /// The bindings exported by `file`, if known.
+    pub fn get(&self, file: &str) -> Option<&ExportedBindings> {
+        self.list.get(file)
+    }
+    /// Add the exports of `other`, files known by both lists get the
+    /// bindings of `other`.
+    pub fn merge(&mut self, other: ExportedBindingsList) {
+        self.list.extend(other.list);
+    }
+}
+#[cfg(feature = "templates")]
+impl<K: Into<Arc<str>>> FromIterator<(K, ExportedBindings)> for ExportedBindingsList {
+    fn from_iter<I: IntoIterator<Item = (K, ExportedBindings)>>(iter: I) -> Self {
+        let list = iter
+            .into_iter()
+            .map(|(file, exports)| (file.into(), exports));
+        Self { list: list.collect() }
+    }
+}
+#[cfg(feature = "templates")]
 #[derive(Debug, Default, Clone)]
 pub struct ExportedBindings(bindings::Export);
+#[cfg(feature = "templates")]
 impl ExportedBindings {
+    /// The names of the exported bindings.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|binding| &*binding.name)
+    }
+    /// Read the entries of the `export` node, either `name` or
+    /// `local="exported"`, as entries or child nodes.
     // TODO(ERR): error handling when name not found in bindings
-    fn from_export(bindings: Bindings, exposed: SpannedNode) -> Self {
-        if let Value::List(values) = exposed.value() {
-            let binding_names: Vec<_> = values
-                .filter_map(|field| {
-                    // TODO(ERR): wrong value declaration on export
-                    let name = field.name().map(|t| t.inner);
-                    let value = &field.value();
-                    let value = if let Value::Bare(kdl_value) = value {
-                        kdl_value.as_string()
-                    } else {
-                        None
-                    };
-                    let from = value.and(name.clone())?;
-                    let to = name.and(value)?;
-                    Some((from, to.to_owned()))
-                })
-                .collect();
-            Self(bindings.exports(&binding_names))
-        } else {
-            panic!()
+    fn from_export(bindings: Bindings, exposed: SpannedNode) -> (Self, Vec<Error>) {
+        let mut errors = Vec::new();
+        let mut binding_names = Vec::new();
+        let fields = match exposed.value() {
+            Value::List(fields) => fields,
+            Value::Bare(value) => {
+                errors.push(Error::new(&value, ErrorType::BadExport));
+                return (Self(bindings.exports(&[])), errors);
+            }
+        };
+        for field in fields {
+            let name = field.name().map(|t| t.inner);
+            let exported = match (name, field.value()) {
+                (None, Value::Bare(value)) => value
+                    .as_string()
+                    .map(|local| (local.to_owned().into(), local.to_owned())),
+                (Some(local), Value::Bare(value)) => value
+                    .as_string()
+                    .map(|exported| (local, exported.to_owned())),
+                (Some(local), Value::List(_)) if matches!(field.value_count(), Value::List(0)) => {
+                    let exported = local.to_string();
+                    Some((local, exported))
+                }
+                (_, Value::List(_)) => None,
+            };
+            match exported {
+                Some(exported) => binding_names.push(exported),
+                None => errors.push(Error::new(&field, ErrorType::BadExport)),
+            }
         }
+        (Self(bindings.exports(&binding_names)), errors)
     }
 }
 
+#[cfg(feature = "templates")]
 /// Returns imports required to read the file.
+///
+/// An empty document doesn't import anything.
 pub fn get_imports(document: &KdlDocument) -> Result<Imports, Error> {
     let first_node = document.nodes().get(0);
     match first_node {
-        None => Err(Error::new(&(&document, 0), ErrorType::NoBody)),
-        Some(node) => Ok(Imports::from_node(node)),
+        None => Ok(Imports::none()),
+        Some(node) => Imports::from_node(node),
     }
 }
+/// The nodes of `document` as they are declared, without template expansion.
+pub fn read_plain(document: KdlDocument) -> impl Iterator<Item = SpannedField> {
+    read_shared_plain(Arc::new(document))
+}
+/// Like [`read_plain`], but reads a document shared with something else,
+/// such as an editor, without cloning it.
+pub fn read_shared_plain(document: Arc<KdlDocument>) -> impl Iterator<Item = SpannedField> {
+    SpannedDocument::new(Marc::from(document), 0)
+        .nodes()
+        .map(SpannedField::Node)
+}
+/// `node` declared at `offset` of a larger document, such as a node extracted
+/// from it, so that its spans point to where it is in that document.
+///
+/// `offset` is where `node` starts, after its leading whitespace and comments.
+fn spanned_node_at(mut node: KdlNode, offset: usize) -> SpannedNode {
+    node.set_leading("");
+    SpannedNode::new(Marc::new(node), offset as u32)
+}
+/// Like [`read_plain`], but reads the single `node` declared at `offset` of a
+/// larger document.
+pub fn read_plain_node_at(node: KdlNode, offset: usize) -> SpannedField {
+    SpannedField::Node(spanned_node_at(node, offset))
+}
+#[cfg(feature = "templates")]
+/// Read the single `node` declared at `offset` of a larger document, its
+/// spans point to where it is in that document.
+///
+/// `node` can invoke the `required` bindings, it can't declare templates.
+pub fn read_node_at(
+    node: KdlNode,
+    offset: usize,
+    required: RequiredBindings,
+    limits: Limits,
+) -> NodeThunk {
+    NodeThunk::new(spanned_node_at(node, offset), required.0, Fuel::new(limits))
+}
+#[cfg(feature = "templates")]
+/// Read a template file, the last node is the node the file represents,
+/// or an `export` node listing the bindings it exports.
+///
+/// The `export` node can also be declared before the last node, in which case
+/// the file both exports bindings and represents a node. All the bindings of
+/// the file can be exported, wherever the `export` node is.
 pub fn read_document(
     document: KdlDocument,
     required: RequiredBindings,
+) -> MultiResult<Document, Error> {
+    read_document_with_limits(document, required, Limits::default())
+}
+#[cfg(feature = "templates")]
+/// Like [`read_document`], but template expansion of the node the file
+/// represents stops when exceeding `limits`.
+///
+/// Since expansion is lazy, the error is reported when reading the node, see
+/// [`NodeThunk::take_expansion_errors`].
+pub fn read_document_with_limits(
+    document: KdlDocument,
+    required: RequiredBindings,
+    limits: Limits,
+) -> MultiResult<Document, Error> {
+    read_shared_document(Arc::new(document), required, limits)
+}
+#[cfg(feature = "templates")]
+/// Like [`read_document_with_limits`], but reads a document shared with
+/// something else, such as an editor, without cloning it.
+pub fn read_shared_document(
+    document: Arc<KdlDocument>,
+    required: RequiredBindings,
+    limits: Limits,
 ) -> MultiResult<Document, Error> {
     let has_import = import::has_node(&document);
-    let doc = SpannedDocument::new(Marc::new(document), 0);
+    let doc = SpannedDocument::new(Marc::from(document), 0);
     let mut errors = MultiError::default();
-    let node_count = KdlDocument::nodes(&doc).len();
+    // The import node is not part of the body
+    let node_count = KdlDocument::nodes(&doc).len() - has_import as usize;
+    // Files are often created empty before being filled, they export nothing
     if node_count == 0 {
-        let err = Error::new(&doc, ErrorType::Empty);
-        return errors.into_errors(err);
+        return errors.into_result(Document::Exports(ExportedBindings::default()));
     }
-    let mut all_nodes = doc.nodes();
-    if has_import {
-        all_nodes.next().unwrap();
+    let mut all_nodes = doc.nodes().skip(has_import as usize);
+    let mut export = None;
+    let mut bindings = required.0;
+    for body in all_nodes.by_ref().take(node_count - 1) {
+        if body.name().value() != "export" {
+            let (binding, errs) = Binding::new(body, bindings);
+            errors.extend_errors(errs);
+            bindings = Bindings::Local(Arc::new(binding));
+        } else if export.is_none() {
+            export = Some(body);
+        } else {
+            errors.add_error(Error::new(&body, ErrorType::MultipleExports));
+        }
     }
-    let binding_nodes = all_nodes.by_ref().take(node_count - 1);
-    let bindings = binding_nodes.fold(required.0, |bindings, body| {
-        let (binding, errs) = Binding::new(body, bindings);
-        errors.extend_errors(errs);
-        Bindings::Local(Arc::new(binding))
-    });
     let last_node = all_nodes.next().unwrap();
     if last_node.name().value() == "export" {
-        let bindings = ExportedBindings::from_export(bindings, last_node);
-        errors.into_result(Document::Exports(bindings))
-    } else {
-        let node = NodeThunk::new(last_node, bindings);
-        errors.into_result(Document::Node(node))
+        if export.is_some() {
+            errors.add_error(Error::new(&last_node, ErrorType::MultipleExports));
+        }
+        let (bindings, errs) = ExportedBindings::from_export(bindings, last_node);
+        errors.extend_errors(errs);
+        return errors.into_result(Document::Exports(bindings));
+    }
+    let node = NodeThunk::new(last_node, bindings.clone(), Fuel::new(limits));
+    match export {
+        None => errors.into_result(Document::Node(node)),
+        Some(export) => {
+            let (exports, errs) = ExportedBindings::from_export(bindings, export);
+            errors.extend_errors(errs);
+            errors.into_result(Document::NodeAndExports(node, exports))
+        }
     }
 }
 
+#[cfg(feature = "templates")]
 pub fn read_thunk(document: KdlDocument) -> MultiResult<NodeThunk, Error> {
     let err = Error::new(&(&document, 0), ErrorType::NotThunk);
-    read_document(document, Default::default()).and_then(|doc| match doc {
-        Document::Exports(_) => MultiResult::Err(vec![err]),
-        Document::Node(node) => MultiResult::Ok(node),
+    read_document(document, Default::default()).and_then(|doc| match doc.node() {
+        None => MultiResult::Err(vec![err]),
+        Some(node) => MultiResult::Ok(node),
     })
 }
@@ -0,0 +1,126 @@
+//! Limits on the output of template expansion.
+//!
+//! A misused template can expand into millions of nodes and freeze whatever
+//! is reading the document. Expansion is lazy, so the nodes produced are
+//! counted as they are expanded, in a [`Fuel`] shared by all the thunks of a
+//! document. Lazy navigation may expand the same invocation several times, it
+//! is only counted the first time.
+#[cfg(feature = "templates")]
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+#[cfg(feature = "templates")]
+use multierr_span::Spanned;
+
+#[cfg(feature = "templates")]
+use crate::{
+    err::{Error, ErrorType},
+    template::NodeThunk,
+};
+
+/// Limits on the output of template expansion, see
+/// [`read_document_with_limits`](crate::read_document_with_limits).
+///
+/// The `Default` limits do not limit expansion.
+///
+/// ```
+/// use template_kdl::Limits;
+///
+/// let limits = Limits::new().max_nodes(Some(10_000)).max_size(Some(1 << 20));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    max_nodes: Option<u32>,
+    max_size: Option<u32>,
+}
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Maximum count of nodes produced by invoking templates and expanding
+    /// targuments. Defaults to `None`, no limit.
+    pub fn max_nodes(mut self, max_nodes: Option<u32>) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+    /// Maximum total size, in bytes of kdl source, of the nodes produced by
+    /// invoking templates and expanding targuments. Defaults to `None`, no
+    /// limit.
+    pub fn max_size(mut self, max_size: Option<u32>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+/// What's left of the [`Limits`] of a document, and the errors found while
+/// expanding it.
+#[cfg(feature = "templates")]
+#[derive(Debug, Default)]
+pub(crate) struct Fuel {
+    limits: Limits,
+    nodes: AtomicU32,
+    size: AtomicU32,
+    exhausted: AtomicBool,
+    /// The sites of the invocations already counted.
+    consumed: Mutex<HashSet<u64>>,
+    /// The errors found while expanding, such as the invocation that
+    /// exhausted the fuel, until they are reported.
+    errors: Mutex<Vec<Error>>,
+}
+#[cfg(feature = "templates")]
+impl Fuel {
+    pub(crate) fn new(limits: Limits) -> Arc<Self> {
+        Arc::new(Fuel { limits, ..Default::default() })
+    }
+    /// Consume the fuel for replacing `invocation` by `expanded`, returns
+    /// `false` if there is not enough fuel left, in which case `invocation`
+    /// must not be expanded.
+    ///
+    /// The invocation at `site` is only charged the first time it is
+    /// expanded.
+    pub(crate) fn consume(
+        &self,
+        site: u64,
+        invocation: &NodeThunk,
+        expanded: &[NodeThunk],
+    ) -> bool {
+        if self.exhausted.load(Ordering::Relaxed) {
+            return false;
+        }
+        if !self.consumed.lock().unwrap().insert(site) {
+            return true;
+        }
+        let added_nodes = expanded.len() as u32;
+        let added_size = expanded.iter().map(|n| n.span().size).sum();
+        let nodes = self.nodes.fetch_add(added_nodes, Ordering::Relaxed) + added_nodes;
+        let size = self.size.fetch_add(added_size, Ordering::Relaxed) + added_size;
+        let exceeded = match self.limits {
+            Limits { max_nodes: Some(max), .. } if nodes > max => ErrorType::TooManyNodes(max),
+            Limits { max_size: Some(max), .. } if size > max => ErrorType::TooLargeExpansion(max),
+            _ => return true,
+        };
+        if !self.exhausted.swap(true, Ordering::Relaxed) {
+            self.report(Error::new(invocation, exceeded));
+        }
+        false
+    }
+    /// Record an error found while expanding, since expansion is lazy, it
+    /// can't be returned to the reader of the document directly.
+    ///
+    /// A node can be expanded several times, its errors are recorded once.
+    pub(crate) fn report(&self, error: Error) {
+        let mut errors = self.errors.lock().unwrap();
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+    }
+    /// The errors found while expanding, that were not already taken.
+    pub(crate) fn take_errors(&self) -> Vec<Error> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+}
@@ -0,0 +1,219 @@
+//! Rename and restructure the nodes and properties of documents, keeping
+//! their comments and formatting, to follow changes of the types they
+//! declare.
+//!
+//! A [`Migration`] is read from a document listing its rules:
+//!
+//! ```kdl
+//! rename "Colour" "Color"
+//! rename "hp" "health" in="Stats"
+//! wrap "speed" "Speed"
+//! split "Stats" {
+//!     Health "health" "regen"
+//!     Mana "mp"
+//! }
+//! ```
+use kdl::{KdlDocument, KdlEntry, KdlIdentifier, KdlNode};
+use multierr_span::Span;
+
+use crate::err::{Error, ErrorType};
+
+/// A change to the nodes and properties of a document, see [`Migration`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Rename nodes and properties named `from` to `to`.
+    Rename { from: String, to: String },
+    /// Declare `field` nodes and properties as `newtype`, with a type
+    /// annotation, for fields which type is now a newtype of the previous one.
+    Wrap { field: String, newtype: String },
+    /// Replace `component` nodes by one node per part, with the fields listed
+    /// for it. Arguments and unlisted fields go to the first part.
+    Split {
+        component: String,
+        parts: Vec<(String, Vec<String>)>,
+    },
+}
+/// [`Rule`]s to apply to documents, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migration {
+    /// The rules, and the name of the nodes they are restricted to.
+    rules: Vec<(Rule, Option<String>)>,
+}
+impl Migration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add `rule`, applied only to the children and properties of `parent`
+    /// nodes if set.
+    pub fn rule(mut self, rule: Rule, parent: Option<&str>) -> Self {
+        self.rules.push((rule, parent.map(str::to_owned)));
+        self
+    }
+    /// Read the rules of `document`, one per node: `rename "old" "new"`,
+    /// `wrap "field" "Newtype"` or `split "Component" { Part "field"...; }`,
+    /// each with an optional `in="Parent"` property.
+    pub fn from_document(document: &KdlDocument) -> Result<Self, Error> {
+        let rules = document.nodes().iter().map(read_rule);
+        Ok(Migration { rules: rules.collect::<Result<_, _>>()? })
+    }
+    /// Apply the rules to `document`, returns how many nodes and properties
+    /// changed.
+    pub fn apply(&self, document: &mut KdlDocument) -> usize {
+        let rules = self.rules.iter();
+        rules
+            .map(|(rule, parent)| apply_rule(document.nodes_mut(), None, parent.as_deref(), rule))
+            .sum()
+    }
+}
+
+/// The string arguments of `node`, `None` if one of them isn't a string.
+fn string_args(node: &KdlNode) -> Option<Vec<String>> {
+    let args = node.entries().iter().filter(|entry| entry.name().is_none());
+    args.map(|arg| arg.value().as_string().map(str::to_owned))
+        .collect()
+}
+fn read_rule(node: &KdlNode) -> Result<(Rule, Option<String>), Error> {
+    let span = Span {
+        offset: node.span().offset() as u32,
+        size: node.span().len() as u32,
+    };
+    let bad = |message| Error { source: ErrorType::BadMigrationRule(message), span };
+    let args = string_args(node).ok_or_else(|| bad("arguments must be strings"))?;
+    let parent = node
+        .entries()
+        .iter()
+        .find(|e| e.name().map(|n| n.value()) == Some("in"));
+    let parent = parent.and_then(|entry| entry.value().as_string());
+    let rule = match (node.name().value(), <[String; 2]>::try_from(args)) {
+        ("rename", Ok([from, to])) => Rule::Rename { from, to },
+        ("wrap", Ok([field, newtype])) => Rule::Wrap { field, newtype },
+        ("split", _) => {
+            let [component]: [String; 1] = string_args(node)
+                .and_then(|args| args.try_into().ok())
+                .ok_or_else(|| bad("split has a single argument, the component to split"))?;
+            let parts = node.children().map_or(&[][..], |c| c.nodes()).iter();
+            let part = |part: &KdlNode| {
+                let fields = string_args(part).ok_or_else(|| bad("fields must be strings"))?;
+                Ok((part.name().value().to_owned(), fields))
+            };
+            let parts = parts.map(part).collect::<Result<Vec<_>, _>>()?;
+            if parts.is_empty() {
+                return Err(bad("split needs at least one part"));
+            }
+            Rule::Split { component, parts }
+        }
+        _ => return Err(bad("expected a rename, wrap or split rule")),
+    };
+    Ok((rule, parent.map(str::to_owned)))
+}
+/// `entry` renamed `to`, keeping how its value is written.
+fn renamed(entry: &KdlEntry, to: &str) -> KdlEntry {
+    let name = KdlIdentifier::from(to);
+    let ty = entry.ty().map_or_else(String::new, |ty| format!("({ty})"));
+    let value = entry
+        .value_repr()
+        .map_or_else(|| entry.value().to_string(), str::to_owned);
+    // unwrap: an identifier, `=` and a value is a valid property
+    let mut renamed: KdlEntry = format!("{name}={ty}{value}").parse().unwrap();
+    renamed.set_leading(entry.leading().unwrap_or(" "));
+    if let Some(trailing) = entry.trailing() {
+        renamed.set_trailing(trailing);
+    }
+    renamed
+}
+/// `node` as one node per part, the first one keeps its position and
+/// formatting.
+fn split(node: KdlNode, parts: &[(String, Vec<String>)]) -> Vec<KdlNode> {
+    let part_of = |field: Option<&str>| {
+        let listed =
+            |(_, fields): &(String, Vec<String>)| fields.iter().any(|f| Some(&**f) == field);
+        parts.iter().position(listed).unwrap_or(0)
+    };
+    let mut split: Vec<_> = parts
+        .iter()
+        .map(|(name, _)| KdlNode::new(name.as_str()))
+        .collect();
+    for entry in node.entries() {
+        split[part_of(entry.name().map(KdlIdentifier::value))].push(entry.clone());
+    }
+    let children = node.children().map_or(&[][..], |c| c.nodes());
+    for child in children {
+        let part = &mut split[part_of(Some(child.name().value()))];
+        let part_children = part.ensure_children();
+        part_children.nodes_mut().push(child.clone());
+        if let Some(children) = node.children() {
+            part_children.set_leading(children.leading().unwrap_or("\n"));
+            part_children.set_trailing(children.trailing().unwrap_or(""));
+        }
+    }
+    let leading = node.leading().unwrap_or("");
+    let indent = &leading[leading.rfind('\n').map_or(0, |i| i + 1)..];
+    for (i, part) in split.iter_mut().enumerate() {
+        part.set_leading(if i == 0 { leading } else { indent });
+        part.set_trailing(node.trailing().unwrap_or("\n"));
+    }
+    split
+}
+/// Apply `rule` to `nodes`, the children of a `current` node, and to their
+/// descendants. `rule` only applies in `parent` nodes if set.
+fn apply_rule(
+    nodes: &mut Vec<KdlNode>,
+    current: Option<&str>,
+    parent: Option<&str>,
+    rule: &Rule,
+) -> usize {
+    let mut changes = 0;
+    let mut migrated = Vec::with_capacity(nodes.len());
+    for mut node in nodes.drain(..) {
+        let name = node.name().value().to_owned();
+        // Properties are fields of the node declaring them
+        if parent.map_or(true, |parent| parent == name) {
+            for entry in node.entries_mut() {
+                let field = entry.name().map(KdlIdentifier::value);
+                match rule {
+                    Rule::Rename { from, to } if field == Some(from.as_str()) => {
+                        *entry = renamed(entry, to);
+                    }
+                    Rule::Wrap { field: wrapped, newtype }
+                        if field == Some(wrapped.as_str()) && entry.ty().is_none() =>
+                    {
+                        entry.set_ty(newtype.as_str());
+                    }
+                    _ => continue,
+                }
+                changes += 1;
+            }
+        }
+        let mut replaced = vec![];
+        if parent.map_or(true, |parent| Some(parent) == current) {
+            let changed = match rule {
+                Rule::Rename { from, to } if name == *from => {
+                    node.set_name(to.as_str());
+                    true
+                }
+                Rule::Wrap { field, newtype } if name == *field && node.ty().is_none() => {
+                    node.set_ty(newtype.as_str());
+                    true
+                }
+                Rule::Split { component, parts } if name == *component => {
+                    replaced = split(node.clone(), parts);
+                    true
+                }
+                _ => false,
+            };
+            changes += usize::from(changed);
+        }
+        if replaced.is_empty() {
+            replaced.push(node);
+        }
+        for mut node in replaced {
+            let name = node.name().value().to_owned();
+            if let Some(children) = node.children_mut() {
+                changes += apply_rule(children.nodes_mut(), Some(&name), parent, rule);
+            }
+            migrated.push(node);
+        }
+    }
+    *nodes = migrated;
+    changes
+}
@@ -26,6 +26,30 @@ impl<E> MultiError<E> {
         self.extend_errors(errs);
         MultiResult::Err(self.0)
     }
+    /// Run `f` with a fresh accumulator, so that it can use `?` on
+    /// [`OrAccumulate::or_accumulate`] results.
+    ///
+    /// `f` returning `None` is an error, it should only do so after
+    /// accumulating at least one error.
+    ///
+    /// ```
+    /// use template_kdl::multi_err::{MultiError, MultiResult, OrAccumulate};
+    ///
+    /// let parse = |s: &str| s.parse::<u8>().map_err(|_| s.to_owned());
+    /// let sum: MultiResult<u8, String> = MultiError::scope(|errs| {
+    ///     let first = parse("1").or_accumulate(errs)?;
+    ///     let second = parse("x").or_accumulate(errs)?;
+    ///     Some(first + second)
+    /// });
+    /// assert_eq!(sum.into_tuple(), (None, vec!["x".to_owned()]));
+    /// ```
+    pub fn scope<T>(f: impl FnOnce(&mut Self) -> Option<T>) -> MultiResult<T, E> {
+        let mut errs = Self::default();
+        match f(&mut errs) {
+            Some(ok) => errs.into_result(ok),
+            None => MultiResult::Err(errs.0),
+        }
+    }
 }
 impl<E> Default for MultiError<E> {
     fn default() -> Self {
@@ -129,6 +153,10 @@ impl<T, E> MultiErrorTrait for MultiResult<T, E> {
 }
 
 impl<T, E> MultiResult<T, E> {
+    /// The value if any, moving all errors into `acc`.
+    pub fn branch_into(self, acc: &mut impl MultiErrorTrait<Error = E>) -> Option<T> {
+        acc.optionally(self)
+    }
     pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> MultiResult<U, E> {
         match self {
             MultiResult::Ok(t) => MultiResult::Ok(f(t)),
@@ -228,6 +256,24 @@ where
         }
     }
 }
+
+/// Move errors into an accumulator, keeping the value if any.
+///
+/// Since this returns an `Option`, it can be used with `?` in functions
+/// returning `Option`, such as the closure of [`MultiError::scope`].
+pub trait OrAccumulate<T, E> {
+    fn or_accumulate(self, acc: &mut impl MultiErrorTrait<Error = E>) -> Option<T>;
+}
+impl<T, E> OrAccumulate<T, E> for MultiResult<T, E> {
+    fn or_accumulate(self, acc: &mut impl MultiErrorTrait<Error = E>) -> Option<T> {
+        self.branch_into(acc)
+    }
+}
+impl<T, E> OrAccumulate<T, E> for Result<T, E> {
+    fn or_accumulate(self, acc: &mut impl MultiErrorTrait<Error = E>) -> Option<T> {
+        acc.optionally(self)
+    }
+}
 impl<T, E> From<Result<T, E>> for MultiResult<T, E> {
     fn from(res: Result<T, E>) -> Self {
         match res {
@@ -240,6 +286,9 @@ impl<T, E> From<Result<T, E>> for MultiResult<T, E> {
 /// Try $body. If value, then value, if no useable values, then
 /// return from encompassing scope with errors accumulated in $acc
 /// and the new error.
+///
+/// See also [`OrAccumulate`] and [`MultiError::scope`], to do the same with
+/// `?` rather than a macro.
 #[macro_export]
 macro_rules! multi_try {
     ($acc:expr, $body:expr) => {
@@ -4,10 +4,9 @@ use kdl::{KdlEntry, KdlValue};
 use mappable_rc::Marc;
 use multierr_span::{Smarc, Span, Spanned};
 
-use crate::{
-    span::{SpannedIdent, SpannedNode},
-    template::{Context, NodeThunk},
-};
+use crate::span::{SpannedIdent, SpannedNode};
+#[cfg(feature = "templates")]
+use crate::template::{is_constant, Context, NodeThunk};
 
 fn into<T, U: Into<T>>(from: U) -> T {
     from.into()
@@ -43,8 +42,10 @@ impl PartialEq for Sstring {
     }
 }
 
+#[cfg(feature = "templates")]
 #[derive(Debug)]
 pub struct ThunkField(pub(crate) ThunkField_);
+#[cfg(feature = "templates")]
 impl ThunkField {
     pub fn node(inner: NodeThunk) -> Self {
         Self(ThunkField_::Node(inner))
@@ -53,6 +54,7 @@ impl ThunkField {
         Self(ThunkField_::Entry(inner, ctx))
     }
 }
+#[cfg(feature = "templates")]
 impl Spanned for ThunkField {
     fn span(&self) -> Span {
         match &self.0 {
@@ -61,6 +63,7 @@ impl Spanned for ThunkField {
         }
     }
 }
+#[cfg(feature = "templates")]
 #[derive(Debug)]
 pub(crate) enum ThunkField_ {
     Node(NodeThunk),
@@ -96,8 +99,13 @@ pub trait Navigable {
     type Fields: Iterator<Item = Self::Field>;
 
     fn value(&self) -> Value<Self::Fields, Self::Val>;
+    /// The name of this field, `None` for arguments and for nodes named `-`,
+    /// which are positional, with or without templating.
     fn name(&self) -> Option<Self::Name>;
     fn ty(&self) -> Option<Self::Name>;
+    /// The value of the property `name` of this node, without expanding its
+    /// children. `None` for entries.
+    fn property(&self, name: &str) -> Option<Self::Val>;
     fn value_count(&self) -> Value<u32, ()> {
         match self.value() {
             Value::Bare(_) => Value::Bare(()),
@@ -110,10 +118,20 @@ pub trait Navigable {
             Value::List(lst) => lst.take(1).any(|e| e.name().is_some()),
         }
     }
+    /// Whether this is an entry of a node, rather than a node.
+    fn is_entry(&self) -> bool {
+        false
+    }
+    /// How the bare value of this field is written in the document, such as
+    /// `0xFF_FF`. `None` if it isn't known, such as when templating replaced it.
+    fn value_repr(&self) -> Option<String> {
+        None
+    }
     // TODO: for making templating generic over Navigable, use `value_count`
     // fn value_ext(&self) -> ValueExt<Self::Field, Self::Val>;
     // enum ValueExt<F, V> { Empty, Single(F), Many(Box<dyn Iterator<Item=F>>), Bare(V) }
 }
+#[cfg(feature = "templates")]
 impl Navigable for ThunkField {
     type Val = Smarc<KdlValue>;
     type Name = Sstring;
@@ -123,11 +141,7 @@ impl Navigable for ThunkField {
     fn value(&self) -> Value<Self::Fields, Smarc<KdlValue>> {
         match &self.0 {
             ThunkField_::Node(n) => n.value(),
-            ThunkField_::Entry(entry, ctx) => {
-                let value = entry.value();
-                let expanded = ctx.arguments.value(&value).cloned();
-                Value::Bare(expanded.unwrap_or(value))
-            }
+            ThunkField_::Entry(entry, ctx) => Value::Bare(ctx.value(entry)),
         }
     }
     fn value_count(&self) -> Value<u32, ()> {
@@ -142,13 +156,36 @@ impl Navigable for ThunkField {
             ThunkField_::Node(node) => Navigable::name(node),
         }
     }
+    fn is_entry(&self) -> bool {
+        matches!(self.0, ThunkField_::Entry(..))
+    }
+    fn value_repr(&self) -> Option<String> {
+        match &self.0 {
+            ThunkField_::Node(n) => n.value_repr(),
+            ThunkField_::Entry(entry, ctx) => {
+                let declared = entry.value();
+                let is_replaced = *ctx.value(entry) != *declared;
+                let repr = entry.value_repr().filter(|_| !is_replaced);
+                repr.map(str::to_owned)
+            }
+        }
+    }
     fn ty(&self) -> Option<Sstring> {
         match &self.0 {
+            // The constant is substituted, its annotation isn't a type
+            ThunkField_::Entry(entry, _) if is_constant(entry) => None,
             ThunkField_::Entry(entry, _) => entry.ty().map(into),
             ThunkField_::Node(node) => node.ty(),
         }
     }
+    fn property(&self, name: &str) -> Option<Smarc<KdlValue>> {
+        match &self.0 {
+            ThunkField_::Entry(..) => None,
+            ThunkField_::Node(node) => node.property(name),
+        }
+    }
 }
+#[cfg(feature = "templates")]
 impl Navigable for NodeThunk {
     type Val = Smarc<KdlValue>;
     type Name = Sstring;
@@ -156,23 +193,18 @@ impl Navigable for NodeThunk {
     type Fields = Box<dyn Iterator<Item = Self::Field>>;
 
     // NOTE: if single argument child without name, then we assume it's a value.
-    #[allow(unused_parens)]
     fn value(&self) -> Value<Self::Fields, Smarc<KdlValue>> {
         if self.is_value() {
             let entry = self.body.entries().next().unwrap();
             ThunkField::entry(entry, self.context.clone()).value()
         } else {
-            let ctx = self.context.clone();
-            let thunk_entry = move |e| ThunkField::entry(e, ctx.clone());
-            let entries = self.body.entries().map(thunk_entry);
+            let entries = self.entries().map(|(e, ctx)| ThunkField::entry(e, ctx));
             // TODO(PERF): find something slightly more efficient than comparing every node
             // name every encountered with all bindings.
             let ctx = self.context.clone();
             let with_param_expanded = move |body| {
                 let body = NodeThunk { body, context: ctx.clone() };
-                let replacement = ctx.expand(&body);
-                let no_repl = replacement.is_empty();
-                (if no_repl { vec![body] } else { replacement })
+                ctx.expand(&body).unwrap_or_else(|| vec![body])
             };
             let doc = self.body.children().into_iter();
             let children = doc
@@ -182,6 +214,14 @@ impl Navigable for NodeThunk {
             Value::List(Box::new(entries.chain(children)))
         }
     }
+    fn value_repr(&self) -> Option<String> {
+        if self.is_value() {
+            let entry = self.body.entries().next().unwrap();
+            ThunkField::entry(entry, self.context.clone()).value_repr()
+        } else {
+            None
+        }
+    }
     fn value_count(&self) -> Value<u32, ()> {
         if self.is_value() {
             Value::Bare(())
@@ -203,16 +243,39 @@ impl Navigable for NodeThunk {
             // NOTE: it's currently impossible to change the type of entries through
             // templating, we are relying on that for this to work.
             let entry = self.body.entries().next().unwrap();
-            entry.ty().map(into)
+            entry.ty().filter(|_| !is_constant(&entry)).map(into)
         } else {
             self.body.ty().map(into)
         }
     }
+    fn property(&self, name: &str) -> Option<Smarc<KdlValue>> {
+        let is_named = |entry: &Smarc<KdlEntry>| entry.name().map_or(false, |n| n.value() == name);
+        let (entry, context) = self.entries().find(|(entry, _)| is_named(entry))?;
+        Some(context.value(&entry))
+    }
 }
+/// A [`Navigable`] over the plain kdl document, without template expansion.
+#[derive(Debug)]
 pub enum SpannedField {
     Node(SpannedNode),
     Entry(Smarc<KdlEntry>),
 }
+impl Spanned for SpannedField {
+    fn span(&self) -> Span {
+        match self {
+            Self::Entry(e) => e.span(),
+            Self::Node(n) => n.span(),
+        }
+    }
+}
+/// The single argument of a node without children, which is read as a value,
+/// like [`NodeThunk`] does.
+fn value_entry(node: &SpannedNode) -> Option<Smarc<KdlEntry>> {
+    let inner = &node.inner;
+    let single_entry = inner.entries().len() == 1 && inner.children().is_none();
+    let entry = single_entry.then(|| node.entries().next()).flatten()?;
+    entry.name().is_none().then_some(entry)
+}
 impl Navigable for SpannedField {
     type Val = Smarc<KdlValue>;
     type Name = Sstring;
@@ -222,25 +285,49 @@ impl Navigable for SpannedField {
     fn value(&self) -> Value<Self::Fields, Smarc<KdlValue>> {
         match self {
             Self::Entry(entry) => Value::Bare(entry.value()),
-            Self::Node(node) => node.value(),
+            Self::Node(node) => match value_entry(node) {
+                Some(entry) => Value::Bare(entry.value()),
+                None => node.value(),
+            },
         }
     }
     fn value_count(&self) -> Value<u32, ()> {
         match self {
             Self::Entry(_) => Value::Bare(()),
+            Self::Node(node) if value_entry(node).is_some() => Value::Bare(()),
             Self::Node(node) => node.value_count(),
         }
     }
     fn name(&self) -> Option<Sstring> {
         match self {
             Self::Entry(entry) => entry.name().map(into),
-            Self::Node(node) => Some(node.name().into()),
+            Self::Node(node) => Navigable::name(node),
         }
     }
+    fn is_entry(&self) -> bool {
+        matches!(self, Self::Entry(_))
+    }
+    fn value_repr(&self) -> Option<String> {
+        let entry = match self {
+            Self::Entry(entry) => entry.clone(),
+            Self::Node(node) => value_entry(node)?,
+        };
+        entry.value_repr().map(str::to_owned)
+    }
+    // NOTE: like `NodeThunk`, forward the type of the argument read as value
     fn ty(&self) -> Option<Sstring> {
         match self {
             Self::Entry(entry) => entry.ty().map(into),
-            Self::Node(node) => node.ty().map(into),
+            Self::Node(node) => match value_entry(node) {
+                Some(entry) => entry.ty().map(into),
+                None => node.ty().map(into),
+            },
+        }
+    }
+    fn property(&self, name: &str) -> Option<Smarc<KdlValue>> {
+        match self {
+            Self::Entry(_) => None,
+            Self::Node(node) => Navigable::property(node, name),
         }
     }
 }
@@ -262,9 +349,14 @@ impl Navigable for SpannedNode {
         Value::List(entries + children)
     }
     fn name(&self) -> Option<Sstring> {
-        Some(self.name().into())
+        let name = self.name();
+        (name.value() != "-").then(|| name.into())
     }
     fn ty(&self) -> Option<Sstring> {
         self.ty().map(into)
     }
+    fn property(&self, name: &str) -> Option<Smarc<KdlValue>> {
+        let is_named = |entry: &Smarc<KdlEntry>| entry.name().map_or(false, |n| n.value() == name);
+        self.entries().find(is_named).map(|entry| entry.value())
+    }
 }
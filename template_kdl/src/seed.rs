@@ -0,0 +1,138 @@
+//! Interpret template kdl nodes as your own values, reusing template
+//! expansion and span tracking.
+//!
+//! A [`Visitor`] handles the two ways a field can be declared: a bare value,
+//! such as an entry or a node with a single argument, or a list of entries
+//! and children. Like `serde` seeds, visitors take `self`, so that they can
+//! carry the state needed to interpret a field, for example the speaker of the
+//! lines of a dialogue. Every visitor is a [`NavigableSeed`], start
+//! interpreting a document with [`interpret_document`].
+use std::fmt;
+
+use kdl::KdlDocument;
+use multierr_span::{Span, Spanned};
+
+use crate::{
+    err::{Error, ErrorType},
+    multi_err::{MultiErrorTrait, MultiResult},
+    navigate::{Navigable, ThunkField, Value},
+    read_document_with_limits, Document, Limits, RequiredBindings,
+};
+
+/// How a field is declared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Declared {
+    /// An entry, or a node with a single argument and no children.
+    Bare,
+    /// Any other node: without entries, with several, or with children.
+    List,
+}
+impl fmt::Display for Declared {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Declared::Bare => write!(f, "a value"),
+            Declared::List => write!(f, "a node with entries or children"),
+        }
+    }
+}
+
+/// A field declared in a way its [`Visitor`] doesn't handle.
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+#[error("expected {expected}, found {found}")]
+pub struct Unexpected {
+    /// What the visitor expected, see [`Visitor::expecting`].
+    pub expected: String,
+    pub found: Declared,
+    pub span: Span,
+}
+impl Spanned for Unexpected {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Interpret fields of type `N` as a `Self::Value`.
+///
+/// The methods not implemented return an [`Unexpected`] error.
+pub trait Visitor<N: Navigable + Spanned>: Sized {
+    type Value;
+    type Error: From<Unexpected>;
+
+    /// What this visitor expects, such as "a dialogue line", for error
+    /// messages.
+    fn expecting(&self) -> String;
+    /// `field` is declared as the bare `value`.
+    fn visit_bare(self, field: &N, value: N::Val) -> MultiResult<Self::Value, Self::Error> {
+        let _ = value;
+        unexpected(self, field, Declared::Bare)
+    }
+    /// `field` is declared with `fields`, its entries followed by its
+    /// children, templates expanded.
+    fn visit_list(self, field: &N, fields: N::Fields) -> MultiResult<Self::Value, Self::Error> {
+        let _ = fields;
+        unexpected(self, field, Declared::List)
+    }
+}
+fn unexpected<N: Navigable + Spanned, V: Visitor<N>>(
+    visitor: V,
+    field: &N,
+    found: Declared,
+) -> MultiResult<V::Value, V::Error> {
+    let expected = visitor.expecting();
+    let error = Unexpected { expected, found, span: field.span() };
+    MultiResult::Err(vec![error.into()])
+}
+
+/// Something interpreting a field of type `N`, keeping state between fields.
+///
+/// All [`Visitor`]s are seeds, implement this directly to handle fields
+/// without distinguishing how they are declared.
+pub trait NavigableSeed<N> {
+    type Value;
+    type Error;
+
+    fn interpret(self, field: N) -> MultiResult<Self::Value, Self::Error>;
+}
+impl<N: Navigable + Spanned, V: Visitor<N>> NavigableSeed<N> for V {
+    type Value = V::Value;
+    type Error = V::Error;
+
+    fn interpret(self, field: N) -> MultiResult<Self::Value, Self::Error> {
+        match field.value() {
+            Value::Bare(value) => self.visit_bare(&field, value),
+            Value::List(fields) => self.visit_list(&field, fields),
+        }
+    }
+}
+
+/// Interpret the node `document` represents with `seed`, see
+/// [`read_document`](crate::read_document).
+///
+/// Errors reading the document and expanding its templates are returned
+/// alongside the errors of `seed`. A document exporting bindings without
+/// representing a node is an error.
+pub fn interpret_document<S>(
+    document: KdlDocument,
+    required: RequiredBindings,
+    limits: Limits,
+    seed: S,
+) -> MultiResult<S::Value, S::Error>
+where
+    S: NavigableSeed<ThunkField>,
+    S::Error: From<Error>,
+{
+    let not_thunk = Error::new(&(&document, 0), ErrorType::NotThunk);
+    let (read, errors) = read_document_with_limits(document, required, limits).into_tuple();
+    let errors = errors.into_iter().map(S::Error::from);
+    let node = match read.map(Document::node) {
+        Some(Some(node)) => node,
+        Some(None) => return MultiResult::Err(errors.chain([not_thunk.into()]).collect()),
+        None => return MultiResult::Err(errors.collect()),
+    };
+    let expansion = node.clone();
+    let mut result = seed.interpret(ThunkField::node(node));
+    result.extend_errors(errors);
+    let expansion_errors = expansion.take_expansion_errors().into_iter();
+    result.extend_errors(expansion_errors.map(S::Error::from));
+    result
+}
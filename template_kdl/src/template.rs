@@ -48,8 +48,9 @@
 //! having recourse to `unsafe`, and thus we can get rid of `appendlist` which was
 //! unsound.
 // TODO: consider using a better hashmap implementation.
-use std::collections::HashMap;
+use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use kdl::{KdlDocument, KdlEntry, KdlIdentifier, KdlNode, KdlValue};
@@ -58,6 +59,7 @@ use multierr_span::{Smarc, Span, Spanned};
 
 use crate::bindings::Bindings;
 use crate::err::{Error, ErrorType};
+use crate::limits::Fuel;
 use crate::multi_err::{MultiError, MultiErrorTrait, MultiResult};
 use crate::multi_try;
 use crate::navigate::{Navigable, ThunkField_, Value};
@@ -68,7 +70,8 @@ pub(crate) enum TdefaultArg {
     None,
     Value(Smarc<KdlValue>),
     Node(SpannedNode),
-    Expand(Option<SpannedDocument>),
+    /// Default entries and nodes of an `expand` parameter.
+    Expand(Vec<Smarc<KdlEntry>>, Option<SpannedDocument>),
 }
 impl From<SpannedNode> for TdefaultArg {
     fn from(node: SpannedNode) -> Self {
@@ -91,11 +94,15 @@ impl TryFrom<SpannedNode> for Tparameter {
     fn try_from(node: SpannedNode) -> Result<Self, Self::Error> {
         let name = node.name();
         if name.value() == "expand" {
-            // TODO error handling here (expand argument not string)
-            let name = node.entries().next().unwrap().value();
-            let name = name.as_string().unwrap().to_owned().into();
-            let doc = node.children();
-            let value = TdefaultArg::Expand(doc);
+            let bad_expand = || Error::new(&name, ErrorType::BadExpandParam);
+            let mut entries = node.entries();
+            let expand_name = entries.next().ok_or_else(bad_expand)?.value();
+            let name = expand_name
+                .as_string()
+                .ok_or_else(bad_expand)?
+                .to_owned()
+                .into();
+            let value = TdefaultArg::Expand(entries.collect(), node.children());
             Ok(Self { name, value })
         } else if let Some(children) = node.children() {
             let node_count = KdlDocument::nodes(&children).len();
@@ -135,9 +142,14 @@ impl TryFrom<Smarc<KdlEntry>> for Tparameter {
 }
 
 /// Template arguments, the actual values with which a template is called.
+///
+/// The maps are only ever accessed by name, never iterated, so their ordering
+/// doesn't affect expansion.
 #[derive(Default, Debug)]
 pub(crate) struct Targuments {
     expand: HashMap<Marc<str>, Vec<NodeThunk>>,
+    /// The entries of `expand` arguments, with the context to read them in.
+    expand_entries: HashMap<Marc<str>, Vec<(Smarc<KdlEntry>, Context)>>,
     values: HashMap<Marc<str>, Smarc<KdlValue>>,
     nodes: HashMap<Marc<str>, NodeThunk>,
 }
@@ -145,6 +157,9 @@ impl Targuments {
     fn expand(&self, key: &str) -> Option<Vec<NodeThunk>> {
         self.expand.get(key).cloned()
     }
+    fn expand_entries(&self, key: &str) -> Option<Vec<(Smarc<KdlEntry>, Context)>> {
+        self.expand_entries.get(key).cloned()
+    }
     pub(crate) fn value(&self, key: &KdlValue) -> Option<&Smarc<KdlValue>> {
         let key = key.as_string()?;
         self.values.get(key)
@@ -152,6 +167,24 @@ impl Targuments {
     pub(crate) fn node(&self, key: &str) -> Option<&NodeThunk> {
         self.nodes.get(key)
     }
+    /// The targuments as ` name=value`, sorted by name.
+    #[cfg(feature = "trace")]
+    pub(crate) fn bound(&self) -> String {
+        let values = self.values.iter().map(|(k, v)| (k, v.to_string()));
+        let nodes = self.nodes.iter().map(|(k, v)| (k, format!("{{{v}}}")));
+        let mut expand_names: Vec<_> = self.expand.keys().collect();
+        expand_names.extend(self.expand_entries.keys());
+        expand_names.sort_unstable();
+        expand_names.dedup();
+        let expand = expand_names.into_iter().map(|k| {
+            let entries = self.expand_entries.get(k).map_or(0, Vec::len);
+            let nodes = self.expand.get(k).map_or(0, Vec::len);
+            (k, format!("[{entries} entries, {nodes} nodes]"))
+        });
+        let mut bound: Vec<_> = values.chain(nodes).chain(expand).collect();
+        bound.sort_unstable_by(|l, r| l.0.cmp(r.0));
+        bound.iter().map(|(k, v)| format!(" {k}={v}")).collect()
+    }
 }
 #[derive(Debug, Clone)]
 pub(crate) struct Declaration {
@@ -165,6 +198,10 @@ impl Declaration {
     fn param_at(&self, index: usize) -> Option<&Tparameter> {
         self.params.get(index)
     }
+    /// The names of the tparameters, in declaration order.
+    pub(crate) fn param_names(&self) -> impl Iterator<Item = &str> {
+        self.params.iter().map(|param| &*param.name)
+    }
     pub(crate) fn new(node: SpannedNode) -> MultiResult<Self, Error> {
         let mut errors = MultiError::default();
         let name = node.name();
@@ -184,23 +221,35 @@ impl Declaration {
     }
     /// Transform tparameters into targuments as specified at `call` site.
     pub(crate) fn call(&self, call: &NodeThunk, def_binds: &Bindings) -> NodeThunk {
+        let def_context = Context {
+            bindings: def_binds.clone(),
+            arguments: Default::default(),
+            depth: call.context.depth + 1,
+            site: call.site(),
+            fuel: call.context.fuel.clone(),
+            constants: call.context.constants.clone(),
+        };
+        let def_thunk = |body: SpannedNode| NodeThunk { body, context: def_context.clone() };
         let mut values = HashMap::<_, Smarc<_>>::default();
         let mut nodes = HashMap::default();
         let mut expand = HashMap::default();
+        let mut expand_entries = HashMap::default();
         // default values
         for param in &self.params {
             match &param.value {
-                TdefaultArg::Expand(Some(doc)) => {
-                    let value = doc.nodes().map(|n| def_binds.thunk(n)).collect();
-                    expand.insert(param.name.clone(), value);
+                TdefaultArg::Expand(entries, doc) => {
+                    let entries = entries.iter().map(|e| (e.clone(), def_context.clone()));
+                    expand_entries.insert(param.name.clone(), entries.collect());
+                    if let Some(doc) = doc {
+                        expand.insert(param.name.clone(), doc.nodes().map(def_thunk).collect());
+                    }
                 }
                 TdefaultArg::Node(n) => {
-                    nodes.insert(param.name.clone(), def_binds.thunk(n.clone()));
+                    nodes.insert(param.name.clone(), def_thunk(n.clone()));
                 }
                 TdefaultArg::Value(v) => {
                     values.insert(param.name.clone(), v.clone());
                 }
-                TdefaultArg::Expand(None) => {}
                 TdefaultArg::None => {}
             }
         }
@@ -213,14 +262,13 @@ impl Declaration {
                     .or_else(|| self.param_at(i));
                 match (field.0, param) {
                     (ThunkField_::Entry(entry, ctx), Some(param)) => {
-                        let value = entry.value();
-                        let expanded = ctx.arguments.value(&value).cloned();
-                        let value = expanded.unwrap_or(value);
+                        let value = ctx.value(&entry);
                         values.insert(param.name.clone(), value);
                     }
                     (ThunkField_::Entry(..), None) => {}
                     (ThunkField_::Node(argument), _) => match self.param_at(i) {
-                        Some(Tparameter { name, value: TdefaultArg::Expand(_) }) => {
+                        Some(Tparameter { name, value: TdefaultArg::Expand(..) }) => {
+                            expand_entries.insert(name.clone(), argument.entries().collect());
                             expand.insert(name.clone(), argument.children().collect());
                         }
                         Some(Tparameter { name, .. }) => {
@@ -231,45 +279,127 @@ impl Declaration {
                 }
             }
         }
-        let arguments = Targuments { values, nodes, expand };
-        let context = Context {
-            arguments: Arc::new(arguments),
-            bindings: def_binds.clone(),
-        };
+        let arguments = Targuments { values, nodes, expand, expand_entries };
+        let context = Context { arguments: Arc::new(arguments), ..def_context };
         NodeThunk { context, body: self.body.clone() }
     }
 }
+/// The type annotation of entries naming a constant, as `(const)"name"`.
+const CONST: &str = "const";
+
+/// Whether `entry` names a constant, see [`Context::value`].
+pub(crate) fn is_constant(entry: &KdlEntry) -> bool {
+    entry.ty().map_or(false, |ty| ty.value() == CONST)
+}
 /// Context used to resolve the abstract nodes into actual nodes.
 #[derive(Clone, Debug)]
 pub(crate) struct Context {
     bindings: Bindings,
     pub(crate) arguments: Arc<Targuments>,
+    /// How many template invocations lead to this context.
+    pub(crate) depth: u32,
+    /// The invocations leading to this context, hashed, see [`NodeThunk::site`].
+    site: u64,
+    fuel: Arc<Fuel>,
+    /// The constants of the document, they are the same in the bodies of
+    /// imported templates.
+    constants: Arc<[(Marc<str>, KdlValue)]>,
 }
 
 impl Context {
-    pub(crate) fn new(bindings: Bindings) -> Self {
-        Self { arguments: Default::default(), bindings }
+    pub(crate) fn new(bindings: Bindings, fuel: Arc<Fuel>) -> Self {
+        Self {
+            arguments: Default::default(),
+            depth: 0,
+            site: 0,
+            fuel,
+            constants: bindings.constants().into(),
+            bindings,
+        }
     }
+    /// The value of `entry`, substituted with the targument it names, or
+    /// with the constant of a `(const)"name"` entry.
+    ///
+    /// Unknown constants are reported with the expansion errors.
+    pub(crate) fn value(&self, entry: &Smarc<KdlEntry>) -> Smarc<KdlValue> {
+        let value = entry.value();
+        if !is_constant(entry) {
+            return self.arguments.value(&value).cloned().unwrap_or(value);
+        }
+        let name = value.as_string().unwrap_or_default();
+        let constant = self
+            .constants
+            .iter()
+            .find(|(constant, _)| &**constant == name);
+        match constant {
+            // Errors on the constant point to where it is used.
+            Some((_, constant)) => {
+                let constant = constant.clone();
+                value.map(|_| Marc::new(constant))
+            }
+            None => {
+                let unknown = ErrorType::UnknownConstant(name.to_owned());
+                self.fuel.report(Error::new(&value, unknown));
+                value
+            }
+        }
+    }
+    /// `expanded` if there is enough fuel left to replace `invocation` by it.
+    fn fueled(&self, invocation: &NodeThunk, expanded: Vec<NodeThunk>) -> Option<Vec<NodeThunk>> {
+        let site = invocation.site();
+        self.fuel
+            .consume(site, invocation, &expanded)
+            .then_some(expanded)
+    }
+    /// The nodes replacing `invocation`, `None` if it is left as-is.
     // TODO: use a result here
-    pub(crate) fn expand(&self, invocation: &NodeThunk) -> Vec<NodeThunk> {
+    pub(crate) fn expand(&self, invocation: &NodeThunk) -> Option<Vec<NodeThunk>> {
         let invoke_name = invocation.name();
         // argument expension before binding expension, because that's what makes sense
         if let Value::List(mut list) = invocation.value() {
             if list.next().is_none() {
                 if let Some(expanded) = self.arguments.node(invoke_name.value()).cloned() {
-                    return vec![expanded];
+                    return self.fueled(invocation, vec![expanded]);
                 }
             }
         }
         if invoke_name.value() == "expand" {
-            let expand_name = invocation.body.borrowed().entries().next().unwrap().value();
-            let expand_name = expand_name.as_string().unwrap();
-            return self.arguments.expand(expand_name).unwrap();
+            // A malformed or unknown `expand` is left as-is, reading it will fail
+            // with a proper error.
+            let entry = invocation.body.borrowed().entries().next();
+            let expand_name = entry.map(|e| e.value());
+            let expanded = expand_name
+                .as_ref()
+                .and_then(|name| name.as_string())
+                .and_then(|name| self.arguments.expand(name))?;
+            return self.fueled(invocation, expanded);
+        }
+        match self.bindings.invoke(invocation) {
+            Ok(Some(thunk)) => {
+                let expanded = self.fueled(invocation, vec![thunk])?;
+                #[cfg(feature = "trace")]
+                crate::trace::invocation(invocation, &expanded[0]);
+                Some(expanded)
+            }
+            Ok(None) => None,
+            // The invocation is left as-is, like unknown templates.
+            Err(error) => {
+                self.fuel.report(error);
+                None
+            }
         }
-        if let Some(thunk) = self.bindings.invoke(invocation) {
-            return vec![thunk];
+    }
+    /// The entries replacing `entry` if it is an `expand="name"` entry, `name`
+    /// being an `expand` parameter.
+    ///
+    /// Like `expand` nodes, unknown `expand` entries are left as-is.
+    fn expand_entry(&self, entry: &Smarc<KdlEntry>) -> Option<Vec<(Smarc<KdlEntry>, Context)>> {
+        let is_expand = entry.name().map_or(false, |name| name.value() == "expand");
+        if !is_expand {
+            return None;
         }
-        vec![]
+        let expand_name = entry.value();
+        self.arguments.expand_entries(expand_name.as_string()?)
     }
 }
 
@@ -293,64 +423,123 @@ impl NodeThunk {
         let no_children = inner.children().is_none();
         single_entry && no_name && no_children
     }
-    pub(crate) fn new(body: SpannedNode, bindings: Bindings) -> Self {
-        Self { body, context: Context::new(bindings) }
+    pub(crate) fn new(body: SpannedNode, bindings: Bindings, fuel: Arc<Fuel>) -> Self {
+        Self { body, context: Context::new(bindings, fuel) }
+    }
+    /// Identifies this node as an invocation: its span and the invocations
+    /// leading to it. Reading a node again expands it in the same site, while
+    /// two invocations of a template expand its body in two sites.
+    fn site(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let span = self.body.span();
+        (self.context.site, span.offset, span.size).hash(&mut hasher);
+        hasher.finish()
+    }
+    /// The errors found while expanding templates, such as the invocation
+    /// that exceeded the expansion [`Limits`](crate::Limits) of the document,
+    /// or an ambiguous invocation of an imported template.
+    ///
+    /// The errors are shared by all the thunks of the document, they are only
+    /// returned once. [`Self::evaluate`] already reports them.
+    pub fn take_expansion_errors(&self) -> Vec<Error> {
+        self.context.fuel.take_errors()
     }
     pub fn name(&self) -> SpannedIdent {
         self.body.name()
     }
+    /// The entries of this node, with the context to read their value in.
+    ///
+    /// `expand="name"` entries are replaced by the entries of the `name`
+    /// argument.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (Smarc<KdlEntry>, Context)> {
+        let context = self.context.clone();
+        self.body.entries().flat_map(move |entry| {
+            let replacement = context.expand_entry(&entry);
+            replacement.unwrap_or_else(|| vec![(entry, context.clone())])
+        })
+    }
     fn children(&self) -> impl Iterator<Item = NodeThunk> {
         let context = self.context.clone();
         // TODO(PERF): find something slightly more efficient than comparing every node
         // name every encountered with all bindings.
         let with_param_expanded = move |body: SpannedNode| {
             let body = NodeThunk { body, context: context.clone() };
-            let replacement = context.expand(&body);
-            if replacement.is_empty() {
-                vec![body]
-            } else {
-                replacement
-            }
+            context.expand(&body).unwrap_or_else(|| vec![body])
         };
         let doc = self.body.children();
         doc.into_iter()
             .flat_map(|d| d.nodes())
             .flat_map(with_param_expanded)
     }
-    /// Transform recursively this `NodeThunk` into a `KdlNode`.
+    /// Transform this `NodeThunk` into a `KdlNode`, with all its children.
     ///
     /// This forces full immediate evaluation. Other methods of `NodeThunk`,
     /// such as [`Self::children`], [`Self::entries`] and [`Self::fields`] are
     /// lazy and only substitute when necessary. You should prefer them to this
     /// method.
     ///
+    /// Nodes are evaluated with an explicit stack, deeply nested documents
+    /// don't overflow the call stack. The expansion errors are reported once
+    /// the whole node is evaluated, in the order they were found.
+    ///
     /// This is useful for testing.
     pub fn evaluate(self) -> MultiResult<KdlNode, Error> {
         let mut errors = MultiError::default();
-        let mut node = KdlNode::new(self.body.name().value());
-        *node.entries_mut() = self
-            .body
-            .entries()
-            .map(|e| {
-                let value = e.value();
-                let expanded = self.context.arguments.value(&value).cloned();
-                let value = KdlValue::clone(&expanded.unwrap_or(value));
+        let mut stack = vec![Evaluating::new(&self)];
+        let node = loop {
+            // unwrap: the root is the last node popped, ending the loop
+            let top = stack.last_mut().unwrap();
+            if let Some(child) = top.children.next() {
+                stack.push(Evaluating::new(&child));
+                continue;
+            }
+            let node = stack.pop().unwrap().complete();
+            match stack.last_mut() {
+                Some(parent) => parent.evaluated.push(node),
+                None => break node,
+            }
+        };
+        if self.context.depth == 0 {
+            errors.extend_errors(self.take_expansion_errors());
+        }
+        errors.into_result(node)
+    }
+    /// The entries of this node, with their value substituted.
+    pub(crate) fn evaluated_entries(&self) -> Vec<KdlEntry> {
+        self.entries()
+            .map(|(e, context)| {
+                let value = KdlValue::clone(&context.value(&e));
                 if let Some(name) = e.name() {
                     KdlEntry::new_prop(KdlIdentifier::clone(&name), value)
                 } else {
                     KdlEntry::new(value)
                 }
             })
-            .collect();
-        let children: MultiResult<Vec<KdlNode>, _> =
-            self.children().map(|n| n.evaluate()).collect();
-        let children = multi_try!(errors, children);
-        if !children.is_empty() {
+            .collect()
+    }
+}
+/// A node of [`NodeThunk::evaluate`] with children left to evaluate.
+struct Evaluating {
+    node: KdlNode,
+    /// The children of `node`, expanded all at once when `node` is reached.
+    children: std::vec::IntoIter<NodeThunk>,
+    evaluated: Vec<KdlNode>,
+}
+impl Evaluating {
+    fn new(thunk: &NodeThunk) -> Self {
+        let mut node = KdlNode::new(thunk.body.name().value());
+        *node.entries_mut() = thunk.evaluated_entries();
+        let children: Vec<_> = thunk.children().collect();
+        let evaluated = Vec::with_capacity(children.len());
+        Self { node, children: children.into_iter(), evaluated }
+    }
+    fn complete(mut self) -> KdlNode {
+        if !self.evaluated.is_empty() {
             let mut document = KdlDocument::new();
-            *document.nodes_mut() = children;
-            node.set_children(document);
+            *document.nodes_mut() = self.evaluated;
+            self.node.set_children(document);
         }
-        errors.into_result(node)
+        self.node
     }
 }
 impl fmt::Display for NodeThunk {
@@ -0,0 +1,221 @@
+//! Classify the names and values of a document, for semantic highlighting.
+//!
+//! This follows how [`crate::read_document`] resolves bindings, but doesn't
+//! expand anything: each token is where it is declared in the document.
+use kdl::{KdlDocument, KdlEntry, KdlNode};
+use miette::SourceSpan;
+use multierr_span::Span;
+
+use crate::import::RequiredBindings;
+
+/// What a [`Token`] of a document is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// The `import` and `export` nodes, and `expand` tparameters.
+    Keyword,
+    /// The name of a template where it is declared, including the names the
+    /// `import` and `export` nodes declare.
+    TemplateDefinition,
+    /// A tparameter where it is declared, and where the template body uses it.
+    Parameter,
+    /// The name of a node invoking a template.
+    CallSite,
+    /// A type name, such as a component: a node name starting with an
+    /// uppercase letter, or a type annotation.
+    Component,
+    /// A property name, or the name of a child node of a component.
+    Field,
+    /// Any other node name, such as `entity`.
+    Node,
+    /// An entry value.
+    Value,
+}
+
+/// A classified part of a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+fn span(source: &SourceSpan) -> Span {
+    let (offset, size) = (source.offset() as u32, source.len() as u32);
+    Span { offset, size }
+}
+fn children(node: &KdlNode) -> &[KdlNode] {
+    node.children().map_or(&[], KdlDocument::nodes)
+}
+
+struct Classifier<'a> {
+    /// The templates the node being classified can invoke.
+    templates: Vec<&'a str>,
+    tokens: Vec<Token>,
+}
+impl<'a> Classifier<'a> {
+    fn push(&mut self, kind: TokenKind, source: &SourceSpan) {
+        self.tokens.push(Token { kind, span: span(source) });
+    }
+    /// The value of `entry`, which is at the end of the entry, after its name
+    /// and type annotation.
+    fn push_value(&mut self, kind: TokenKind, entry: &KdlEntry) {
+        let entry_span = span(entry.span());
+        let size = match entry.value_repr() {
+            Some(repr) => repr.len() as u32,
+            None => entry.value().to_string().len() as u32,
+        };
+        let end = entry_span.offset + entry_span.size;
+        let span = Span { offset: end.saturating_sub(size), size };
+        self.tokens.push(Token { kind, span });
+    }
+    /// Classify `entries`, property names are `property`.
+    fn entries(&mut self, entries: &[KdlEntry], params: &[&str], property: TokenKind) {
+        for entry in entries {
+            let string = entry.value().as_string();
+            let is_param = string.map_or(false, |string| params.contains(&string));
+            match entry.name() {
+                // `expand="name"` expands the entries of a tparameter
+                Some(name) if is_param && name.value() == "expand" => {
+                    self.push(TokenKind::Keyword, name.span());
+                }
+                Some(name) => self.push(property, name.span()),
+                None => {}
+            }
+            if let Some(ty) = entry.ty() {
+                self.push(TokenKind::Component, ty.span());
+            }
+            let value = if is_param {
+                TokenKind::Parameter
+            } else {
+                TokenKind::Value
+            };
+            self.push_value(value, entry);
+        }
+    }
+    /// Classify `node`, the body of a template with the tparameters `params`,
+    /// `in_component` if it is declared in a component.
+    fn node(&mut self, node: &KdlNode, params: &[&str], in_component: bool) {
+        let name = node.name().value();
+        let kind = if params.contains(&name) {
+            TokenKind::Parameter
+        } else if self.templates.contains(&name) {
+            TokenKind::CallSite
+        } else if name == "expand" && !params.is_empty() {
+            TokenKind::Keyword
+        } else if name.starts_with(char::is_uppercase) {
+            TokenKind::Component
+        } else if in_component {
+            TokenKind::Field
+        } else {
+            TokenKind::Node
+        };
+        self.push(kind, node.name().span());
+        if let Some(ty) = node.ty() {
+            self.push(TokenKind::Component, ty.span());
+        }
+        let property = match kind {
+            TokenKind::CallSite => TokenKind::Parameter,
+            _ => TokenKind::Field,
+        };
+        self.entries(node.entries(), params, property);
+        let in_component = match kind {
+            TokenKind::Component => true,
+            TokenKind::Field => in_component,
+            _ => false,
+        };
+        for child in children(node) {
+            self.node(child, params, in_component);
+        }
+    }
+    /// Classify the template declared by `node`, see [`crate::template`].
+    fn definition(&mut self, node: &'a KdlNode) {
+        self.push(TokenKind::TemplateDefinition, node.name().span());
+        let mut params = Vec::new();
+        for entry in node.entries() {
+            match (entry.name(), entry.value().as_string()) {
+                (Some(name), _) => {
+                    self.push(TokenKind::Parameter, name.span());
+                    self.push_value(TokenKind::Value, entry);
+                    params.push(name.value());
+                }
+                (None, Some(name)) => {
+                    self.push_value(TokenKind::Parameter, entry);
+                    params.push(name);
+                }
+                (None, None) => self.push_value(TokenKind::Value, entry),
+            }
+        }
+        let Some((body, node_params)) = children(node).split_last() else {
+            self.templates.push(node.name().value());
+            return;
+        };
+        for param in node_params {
+            let name = param.name().value();
+            let entries = param.entries();
+            match entries.first().and_then(|e| e.value().as_string()) {
+                Some(expanded) if name == "expand" => {
+                    self.push(TokenKind::Keyword, param.name().span());
+                    self.push_value(TokenKind::Parameter, &entries[0]);
+                    params.push(expanded);
+                    // The default entries of the tparameter
+                    self.entries(&entries[1..], &[], TokenKind::Field);
+                }
+                _ => {
+                    self.push(TokenKind::Parameter, param.name().span());
+                    params.push(name);
+                }
+            }
+            // The default nodes of the tparameter
+            for default in children(param) {
+                self.node(default, &[], false);
+            }
+        }
+        self.node(body, &params, false);
+        // Templates can only be invoked after they are declared.
+        self.templates.push(node.name().value());
+    }
+    /// Classify the `import` or `export` node `node`.
+    fn keyword(&mut self, node: &KdlNode) {
+        self.push(TokenKind::Keyword, node.name().span());
+        for entry in node.entries() {
+            if let Some(name) = entry.name() {
+                self.push(TokenKind::TemplateDefinition, name.span());
+            }
+            self.push_value(TokenKind::Value, entry);
+        }
+        for child in children(node) {
+            self.push(TokenKind::TemplateDefinition, child.name().span());
+            for entry in child.entries() {
+                self.push_value(TokenKind::Value, entry);
+            }
+        }
+    }
+}
+
+/// The tokens of `document`, sorted by offset, to highlight it semantically.
+///
+/// `required` are the templates `document` imports, node names invoking them
+/// are [`TokenKind::CallSite`]s. Unlike [`crate::read_document`], this never
+/// fails, the nodes are classified by how they are declared.
+pub fn classify_tokens(document: &KdlDocument, required: &RequiredBindings) -> Vec<Token> {
+    let mut classifier = Classifier {
+        templates: required.0.imported_names().collect(),
+        tokens: Vec::new(),
+    };
+    let nodes = document.nodes();
+    let (value, declarations) = match nodes.split_last() {
+        Some((last, rest)) if last.name().value() != "export" => (Some(last), rest),
+        _ => (None, nodes),
+    };
+    for node in declarations {
+        match node.name().value() {
+            "import" | "export" => classifier.keyword(node),
+            _ => classifier.definition(node),
+        }
+    }
+    if let Some(value) = value {
+        classifier.node(value, &[], false);
+    }
+    let mut tokens = classifier.tokens;
+    tokens.sort_by_key(|token| token.span.offset);
+    tokens
+}
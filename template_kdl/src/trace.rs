@@ -0,0 +1,31 @@
+//! Print template invocations to stderr, with the `trace` feature.
+//!
+//! Each invocation prints the template name, the targuments it was called
+//! with and the node it expands to, indented by expansion depth. Since
+//! expansion is lazy, the children of the expanded node are printed as
+//! declared, and nested invocations are printed when they are read. An
+//! invocation is printed each time it is read.
+use kdl::KdlNode;
+
+use crate::template::NodeThunk;
+
+/// Print `invocation`, expanded into `expanded`.
+///
+/// Only the entries of `expanded` are substituted, evaluating its children
+/// would expand them, consuming the [`Limits`](crate::Limits) of the
+/// document even if they are never read.
+pub(crate) fn invocation(invocation: &NodeThunk, expanded: &NodeThunk) {
+    let indent = "  ".repeat(invocation.context.depth as usize);
+    let name = invocation.name();
+    let arguments = expanded.context.arguments.bound();
+    eprintln!("{indent}{}{arguments}", name.value());
+
+    let mut node = KdlNode::new(expanded.name().value());
+    *node.entries_mut() = expanded.evaluated_entries();
+    if let Some(children) = expanded.body.inner.children() {
+        node.set_children(children.clone());
+    }
+    for line in node.to_string().trim().lines() {
+        eprintln!("{indent}| {line}");
+    }
+}
@@ -0,0 +1,94 @@
+//! Spans of nodes and entries stay accurate around comments, slashdashed
+//! nodes and entries, line continuations and unusual whitespace.
+use kdl::{KdlDocument, KdlIdentifier, KdlValue};
+use multierr_span::{Span, Spanned};
+use template_kdl::{
+    navigate::{Navigable, SpannedField, Value},
+    read_plain,
+};
+
+/// The text of the names and bare values of `field` and its fields, in
+/// declaration order.
+fn field_texts(field: SpannedField, text: &str, texts: &mut Vec<String>) {
+    let mut push = |span: Span| {
+        texts.push(text[span.offset as usize..span.end() as usize].to_owned());
+    };
+    if let Some(name) = field.name() {
+        push(name.span());
+    }
+    match field.value() {
+        Value::Bare(value) => push(value.span()),
+        Value::List(fields) => fields.for_each(|field| field_texts(field, text, texts)),
+    }
+}
+fn texts(document: KdlDocument) -> Vec<String> {
+    let text = document.to_string();
+    let mut texts = Vec::new();
+    for node in read_plain(document) {
+        field_texts(node, &text, &mut texts);
+    }
+    texts
+}
+
+#[test]
+fn comments_and_slashdash() {
+    let text = r#"// leading comment
+/- Disabled 1 { Child; }
+/* multi
+   line */ Point /- 0 x=1.0 /* inline */ y=2.0 /- z=3.0 ;
+Root	/- "ignored" {  /* after brace */
+    Child "a" /- "b"   ; // trailing
+    /-Skipped
+    Other 3 \
+        4 /-{ Gone; }
+    /* a
+     * long comment */
+    Last	　key="value"
+}
+/- Trailing
+"#;
+    let expected = [
+        "Point",
+        "x",
+        "1.0",
+        "y",
+        "2.0",
+        "Root",
+        "Child",
+        "\"a\"",
+        "Other",
+        "3",
+        "4",
+        "Last",
+        "key",
+        "\"value\"",
+    ];
+    assert_eq!(texts(text.parse().unwrap()), expected);
+}
+
+#[test]
+fn semicolons() {
+    let text = "First 1;Second 2 ;  Third { Child 3; };Fourth 4;\n";
+    let expected = [
+        "First", "1", "Second", "2", "Third", "Child", "3", "Fourth", "4",
+    ];
+    assert_eq!(texts(text.parse().unwrap()), expected);
+}
+
+#[test]
+fn edited_names_and_strings() {
+    let mut document: KdlDocument = "node \"value\" key=1\nother 2".parse().unwrap();
+    let node = &mut document.nodes_mut()[0];
+    // Set names and values print without their repr, quoted when needed
+    node.set_name(KdlIdentifier::from("two words"));
+    node.entries_mut()[0].set_value(KdlValue::String("a \"quote\"\n\tand\\".to_owned()));
+    let expected = [
+        r#""two words""#,
+        r#""a \"quote\"\n\tand\\""#,
+        "key",
+        "1",
+        "other",
+        "2",
+    ];
+    assert_eq!(texts(document), expected);
+}
@@ -0,0 +1,70 @@
+//! `completions_at` finds the bindings, tparameters and targuments valid at a
+//! position, even in incomplete documents.
+use template_kdl::analysis::{completions_at, parse_tolerant, Completions};
+
+/// The completions at the `|` in `text`.
+fn completions(text: &str) -> Completions {
+    let offset = text.find('|').unwrap();
+    let text = text.replacen('|', "", 1);
+    completions_at(&text, offset, &Default::default())
+}
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+const TEMPLATES: &str = r#"label "text" { Text "text"; }
+button "label" {
+  expand "style"
+  Button
+}
+"#;
+
+#[test]
+fn template_body() {
+    let text = r#"label "text" { Text "text"; }
+button "label" {
+  expand "style"
+  Button {
+    |
+  }
+}
+root { button; }"#;
+    let expected = Completions {
+        bindings: strings(&["label"]),
+        parameters: strings(&["label", "style"]),
+        fields: Vec::new(),
+    };
+    assert_eq!(completions(text), expected);
+}
+
+#[test]
+fn unclosed_children() {
+    let text = format!("{TEMPLATES}root {{\n  button label=\"Ok\" {{\n    |");
+    let expected = Completions {
+        bindings: strings(&["button", "label"]),
+        parameters: Vec::new(),
+        fields: strings(&["style"]),
+    };
+    assert_eq!(completions(&text), expected);
+}
+
+#[test]
+fn unclosed_string() {
+    let text = format!("{TEMPLATES}root {{\n  button \"O|\n}}\n");
+    let expected = Completions {
+        bindings: strings(&["button", "label"]),
+        parameters: Vec::new(),
+        fields: strings(&["style"]),
+    };
+    assert_eq!(completions(&text), expected);
+}
+
+#[test]
+fn drops_incomplete_line() {
+    let text = "root {\n  Node value=|\n}\n";
+    let offset = text.find('|').unwrap();
+    let text = text.replacen('|', "", 1);
+    let document = parse_tolerant(&text, offset).unwrap();
+    assert_eq!(document.nodes().len(), 1);
+    assert_eq!(document.nodes()[0].children().unwrap().nodes().len(), 0);
+}
@@ -0,0 +1,95 @@
+//! Constants injected by the host are substituted in `(const)"name"` entries.
+
+use kdl::{KdlDocument, KdlNode, KdlValue, NodeKey};
+use template_kdl::{
+    err::ErrorType, get_imports, read_document, Document, ExportedBindingsList, RequiredBindings,
+};
+
+fn expand(text: &str, required: RequiredBindings) -> Result<KdlNode, Vec<ErrorType>> {
+    let document: KdlDocument = text.parse().unwrap();
+    match read_document(document, required).into_result() {
+        Ok(Document::Node(node)) => node
+            .evaluate()
+            .into_result()
+            .map_err(|errors| errors.into_iter().map(|err| err.source).collect()),
+        _ => panic!("text must be a node"),
+    }
+}
+fn get(node: &KdlNode, key: impl Into<NodeKey>) -> KdlValue {
+    node.get(key).unwrap().value().clone()
+}
+fn string(value: &str) -> KdlValue {
+    KdlValue::String(value.to_owned())
+}
+
+#[test]
+fn constants_in_node_and_templates() {
+    let text = r#"
+    panel "width" {
+      Panel width="width" height=(const)"screen_height"
+    }
+    Root width=(const)"screen_width" profile=(const)"build_profile" "unrelated" {
+      panel 100
+      panel (const)"screen_width"
+    }
+    "#;
+    let required = RequiredBindings::default()
+        .with_constant("screen_width", 1920_i64)
+        .with_constant("screen_height", 1080_i64)
+        .with_constant("build_profile", "debug")
+        .with_constant("build_profile", "release");
+    let root = expand(text, required).unwrap();
+    assert_eq!(get(&root, "width"), KdlValue::Base10(1920));
+    assert_eq!(get(&root, "profile"), string("release"));
+    assert_eq!(get(&root, 0), string("unrelated"));
+
+    let panels = root.children().unwrap().nodes();
+    assert_eq!(get(&panels[0], "width"), KdlValue::Base10(100));
+    assert_eq!(get(&panels[0], "height"), KdlValue::Base10(1080));
+    // Constants can be passed as targuments
+    assert_eq!(get(&panels[1], "width"), KdlValue::Base10(1920));
+}
+
+#[test]
+fn strings_named_like_constants_are_kept() {
+    let text = r#"Root title="width" width=(const)"width""#;
+    let required = RequiredBindings::default().with_constant("width", 1920_i64);
+    let root = expand(text, required).unwrap();
+    assert_eq!(get(&root, "title"), string("width"));
+    assert_eq!(get(&root, "width"), KdlValue::Base10(1920));
+}
+
+#[test]
+fn constants_in_imported_templates() {
+    let lib = r#"
+    panel "title" {
+      Panel title="title" width=(const)"screen_width"
+    }
+    export "panel"
+    "#;
+    let lib = match read_document(lib.parse().unwrap(), Default::default()).into_result() {
+        Ok(Document::Exports(exports)) => exports,
+        _ => panic!("lib must be an export file"),
+    };
+    let list: ExportedBindingsList = [("lib.kdl", lib)].into_iter().collect();
+    let text = r#"
+    import panel="lib.kdl/panel"
+    Root { panel "menu"; }
+    "#;
+    let imports = get_imports(&text.parse().unwrap()).unwrap();
+    let required = imports.bindings(&list).unwrap();
+    let root = expand(text, required.with_constant("screen_width", 1920_i64)).unwrap();
+    let panel = &root.children().unwrap().nodes()[0];
+    assert_eq!(get(panel, "title"), string("menu"));
+    assert_eq!(get(panel, "width"), KdlValue::Base10(1920));
+}
+
+#[test]
+fn unknown_constant() {
+    let text = r#"Root width=(const)"screen_width""#;
+    let errors = expand(text, RequiredBindings::default()).unwrap_err();
+    assert_eq!(
+        errors,
+        [ErrorType::UnknownConstant("screen_width".to_owned())]
+    );
+}
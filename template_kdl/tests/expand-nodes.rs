@@ -0,0 +1,50 @@
+//! `expand` nodes in template bodies insert the children of their targument,
+//! the ones naming no `expand` tparameter are left as-is.
+use kdl::{KdlDocument, KdlNode};
+use template_kdl::read_thunk;
+
+/// `node` without formatting, `name entries {children}`.
+fn canonical(node: &KdlNode) -> String {
+    let mut out = node.name().value().to_owned();
+    for entry in node.entries() {
+        out += &format!(" {}", entry.value());
+    }
+    if let Some(children) = node.children() {
+        let children: Vec<_> = children.nodes().iter().map(canonical).collect();
+        out += &format!(" {{{}}}", children.join("; "));
+    }
+    out
+}
+fn expand(text: &str) -> String {
+    let document: KdlDocument = text.parse().unwrap();
+    let thunk = read_thunk(document).into_result().unwrap();
+    canonical(&thunk.evaluate().into_result().unwrap())
+}
+
+#[test]
+fn expands_children() {
+    let text = r#"
+    machine { expand "metadata"; Machine { expand "metadata"; Last; }; }
+    root { machine { metadata { First; Second; }; }; }
+    "#;
+    assert_eq!(expand(text), "root {Machine {First; Second; Last}}");
+}
+
+#[test]
+fn empty_targument_expands_to_nothing() {
+    let text = r#"
+    machine { expand "metadata"; Machine { expand "metadata"; Last; }; }
+    root { machine { metadata; }; }
+    "#;
+    assert_eq!(expand(text), "root {Machine {Last}}");
+}
+
+#[test]
+fn unknown_and_malformed_left_as_is() {
+    let text = r#"
+    machine { expand "metadata"; Machine { expand "other"; expand 3; Last; }; }
+    root { machine { metadata { First; }; }; }
+    "#;
+    let expected = r#"root {Machine {expand "other"; expand 3; Last}}"#;
+    assert_eq!(expand(text), expected);
+}
@@ -0,0 +1,75 @@
+//! Template expansion stops with an error when exceeding the `Limits`.
+use kdl::KdlDocument;
+use template_kdl::{
+    err::ErrorType, navigate::Navigable, read_document_with_limits, template::NodeThunk, Document,
+    Limits,
+};
+
+/// Three levels of templates, each invoking the previous one four times.
+const EXPLOSIVE: &str = r#"
+a { A; }
+b { B { a; a; a; a; }; }
+c { C { b; b; b; b; }; }
+root { c; c; c; c; }
+"#;
+
+fn read(text: &str, limits: Limits) -> NodeThunk {
+    let document: KdlDocument = text.parse().unwrap();
+    match read_document_with_limits(document, Default::default(), limits).into_result() {
+        Ok(Document::Node(node)) => node,
+        _ => panic!("text must be a valid node"),
+    }
+}
+fn evaluate(text: &str, limits: Limits) -> Result<String, Vec<ErrorType>> {
+    evaluate_node(read(text, limits))
+}
+fn evaluate_node(node: NodeThunk) -> Result<String, Vec<ErrorType>> {
+    match node.evaluate().into_result() {
+        Ok(node) => Ok(node.to_string()),
+        Err(errors) => Err(errors.into_iter().map(|e| e.source).collect()),
+    }
+}
+
+#[test]
+fn unlimited_by_default() {
+    assert!(evaluate(EXPLOSIVE, Limits::default()).is_ok());
+}
+
+#[test]
+fn within_limits() {
+    // 4 c + 16 b + 64 a
+    let limits = Limits::new().max_nodes(Some(84));
+    assert!(evaluate(EXPLOSIVE, limits).is_ok());
+}
+
+#[test]
+fn too_many_nodes() {
+    let limits = Limits::new().max_nodes(Some(83));
+    let expected = vec![ErrorType::TooManyNodes(83)];
+    assert_eq!(evaluate(EXPLOSIVE, limits), Err(expected));
+}
+
+#[test]
+fn too_large_expansion() {
+    let limits = Limits::new().max_size(Some(100));
+    let expected = vec![ErrorType::TooLargeExpansion(100)];
+    assert_eq!(evaluate(EXPLOSIVE, limits), Err(expected));
+}
+
+#[test]
+fn read_again_within_limits() {
+    let limits = Limits::new().max_nodes(Some(84));
+    let node = read(EXPLOSIVE, limits);
+    // The nodes expanded by the first evaluation are not counted again
+    for _ in 0..3 {
+        assert!(evaluate_node(node.clone()).is_ok());
+    }
+}
+
+#[test]
+fn read_lazily_within_limits() {
+    // Reading the children of root only expands the 4 c
+    let node = read(EXPLOSIVE, Limits::new().max_nodes(Some(4)));
+    assert_eq!(node.value().unwrap_list().count(), 4);
+    assert!(node.take_expansion_errors().is_empty());
+}
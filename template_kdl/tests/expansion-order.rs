@@ -0,0 +1,122 @@
+//! Template expansion must not depend on hashing, so that the same file always
+//! expands to the same nodes.
+
+use kdl::{KdlDocument, KdlNode};
+use template_kdl::{
+    err::ErrorType, get_imports, read_document, read_thunk, Document, ExportedBindingsList,
+};
+
+/// `node` without formatting, `name entries {children}`.
+fn canonical(node: &KdlNode) -> String {
+    let mut out = node.name().value().to_owned();
+    for entry in node.entries() {
+        match entry.name() {
+            Some(name) => out += &format!(" {}={}", name.value(), entry.value()),
+            None => out += &format!(" {}", entry.value()),
+        }
+    }
+    if let Some(children) = node.children() {
+        let children: Vec<_> = children.nodes().iter().map(canonical).collect();
+        out += &format!(" {{{}}}", children.join("; "));
+    }
+    out
+}
+fn expand(text: &str) -> String {
+    let document: KdlDocument = text.parse().unwrap();
+    let thunk = read_thunk(document).into_result().unwrap();
+    canonical(&thunk.evaluate().into_result().unwrap())
+}
+fn expand_with_lib(lib: &str, text: &str) -> Result<String, ErrorType> {
+    let lib = match read_document(lib.parse().unwrap(), Default::default()).into_result() {
+        Ok(Document::Exports(exports)) => exports,
+        _ => panic!("lib must be an export file"),
+    };
+    let list: ExportedBindingsList = [("lib.kdl", lib)].into_iter().collect();
+    let document: KdlDocument = text.parse().unwrap();
+    let imports = get_imports(&document).unwrap();
+    let required = imports.bindings(&list).map_err(|err| err.source)?;
+    match read_document(document, required).into_result() {
+        Ok(Document::Node(node)) => Ok(canonical(&node.evaluate().into_result().unwrap())),
+        _ => panic!("text must be a node"),
+    }
+}
+
+#[test]
+fn arguments_and_children_in_declaration_order() {
+    let text = r#"
+    point "x" "y" z=0 {
+      Point x="x" y="y" z="z"
+    }
+    group "@marker" {
+      Group {
+        @marker
+        point 1 2
+        point y=4 x=3 z=5
+      }
+    }
+    root {
+      group {
+        Marker "a"
+      }
+      point 7 8
+    }"#;
+    let expected =
+        r#"root {Group {Marker "a"; Point x=1 y=2 z=0; Point x=3 y=4 z=5}; Point x=7 y=8 z=0}"#;
+    // Each run creates new hash maps, with a different random seed.
+    for _ in 0..32 {
+        assert_eq!(expand(text), expected);
+    }
+}
+
+#[test]
+fn last_import_shadows_earlier_ones() {
+    let lib = r#"
+    a { A; }
+    b { B; }
+    export a="a" b="b"
+    "#;
+    let text = r#"
+    import x="lib.kdl/a" x="lib.kdl/b"
+    root { x; }
+    "#;
+    for _ in 0..32 {
+        assert_eq!(expand_with_lib(lib, text), Ok("root {B}".to_owned()));
+    }
+}
+
+#[test]
+fn missing_imports_in_declaration_order() {
+    let lib = r#"
+    a { A; }
+    export a="a"
+    "#;
+    let text = r#"
+    import z="lib.kdl/z" a="lib.kdl/a" y="lib.kdl/y" x="lib.kdl/x"
+    root { a; }
+    "#;
+    let missing = ["lib.kdl/z", "lib.kdl/y", "lib.kdl/x"].map(str::to_owned);
+    for _ in 0..32 {
+        let expected = ErrorType::MissingTemplates(missing.to_vec());
+        assert_eq!(expand_with_lib(lib, text), Err(expected));
+    }
+}
+
+#[test]
+fn whole_file_import_after_template_import() {
+    let lib = r#"
+    a { A; }
+    b { B; }
+    export a="a" b="b"
+    "#;
+    let text = r#"
+    import b="lib.kdl/a" "lib.kdl"
+    root { a; b; }
+    "#;
+    for _ in 0..32 {
+        assert_eq!(expand_with_lib(lib, text), Ok("root {A; B}".to_owned()));
+    }
+    let missing = vec!["other.kdl".to_owned()];
+    let text = r#"import "other.kdl"; root;"#;
+    let expected = ErrorType::MissingTemplates(missing);
+    assert_eq!(expand_with_lib(lib, text), Err(expected));
+}
@@ -0,0 +1,137 @@
+//! The `export` node accepts `name` and `local="exported"` entries, anything
+//! else is reported as an error. It may be followed by the node the file
+//! represents.
+
+use kdl::KdlDocument;
+use template_kdl::{err::ErrorType, get_imports, read_document, Document, ExportedBindingsList};
+
+const TEMPLATES: &str = r#"
+a { A; }
+b { B; }
+"#;
+
+fn read_exports(export: &str) -> Result<Document, Vec<ErrorType>> {
+    let document: KdlDocument = format!("{TEMPLATES}{export}").parse().unwrap();
+    let result = read_document(document, Default::default()).into_result();
+    result.map_err(|errs| errs.into_iter().map(|err| err.source).collect())
+}
+/// The name of the node `a` and `b` expand to, when imported as `name` from
+/// a file with the given `export` node.
+fn expand_import(export: &str, name: &str) -> String {
+    let lib = match read_exports(export) {
+        Ok(Document::Exports(exports)) => exports,
+        other => panic!("{export}: expected exports, got {other:?}"),
+    };
+    let list: ExportedBindingsList = [("lib.kdl", lib)].into_iter().collect();
+    let text = format!(r#"import x="lib.kdl/{name}"; root {{ x; }}"#);
+    let document: KdlDocument = text.parse().unwrap();
+    let imports = get_imports(&document).unwrap();
+    let required = imports.bindings(&list).unwrap();
+    match read_document(document, required).into_result() {
+        Ok(Document::Node(node)) => {
+            let node = node.evaluate().into_result().unwrap();
+            let children = node.children().unwrap().nodes();
+            children[0].name().value().to_owned()
+        }
+        other => panic!("{export}: expected a node, got {other:?}"),
+    }
+}
+
+#[test]
+fn accepted_forms() {
+    assert_eq!(expand_import(r#"export "a" "b""#, "b"), "B");
+    assert_eq!(expand_import("export { a; b; }", "a"), "A");
+    assert_eq!(expand_import(r#"export a="c""#, "c"), "A");
+    assert_eq!(expand_import(r#"export { b "c"; }"#, "c"), "B");
+}
+
+#[test]
+fn malformed_entries() {
+    let malformed = [
+        "export 1",
+        "export a=1",
+        "export null",
+        "export { a 1; }",
+        r#"export { a "b" "c"; }"#,
+        r#"export { a x="b"; }"#,
+        "export { a { A; }; }",
+    ];
+    for export in malformed {
+        assert_eq!(
+            read_exports(export).unwrap_err(),
+            vec![ErrorType::BadExport],
+            "{export}"
+        );
+    }
+}
+
+#[test]
+fn each_malformed_entry_is_reported() {
+    let errors = read_exports(r#"export "a" 1 b=2 { b; c 3; }"#).unwrap_err();
+    assert_eq!(errors, vec![ErrorType::BadExport; 3]);
+}
+
+#[test]
+fn exports_and_node() {
+    let text = r#"
+    a { A; }
+    export "a" "b"
+    b { B; }
+    demo { a; b; }
+    "#;
+    let document = read_document(text.parse().unwrap(), Default::default());
+    let Ok(Document::NodeAndExports(node, exports)) = document.into_result() else {
+        panic!("expected both a node and exports");
+    };
+    let node = node.evaluate().into_result().unwrap();
+    let children: Vec<_> = node.children().unwrap().nodes().iter().collect();
+    assert_eq!(children[0].name().value(), "A");
+    assert_eq!(children[1].name().value(), "B");
+
+    let list: ExportedBindingsList = [("lib.kdl", exports)].into_iter().collect();
+    let text = r#"import x="lib.kdl/b"; root { x; }"#;
+    let document: KdlDocument = text.parse().unwrap();
+    let imports = get_imports(&document).unwrap();
+    let required = imports.bindings(&list).unwrap();
+    let Ok(Document::Node(node)) = read_document(document, required).into_result() else {
+        panic!("expected a node");
+    };
+    let node = node.evaluate().into_result().unwrap();
+    assert_eq!(node.children().unwrap().nodes()[0].name().value(), "B");
+}
+
+#[test]
+fn single_export_node() {
+    let errors = read_exports(r#"export "a"; export "b""#).unwrap_err();
+    assert_eq!(errors, vec![ErrorType::MultipleExports]);
+    let errors = read_exports(r#"export "a"; export "b"; demo { a; }"#).unwrap_err();
+    assert_eq!(errors, vec![ErrorType::MultipleExports]);
+}
+
+#[test]
+fn merged_lists() {
+    let exports = |export| match read_exports(export) {
+        Ok(Document::Exports(exports)) => exports,
+        other => panic!("{export}: expected exports, got {other:?}"),
+    };
+    let mut list: ExportedBindingsList = [("lib.kdl", exports(r#"export a="x""#))]
+        .into_iter()
+        .collect();
+    let mut newer = ExportedBindingsList::new();
+    newer.insert("lib.kdl", exports(r#"export b="x""#));
+    newer.insert("other.kdl", exports(r#"export "a""#));
+    list.merge(newer);
+
+    let document: KdlDocument = r#"import x="lib.kdl/x" a="other.kdl/a"; root { x; a; }"#
+        .parse()
+        .unwrap();
+    let imports = get_imports(&document).unwrap();
+    let required = imports.bindings(&list).unwrap();
+    let Ok(Document::Node(node)) = read_document(document, required).into_result() else {
+        panic!("expected a node");
+    };
+    let node = node.evaluate().into_result().unwrap();
+    let children = node.children().unwrap().nodes();
+    assert_eq!(children[0].name().value(), "B");
+    assert_eq!(children[1].name().value(), "A");
+}
@@ -0,0 +1,75 @@
+//! `fmt_document` normalizes whitespace but keeps comments in place.
+use kdl::KdlDocument;
+use pretty_assertions::assert_eq;
+use template_kdl::fmt_document;
+
+fn format(text: &str) -> String {
+    let mut document: KdlDocument = text.parse().unwrap();
+    fmt_document(&mut document);
+    document.to_string()
+}
+
+#[test]
+fn normalizes_spacing() {
+    let text = r#"
+  point "x"   "y" z=0 {
+        Point  x="x"   y="y"
+      Other   1;   Another 2
+  }
+root    {
+point 1 \
+   2
+  }
+"#;
+    let expected = r#"point "x" "y" z=0 {
+    Point x="x" y="y"
+    Other 1
+    Another 2
+}
+root {
+    point 1 2
+}
+"#;
+    assert_eq!(format(text), expected);
+}
+
+#[test]
+fn keeps_comments() {
+    let text = r#"// leading comment
+point   "x" /* inline */ "y"   // trailing
+/- Disabled 1
+   root /- "slashdashed"   3 /* before children */ {
+  /* block
+     comment */
+  point  1 2;
+}
+"#;
+    let expected = r#"// leading comment
+point "x" /* inline */ "y" // trailing
+/- Disabled 1
+root /- "slashdashed" 3 /* before children */ {
+    /* block
+     comment */
+    point 1 2
+}
+"#;
+    assert_eq!(format(text), expected);
+}
+
+#[test]
+fn idempotent() {
+    let text = r#"
+point "x" /* inline */ "y" {
+  Point x="x" /- y="y" // trailing
+}
+"#;
+    let formatted = format(text);
+    assert_eq!(format(&formatted), formatted);
+}
+
+#[test]
+fn keeps_trailing_slashdash() {
+    let text = "Point x=1   /- y=2\nPoint x=1 /- y=2 // trailing\nPoint x=1 /-{\n  Child\n}\n";
+    let expected = "Point x=1 /- y=2\nPoint x=1 /- y=2 // trailing\nPoint x=1 /-{\n  Child\n}\n";
+    assert_eq!(format(text), expected);
+}
@@ -0,0 +1,71 @@
+//! Evaluating large generated documents doesn't overflow the stack.
+use kdl::{KdlDocument, KdlNode};
+use template_kdl::{read_document, Document};
+
+/// How many `level` nodes are nested in each other.
+const DEPTH: usize = 1_000;
+/// How many `leaf` template invocations each `level` has.
+const LEAVES: usize = 49;
+
+fn with_children(name: &str, children: impl IntoIterator<Item = KdlNode>) -> KdlNode {
+    let mut node = KdlNode::new(name);
+    let mut document = KdlDocument::new();
+    document.nodes_mut().extend(children);
+    node.set_children(document);
+    node
+}
+
+/// A `leaf { Leaf 1; }` template, and `DEPTH` nested `level` nodes each
+/// invoking it `LEAVES` times: 50k nodes once evaluated.
+///
+/// The document is built rather than parsed, parsing it would recurse as
+/// deep as it is nested.
+fn generated() -> KdlDocument {
+    let mut leaf = KdlNode::new("Leaf");
+    leaf.push(1_i64);
+    let template = with_children("leaf", [leaf]);
+    let mut level = None;
+    for _ in 0..DEPTH {
+        let leaves = (0..LEAVES).map(|_| KdlNode::new("leaf"));
+        level = Some(with_children("level", leaves.chain(level.take())));
+    }
+    let mut document = KdlDocument::new();
+    *document.nodes_mut() = vec![template, level.unwrap()];
+    document
+}
+
+/// The node count and the depth of `node`, without recursion.
+fn shape(node: &KdlNode) -> (usize, usize) {
+    let (mut count, mut depth) = (0, 0);
+    let mut stack = vec![(node, 1)];
+    while let Some((node, level)) = stack.pop() {
+        count += 1;
+        depth = depth.max(level);
+        let children = node.children().map_or(&[][..], |c| c.nodes());
+        stack.extend(children.iter().map(|child| (child, level + 1)));
+    }
+    (count, depth)
+}
+
+#[test]
+fn deeply_nested() {
+    let node = match read_document(generated(), Default::default()).into_result() {
+        Ok(Document::Node(node)) => node,
+        _ => panic!("the generated document must be a node"),
+    };
+    let node = node.evaluate().into_result().unwrap();
+    // The innermost `level` has `LEAVES` `Leaf` nodes, each level above it
+    // adds itself and its own leaves.
+    assert_eq!(shape(&node), (DEPTH * (LEAVES + 1), DEPTH + 1));
+
+    let mut level = &node;
+    while let Some(children) = level.children() {
+        let (leaves, rest) = children.nodes().split_at(LEAVES);
+        assert!(leaves.iter().all(|l| l.name().value() == "Leaf"));
+        match rest {
+            [next] => level = next,
+            [] => break,
+            _ => panic!("each level has a single nested level"),
+        }
+    }
+}
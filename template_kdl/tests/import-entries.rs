@@ -0,0 +1,131 @@
+//! The entries of the `import` node are exposed with their spans, each
+//! unresolved entry can be reported with its own error. Templates exported by
+//! several files imported as a whole must be invoked with a namespace.
+use kdl::KdlDocument;
+use template_kdl::{err::ErrorType, get_imports, read_document, Document, ExportedBindingsList};
+
+const LIB: &str = r#"
+button { Button; }
+label { Label; }
+export "button" "label"
+"#;
+
+const OTHER: &str = r#"
+button { OtherButton; }
+export "button"
+"#;
+
+fn lib() -> ExportedBindingsList {
+    let exports = |text: &str| {
+        let document = text.parse().unwrap();
+        match read_document(document, Default::default()).into_result() {
+            Ok(Document::Exports(exports)) => exports,
+            _ => panic!("lib must be an export file"),
+        }
+    };
+    [("ui.kdl", exports(LIB)), ("other.kdl", exports(OTHER))]
+        .into_iter()
+        .collect()
+}
+/// The names of the children of the node `text` represents.
+fn expand(text: &str) -> Result<Vec<String>, Vec<ErrorType>> {
+    let document: KdlDocument = text.parse().unwrap();
+    let imports = get_imports(&document).unwrap();
+    let required = imports.bindings(&lib()).unwrap();
+    let Ok(Document::Node(node)) = read_document(document, required).into_result() else {
+        panic!("expected a node");
+    };
+    match node.evaluate().into_result() {
+        Ok(node) => {
+            let children = node.children().unwrap().nodes();
+            Ok(children
+                .iter()
+                .map(|n| n.name().value().to_owned())
+                .collect())
+        }
+        Err(errors) => Err(errors.into_iter().map(|e| e.source).collect()),
+    }
+}
+
+#[test]
+fn structured_entries() {
+    let text = r#"import b="ui.kdl/button" "ui.kdl" as="ui"; root;"#;
+    let document: KdlDocument = text.parse().unwrap();
+    let imports = get_imports(&document).unwrap();
+    let entries = imports.entries();
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].file(), Some("ui.kdl"));
+    assert_eq!(entries[0].binding(), Some("button"));
+    assert_eq!(entries[0].name.as_deref(), Some("b"));
+    let span = entries[0].span;
+    let declared = &text[span.offset as usize..span.end() as usize];
+    assert_eq!(declared, r#"b="ui.kdl/button""#);
+
+    assert_eq!(entries[1].file(), Some("ui.kdl"));
+    assert_eq!(entries[1].binding(), None);
+    assert_eq!(entries[1].alias.as_deref(), Some("ui"));
+}
+
+#[test]
+fn precise_errors() {
+    let text = r#"import b="ui.kdl/button" c="ui.kdl/checkbox" "missing.kdl" d="nofile"; root;"#;
+    let document: KdlDocument = text.parse().unwrap();
+    let imports = get_imports(&document).unwrap();
+    let list = lib();
+    let errors: Vec<_> = imports
+        .entries()
+        .iter()
+        .filter_map(|import| import.missing(&list))
+        .map(|err| err.source)
+        .collect();
+    let not_exported = ErrorType::NotExported {
+        binding: "checkbox".to_owned(),
+        file: "ui.kdl".to_owned(),
+    };
+    let expected = vec![
+        not_exported,
+        ErrorType::NotLoaded("missing.kdl".to_owned()),
+        ErrorType::BadImportPath("nofile".to_owned()),
+    ];
+    assert_eq!(errors, expected);
+}
+
+#[test]
+fn aliased_namespace() {
+    let text = r#"import "ui.kdl" as="ui"; root { ui:button; ui:label; }"#;
+    assert_eq!(
+        expand(text),
+        Ok(vec!["Button".to_owned(), "Label".to_owned()])
+    );
+}
+
+#[test]
+fn ambiguous_invocation() {
+    let ambiguous = ErrorType::AmbiguousTemplate {
+        name: "button".to_owned(),
+        files: vec!["ui.kdl".to_owned(), "other.kdl".to_owned()],
+    };
+    let text = r#"import "ui.kdl" "other.kdl"; root { label; button; }"#;
+    assert_eq!(expand(text), Err(vec![ambiguous]));
+
+    let names = |names: &[&str]| Ok(names.iter().map(|n| n.to_string()).collect());
+    let text = r#"import "ui.kdl" "other.kdl" as="other"; root { button; other:button; }"#;
+    assert_eq!(expand(text), names(&["Button", "OtherButton"]));
+    let text = r#"import "ui.kdl" "other.kdl" button="other.kdl/button"; root { button; }"#;
+    assert_eq!(expand(text), names(&["OtherButton"]));
+    let text = r#"import "ui.kdl" "other.kdl"; button { Local; }; root { button; }"#;
+    assert_eq!(expand(text), names(&["Local"]));
+}
+
+#[test]
+fn misplaced_alias() {
+    for text in [
+        r#"import as="ui"; root;"#,
+        r#"import b="ui.kdl/button" as="ui"; root;"#,
+    ] {
+        let document: KdlDocument = text.parse().unwrap();
+        let error = get_imports(&document).err().unwrap();
+        assert_eq!(error.source, ErrorType::BadImportAlias, "{text}");
+    }
+}
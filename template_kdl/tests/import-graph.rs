@@ -0,0 +1,69 @@
+//! The import graph lists the templates imported through each edge.
+use kdl::KdlDocument;
+use template_kdl::{
+    get_imports, graph::ImportGraph, read_document, Document, ExportedBindingsList,
+};
+
+fn exports(text: &str) -> template_kdl::ExportedBindings {
+    let document: KdlDocument = text.parse().unwrap();
+    match read_document(document, Default::default()).into_result() {
+        Ok(Document::Exports(exports)) => exports,
+        other => panic!("expected exports, got {other:?}"),
+    }
+}
+
+fn graph() -> ImportGraph {
+    let list: ExportedBindingsList = [(
+        "widgets.kdl",
+        exports(r#"button { B; }; label { L; }; export "button" "label""#),
+    )]
+    .into_iter()
+    .collect();
+    let files = [
+        (
+            "menu.kdl",
+            r#"import "widgets.kdl"; menu { button; }; export "menu""#,
+        ),
+        (
+            "main.kdl",
+            r#"import m="menu.kdl/menu" "lib.kdl"; Root { m; }"#,
+        ),
+    ];
+    let mut graph = ImportGraph::new();
+    for (file, text) in files {
+        let document: KdlDocument = text.parse().unwrap();
+        graph.add(file, &get_imports(&document).unwrap(), &list);
+    }
+    graph
+}
+
+#[test]
+fn edge_bindings() {
+    let graph = graph();
+    assert_eq!(
+        graph.files(),
+        ["menu.kdl", "widgets.kdl", "main.kdl", "lib.kdl"]
+    );
+    let edges: Vec<_> = graph
+        .edges()
+        .iter()
+        .map(|e| (&*e.from, &*e.to, e.bindings.join(",")))
+        .collect();
+    let expected = [
+        ("menu.kdl", "widgets.kdl", "button,label".to_owned()),
+        ("main.kdl", "menu.kdl", "menu".to_owned()),
+        // The exports of lib.kdl are unknown
+        ("main.kdl", "lib.kdl", "*".to_owned()),
+    ];
+    assert_eq!(edges, expected);
+}
+
+#[test]
+fn formats() {
+    let dot = graph().to_dot();
+    assert!(dot.starts_with("digraph imports {\n"));
+    assert!(dot.contains(r#"  "main.kdl" -> "menu.kdl" [label="menu"];"#));
+    let mermaid = graph().to_mermaid();
+    assert!(mermaid.starts_with("graph LR\n"));
+    assert!(mermaid.contains(r#"  f2 -->|"menu"| f0"#));
+}
@@ -0,0 +1,76 @@
+//! Migrations rename and restructure documents, keeping their comments.
+use kdl::KdlDocument;
+use template_kdl::{
+    err::ErrorType,
+    migrate::{Migration, Rule},
+};
+
+const RULES: &str = r#"
+rename "Colour" "Color"
+rename "hp" "health" in="Stats"
+wrap "speed" "Speed"
+split "Stats" {
+    Health "health"
+    Mana "mp"
+}
+"#;
+
+#[test]
+fn applies_rules() {
+    let migration = Migration::from_document(&RULES.parse().unwrap()).unwrap();
+    let mut document: KdlDocument = r#"// The player
+entity "player" {
+    Colour 1.0 0.0 0.0 // red
+    Stats hp=0x10 mp=5
+    Mover speed=2.5
+    Enemy hp=3
+}
+"#
+    .parse()
+    .unwrap();
+    assert_eq!(migration.apply(&mut document), 4);
+    let text = document.to_string();
+    let expected = [
+        "// The player",
+        "Color 1.0 0.0 0.0 // red",
+        "Health health=0x10",
+        "Mana mp=5",
+        "Mover speed=(Speed)2.5",
+        // `hp` is only renamed in `Stats`
+        "Enemy hp=3",
+    ];
+    for line in expected {
+        assert!(text.contains(line), "{line:?} not in:\n{text}");
+    }
+}
+
+#[test]
+fn built_rules() {
+    let migration = Migration::new()
+        .rule(
+            Rule::Rename { from: "Hp".to_owned(), to: "Health".to_owned() },
+            None,
+        )
+        .rule(
+            Rule::Wrap {
+                field: "max".to_owned(),
+                newtype: "Points".to_owned(),
+            },
+            Some("Health"),
+        );
+    let mut document: KdlDocument = "Hp 10 max=20 { max 1; }\nLimits max=3".parse().unwrap();
+    // The rules apply in order, `max` is wrapped in the renamed `Health`
+    assert_eq!(migration.apply(&mut document), 3);
+    assert_eq!(
+        document.to_string(),
+        "Health 10 max=(Points)20 { (Points)max 1; }\nLimits max=3"
+    );
+}
+
+#[test]
+fn bad_rules() {
+    let rules = r#"rename "hp""#.parse().unwrap();
+    let error = Migration::from_document(&rules).unwrap_err();
+    assert!(matches!(error.source, ErrorType::BadMigrationRule(_)));
+    assert_eq!(error.span.offset, 0);
+}
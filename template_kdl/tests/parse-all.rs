@@ -0,0 +1,78 @@
+//! `parse_all` reads the files of a project and the files they import, each
+//! with the exports of its imports.
+use std::{fs, path::PathBuf};
+
+use template_kdl::{err::ErrorType, parse_all, Document};
+
+/// A fresh folder with `files`, as `(name, content)`.
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("template-kdl-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    for (file, content) in files {
+        fs::write(root.join(file), content).unwrap();
+    }
+    root
+}
+
+#[test]
+fn reads_imports_first() {
+    let root = project(
+        "imports",
+        &[
+            ("button.kdl", r#"button { Button; }; export "button""#),
+            (
+                "menu.kdl",
+                r#"import "button.kdl"; menu { Menu { button; }; }; export "menu""#,
+            ),
+            ("main.kdl", r#"import "menu.kdl"; Root { menu; button; }"#),
+            ("broken.kdl", "Root {"),
+        ],
+    );
+    let mut results = parse_all(&root, ["main.kdl", "broken.kdl"]);
+    // The files imported by main.kdl are read too
+    let mut files: Vec<_> = results.keys().cloned().collect();
+    files.sort_unstable();
+    assert_eq!(files, ["broken.kdl", "button.kdl", "main.kdl", "menu.kdl"]);
+
+    let main = results.remove("main.kdl").unwrap();
+    let imported: Vec<_> = main.imports.unwrap().required_files().into_iter().collect();
+    assert_eq!(imported, ["menu.kdl"]);
+    let node = match main.document.into_result() {
+        Ok(Document::Node(node)) => node.evaluate().into_result().unwrap(),
+        other => panic!("main.kdl must be a node, got {other:?}"),
+    };
+    let children = node.children().unwrap().nodes();
+    let menu = children[0].children().unwrap().nodes();
+    assert_eq!(children[0].name().value(), "Menu");
+    assert_eq!(menu[0].name().value(), "Button");
+    // `button` is not imported by main.kdl, it's a plain node
+    assert_eq!(children[1].name().value(), "button");
+
+    let broken = results.remove("broken.kdl").unwrap();
+    assert!(broken.imports.is_none());
+    let errors = broken.document.into_result().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0].source, ErrorType::Kdl(_)));
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn missing_imports() {
+    let root = project(
+        "missing",
+        &[("lonely.kdl", r#"import "missing.kdl"; Root"#)],
+    );
+    let mut results = parse_all(&root, ["lonely.kdl"]);
+    let errors = results
+        .remove("missing.kdl")
+        .unwrap()
+        .document
+        .into_result()
+        .unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0].source, ErrorType::Unreadable { .. }));
+    let lonely = results.remove("lonely.kdl").unwrap();
+    assert!(lonely.document.into_result().is_err());
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,137 @@
+//! Custom interpreters read template kdl documents with `seed::Visitor`s,
+//! templates expanded.
+use kdl::KdlDocument;
+use multierr_span::{Span, Spanned};
+use template_kdl::{
+    err::{Error, ErrorType},
+    multi_err::{MultiError, MultiErrorTrait, MultiResult},
+    navigate::{Navigable, ThunkField, Value},
+    seed::{interpret_document, Declared, NavigableSeed, Unexpected, Visitor},
+};
+
+#[derive(Debug, PartialEq)]
+struct Line {
+    who: String,
+    text: String,
+}
+
+#[derive(Debug)]
+enum DialogueError {
+    Unexpected(Unexpected),
+    Template(Error),
+    Incomplete(Span),
+}
+impl From<Unexpected> for DialogueError {
+    fn from(err: Unexpected) -> Self {
+        Self::Unexpected(err)
+    }
+}
+impl From<Error> for DialogueError {
+    fn from(err: Error) -> Self {
+        Self::Template(err)
+    }
+}
+
+/// The lines of a dialogue.
+struct Dialogue;
+impl Visitor<ThunkField> for Dialogue {
+    type Value = Vec<Line>;
+    type Error = DialogueError;
+
+    fn expecting(&self) -> String {
+        "a list of lines".to_owned()
+    }
+    fn visit_list(
+        self,
+        _: &ThunkField,
+        fields: Box<dyn Iterator<Item = ThunkField>>,
+    ) -> MultiResult<Vec<Line>, DialogueError> {
+        MultiError::scope(|errs| {
+            let lines = fields.filter_map(|field| errs.optionally(LineSeed.interpret(field)));
+            Some(lines.collect())
+        })
+    }
+}
+
+/// A `line who="speaker" "text"` node.
+struct LineSeed;
+impl Visitor<ThunkField> for LineSeed {
+    type Value = Line;
+    type Error = DialogueError;
+
+    fn expecting(&self) -> String {
+        "a line".to_owned()
+    }
+    fn visit_list(
+        self,
+        line: &ThunkField,
+        fields: Box<dyn Iterator<Item = ThunkField>>,
+    ) -> MultiResult<Line, DialogueError> {
+        let (mut who, mut text) = (None, None);
+        for field in fields {
+            let value = match field.value() {
+                Value::Bare(value) => value.as_string().map(str::to_owned),
+                Value::List(_) => None,
+            };
+            match field.name().as_deref() {
+                Some("who") => who = value,
+                None => text = value,
+                Some(_) => {}
+            }
+        }
+        match (who, text) {
+            (Some(who), Some(text)) => MultiResult::Ok(Line { who, text }),
+            _ => MultiResult::Err(vec![DialogueError::Incomplete(line.span())]),
+        }
+    }
+}
+
+fn interpret(text: &str) -> Result<Vec<Line>, Vec<DialogueError>> {
+    let document: KdlDocument = text.parse().unwrap();
+    interpret_document(document, Default::default(), Default::default(), Dialogue).into_result()
+}
+fn line(who: &str, text: &str) -> Line {
+    Line { who: who.to_owned(), text: text.to_owned() }
+}
+
+#[test]
+fn templates_are_expanded() {
+    let text = r#"
+alice "text" { line who="Alice" "text"; }
+dialogue {
+  alice "Hello"
+  line who="Bob" "Hi Alice"
+  alice "Bye"
+}"#;
+    let expected = vec![
+        line("Alice", "Hello"),
+        line("Bob", "Hi Alice"),
+        line("Alice", "Bye"),
+    ];
+    assert_eq!(interpret(text).unwrap(), expected);
+}
+
+#[test]
+fn errors_have_spans() {
+    let text = r#"dialogue { line who="Bob"; line "Hi"; }"#;
+    let errors = interpret(text).unwrap_err();
+    let spans: Vec<_> = errors
+        .iter()
+        .map(|err| match err {
+            DialogueError::Incomplete(span) => &text[span.offset as usize..][..span.size as usize],
+            other => panic!("expected incomplete lines, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(spans, [r#"line who="Bob""#, r#"line "Hi""#]);
+
+    let errors = interpret(r#"dialogue "Hi""#).unwrap_err();
+    match &errors[..] {
+        [DialogueError::Unexpected(Unexpected { found: Declared::Bare, .. })] => {}
+        other => panic!("expected a bare value error, got {other:?}"),
+    }
+    let errors = interpret(r#"a { A; }; export "a""#).unwrap_err();
+    match &errors[..] {
+        [DialogueError::Template(err)] => assert_eq!(err.source, ErrorType::NotThunk),
+        other => panic!("expected a not thunk error, got {other:?}"),
+    }
+}
@@ -0,0 +1,70 @@
+//! `classify_tokens` classifies names and values by how they are declared,
+//! without expanding templates.
+use kdl::KdlDocument;
+use template_kdl::{classify_tokens, TokenKind};
+
+/// The text of each token of `text` with its kind.
+fn tokens(text: &str) -> Vec<(&str, TokenKind)> {
+    let document: KdlDocument = text.parse().unwrap();
+    classify_tokens(&document, &Default::default())
+        .into_iter()
+        .map(|token| {
+            let start = token.span.offset as usize;
+            (&text[start..start + token.span.size as usize], token.kind)
+        })
+        .collect()
+}
+
+#[test]
+fn classifies_templates() {
+    use TokenKind::*;
+    let text = r#"button "label" {
+  expand "style" color="grey"
+  Button label="label" expand="style"
+}
+entity {
+  button "Ok"
+  Transform { translation x=1.0 }
+}"#;
+    let expected = vec![
+        ("button", TemplateDefinition),
+        ("\"label\"", Parameter),
+        ("expand", Keyword),
+        ("\"style\"", Parameter),
+        ("color", Field),
+        ("\"grey\"", Value),
+        ("Button", Component),
+        ("label", Field),
+        ("\"label\"", Parameter),
+        ("expand", Keyword),
+        ("\"style\"", Parameter),
+        ("entity", Node),
+        ("button", CallSite),
+        ("\"Ok\"", Value),
+        ("Transform", Component),
+        ("translation", Field),
+        ("x", Field),
+        ("1.0", Value),
+    ];
+    assert_eq!(tokens(text), expected);
+}
+
+#[test]
+fn classifies_keywords() {
+    use TokenKind::*;
+    let text = r#"import "ui.kdl" label="button"
+panel { Panel; }
+export "panel"
+"#;
+    let expected = vec![
+        ("import", Keyword),
+        ("\"ui.kdl\"", Value),
+        ("label", TemplateDefinition),
+        ("\"button\"", Value),
+        ("panel", TemplateDefinition),
+        ("Panel", Component),
+        ("export", Keyword),
+        ("\"panel\"", Value),
+    ];
+    assert_eq!(tokens(text), expected);
+}
@@ -0,0 +1,63 @@
+//! Spans of values cover the value as written in the document, whatever
+//! notation it uses.
+use kdl::{KdlDocument, KdlValue};
+use multierr_span::Spanned;
+use template_kdl::{
+    navigate::{Navigable, Value},
+    read_plain,
+};
+
+/// The text each value of the first node of `document` spans, in the printed
+/// `document`.
+fn value_texts(document: KdlDocument) -> Vec<String> {
+    let text = document.to_string();
+    let node = read_plain(document).next().unwrap();
+    let Value::List(fields) = node.value() else {
+        panic!("the node must have several values");
+    };
+    fields
+        .map(|field| {
+            let Value::Bare(value) = field.value() else {
+                panic!("the node must only have values");
+            };
+            let span = value.span();
+            text[span.offset as usize..span.end() as usize].to_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn declared_notation() {
+    let text = r#"values 1.0e10 -0.0 1.50 0x00ff 1_000 -0o17 "after""#;
+    let expected = [
+        "1.0e10",
+        "-0.0",
+        "1.50",
+        "0x00ff",
+        "1_000",
+        "-0o17",
+        r#""after""#,
+    ];
+    assert_eq!(value_texts(text.parse().unwrap()), expected);
+}
+
+#[test]
+fn printed_values() {
+    let values = [
+        KdlValue::Base10Float(1.0e10),
+        KdlValue::Base10Float(-0.0),
+        KdlValue::Base10Float(f64::INFINITY),
+        KdlValue::Base10(i64::MIN),
+        KdlValue::Base8(-1),
+        KdlValue::Base16(0),
+        KdlValue::Base2(5),
+    ];
+    let mut document: KdlDocument = "values 0 0 0 0 0 0 0".parse().unwrap();
+    let entries = document.nodes_mut()[0].entries_mut();
+    for (entry, value) in entries.iter_mut().zip(values.clone()) {
+        // Setting the value clears its repr, it is printed from the value
+        entry.set_value(value);
+    }
+    let expected: Vec<_> = values.iter().map(ToString::to_string).collect();
+    assert_eq!(value_texts(document), expected);
+}